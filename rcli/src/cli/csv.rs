@@ -1,6 +1,6 @@
 use std::{fmt, str::FromStr};
 
-use clap::Parser;
+use clap::{ArgAction, Parser};
 
 use crate::CmdExecutor;
 
@@ -10,6 +10,10 @@ use super::verify_file;
 pub enum CsvOutputFormat {
     Json,
     Yaml,
+    Toml,
+    Ndjson,
+    Parquet,
+    Markdown,
 }
 
 #[derive(Debug, Parser)]
@@ -17,16 +21,19 @@ pub struct CsvOpts {
     #[arg(short, long, value_parser = verify_file)]
     pub input: String,
 
+    /// output file, "-" for stdout
     #[arg(short, long)] // "output.json".into()
     pub output: Option<String>,
 
+    /// json, yaml, toml, ndjson, parquet or md
     #[arg(long, value_parser = parse_format, default_value = "json")]
     pub format: CsvOutputFormat,
 
     #[arg(short, long, default_value_t = ',')]
     pub delimiter: char,
 
-    #[arg(long, default_value_t = true)]
+    /// whether the first record names the columns, they are named column1, column2... otherwise
+    #[arg(long, default_value_t = true, action = ArgAction::Set)]
     pub header: bool,
 }
 
@@ -37,7 +44,13 @@ impl CmdExecutor for CsvOpts {
         } else {
             format!("output.{}", self.format)
         };
-        crate::process_csv(&self.input, output, self.format)
+        crate::process_csv(
+            &self.input,
+            &output,
+            self.format,
+            self.delimiter,
+            self.header,
+        )
     }
 }
 
@@ -50,6 +63,10 @@ impl From<CsvOutputFormat> for &'static str {
         match format {
             CsvOutputFormat::Json => "json",
             CsvOutputFormat::Yaml => "yaml",
+            CsvOutputFormat::Toml => "toml",
+            CsvOutputFormat::Ndjson => "ndjson",
+            CsvOutputFormat::Parquet => "parquet",
+            CsvOutputFormat::Markdown => "md",
         }
     }
 }
@@ -61,6 +78,10 @@ impl FromStr for CsvOutputFormat {
         match s {
             "json" => Ok(CsvOutputFormat::Json),
             "yaml" => Ok(CsvOutputFormat::Yaml),
+            "toml" => Ok(CsvOutputFormat::Toml),
+            "ndjson" => Ok(CsvOutputFormat::Ndjson),
+            "parquet" => Ok(CsvOutputFormat::Parquet),
+            "md" | "markdown" => Ok(CsvOutputFormat::Markdown),
             _ => Err(anyhow::anyhow!("Invalid format")),
         }
     }
@@ -1,17 +1,22 @@
 use std::path::PathBuf;
+use std::time::Duration;
 
 use clap::Parser;
 use enum_dispatch::enum_dispatch;
 
-use crate::{process_http_serve, CmdExecutor};
+use crate::{process_http_proxy, process_http_request, process_http_serve, CmdExecutor};
 
-use super::verify_path;
+use super::{verify_file, verify_path};
 
 #[derive(Debug, Parser)]
 #[enum_dispatch(CmdExecutor)]
 pub enum HttpSubCommand {
     #[command(about = "Serve static files over HTTP.")]
     Serve(HttpServeOpts),
+    #[command(about = "Send a request and print the response, HTTPie style.")]
+    Request(HttpRequestOpts),
+    #[command(about = "Reverse proxy a local port to an upstream.")]
+    Proxy(HttpProxyOpts),
 }
 
 #[derive(Debug, Parser)]
@@ -21,10 +26,88 @@ pub struct HttpServeOpts {
 
     #[arg(short, long, default_value = "8080")]
     pub port: u16,
+
+    /// accept multipart uploads into the served directories
+    #[arg(long)]
+    pub upload: bool,
+
+    /// PEM certificate chain, serves HTTPS along with --key
+    #[arg(long, value_parser = verify_file, requires = "key")]
+    pub cert: Option<String>,
+
+    /// PEM private key of the certificate
+    #[arg(long, value_parser = verify_file, requires = "cert")]
+    pub key: Option<String>,
+
+    /// require basic auth with these credentials, as user:password
+    #[arg(long, value_parser = parse_credentials)]
+    pub auth: Option<String>,
 }
 
 impl CmdExecutor for HttpServeOpts {
     async fn execute(self) -> anyhow::Result<()> {
-        process_http_serve(self.dir, self.port).await
+        let tls = self.cert.zip(self.key);
+        process_http_serve(self.dir, self.port, self.upload, tls, self.auth).await
+    }
+}
+
+#[derive(Debug, Parser)]
+pub struct HttpRequestOpts {
+    /// [METHOD] URL [ITEM]..., an item is Header:Value, name==value for the query,
+    /// name=value for a JSON string field or name:=json for a raw JSON field
+    #[arg(required = true, value_name = "ARGS")]
+    pub args: Vec<String>,
+
+    /// print the request as well
+    #[arg(short, long)]
+    pub verbose: bool,
+
+    /// follow redirects
+    #[arg(short = 'F', long)]
+    pub follow: bool,
+
+    #[arg(long, value_parser = humantime::parse_duration)]
+    pub timeout: Option<Duration>,
+}
+
+impl CmdExecutor for HttpRequestOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        process_http_request(&self.args, self.verbose, self.follow, self.timeout).await
+    }
+}
+
+#[derive(Debug, Parser)]
+pub struct HttpProxyOpts {
+    #[arg(short, long, default_value = "8080")]
+    pub port: u16,
+
+    /// the url requests are forwarded to
+    #[arg(short, long)]
+    pub upstream: String,
+
+    /// replace a path prefix, as /from=/to, the first matching one applies
+    #[arg(short, long = "rewrite", value_parser = parse_rewrite)]
+    pub rewrites: Vec<(String, String)>,
+}
+
+impl CmdExecutor for HttpProxyOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        process_http_proxy(self.port, &self.upstream, self.rewrites).await
+    }
+}
+
+fn parse_rewrite(rewrite: &str) -> Result<(String, String), &'static str> {
+    match rewrite.split_once('=') {
+        Some((from, to)) if from.starts_with('/') && to.starts_with('/') => {
+            Ok((from.to_string(), to.to_string()))
+        }
+        _ => Err("Rewrites must be given as /from=/to"),
+    }
+}
+
+fn parse_credentials(credentials: &str) -> Result<String, &'static str> {
+    match credentials.split_once(':') {
+        Some((user, _)) if !user.is_empty() => Ok(credentials.to_string()),
+        _ => Err("Credentials must be given as user:password"),
     }
 }
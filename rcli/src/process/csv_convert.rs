@@ -1,40 +1,823 @@
-use std::fs;
-
-use csv::Reader;
-use serde::{Deserialize, Serialize};
-
-use crate::cli::CsvOutputFormat;
-
-#[derive(Debug, Serialize, Deserialize)]
-#[serde(rename_all = "PascalCase")]
-pub struct Player {
-    name: String,
-    position: String,
-    #[serde(rename = "DOB")]
-    dob: String,
-    nationality: String,
-    #[serde(rename = "Kit Number")]
-    kit: u8,
-}
-
-pub fn process_csv(input: &str, output: String, format: CsvOutputFormat) -> anyhow::Result<()> {
-    let mut reader = Reader::from_path(input)?;
-    let mut ret = Vec::with_capacity(128);
-    let headers = reader.headers()?.clone();
-    for result in reader.records() {
-        let record = result?;
-        let iter = headers.iter().zip(record.iter());
-        let value = match format {
-            CsvOutputFormat::Json => iter.collect::<serde_json::Value>(),
-            CsvOutputFormat::Yaml => iter.collect::<serde_json::Value>(),
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::sync::Arc;
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Datelike, NaiveDate, NaiveDateTime, Timelike};
+use csv::{ReaderBuilder, StringRecord};
+use parquet::basic::{Compression, LogicalType, Repetition, Type as PhysicalType};
+use parquet::column::writer::ColumnWriter;
+use parquet::data_type::ByteArray;
+use parquet::file::properties::WriterProperties;
+use parquet::file::writer::SerializedFileWriter;
+use parquet::schema::types::Type;
+use serde::ser::{Serialize, SerializeMap, Serializer};
+
+use crate::{cli::CsvOutputFormat, get_reader};
+
+// the types of the columns are inferred from the first records, the others are streamed
+const SAMPLE_SIZE: usize = 1000;
+// records buffered by the parquet writer before writing a row group
+const ROW_GROUP_SIZE: usize = 8192;
+
+/// The type of a column, the narrowest one which fits all of its sampled values.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum FieldType {
+    /// only empty values were seen
+    Null,
+    Bool,
+    Int,
+    Float,
+    Date,
+    DateTime,
+    String,
+}
+
+/// A value of a record, converted to the type of its column.
+#[derive(Debug, Clone, PartialEq)]
+enum Field {
+    Null,
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    Date(NaiveDate),
+    DateTime(NaiveDateTime),
+    /// a value which isn't of the type of its column is kept as is
+    String(String),
+}
+
+// a record with its column names, serialized as a map in the order of the columns
+struct Record<'a> {
+    headers: &'a [String],
+    fields: &'a [Field],
+    // toml has its own date types and no null
+    toml: bool,
+}
+
+trait RecordWriter {
+    fn write(&mut self, fields: &[Field]) -> Result<()>;
+    fn finish(self: Box<Self>) -> Result<()>;
+}
+
+pub fn process_csv(
+    input: &str,
+    output: &str,
+    format: CsvOutputFormat,
+    delimiter: char,
+    header: bool,
+) -> Result<()> {
+    if !delimiter.is_ascii() {
+        return Err(anyhow!("Delimiter must be an ASCII character"));
+    }
+    let mut reader = ReaderBuilder::new()
+        .delimiter(delimiter as u8)
+        .has_headers(header)
+        .from_reader(get_reader(input)?);
+    let names: Option<Vec<String>> = match header {
+        true => Some(reader.headers()?.iter().map(String::from).collect()),
+        false => None,
+    };
+    let mut records = reader.into_records();
+    let sample = records
+        .by_ref()
+        .take(SAMPLE_SIZE)
+        .collect::<Result<Vec<_>, _>>()?;
+    let headers = names.unwrap_or_else(|| {
+        let width = sample.first().map(StringRecord::len).unwrap_or_default();
+        (1..=width).map(|i| format!("column{}", i)).collect()
+    });
+    let types = infer_types(headers.len(), &sample);
+
+    let mut writer = record_writer(format, output, headers, &types)?;
+    for record in sample.into_iter().map(Ok).chain(records) {
+        let fields = parse_record(&types, &record?);
+        writer.write(&fields)?;
+    }
+    writer.finish()
+}
+
+/// The type of each column, given a sample of the records.
+fn infer_types(width: usize, sample: &[StringRecord]) -> Vec<FieldType> {
+    let mut types = vec![FieldType::Null; width];
+    for record in sample {
+        for (ty, value) in types.iter_mut().zip(record.iter()) {
+            *ty = ty.merge(FieldType::of(value));
+        }
+    }
+    types
+}
+
+fn parse_record(types: &[FieldType], record: &StringRecord) -> Vec<Field> {
+    types
+        .iter()
+        .zip(record.iter())
+        .map(|(ty, value)| ty.parse(value))
+        .collect()
+}
+
+impl FieldType {
+    /// The narrowest type of a single value.
+    fn of(value: &str) -> Self {
+        if value.is_empty() {
+            FieldType::Null
+        } else if parse_bool(value).is_some() {
+            FieldType::Bool
+        } else if parse_int(value).is_some() {
+            FieldType::Int
+        } else if parse_float(value).is_some() {
+            FieldType::Float
+        } else if parse_date(value).is_some() {
+            FieldType::Date
+        } else if parse_datetime(value).is_some() {
+            FieldType::DateTime
+        } else {
+            FieldType::String
+        }
+    }
+
+    /// The narrowest type which fits the values of both types.
+    fn merge(self, other: Self) -> Self {
+        use FieldType::*;
+        match (self, other) {
+            (a, b) if a == b => a,
+            (Null, t) | (t, Null) => t,
+            (Int, Float) | (Float, Int) => Float,
+            (Date, DateTime) | (DateTime, Date) => DateTime,
+            _ => String,
+        }
+    }
+
+    /// Convert a value to the type, a value which doesn't fit stays a string.
+    fn parse(self, value: &str) -> Field {
+        if value.is_empty() {
+            return Field::Null;
+        }
+        let field = match self {
+            FieldType::Null | FieldType::String => None,
+            FieldType::Bool => parse_bool(value).map(Field::Bool),
+            FieldType::Int => parse_int(value).map(Field::Int),
+            FieldType::Float => parse_float(value).map(Field::Float),
+            FieldType::Date => parse_date(value).map(Field::Date),
+            FieldType::DateTime => parse_datetime(value)
+                .or_else(|| parse_date(value).and_then(|date| date.and_hms_opt(0, 0, 0)))
+                .map(Field::DateTime),
+        };
+        field.unwrap_or_else(|| Field::String(value.to_string()))
+    }
+}
+
+fn parse_bool(value: &str) -> Option<bool> {
+    match value.to_ascii_lowercase().as_str() {
+        "true" => Some(true),
+        "false" => Some(false),
+        _ => None,
+    }
+}
+
+// a leading zero makes an identifier, such as a zip code, rather than a number
+fn parse_int(value: &str) -> Option<i64> {
+    let digits = value.strip_prefix('-').unwrap_or(value);
+    if digits.len() > 1 && digits.starts_with('0') {
+        return None;
+    }
+    value.parse().ok()
+}
+
+fn parse_float(value: &str) -> Option<f64> {
+    // "inf" and "NaN" are words rather than numbers
+    if !value.bytes().any(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    value.parse().ok().filter(|v: &f64| v.is_finite())
+}
+
+fn parse_date(value: &str) -> Option<NaiveDate> {
+    NaiveDate::parse_from_str(value, "%Y-%m-%d").ok()
+}
+
+// a time with an offset is converted to utc
+fn parse_datetime(value: &str) -> Option<NaiveDateTime> {
+    if let Ok(datetime) = DateTime::parse_from_rfc3339(value) {
+        return Some(datetime.naive_utc());
+    }
+    ["%Y-%m-%dT%H:%M:%S%.f", "%Y-%m-%d %H:%M:%S%.f"]
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(value, format).ok())
+}
+
+impl Serialize for Field {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Field::Null => serializer.serialize_none(),
+            Field::Bool(v) => serializer.serialize_bool(*v),
+            Field::Int(v) => serializer.serialize_i64(*v),
+            Field::Float(v) => serializer.serialize_f64(*v),
+            Field::Date(v) => serializer.collect_str(&v.format("%Y-%m-%d")),
+            Field::DateTime(v) => serializer.collect_str(&v.format("%Y-%m-%dT%H:%M:%S%.f")),
+            Field::String(v) => serializer.serialize_str(v),
+        }
+    }
+}
+
+impl Serialize for Record<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(None)?;
+        for (name, field) in self.headers.iter().zip(self.fields) {
+            match (self.toml, field) {
+                (true, Field::Null) => {}
+                (true, Field::Date(_) | Field::DateTime(_)) => {
+                    map.serialize_entry(name, &toml_datetime(field))?
+                }
+                _ => map.serialize_entry(name, field)?,
+            }
+        }
+        map.end()
+    }
+}
+
+fn toml_datetime(field: &Field) -> toml::value::Datetime {
+    let date = |date: &NaiveDate| toml::value::Date {
+        year: date.year() as u16,
+        month: date.month() as u8,
+        day: date.day() as u8,
+    };
+    match field {
+        Field::Date(v) => toml::value::Datetime {
+            date: Some(date(v)),
+            time: None,
+            offset: None,
+        },
+        Field::DateTime(v) => toml::value::Datetime {
+            date: Some(date(&v.date())),
+            time: Some(toml::value::Time {
+                hour: v.hour() as u8,
+                minute: v.minute() as u8,
+                second: v.second() as u8,
+                nanosecond: v.nanosecond(),
+            }),
+            offset: None,
+        },
+        _ => unreachable!("only dates are converted"),
+    }
+}
+
+fn record_writer(
+    format: CsvOutputFormat,
+    output: &str,
+    headers: Vec<String>,
+    types: &[FieldType],
+) -> Result<Box<dyn RecordWriter>> {
+    let out: Box<dyn Write + Send> = match output {
+        "-" => Box::new(io::stdout()),
+        path => Box::new(File::create(path)?),
+    };
+    let out = BufWriter::new(out);
+    let writer: Box<dyn RecordWriter> = match format {
+        CsvOutputFormat::Json => Box::new(JsonWriter {
+            out,
+            headers,
+            count: 0,
+        }),
+        CsvOutputFormat::Ndjson => Box::new(NdjsonWriter { out, headers }),
+        CsvOutputFormat::Yaml => Box::new(YamlWriter {
+            out,
+            headers,
+            count: 0,
+        }),
+        CsvOutputFormat::Toml => Box::new(TomlWriter { out, headers }),
+        CsvOutputFormat::Markdown => Box::new(MarkdownWriter::new(out, &headers, types)?),
+        CsvOutputFormat::Parquet => Box::new(ParquetWriter::new(out, &headers, types)?),
+    };
+    Ok(writer)
+}
+
+// a pretty printed array, each record indented on its own lines
+struct JsonWriter<W: Write> {
+    out: W,
+    headers: Vec<String>,
+    count: usize,
+}
+
+impl<W: Write> RecordWriter for JsonWriter<W> {
+    fn write(&mut self, fields: &[Field]) -> Result<()> {
+        let record = Record {
+            headers: &self.headers,
+            fields,
+            toml: false,
+        };
+        let json = serde_json::to_string_pretty(&record)?;
+        self.out
+            .write_all(if self.count == 0 { b"[\n" } else { b",\n" })?;
+        // newlines are escaped within json strings, every line can be indented
+        for (i, line) in json.lines().enumerate() {
+            if i > 0 {
+                self.out.write_all(b"\n")?;
+            }
+            write!(self.out, "  {}", line)?;
+        }
+        self.count += 1;
+        Ok(())
+    }
+
+    fn finish(mut self: Box<Self>) -> Result<()> {
+        match self.count {
+            0 => self.out.write_all(b"[]\n")?,
+            _ => self.out.write_all(b"\n]\n")?,
+        }
+        self.out.flush()?;
+        Ok(())
+    }
+}
+
+struct NdjsonWriter<W: Write> {
+    out: W,
+    headers: Vec<String>,
+}
+
+impl<W: Write> RecordWriter for NdjsonWriter<W> {
+    fn write(&mut self, fields: &[Field]) -> Result<()> {
+        let record = Record {
+            headers: &self.headers,
+            fields,
+            toml: false,
+        };
+        serde_json::to_writer(&mut self.out, &record)?;
+        self.out.write_all(b"\n")?;
+        Ok(())
+    }
+
+    fn finish(mut self: Box<Self>) -> Result<()> {
+        self.out.flush()?;
+        Ok(())
+    }
+}
+
+// a sequence, written one item at a time
+struct YamlWriter<W: Write> {
+    out: W,
+    headers: Vec<String>,
+    count: usize,
+}
+
+impl<W: Write> RecordWriter for YamlWriter<W> {
+    fn write(&mut self, fields: &[Field]) -> Result<()> {
+        let record = Record {
+            headers: &self.headers,
+            fields,
+            toml: false,
+        };
+        self.out
+            .write_all(serde_yaml::to_string(&[record])?.as_bytes())?;
+        self.count += 1;
+        Ok(())
+    }
+
+    fn finish(mut self: Box<Self>) -> Result<()> {
+        if self.count == 0 {
+            self.out.write_all(b"[]\n")?;
+        }
+        self.out.flush()?;
+        Ok(())
+    }
+}
+
+// an array of tables named rows, as a toml document can't be an array
+struct TomlWriter<W: Write> {
+    out: W,
+    headers: Vec<String>,
+}
+
+impl<W: Write> RecordWriter for TomlWriter<W> {
+    fn write(&mut self, fields: &[Field]) -> Result<()> {
+        let record = Record {
+            headers: &self.headers,
+            fields,
+            toml: true,
         };
-        ret.push(value);
+        write!(self.out, "[[rows]]\n{}\n", toml::to_string(&record)?)?;
+        Ok(())
+    }
+
+    fn finish(mut self: Box<Self>) -> Result<()> {
+        self.out.flush()?;
+        Ok(())
+    }
+}
+
+struct MarkdownWriter<W: Write> {
+    out: W,
+}
+
+impl<W: Write> MarkdownWriter<W> {
+    fn new(mut out: W, headers: &[String], types: &[FieldType]) -> Result<Self> {
+        let cells = headers.iter().map(|name| markdown_cell(name));
+        writeln!(out, "| {} |", cells.collect::<Vec<_>>().join(" | "))?;
+        // numbers are aligned to the right
+        let aligns = types.iter().map(|ty| match ty {
+            FieldType::Int | FieldType::Float => "---:",
+            _ => "---",
+        });
+        writeln!(out, "| {} |", aligns.collect::<Vec<_>>().join(" | "))?;
+        Ok(Self { out })
+    }
+}
+
+impl<W: Write> RecordWriter for MarkdownWriter<W> {
+    fn write(&mut self, fields: &[Field]) -> Result<()> {
+        let cells = fields.iter().map(|field| match field {
+            Field::Null => String::new(),
+            Field::String(v) => markdown_cell(v),
+            field => serde_json::to_value(field)
+                .map(|v| {
+                    v.as_str()
+                        .map(String::from)
+                        .unwrap_or_else(|| v.to_string())
+                })
+                .unwrap_or_default(),
+        });
+        writeln!(self.out, "| {} |", cells.collect::<Vec<_>>().join(" | "))?;
+        Ok(())
+    }
+
+    fn finish(mut self: Box<Self>) -> Result<()> {
+        self.out.flush()?;
+        Ok(())
+    }
+}
+
+// a pipe ends the cell and a newline ends the table
+fn markdown_cell(value: &str) -> String {
+    value
+        .replace('|', "\\|")
+        .replace("\r\n", "<br>")
+        .replace('\n', "<br>")
+}
+
+// the records are buffered by column and written as a row group every ROW_GROUP_SIZE records
+struct ParquetWriter<W: Write + Send> {
+    writer: SerializedFileWriter<W>,
+    headers: Vec<String>,
+    types: Vec<FieldType>,
+    columns: Vec<Vec<Field>>,
+    // records written so far, to locate a value which doesn't fit its column
+    records: usize,
+}
+
+impl<W: Write + Send> ParquetWriter<W> {
+    fn new(out: W, headers: &[String], types: &[FieldType]) -> Result<Self> {
+        let fields = headers
+            .iter()
+            .zip(types)
+            .map(|(name, ty)| parquet_type(name, *ty).map(Arc::new))
+            .collect::<Result<Vec<_>, _>>()?;
+        let schema = Type::group_type_builder("schema")
+            .with_fields(fields)
+            .build()?;
+        let props = WriterProperties::builder()
+            .set_compression(Compression::SNAPPY)
+            .build();
+        Ok(Self {
+            writer: SerializedFileWriter::new(out, Arc::new(schema), Arc::new(props))?,
+            headers: headers.to_vec(),
+            types: types.to_vec(),
+            columns: vec![Vec::with_capacity(ROW_GROUP_SIZE); types.len()],
+            records: 0,
+        })
+    }
+
+    fn write_row_group(&mut self) -> Result<()> {
+        let mut row_group = self.writer.next_row_group()?;
+        let mut columns = self.columns.iter_mut().zip(&self.types);
+        while let Some(mut writer) = row_group.next_column()? {
+            let (column, ty) = columns
+                .next()
+                .ok_or_else(|| anyhow!("Column missing from the parquet schema"))?;
+            let levels = column
+                .iter()
+                .map(|field| i16::from(!matches!(field, Field::Null)))
+                .collect::<Vec<_>>();
+            match (writer.untyped(), ty) {
+                (ColumnWriter::BoolColumnWriter(w), _) => {
+                    let values = values(column, |f| match f {
+                        Field::Bool(v) => Some(*v),
+                        _ => None,
+                    });
+                    w.write_batch(&values, Some(&levels), None)?;
+                }
+                (ColumnWriter::Int32ColumnWriter(w), _) => {
+                    let values = values(column, |f| match f {
+                        Field::Date(v) => Some(days_since_epoch(v)),
+                        _ => None,
+                    });
+                    w.write_batch(&values, Some(&levels), None)?;
+                }
+                (ColumnWriter::Int64ColumnWriter(w), _) => {
+                    let values = values(column, |f| match f {
+                        Field::Int(v) => Some(*v),
+                        Field::DateTime(v) => Some(v.and_utc().timestamp_millis()),
+                        _ => None,
+                    });
+                    w.write_batch(&values, Some(&levels), None)?;
+                }
+                (ColumnWriter::DoubleColumnWriter(w), _) => {
+                    let values = values(column, |f| match f {
+                        Field::Float(v) => Some(*v),
+                        Field::Int(v) => Some(*v as f64),
+                        _ => None,
+                    });
+                    w.write_batch(&values, Some(&levels), None)?;
+                }
+                (ColumnWriter::ByteArrayColumnWriter(w), _) => {
+                    let values = values(column, |f| match f {
+                        Field::String(v) => Some(ByteArray::from(v.as_str())),
+                        _ => None,
+                    });
+                    w.write_batch(&values, Some(&levels), None)?;
+                }
+                (_, ty) => return Err(anyhow!("Unsupported parquet column type {:?}", ty)),
+            }
+            writer.close()?;
+            column.clear();
+        }
+        row_group.close()?;
+        Ok(())
     }
+}
 
-    let content = match format {
-        CsvOutputFormat::Json => serde_json::to_string_pretty(&ret)?,
-        CsvOutputFormat::Yaml => serde_yaml::to_string(&ret)?,
+impl<W: Write + Send> RecordWriter for ParquetWriter<W> {
+    fn write(&mut self, fields: &[Field]) -> Result<()> {
+        self.records += 1;
+        let columns = self.columns.iter_mut().zip(&self.types).zip(&self.headers);
+        for (((column, ty), name), field) in columns.zip(fields) {
+            let field = match (ty, field) {
+                // string columns hold any value, the others only their own type
+                (FieldType::String | FieldType::Null, Field::String(_) | Field::Null) => {
+                    field.clone()
+                }
+                (FieldType::String | FieldType::Null, field) => {
+                    Field::String(serde_json::to_value(field)?.to_string())
+                }
+                // the schema is already written, the column can't be widened anymore
+                (_, Field::String(value)) => {
+                    return Err(anyhow!(
+                        "Value {:?} of column {:?} in record {} is not {:?} as inferred from the first {} records",
+                        value,
+                        name,
+                        self.records,
+                        ty,
+                        SAMPLE_SIZE
+                    ));
+                }
+                (_, field) => field.clone(),
+            };
+            column.push(field);
+        }
+        if self.columns.first().map(Vec::len) >= Some(ROW_GROUP_SIZE) {
+            self.write_row_group()?;
+        }
+        Ok(())
+    }
+
+    fn finish(mut self: Box<Self>) -> Result<()> {
+        if self
+            .columns
+            .first()
+            .is_some_and(|column| !column.is_empty())
+        {
+            self.write_row_group()?;
+        }
+        let mut out = self.writer.into_inner()?;
+        out.flush()?;
+        Ok(())
+    }
+}
+
+// a nullable column, columns without any value are strings
+fn parquet_type(name: &str, ty: FieldType) -> parquet::errors::Result<Type> {
+    let (physical, logical) = match ty {
+        FieldType::Bool => (PhysicalType::BOOLEAN, None),
+        FieldType::Int => (PhysicalType::INT64, None),
+        FieldType::Float => (PhysicalType::DOUBLE, None),
+        FieldType::Date => (PhysicalType::INT32, Some(LogicalType::Date)),
+        FieldType::DateTime => (
+            PhysicalType::INT64,
+            Some(LogicalType::Timestamp {
+                is_adjusted_to_u_t_c: false,
+                unit: parquet::basic::TimeUnit::MILLIS(Default::default()),
+            }),
+        ),
+        FieldType::Null | FieldType::String => {
+            (PhysicalType::BYTE_ARRAY, Some(LogicalType::String))
+        }
     };
-    fs::write(output, content)?;
-    Ok(())
+    Type::primitive_type_builder(name, physical)
+        .with_repetition(Repetition::OPTIONAL)
+        .with_logical_type(logical)
+        .build()
+}
+
+// the non null values of a column, parquet takes them apart from their definition levels
+fn values<T>(column: &[Field], f: impl Fn(&Field) -> Option<T>) -> Vec<T> {
+    column.iter().filter_map(f).collect()
+}
+
+fn days_since_epoch(date: &NaiveDate) -> i32 {
+    (*date - NaiveDate::default()).num_days() as i32
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use parquet::file::reader::{FileReader, SerializedFileReader};
+    use parquet::record::{Field as ParquetField, Row, RowAccessor};
+
+    use super::*;
+
+    #[test]
+    fn test_infer_types() {
+        let sample = [
+            StringRecord::from(vec!["1", "1.5", "true", "2024-01-31", "00123", ""]),
+            StringRecord::from(vec!["-2", "3", "FALSE", "2024-02-01T10:00:00Z", "a", ""]),
+        ];
+        let types = infer_types(6, &sample);
+        assert_eq!(
+            types,
+            vec![
+                FieldType::Int,
+                FieldType::Float,
+                FieldType::Bool,
+                FieldType::DateTime,
+                FieldType::String,
+                FieldType::Null
+            ]
+        );
+        assert_eq!(FieldType::Float.parse("3"), Field::Float(3.0));
+        assert_eq!(FieldType::Int.parse("n/a"), Field::String("n/a".into()));
+        assert_eq!(FieldType::of("inf"), FieldType::String);
+        assert_eq!(
+            FieldType::DateTime.parse("2024-01-31"),
+            Field::DateTime(parse_datetime("2024-01-31T00:00:00").unwrap())
+        );
+    }
+
+    // write the csv into dir and convert it, the output is read back unless it is binary
+    fn convert(dir: &Path, csv: &str, format: CsvOutputFormat, header: bool) -> Result<String> {
+        let input = dir.join("input.csv");
+        std::fs::write(&input, csv)?;
+        let output = dir.join(format!("output.{}", format));
+        process_csv(
+            input.to_str().unwrap(),
+            output.to_str().unwrap(),
+            format,
+            ';',
+            header,
+        )?;
+        Ok(std::fs::read_to_string(output).unwrap_or_default())
+    }
+
+    fn parquet_reader(dir: &Path) -> Result<SerializedFileReader<File>> {
+        Ok(SerializedFileReader::new(File::open(
+            dir.join("output.parquet"),
+        )?)?)
+    }
+
+    #[test]
+    fn test_process_csv_formats() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let dir = dir.path();
+        let csv = "id;name;score\n1;a|b;1.5\n2;;2\n";
+
+        let json: serde_json::Value =
+            serde_json::from_str(&convert(dir, csv, CsvOutputFormat::Json, true)?)?;
+        assert_eq!(
+            json,
+            serde_json::json!([
+                {"id": 1, "name": "a|b", "score": 1.5},
+                {"id": 2, "name": null, "score": 2.0}
+            ])
+        );
+        let ndjson = convert(dir, csv, CsvOutputFormat::Ndjson, false)?;
+        assert_eq!(
+            ndjson.lines().next(),
+            Some(r#"{"column1":"id","column2":"name","column3":"score"}"#)
+        );
+        let toml: toml::Table = convert(dir, csv, CsvOutputFormat::Toml, true)?.parse()?;
+        assert_eq!(toml["rows"][1].as_table().unwrap().len(), 2);
+        let markdown = convert(dir, csv, CsvOutputFormat::Markdown, true)?;
+        assert_eq!(
+            markdown,
+            "| id | name | score |\n| ---: | --- | ---: |\n| 1 | a\\|b | 1.5 |\n| 2 |  | 2.0 |\n"
+        );
+        let yaml: serde_yaml::Value =
+            serde_yaml::from_str(&convert(dir, csv, CsvOutputFormat::Yaml, true)?)?;
+        assert_eq!(
+            yaml,
+            serde_yaml::from_str::<serde_yaml::Value>(
+                "- id: 1\n  name: a|b\n  score: 1.5\n- id: 2\n  name: null\n  score: 2.0\n"
+            )?
+        );
+
+        convert(dir, csv, CsvOutputFormat::Parquet, true)?;
+        let reader = parquet_reader(dir)?;
+        assert_eq!(reader.metadata().file_metadata().num_rows(), 2);
+        let schema = reader.metadata().file_metadata().schema_descr();
+        assert_eq!(schema.column(0).physical_type(), PhysicalType::INT64);
+        assert_eq!(schema.column(2).physical_type(), PhysicalType::DOUBLE);
+
+        // a value past the sample which doesn't fit its column fails rather than becoming null
+        let mut csv = String::from("id;name;score\n");
+        for i in 0..SAMPLE_SIZE {
+            csv.push_str(&format!("{};n{};1\n", i, i));
+        }
+        csv.push_str("x;late;1\n");
+        let err = convert(dir, &csv, CsvOutputFormat::Parquet, true).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains(r#"Value "x" of column "id" in record 1001"#));
+        let json: serde_json::Value =
+            serde_json::from_str(&convert(dir, &csv, CsvOutputFormat::Json, true)?)?;
+        assert_eq!(json[SAMPLE_SIZE]["id"], "x");
+        Ok(())
+    }
+
+    #[test]
+    fn test_parquet_column_types() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let csv = "day;at;flag\n2024-01-31;2024-02-01T10:00:00Z;true\n;2024-02-01;FALSE\n";
+        convert(dir.path(), csv, CsvOutputFormat::Parquet, true)?;
+        let reader = parquet_reader(dir.path())?;
+        let schema = reader.metadata().file_metadata().schema_descr();
+        assert_eq!(schema.column(0).physical_type(), PhysicalType::INT32);
+        assert_eq!(schema.column(0).logical_type(), Some(LogicalType::Date));
+        assert_eq!(schema.column(1).physical_type(), PhysicalType::INT64);
+        assert!(matches!(
+            schema.column(1).logical_type(),
+            Some(LogicalType::Timestamp { .. })
+        ));
+        assert_eq!(schema.column(2).physical_type(), PhysicalType::BOOLEAN);
+
+        let rows = reader.get_row_iter(None)?.collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(rows.len(), 2);
+        // 2024-01-31 is 19753 days after the epoch
+        let day = |row: &Row| row.get_column_iter().next().map(|(_, v)| v.clone());
+        assert_eq!(day(&rows[0]), Some(ParquetField::Date(19753)));
+        assert_eq!(rows[0].get_timestamp_millis(1)?, 1_706_781_600_000);
+        assert!(rows[0].get_bool(2)?);
+        assert_eq!(day(&rows[1]), Some(ParquetField::Null));
+        assert_eq!(rows[1].get_timestamp_millis(1)?, 1_706_745_600_000);
+        assert!(!rows[1].get_bool(2)?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_empty_csv() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let dir = dir.path();
+        let csv = "id;name\n";
+        assert_eq!(convert(dir, csv, CsvOutputFormat::Json, true)?, "[]\n");
+        assert_eq!(convert(dir, csv, CsvOutputFormat::Yaml, true)?, "[]\n");
+        assert_eq!(convert(dir, csv, CsvOutputFormat::Ndjson, true)?, "");
+        assert_eq!(
+            convert(dir, csv, CsvOutputFormat::Markdown, true)?,
+            "| id | name |\n| --- | --- |\n"
+        );
+        convert(dir, csv, CsvOutputFormat::Parquet, true)?;
+        let reader = parquet_reader(dir)?;
+        assert_eq!(reader.metadata().file_metadata().num_rows(), 0);
+        assert_eq!(
+            reader
+                .metadata()
+                .file_metadata()
+                .schema_descr()
+                .num_columns(),
+            2
+        );
+
+        assert_eq!(convert(dir, "", CsvOutputFormat::Json, false)?, "[]\n");
+        Ok(())
+    }
+
+    #[test]
+    fn test_without_header() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let dir = dir.path();
+        let csv = "id;score\n1;1.5\n";
+        assert_eq!(
+            convert(dir, csv, CsvOutputFormat::Markdown, false)?,
+            "| column1 | column2 |\n| --- | --- |\n| id | score |\n| 1 | 1.5 |\n"
+        );
+
+        convert(dir, csv, CsvOutputFormat::Parquet, false)?;
+        let reader = parquet_reader(dir)?;
+        assert_eq!(reader.metadata().file_metadata().num_rows(), 2);
+        let schema = reader.metadata().file_metadata().schema_descr();
+        assert_eq!(schema.column(0).name(), "column1");
+        // the first record is data, its text makes the columns strings
+        assert_eq!(schema.column(1).physical_type(), PhysicalType::BYTE_ARRAY);
+        let rows = reader.get_row_iter(None)?.collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(rows[0].get_string(0)?, "id");
+        assert_eq!(rows[1].get_string(1)?, "1.5");
+        Ok(())
+    }
 }
@@ -0,0 +1,261 @@
+use std::time::Instant;
+use std::{net::SocketAddr, sync::Arc};
+
+use anyhow::{bail, Result};
+use axum::{
+    body::{Body, HttpBody},
+    extract::{ConnectInfo, Request, State},
+    http::{header, HeaderMap, HeaderName, HeaderValue, StatusCode, Uri},
+    response::{IntoResponse, Response},
+    Router,
+};
+use futures::{channel::mpsc, SinkExt, StreamExt};
+use reqwest::{redirect::Policy, Client, Url};
+use tracing::{info, warn};
+
+// the headers of a single connection, which are not forwarded
+const HOP_BY_HOP: [HeaderName; 8] = [
+    header::CONNECTION,
+    HeaderName::from_static("keep-alive"),
+    header::PROXY_AUTHENTICATE,
+    header::PROXY_AUTHORIZATION,
+    header::TE,
+    header::TRAILER,
+    header::TRANSFER_ENCODING,
+    header::UPGRADE,
+];
+
+#[derive(Debug)]
+struct HttpProxyState {
+    client: Client,
+    upstream: Url,
+    // path prefixes and their replacement, the first matching one applies
+    rewrites: Vec<(String, String)>,
+}
+
+pub async fn process_http_proxy(
+    port: u16,
+    upstream: &str,
+    rewrites: Vec<(String, String)>,
+) -> Result<()> {
+    let upstream = Url::parse(upstream)?;
+    if !matches!(upstream.scheme(), "http" | "https") {
+        bail!("The upstream must be an http or https url");
+    }
+    let addr = SocketAddr::from(([0, 0, 0, 0], port));
+    info!("Proxying http://{} to {}", addr, upstream);
+    let state = HttpProxyState {
+        // redirects are for the client to follow
+        client: Client::builder().redirect(Policy::none()).build()?,
+        upstream,
+        rewrites,
+    };
+    let router = router(Arc::new(state));
+    let listener = tokio::net::TcpListener::bind(&addr).await?;
+    axum::serve(
+        listener,
+        router.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .await?;
+    Ok(())
+}
+
+fn router(state: Arc<HttpProxyState>) -> Router {
+    Router::new().fallback(proxy).with_state(state)
+}
+
+async fn proxy(State(state): State<Arc<HttpProxyState>>, req: Request) -> Response {
+    let start = Instant::now();
+    let url = upstream_url(&state.upstream, &state.rewrites, req.uri());
+    let peer = req
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| addr.ip());
+    let (parts, body) = req.into_parts();
+
+    let mut headers = forwarded_headers(&parts.headers);
+    // the host is the one of the upstream url
+    headers.remove(header::HOST);
+    if let Some(ip) = peer {
+        let forwarded_for = match parts.headers.get("x-forwarded-for") {
+            Some(previous) => format!("{}, {}", String::from_utf8_lossy(previous.as_bytes()), ip),
+            None => ip.to_string(),
+        };
+        if let Ok(value) = HeaderValue::from_str(&forwarded_for) {
+            headers.insert("x-forwarded-for", value);
+        }
+    }
+    if let Some(host) = parts.headers.get(header::HOST) {
+        headers.insert("x-forwarded-host", host.clone());
+    }
+    headers.insert("x-forwarded-proto", HeaderValue::from_static("http"));
+
+    let mut request = state
+        .client
+        .request(parts.method.clone(), url.clone())
+        .headers(headers);
+    if !body.is_end_stream() {
+        request = request.body(forward_body(body));
+    }
+    let result = request.send().await;
+    match result {
+        Ok(upstream) => {
+            info!(
+                "{} {} -> {} {} in {:.2?}",
+                parts.method,
+                parts.uri,
+                url,
+                upstream.status(),
+                start.elapsed()
+            );
+            let status = upstream.status();
+            let headers = forwarded_headers(upstream.headers());
+            let mut response = Response::new(Body::from_stream(upstream.bytes_stream()));
+            *response.status_mut() = status;
+            *response.headers_mut() = headers;
+            response
+        }
+        Err(e) => {
+            warn!("{} {} -> {} failed: {}", parts.method, parts.uri, url, e);
+            (StatusCode::BAD_GATEWAY, format!("Upstream error: {}", e)).into_response()
+        }
+    }
+}
+
+/// The upstream url of a request. The prefix of its path is replaced by the first matching
+/// rewrite, and the result is appended to the path of `upstream`.
+fn upstream_url(upstream: &Url, rewrites: &[(String, String)], uri: &Uri) -> Url {
+    let path = uri.path();
+    let path = rewrites
+        .iter()
+        .find_map(|(from, to)| {
+            // "/api" matches "/api" and "/api/users" but not "/apis"
+            let rest = path.strip_prefix(from.trim_end_matches('/'))?;
+            (rest.is_empty() || rest.starts_with('/'))
+                .then(|| format!("{}{}", to.trim_end_matches('/'), rest))
+        })
+        .unwrap_or_else(|| path.to_string());
+    let path = format!("{}{}", upstream.path().trim_end_matches('/'), path);
+
+    let mut url = upstream.clone();
+    url.set_path(if path.is_empty() { "/" } else { &path });
+    url.set_query(uri.query());
+    url
+}
+
+// the request body isn't Sync as reqwest requires, it is streamed through a channel instead
+fn forward_body(body: Body) -> reqwest::Body {
+    let (mut tx, rx) = mpsc::channel(1);
+    tokio::spawn(async move {
+        let mut stream = body.into_data_stream();
+        while let Some(chunk) = stream.next().await {
+            if tx.send(chunk).await.is_err() {
+                break;
+            }
+        }
+    });
+    reqwest::Body::wrap_stream(rx)
+}
+
+// the headers without the hop-by-hop ones, including those listed in Connection
+fn forwarded_headers(headers: &HeaderMap) -> HeaderMap {
+    let listed = headers
+        .get_all(header::CONNECTION)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .filter_map(|name| HeaderName::from_bytes(name.trim().as_bytes()).ok())
+        .collect::<Vec<_>>();
+    let mut forwarded = headers.clone();
+    for name in HOP_BY_HOP.iter().chain(&listed) {
+        forwarded.remove(name);
+    }
+    forwarded
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::routing::any;
+    use tower_service::Service;
+
+    use super::*;
+
+    #[test]
+    fn test_upstream_url() {
+        let rewrites = vec![
+            ("/api/".to_string(), "/".to_string()),
+            ("/static".to_string(), "/assets/v1".to_string()),
+        ];
+        let upstream = Url::parse("http://localhost:3000/base").unwrap();
+        let url = |uri: &str| upstream_url(&upstream, &rewrites, &uri.parse().unwrap()).to_string();
+        assert_eq!(
+            url("/api/users?page=2"),
+            "http://localhost:3000/base/users?page=2"
+        );
+        assert_eq!(url("/api"), "http://localhost:3000/base");
+        assert_eq!(url("/apis"), "http://localhost:3000/base/apis");
+        assert_eq!(
+            url("/static/app.js"),
+            "http://localhost:3000/base/assets/v1/app.js"
+        );
+        let root = Url::parse("http://localhost:3000").unwrap();
+        assert_eq!(
+            upstream_url(&root, &rewrites, &"/api".parse().unwrap()).as_str(),
+            "http://localhost:3000/"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_proxy() -> Result<()> {
+        // the upstream echoes what it received
+        let echo = Router::new().fallback(any(|req: Request| async move {
+            let forwarded = req.headers().get("x-forwarded-host").cloned();
+            let dropped = req.headers().get("x-drop").is_none();
+            let body = axum::body::to_bytes(req.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            (
+                [("x-upstream", "echo"), ("connection", "x-upstream")],
+                format!(
+                    "{} {:?} {}",
+                    String::from_utf8_lossy(&body),
+                    forwarded,
+                    dropped
+                ),
+            )
+        }));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        tokio::spawn(async move { axum::serve(listener, echo).await });
+
+        let state = HttpProxyState {
+            client: Client::new(),
+            upstream: Url::parse(&format!("http://{}", addr))?,
+            rewrites: vec![("/api".to_string(), "/".to_string())],
+        };
+        let mut router = router(Arc::new(state));
+        let req = Request::post("/api/echo")
+            .header(header::HOST, "proxy.local")
+            .header(header::CONNECTION, "x-drop")
+            .header("x-drop", "1")
+            .body(Body::from("hello"))?;
+        let response = router.call(req).await?;
+        assert_eq!(response.status(), StatusCode::OK);
+        // the upstream's Connection header lists x-upstream as hop-by-hop
+        assert!(response.headers().get("x-upstream").is_none());
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await?;
+        assert_eq!(body, "hello Some(\"proxy.local\") true");
+
+        let state = HttpProxyState {
+            client: Client::new(),
+            upstream: Url::parse("http://127.0.0.1:1")?,
+            rewrites: vec![],
+        };
+        let mut unreachable = super::router(Arc::new(state));
+        let response = unreachable
+            .call(Request::get("/").body(Body::empty())?)
+            .await?;
+        assert_eq!(response.status(), StatusCode::BAD_GATEWAY);
+        Ok(())
+    }
+}
@@ -0,0 +1,269 @@
+use std::cmp::Reverse;
+use std::io::Write;
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, bail, Result};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, ACCEPT, CONTENT_TYPE};
+use reqwest::{redirect::Policy, Client, Method, Request, Url};
+use serde_json::{Map, Value};
+
+/// An argument after the url, as HTTPie takes them.
+#[derive(Debug, PartialEq)]
+enum RequestItem {
+    // Name:Value
+    Header(String, String),
+    // name==value
+    Query(String, String),
+    // name=value, a string field of the JSON body
+    Field(String, String),
+    // name:=json, a raw JSON field of the body
+    JsonField(String, Value),
+}
+
+impl RequestItem {
+    fn parse(item: &str) -> Result<Self> {
+        const SEPARATORS: [&str; 4] = [":=", "==", "=", ":"];
+        // the first separator wins, the longest one when several start at the same place
+        let (pos, sep) = SEPARATORS
+            .iter()
+            .filter_map(|sep| item.find(sep).map(|pos| (pos, *sep)))
+            .min_by_key(|(pos, sep)| (*pos, Reverse(sep.len())))
+            .ok_or_else(|| {
+                anyhow!(
+                    "Invalid item {:?}, expected Header:Value, name==value, name=value or name:=json",
+                    item
+                )
+            })?;
+        let (name, value) = (item[..pos].to_string(), &item[pos + sep.len()..]);
+        if name.is_empty() {
+            bail!("Invalid item {:?}, the name is missing", item);
+        }
+        Ok(match sep {
+            ":" => Self::Header(name, value.to_string()),
+            "==" => Self::Query(name, value.to_string()),
+            "=" => Self::Field(name, value.to_string()),
+            _ => {
+                let value = serde_json::from_str(value)
+                    .map_err(|e| anyhow!("Invalid JSON in {:?}: {}", item, e))?;
+                Self::JsonField(name, value)
+            }
+        })
+    }
+}
+
+pub async fn process_http_request(
+    args: &[String],
+    verbose: bool,
+    follow: bool,
+    timeout: Option<Duration>,
+) -> Result<()> {
+    let mut client = Client::builder().redirect(match follow {
+        true => Policy::limited(10),
+        false => Policy::none(),
+    });
+    if let Some(timeout) = timeout {
+        client = client.timeout(timeout);
+    }
+    let client = client.build()?;
+    let request = build_request(&client, args)?;
+
+    let mut stdout = std::io::stdout().lock();
+    if verbose {
+        writeln!(
+            stdout,
+            "{} {} {:?}",
+            request.method(),
+            request.url(),
+            request.version()
+        )?;
+        write_headers(&mut stdout, request.headers())?;
+        let body = request.body().and_then(|body| body.as_bytes());
+        if let Some(body) = body {
+            writeln!(stdout, "{}", pretty_body(request.headers(), body))?;
+        }
+        writeln!(stdout)?;
+    }
+
+    let start = Instant::now();
+    let response = client.execute(request).await?;
+    let headers_at = start.elapsed();
+    writeln!(stdout, "{:?} {}", response.version(), response.status())?;
+    write_headers(&mut stdout, response.headers())?;
+    let headers = response.headers().clone();
+    let body = response.bytes().await?;
+    let elapsed = start.elapsed();
+    if !body.is_empty() {
+        writeln!(stdout, "{}", pretty_body(&headers, &body))?;
+    }
+    stdout.flush()?;
+    // timings go to stderr, so that the response can be piped
+    eprintln!(
+        "\n{} bytes in {:.2?} (headers after {:.2?})",
+        body.len(),
+        elapsed,
+        headers_at
+    );
+    Ok(())
+}
+
+// [METHOD] URL [ITEM]..., the method is POST when there is a body and GET otherwise
+fn build_request(client: &Client, args: &[String]) -> Result<Request> {
+    let is_method = |arg: &String| !arg.is_empty() && arg.chars().all(|c| c.is_ascii_uppercase());
+    let (method, args) = match args {
+        [method, _, ..] if is_method(method) => (Some(method.as_str()), &args[1..]),
+        _ => (None, args),
+    };
+    let Some((url, items)) = args.split_first() else {
+        bail!("The url is missing");
+    };
+
+    let mut url = Url::parse(&normalize_url(url))?;
+    let mut headers = HeaderMap::new();
+    let mut body = Map::new();
+    for item in items {
+        match RequestItem::parse(item)? {
+            RequestItem::Header(name, value) => {
+                headers.append(
+                    HeaderName::from_bytes(name.as_bytes())?,
+                    HeaderValue::from_str(&value)?,
+                );
+            }
+            RequestItem::Query(name, value) => {
+                url.query_pairs_mut().append_pair(&name, &value);
+            }
+            RequestItem::Field(name, value) => {
+                body.insert(name, Value::String(value));
+            }
+            RequestItem::JsonField(name, value) => {
+                body.insert(name, value);
+            }
+        }
+    }
+    let method = match method {
+        Some(method) => Method::from_bytes(method.as_bytes())?,
+        None if body.is_empty() => Method::GET,
+        None => Method::POST,
+    };
+
+    let mut request = client.request(method, url);
+    if !body.is_empty() {
+        // explicit headers take precedence
+        if !headers.contains_key(CONTENT_TYPE) {
+            headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+        }
+        if !headers.contains_key(ACCEPT) {
+            headers.insert(
+                ACCEPT,
+                HeaderValue::from_static("application/json, */*;q=0.5"),
+            );
+        }
+        request = request.body(serde_json::to_vec(&body)?);
+    }
+    Ok(request.headers(headers).build()?)
+}
+
+// ":8080/path" is a port of localhost, and a url without scheme is http
+fn normalize_url(url: &str) -> String {
+    match url.strip_prefix(':') {
+        Some(rest) if rest.starts_with('/') => format!("http://localhost{}", rest),
+        Some(rest) => format!("http://localhost:{}", rest),
+        None if url.contains("://") => url.to_string(),
+        None => format!("http://{}", url),
+    }
+}
+
+fn write_headers(w: &mut impl Write, headers: &HeaderMap) -> std::io::Result<()> {
+    for (name, value) in headers {
+        writeln!(w, "{}: {}", name, String::from_utf8_lossy(value.as_bytes()))?;
+    }
+    writeln!(w)
+}
+
+// a JSON body is indented, a binary one is only described
+fn pretty_body(headers: &HeaderMap, body: &[u8]) -> String {
+    let is_json = headers
+        .get(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.contains("json"));
+    if is_json {
+        if let Ok(value) = serde_json::from_slice::<Value>(body) {
+            if let Ok(pretty) = serde_json::to_string_pretty(&value) {
+                return pretty;
+            }
+        }
+    }
+    match std::str::from_utf8(body) {
+        Ok(text) if !text.contains('\0') => text.to_string(),
+        _ => format!("[binary data, {} bytes]", body.len()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_request_item() -> Result<()> {
+        assert_eq!(
+            RequestItem::parse("X-Token:a=b")?,
+            RequestItem::Header("X-Token".into(), "a=b".into())
+        );
+        assert_eq!(
+            RequestItem::parse("q==a:b")?,
+            RequestItem::Query("q".into(), "a:b".into())
+        );
+        assert_eq!(
+            RequestItem::parse("name=x:=y")?,
+            RequestItem::Field("name".into(), "x:=y".into())
+        );
+        assert_eq!(
+            RequestItem::parse("tags:=[1, 2]")?,
+            RequestItem::JsonField("tags".into(), serde_json::json!([1, 2]))
+        );
+        assert!(RequestItem::parse("tags:=[1,").is_err());
+        assert!(RequestItem::parse("=value").is_err());
+        assert!(RequestItem::parse("plain").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_build_request() -> Result<()> {
+        let client = Client::new();
+        let request = build_request(&client, &args(&[":8080/users", "page==2"]))?;
+        assert_eq!(request.method(), Method::GET);
+        assert_eq!(request.url().as_str(), "http://localhost:8080/users?page=2");
+
+        let request = build_request(
+            &client,
+            &args(&["example.com/users", "name=alice", "age:=30", "X-Id:7"]),
+        )?;
+        assert_eq!(request.method(), Method::POST);
+        assert_eq!(request.url().as_str(), "http://example.com/users");
+        assert_eq!(request.headers()["x-id"], "7");
+        assert_eq!(request.headers()[CONTENT_TYPE], "application/json");
+        let body = request.body().and_then(|b| b.as_bytes()).unwrap();
+        assert_eq!(
+            serde_json::from_slice::<Value>(body)?,
+            serde_json::json!({"name": "alice", "age": 30})
+        );
+
+        let request = build_request(&client, &args(&["PUT", "https://example.com"]))?;
+        assert_eq!(request.method(), Method::PUT);
+        assert!(build_request(&client, &[]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_pretty_body() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(pretty_body(&headers, b"{\"a\":1}"), "{\"a\":1}");
+        assert_eq!(pretty_body(&headers, b"\x00\x01"), "[binary data, 2 bytes]");
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+        assert_eq!(pretty_body(&headers, b"{\"a\":1}"), "{\n  \"a\": 1\n}");
+        assert_eq!(pretty_body(&headers, b"{oops"), "{oops");
+    }
+}
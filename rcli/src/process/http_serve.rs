@@ -1,122 +1,638 @@
-use std::convert::Infallible;
-use std::task::{Context, Poll};
-use std::{net::SocketAddr, path::PathBuf, sync::Arc};
+use std::cmp::Ordering;
+use std::path::{Component, Path, PathBuf};
+use std::time::SystemTime;
+use std::{net::SocketAddr, sync::Arc};
 
 use anyhow::Result;
 use axum::{
-    extract::State,
-    http::StatusCode,
-    response::{Html, IntoResponse, Response},
-    Router,
+    body::Body,
+    extract::{DefaultBodyLimit, FromRequest, Multipart, Query, Request, State},
+    http::{header, Method, StatusCode},
+    middleware::{self, Next},
+    response::{Html, IntoResponse, Redirect, Response},
+    Json, Router,
 };
-use tower_http::services::ServeDir;
+use axum_server::tls_rustls::RustlsConfig;
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use chrono::{DateTime, SecondsFormat, Utc};
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use serde::{Deserialize, Serialize};
+use tokio::io::AsyncWriteExt;
+use tower_http::services::ServeFile;
 use tower_service::Service;
-use tracing::info;
+use tracing::{info, warn};
+
+// the characters which are left as is in a path segment of a link
+const SEGMENT: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~');
 
 #[derive(Debug, Clone)]
 struct HttpServeState {
+    // canonical, so that a resolved path is confined to it when it starts with it
     path: PathBuf,
+    upload: bool,
+    // the expected authorization header, None when basic auth is disabled
+    auth: Option<String>,
 }
 
-pub async fn process_http_serve(path: PathBuf, port: u16) -> Result<()> {
-    let addr = SocketAddr::from(([0, 0, 0, 0], port));
-    info!("Serving {:?} on {}", path, addr);
-
-    let state = HttpServeState { path: path.clone() };
-    let dir_service = ServeDir::new(path)
-        .append_index_html_on_directories(true)
-        .precompressed_gzip()
-        .precompressed_br()
-        .precompressed_deflate()
-        .precompressed_zstd()
-        .fallback(ListDirService {
-            state: Arc::new(state.clone()),
-        });
+#[derive(Debug, Default, Deserialize)]
+struct ListQuery {
+    #[serde(default)]
+    sort: SortKey,
+    #[serde(default)]
+    order: SortOrder,
+    format: Option<ListFormat>,
+}
 
-    let router = Router::new()
-        .nest_service("/", dir_service)
-        .with_state(Arc::new(state));
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum SortKey {
+    #[default]
+    Name,
+    Size,
+    Mtime,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum SortOrder {
+    #[default]
+    Asc,
+    Desc,
+}
 
-    let listener = tokio::net::TcpListener::bind(&addr).await?;
-    axum::serve(listener, router.into_make_service()).await?;
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum ListFormat {
+    Html,
+    Json,
+}
+
+#[derive(Debug, Serialize)]
+struct DirEntry {
+    name: String,
+    is_dir: bool,
+    size: u64,
+    // rfc3339, None if the platform doesn't report it
+    modified: Option<String>,
+    #[serde(skip)]
+    mtime: Option<SystemTime>,
+}
+
+pub async fn process_http_serve(
+    path: PathBuf,
+    port: u16,
+    upload: bool,
+    tls: Option<(String, String)>,
+    auth: Option<String>,
+) -> Result<()> {
+    let addr = SocketAddr::from(([0, 0, 0, 0], port));
+    let state = HttpServeState {
+        path: path.canonicalize()?,
+        upload,
+        auth: auth.map(|credentials| format!("Basic {}", STANDARD.encode(credentials))),
+    };
+    let router = router(Arc::new(state));
+
+    match tls {
+        Some((cert, key)) => {
+            info!("Serving {:?} on https://{}", path, addr);
+            let config = RustlsConfig::from_pem_file(cert, key).await?;
+            axum_server::bind_rustls(addr, config)
+                .serve(router.into_make_service())
+                .await?;
+        }
+        None => {
+            info!("Serving {:?} on http://{}", path, addr);
+            let listener = tokio::net::TcpListener::bind(&addr).await?;
+            axum::serve(listener, router.into_make_service()).await?;
+        }
+    }
     Ok(())
 }
 
-async fn list_dir(
+fn router(state: Arc<HttpServeState>) -> Router {
+    Router::new()
+        .fallback(serve)
+        // uploads are streamed to disk, their size is not limited
+        .layer(DefaultBodyLimit::disable())
+        .layer(middleware::from_fn_with_state(state.clone(), basic_auth))
+        .with_state(state)
+}
+
+async fn basic_auth(
     State(state): State<Arc<HttpServeState>>,
-    path: &str,
-) -> Result<Response, StatusCode> {
-    let full_path = std::path::Path::new(&state.path).join(path);
-    if !full_path.exists() {
-        return Err(StatusCode::NOT_FOUND);
-    }
-    let mut html = String::from("<html><body><ul>");
-    full_path.read_dir().unwrap().for_each(|entry| {
-        if let Ok(entry) = entry {
-            let path = entry.path();
-            let name = path.file_name().unwrap().to_string_lossy();
-            let mut dash = String::from("");
-            if entry.path().is_dir() {
-                dash = String::from("/");
+    req: Request,
+    next: Next,
+) -> Response {
+    let Some(expected) = &state.auth else {
+        return next.run(req).await;
+    };
+    let given = req
+        .headers()
+        .get(header::AUTHORIZATION)
+        .map(|v| v.as_bytes())
+        .unwrap_or_default();
+    // blake3 hashes are compared in constant time, unlike the credentials themselves
+    if blake3::hash(given) == blake3::hash(expected.as_bytes()) {
+        return next.run(req).await;
+    }
+    (
+        StatusCode::UNAUTHORIZED,
+        [(header::WWW_AUTHENTICATE, "Basic realm=\"rcli\"")],
+    )
+        .into_response()
+}
+
+async fn serve(State(state): State<Arc<HttpServeState>>, req: Request) -> Response {
+    let Some(path) = resolve(&state.path, req.uri().path()) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    if path.is_dir() && !req.uri().path().ends_with('/') {
+        // links in the listing are relative to the directory
+        let location = match req.uri().query() {
+            Some(query) => format!("{}/?{}", req.uri().path(), query),
+            None => format!("{}/", req.uri().path()),
+        };
+        return Redirect::temporary(&location).into_response();
+    }
+    match (req.method(), path.is_dir()) {
+        (&Method::POST, true) if state.upload => upload(state, path, req).await,
+        (&Method::GET | &Method::HEAD, true) if path.join("index.html").is_file() => {
+            serve_file(path.join("index.html"), req).await
+        }
+        (&Method::GET | &Method::HEAD, true) => {
+            let query = Query::<ListQuery>::try_from_uri(req.uri());
+            let Ok(Query(query)) = query else {
+                return (StatusCode::BAD_REQUEST, "Invalid listing query").into_response();
+            };
+            let json = query.format == Some(ListFormat::Json)
+                || (query.format.is_none() && accepts_json(&req));
+            match list_dir(&state, &path, &query, json).await {
+                Ok(response) => response,
+                Err(status) => status.into_response(),
             }
-            html.push_str(&format!(
-                "<li><a href=\"{}{}\">{}{}</a></li>",
-                name, dash, name, dash
-            ));
+        }
+        (&Method::GET | &Method::HEAD, false) => serve_file(path, req).await,
+        _ => StatusCode::METHOD_NOT_ALLOWED.into_response(),
+    }
+}
+
+/// The file or directory of a request path. None if it doesn't exist or if it is outside of
+/// `root`, through `..` or a symbolic link.
+fn resolve(root: &Path, uri_path: &str) -> Option<PathBuf> {
+    let decoded = percent_decode_str(uri_path).decode_utf8().ok()?;
+    let mut path = root.to_path_buf();
+    for segment in decoded.split('/').filter(|s| !s.is_empty() && *s != ".") {
+        // a segment such as "..", "a\b" or "C:" is refused rather than interpreted
+        let mut components = Path::new(segment).components();
+        match (components.next(), components.next()) {
+            (Some(Component::Normal(name)), None) if !segment.contains('\\') => path.push(name),
+            _ => return None,
+        }
+    }
+    let path = path.canonicalize().ok()?;
+    path.starts_with(root).then_some(path)
+}
+
+// range and conditional requests are handled by ServeFile, its precompressed variants are
+// not used as those sibling files would bypass `resolve`
+async fn serve_file(path: PathBuf, req: Request) -> Response {
+    let mut service = ServeFile::new(path);
+    match service.call(req).await {
+        Ok(response) => response.map(Body::new),
+        Err(e) => match e {},
+    }
+}
+
+fn accepts_json(req: &Request) -> bool {
+    req.headers()
+        .get(header::ACCEPT)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.contains("application/json"))
+}
+
+async fn list_dir(
+    state: &HttpServeState,
+    path: &Path,
+    query: &ListQuery,
+    json: bool,
+) -> Result<Response, StatusCode> {
+    let mut dir = tokio::fs::read_dir(path)
+        .await
+        .map_err(|_| StatusCode::FORBIDDEN)?;
+    let mut entries = Vec::new();
+    while let Some(entry) = dir
+        .next_entry()
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+    {
+        // an entry removed meanwhile or a broken link is left out
+        let Ok(metadata) = tokio::fs::metadata(entry.path()).await else {
+            continue;
+        };
+        let mtime = metadata.modified().ok();
+        entries.push(DirEntry {
+            name: entry.file_name().to_string_lossy().to_string(),
+            is_dir: metadata.is_dir(),
+            size: if metadata.is_dir() { 0 } else { metadata.len() },
+            modified: mtime
+                .map(|t| DateTime::<Utc>::from(t).to_rfc3339_opts(SecondsFormat::Secs, true)),
+            mtime,
+        });
+    }
+    sort_entries(&mut entries, query.sort, query.order);
+
+    if json {
+        return Ok(Json(entries).into_response());
+    }
+    let root = path == state.path;
+    Ok(Html(render_listing(
+        path,
+        &state.path,
+        &entries,
+        query,
+        root,
+        state.upload,
+    ))
+    .into_response())
+}
+
+// directories first, then by the key in the given order
+fn sort_entries(entries: &mut [DirEntry], key: SortKey, order: SortOrder) {
+    entries.sort_by(|a, b| {
+        let ordering = match key {
+            SortKey::Name => a.name.cmp(&b.name),
+            SortKey::Size => a.size.cmp(&b.size).then_with(|| a.name.cmp(&b.name)),
+            SortKey::Mtime => a.mtime.cmp(&b.mtime).then_with(|| a.name.cmp(&b.name)),
+        };
+        let ordering = match order {
+            SortOrder::Asc => ordering,
+            SortOrder::Desc => ordering.reverse(),
+        };
+        match (a.is_dir, b.is_dir) {
+            (true, false) => Ordering::Less,
+            (false, true) => Ordering::Greater,
+            _ => ordering,
         }
     });
-    html.push_str("</ul></body></html>");
-    let response = Html(html).into_response();
-    Ok(response)
 }
 
-#[derive(Debug, Clone)]
-struct ListDirService {
-    state: Arc<HttpServeState>,
-}
-
-impl<B> Service<axum::http::Request<B>> for ListDirService
-where
-    B: Send + 'static,
-{
-    type Response = Response;
-    type Error = Infallible;
-    type Future = futures::future::BoxFuture<'static, Result<Self::Response, Self::Error>>;
-
-    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<std::result::Result<(), Self::Error>> {
-        Poll::Ready(Ok(()))
-    }
-
-    fn call(&mut self, req: axum::http::Request<B>) -> Self::Future {
-        let state = self.state.clone();
-        let path = req.uri().path()[1..].to_string();
-
-        Box::pin(async move {
-            match list_dir(State(state), &path).await {
-                Ok(response) => Ok(response),
-                Err(status_code) => {
-                    let response = Response::builder()
-                        .status(status_code)
-                        .body("".into())
-                        .unwrap();
-                    Ok(response)
-                }
+fn render_listing(
+    path: &Path,
+    root: &Path,
+    entries: &[DirEntry],
+    query: &ListQuery,
+    is_root: bool,
+    upload: bool,
+) -> String {
+    let title = format!(
+        "/{}",
+        path.strip_prefix(root).unwrap_or(path).to_string_lossy()
+    );
+    // clicking on the column of the current sort reverses it
+    let header = |key: SortKey, label: &str| {
+        let order = match (query.sort == key, query.order) {
+            (true, SortOrder::Asc) => "desc",
+            _ => "asc",
+        };
+        let name = match key {
+            SortKey::Name => "name",
+            SortKey::Size => "size",
+            SortKey::Mtime => "mtime",
+        };
+        format!(
+            "<th><a href=\"?sort={}&amp;order={}\">{}</a></th>",
+            name, order, label
+        )
+    };
+    let mut html = format!(
+        "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>{}</title></head><body>\n<h1>{}</h1>\n",
+        escape_html(&title),
+        escape_html(&title)
+    );
+    if upload {
+        html.push_str(
+            "<form method=\"post\" enctype=\"multipart/form-data\"><input type=\"file\" name=\"file\" multiple> <button type=\"submit\">Upload</button></form>\n",
+        );
+    }
+    html.push_str(&format!(
+        "<table>\n<tr>{}{}{}</tr>\n",
+        header(SortKey::Name, "Name"),
+        header(SortKey::Size, "Size"),
+        header(SortKey::Mtime, "Modified")
+    ));
+    if !is_root {
+        html.push_str("<tr><td><a href=\"../\">../</a></td><td></td><td></td></tr>\n");
+    }
+    for entry in entries {
+        let suffix = if entry.is_dir { "/" } else { "" };
+        let size = match entry.is_dir {
+            true => "-".to_string(),
+            false => human_size(entry.size),
+        };
+        html.push_str(&format!(
+            "<tr><td><a href=\"{}{}\">{}{}</a></td><td>{}</td><td>{}</td></tr>\n",
+            utf8_percent_encode(&entry.name, SEGMENT),
+            suffix,
+            escape_html(&entry.name),
+            suffix,
+            size,
+            entry.modified.as_deref().unwrap_or_default()
+        ));
+    }
+    html.push_str("</table>\n</body></html>\n");
+    html
+}
+
+fn escape_html(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn human_size(size: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    if size < 1024 {
+        return format!("{} B", size);
+    }
+    let mut value = size as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", value, UNITS[unit])
+}
+
+// the files of a multipart form are streamed into the directory, existing files are kept and
+// the request fails as a whole
+async fn upload(state: Arc<HttpServeState>, dir: PathBuf, req: Request) -> Response {
+    let browser = req
+        .headers()
+        .get(header::ACCEPT)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.contains("text/html"));
+    let location = req.uri().path().to_string();
+    let mut multipart = match Multipart::from_request(req, &state).await {
+        Ok(multipart) => multipart,
+        Err(rejection) => return rejection.into_response(),
+    };
+    let mut uploaded = Vec::new();
+    let failed = loop {
+        let mut field = match multipart.next_field().await {
+            Ok(Some(field)) => field,
+            Ok(None) => break None,
+            Err(e) => break Some((StatusCode::BAD_REQUEST, e.body_text()).into_response()),
+        };
+        // fields without a file name are regular form values
+        let Some(name) = field.file_name().map(String::from) else {
+            continue;
+        };
+        let mut components = Path::new(&name).components();
+        let valid = matches!(
+            (components.next(), components.next()),
+            (Some(Component::Normal(_)), None)
+        ) && !name.contains('\\');
+        if !valid {
+            break Some(
+                (
+                    StatusCode::BAD_REQUEST,
+                    format!("Invalid file name {:?}", name),
+                )
+                    .into_response(),
+            );
+        }
+        let target = dir.join(&name);
+        let mut file = match tokio::fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&target)
+            .await
+        {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+                break Some(
+                    (StatusCode::CONFLICT, format!("{} already exists", name)).into_response(),
+                )
             }
-        })
+            Err(e) => {
+                warn!("Failed to create {:?}: {}", target, e);
+                break Some(StatusCode::INTERNAL_SERVER_ERROR.into_response());
+            }
+        };
+        let written = async {
+            while let Some(chunk) = field.chunk().await? {
+                file.write_all(&chunk).await?;
+            }
+            file.flush().await?;
+            anyhow::Ok(())
+        };
+        if let Err(e) = written.await {
+            // a partial file is not left behind
+            let _ = tokio::fs::remove_file(&target).await;
+            break Some((StatusCode::BAD_REQUEST, e.to_string()).into_response());
+        }
+        info!("Uploaded {:?}", target);
+        uploaded.push(name);
+    };
+    // the files of a request are stored all or none, a failure removes those already written
+    if let Some(response) = failed {
+        for name in uploaded {
+            let _ = tokio::fs::remove_file(dir.join(name)).await;
+        }
+        return response;
+    }
+    match browser {
+        true => Redirect::to(&location).into_response(),
+        false => (
+            StatusCode::CREATED,
+            Json(serde_json::json!({ "uploaded": uploaded })),
+        )
+            .into_response(),
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use axum::http::HeaderValue;
+
     use super::*;
 
+    // a directory with a few files, removed by the test
+    fn served_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("rcli-serve-{}-{}", std::process::id(), name));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("sub")).unwrap();
+        std::fs::write(dir.join("a.txt"), "0123456789").unwrap();
+        std::fs::write(dir.join("<b>&.txt"), "x").unwrap();
+        dir.canonicalize().unwrap()
+    }
+
+    fn state(path: &Path) -> Arc<HttpServeState> {
+        Arc::new(HttpServeState {
+            path: path.to_path_buf(),
+            upload: true,
+            auth: None,
+        })
+    }
+
+    async fn request(router: &mut Router, req: Request) -> (StatusCode, String) {
+        let response = router.call(req).await.unwrap();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, String::from_utf8_lossy(&body).to_string())
+    }
+
+    fn get(uri: &str) -> Request {
+        Request::get(uri).body(Body::empty()).unwrap()
+    }
+
     #[tokio::test]
     async fn test_file_handler() {
-        let path = PathBuf::from("./src");
-        let state = Arc::new(HttpServeState { path: path.clone() });
-        let result = list_dir(State(state), "process").await;
+        let path = PathBuf::from("./src").canonicalize().unwrap();
+        let state = state(&path);
+        let result = list_dir(&state, &path.join("process"), &ListQuery::default(), false).await;
         assert!(result.is_ok());
         let response = result.unwrap();
         assert_eq!(response.status(), StatusCode::OK);
     }
+
+    #[tokio::test]
+    async fn test_listing_and_confinement() {
+        let dir = served_dir("listing");
+        let mut router = router(state(&dir));
+
+        let (status, body) = request(&mut router, get("/?sort=size&order=desc&format=json")).await;
+        assert_eq!(status, StatusCode::OK);
+        let entries: serde_json::Value = serde_json::from_str(&body).unwrap();
+        let names = entries
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|e| e["name"].as_str().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["sub", "a.txt", "<b>&.txt"]);
+        assert_eq!(entries[1]["size"], 10);
+
+        let (_, html) = request(&mut router, get("/")).await;
+        assert!(html.contains("<a href=\"%3Cb%3E%26.txt\">&lt;b&gt;&amp;.txt</a>"));
+        let (status, _) = request(&mut router, get("/sub")).await;
+        assert_eq!(status, StatusCode::TEMPORARY_REDIRECT);
+
+        for uri in [
+            "/../a.txt",
+            "/%2e%2e/a.txt",
+            "/sub/..%2f..%2fetc",
+            "/missing",
+        ] {
+            let (status, _) = request(&mut router, get(uri)).await;
+            assert_eq!(status, StatusCode::NOT_FOUND, "{}", uri);
+        }
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_range_and_auth() {
+        let dir = served_dir("range");
+        let state = Arc::new(HttpServeState {
+            path: dir.clone(),
+            upload: false,
+            auth: Some(format!("Basic {}", STANDARD.encode("user:secret"))),
+        });
+        let mut router = router(state);
+        let (status, _) = request(&mut router, get("/a.txt")).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+        let mut req = get("/a.txt");
+        let credentials = format!("Basic {}", STANDARD.encode("user:secret"));
+        req.headers_mut().insert(
+            header::AUTHORIZATION,
+            HeaderValue::from_str(&credentials).unwrap(),
+        );
+        req.headers_mut()
+            .insert(header::RANGE, HeaderValue::from_static("bytes=2-5"));
+        let (status, body) = request(&mut router, req).await;
+        assert_eq!(status, StatusCode::PARTIAL_CONTENT);
+        assert_eq!(body, "2345");
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_compressed_sibling_confined() {
+        let dir = served_dir("sibling");
+        let outside = dir.with_extension("secret");
+        std::fs::write(&outside, "secret").unwrap();
+        std::os::unix::fs::symlink(&outside, dir.join("a.txt.gz")).unwrap();
+        let mut router = router(state(&dir));
+
+        let mut req = get("/a.txt");
+        req.headers_mut()
+            .insert(header::ACCEPT_ENCODING, HeaderValue::from_static("gzip"));
+        let (status, body) = request(&mut router, req).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, "0123456789");
+        let (status, _) = request(&mut router, get("/a.txt.gz")).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        std::fs::remove_dir_all(dir).unwrap();
+        std::fs::remove_file(outside).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_upload() {
+        let dir = served_dir("upload");
+        let mut router = router(state(&dir));
+        let upload = |names: &[&str]| {
+            let mut body = String::new();
+            for name in names {
+                body.push_str(&format!(
+                    "--XX\r\nContent-Disposition: form-data; name=\"file\"; filename=\"{}\"\r\n\r\nhello\r\n",
+                    name
+                ));
+            }
+            body.push_str("--XX--\r\n");
+            Request::post("/sub/")
+                .header(header::CONTENT_TYPE, "multipart/form-data; boundary=XX")
+                .body(Body::from(body))
+                .unwrap()
+        };
+        let (status, body) = request(&mut router, upload(&["new.txt"])).await;
+        assert_eq!(status, StatusCode::CREATED, "{}", body);
+        assert_eq!(
+            std::fs::read_to_string(dir.join("sub/new.txt")).unwrap(),
+            "hello"
+        );
+        let (status, _) = request(&mut router, upload(&["new.txt"])).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        let (status, _) = request(&mut router, upload(&["../escape.txt"])).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(!dir.join("escape.txt").exists());
+
+        // a failed request leaves none of its files behind
+        let (status, _) = request(&mut router, upload(&["first.txt", "new.txt"])).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert!(!dir.join("sub/first.txt").exists());
+        let (status, _) = request(&mut router, upload(&["first.txt", "../escape.txt"])).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(!dir.join("sub/first.txt").exists());
+        let (status, body) = request(&mut router, upload(&["first.txt", "second.txt"])).await;
+        assert_eq!(status, StatusCode::CREATED, "{}", body);
+        assert!(dir.join("sub/first.txt").exists() && dir.join("sub/second.txt").exists());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
 }
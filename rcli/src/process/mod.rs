@@ -1,6 +1,8 @@
 pub use b64::{process_decode, process_encode};
 pub use csv_convert::process_csv;
 pub use gen_pass::process_genpass;
+pub use http_proxy::process_http_proxy;
+pub use http_request::process_http_request;
 pub use http_serve::process_http_serve;
 pub use jwt::{process_jwt_decode, process_jwt_encode, Claim};
 pub use text::{
@@ -11,6 +13,8 @@ pub use text::{
 mod b64;
 mod csv_convert;
 mod gen_pass;
+mod http_proxy;
+mod http_request;
 mod http_serve;
 mod jwt;
 mod text;
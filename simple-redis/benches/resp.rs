@@ -1,8 +1,16 @@
+use std::net::SocketAddr;
+
 use anyhow::Result;
-use bytes::BytesMut;
-use criterion::{black_box, Criterion, criterion_group, criterion_main};
+use bytes::{Buf, BytesMut};
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::runtime::Runtime;
+
+use simple_redis::{network, parse_frame, parse_frame_length, Backend, RespFrame};
 
-use simple_redis::{parse_frame, parse_frame_length, RespFrame};
+// number of commands sent per iteration of the end-to-end benchmarks
+const COMMANDS: usize = 100;
 
 const DATA: &str = "*3\r\n$3\r\nSET\r\n$3\r\nkey\r\n$5\r\nvalue\r\n*1\r\n+OK\r\n*2\r\n$3\r\nGET\r\n$3\r\nkey\r\n$5\r\nvalue\r\n*4\r\n$4\r\nHSET\r\n$3\r\nkey\r\n$5\r\nfield\r\n$5\r\nvalue\r\n*1\r\n-ERR\r\n*3\r\n$4\r\nHGET\r\n$3\r\nkey\r\n$5\r\nfield\r\n$5\r\nvalue\r\n*3\r\n$4\r\nSADD\r\n$3\r\nkey\r\n$6\r\nmember\r\n:1\r\n";
 
@@ -86,5 +94,90 @@ fn criterion_benchmark(c: &mut Criterion) {
     });
 }
 
-criterion_group!(benches, criterion_benchmark);
+async fn start_server() -> Result<SocketAddr> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+    let backend = Backend::new();
+    tokio::spawn(async move {
+        while let Ok((stream, raddr)) = listener.accept().await {
+            stream.set_nodelay(true).ok();
+            tokio::spawn(network::stream_handler(stream, raddr, backend.clone()));
+        }
+    });
+    Ok(addr)
+}
+
+// wait for the replies of n commands
+async fn read_replies(stream: &mut TcpStream, buf: &mut BytesMut, mut n: usize) -> Result<()> {
+    while n > 0 {
+        match parse_frame_length(buf) {
+            Ok(len) => {
+                buf.advance(len);
+                n -= 1;
+            }
+            Err(_) => {
+                if stream.read_buf(buf).await? == 0 {
+                    anyhow::bail!("connection closed");
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+// one round trip per command
+async fn sequential(
+    stream: &mut TcpStream,
+    buf: &mut BytesMut,
+    commands: &[Vec<u8>],
+) -> Result<()> {
+    for cmd in commands {
+        stream.write_all(cmd).await?;
+        read_replies(stream, buf, 1).await?;
+    }
+    Ok(())
+}
+
+// all the commands in a single write, then all the replies
+async fn pipelined(stream: &mut TcpStream, buf: &mut BytesMut, batch: &[u8]) -> Result<()> {
+    stream.write_all(batch).await?;
+    read_replies(stream, buf, COMMANDS).await
+}
+
+fn e2e_benchmark(c: &mut Criterion) {
+    let rt = Runtime::new().unwrap();
+    let mut stream = rt
+        .block_on(async {
+            let addr = start_server().await?;
+            let stream = TcpStream::connect(addr).await?;
+            stream.set_nodelay(true)?;
+            Ok::<_, anyhow::Error>(stream)
+        })
+        .unwrap();
+    let mut buf = BytesMut::new();
+    let commands = (0..COMMANDS)
+        .map(|i| match i % 2 {
+            0 => format!("*3\r\n$3\r\nset\r\n$3\r\nkey\r\n$5\r\nv{:04}\r\n", i),
+            _ => "*2\r\n$3\r\nget\r\n$3\r\nkey\r\n".to_string(),
+        })
+        .map(String::into_bytes)
+        .collect::<Vec<_>>();
+    let batch = commands.concat();
+
+    c.bench_function("e2e 100 commands sequential", |b| {
+        b.iter(|| {
+            rt.block_on(sequential(&mut stream, &mut buf, &commands))
+                .unwrap()
+        })
+    });
+
+    c.bench_function("e2e 100 commands pipelined", |b| {
+        b.iter(|| {
+            rt.block_on(pipelined(&mut stream, &mut buf, &batch))
+                .unwrap()
+        })
+    });
+}
+
+criterion_group!(benches, criterion_benchmark, e2e_benchmark);
 criterion_main!(benches);
@@ -0,0 +1,507 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::{PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+use bytes::Bytes;
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+
+use crate::cmd::{CommandSpec, ACL_CATEGORIES, COMMAND_TABLE};
+use crate::{glob_match, Backend};
+
+const DEFAULT_USER: &str = "default";
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum AclError {
+    #[error("NOAUTH Authentication required.")]
+    NoAuth,
+    #[error("WRONGPASS invalid username-password pair or user is disabled.")]
+    WrongPass,
+    #[error("NOPERM User {0} has no permissions to run the '{1}' command")]
+    CommandDenied(String, String),
+    #[error("NOPERM No permissions to access a key")]
+    KeyDenied,
+    #[error("NOPERM No permissions to access a channel")]
+    ChannelDenied,
+    #[error("ERR Error in ACL SETUSER modifier '{0}': {1}")]
+    InvalidRule(String, &'static str),
+    #[error("ERR The 'default' user cannot be removed")]
+    DefaultUser,
+}
+
+/// The users known to the server, who a client authenticates as.
+#[derive(Debug)]
+pub struct Acl {
+    users: RwLock<BTreeMap<String, User>>,
+}
+
+/// A user with its passwords and what it is allowed to do, as set by ACL SETUSER.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct User {
+    name: String,
+    enabled: bool,
+    // any password is accepted
+    nopass: bool,
+    // hex encoded SHA-256 digests of the passwords
+    passwords: BTreeSet<String>,
+    commands: BTreeSet<&'static str>,
+    keys: Vec<KeyPattern>,
+    channels: Vec<String>,
+}
+
+// a pattern of the keys a user may read, write or both
+#[derive(Debug, Clone, PartialEq)]
+struct KeyPattern {
+    pattern: String,
+    read: bool,
+    write: bool,
+}
+
+impl Backend {
+    pub fn acl(&self) -> &Acl {
+        &self.acl
+    }
+}
+
+impl Acl {
+    /// The default user may run any command on any key, with a password if `requirepass` is set.
+    pub fn new(requirepass: Option<&str>) -> Self {
+        let mut default = User::new(DEFAULT_USER);
+        let password = match requirepass {
+            Some(password) => format!(">{}", password),
+            None => "nopass".to_string(),
+        };
+        for rule in ["on", &password, "allkeys", "allchannels", "allcommands"] {
+            default
+                .apply(rule)
+                .expect("the rules of the default user are valid");
+        }
+        Self {
+            users: RwLock::new(BTreeMap::from([(DEFAULT_USER.to_string(), default)])),
+        }
+    }
+
+    /// Replace the passwords of the default user, as CONFIG SET requirepass does.
+    pub fn set_requirepass(&self, requirepass: Option<&str>) {
+        let password = match requirepass {
+            Some(password) => format!(">{}", password),
+            None => "nopass".to_string(),
+        };
+        if let Some(default) = self.write().get_mut(DEFAULT_USER) {
+            for rule in ["resetpass", &password] {
+                default.apply(rule).expect("password rules are valid");
+            }
+        }
+    }
+
+    /// Whether new connections are authenticated as the default user, which is the case
+    /// unless it requires a password.
+    pub fn auto_login(&self) -> bool {
+        self.read()
+            .get(DEFAULT_USER)
+            .is_some_and(|user| user.enabled && user.nopass)
+    }
+
+    /// Check the password of an enabled user.
+    pub fn authenticate(&self, username: &str, password: &str) -> Result<(), AclError> {
+        match self.read().get(username) {
+            Some(user)
+                if user.enabled && (user.nopass || user.passwords.contains(&digest(password))) =>
+            {
+                Ok(())
+            }
+            _ => Err(AclError::WrongPass),
+        }
+    }
+
+    /// Check that the user may run the command on the keys and the channels of the request.
+    /// A user which no longer exists must authenticate again.
+    pub fn check(
+        &self,
+        username: &str,
+        spec: &CommandSpec,
+        keys: &[Bytes],
+        channels: &[Bytes],
+    ) -> Result<(), AclError> {
+        let users = self.read();
+        let user = users.get(username).ok_or(AclError::NoAuth)?;
+        if !user.commands.contains(spec.name) {
+            return Err(AclError::CommandDenied(
+                username.to_string(),
+                spec.name.to_string(),
+            ));
+        }
+        let write = spec.has_flag("write");
+        let key_allowed = |key: &Bytes| {
+            user.keys.iter().any(|pattern| {
+                (if write { pattern.write } else { pattern.read })
+                    && glob_match(pattern.pattern.as_bytes(), key)
+            })
+        };
+        if !keys.iter().all(key_allowed) {
+            return Err(AclError::KeyDenied);
+        }
+        // the patterns of PSUBSCRIBE must be allowed as they are, not only the channels they match
+        let literal = spec.name == "psubscribe";
+        let channel_allowed = |channel: &Bytes| {
+            user.channels.iter().any(|pattern| match literal {
+                true => pattern == "*" || pattern.as_bytes() == channel.as_ref(),
+                false => glob_match(pattern.as_bytes(), channel),
+            })
+        };
+        if !channels.iter().all(channel_allowed) {
+            return Err(AclError::ChannelDenied);
+        }
+        Ok(())
+    }
+
+    /// Create or modify a user by applying the rules in order. The user is left untouched if
+    /// any of the rules is invalid.
+    pub fn set_user(&self, username: &str, rules: &[String]) -> Result<(), AclError> {
+        let mut users = self.write();
+        // a new user is disabled and can do nothing until rules allow it
+        let mut user = users
+            .get(username)
+            .cloned()
+            .unwrap_or_else(|| User::new(username));
+        for rule in rules {
+            user.apply(rule)?;
+        }
+        users.insert(username.to_string(), user);
+        Ok(())
+    }
+
+    pub fn user(&self, username: &str) -> Option<User> {
+        self.read().get(username).cloned()
+    }
+
+    pub fn users(&self) -> Vec<User> {
+        self.read().values().cloned().collect()
+    }
+
+    /// Remove users. Returns the number of users which existed.
+    pub fn del_users(&self, usernames: &[String]) -> Result<usize, AclError> {
+        if usernames.iter().any(|name| name == DEFAULT_USER) {
+            return Err(AclError::DefaultUser);
+        }
+        let mut users = self.write();
+        Ok(usernames
+            .iter()
+            .filter(|name| users.remove(name.as_str()).is_some())
+            .count())
+    }
+
+    fn read(&self) -> RwLockReadGuard<'_, BTreeMap<String, User>> {
+        self.users.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn write(&self) -> RwLockWriteGuard<'_, BTreeMap<String, User>> {
+        self.users.write().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl User {
+    fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            ..Default::default()
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn nopass(&self) -> bool {
+        self.nopass
+    }
+
+    /// The digests of the passwords, as listed by ACL GETUSER.
+    pub fn passwords(&self) -> impl Iterator<Item = &str> {
+        self.passwords.iter().map(|v| v.as_str())
+    }
+
+    /// The allowed commands, as the rules which allow them starting from no command.
+    pub fn describe_commands(&self) -> String {
+        let all = COMMAND_TABLE.len();
+        if self.commands.len() == all {
+            return "+@all".to_string();
+        }
+        // the shortest description of the two
+        if self.commands.len() * 2 > all {
+            let denied = COMMAND_TABLE
+                .iter()
+                .filter(|spec| !self.commands.contains(spec.name))
+                .map(|spec| format!(" -{}", spec.name));
+            std::iter::once("+@all".to_string()).chain(denied).collect()
+        } else {
+            let allowed = self.commands.iter().map(|name| format!(" +{}", name));
+            std::iter::once("-@all".to_string())
+                .chain(allowed)
+                .collect()
+        }
+    }
+
+    pub fn describe_keys(&self) -> String {
+        self.keys
+            .iter()
+            .map(|key| match (key.read, key.write) {
+                (true, true) => format!("~{}", key.pattern),
+                (true, false) => format!("%R~{}", key.pattern),
+                _ => format!("%W~{}", key.pattern),
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    pub fn describe_channels(&self) -> String {
+        self.channels
+            .iter()
+            .map(|channel| format!("&{}", channel))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// The user as the rules which create it, as listed by ACL LIST.
+    pub fn describe(&self) -> String {
+        let mut rules = vec![
+            "user".to_string(),
+            self.name.clone(),
+            if self.enabled { "on" } else { "off" }.to_string(),
+        ];
+        if self.nopass {
+            rules.push("nopass".to_string());
+        }
+        rules.extend(self.passwords.iter().map(|v| format!("#{}", v)));
+        if !self.keys.is_empty() {
+            rules.push(self.describe_keys());
+        }
+        rules.push(match self.channels.is_empty() {
+            true => "resetchannels".to_string(),
+            false => self.describe_channels(),
+        });
+        rules.push(self.describe_commands());
+        rules.join(" ")
+    }
+
+    fn apply(&mut self, rule: &str) -> Result<(), AclError> {
+        let invalid = |reason| AclError::InvalidRule(rule.to_string(), reason);
+        match rule.to_ascii_lowercase().as_str() {
+            "on" => self.enabled = true,
+            "off" => self.enabled = false,
+            "nopass" => {
+                self.nopass = true;
+                self.passwords.clear();
+            }
+            "resetpass" => {
+                self.nopass = false;
+                self.passwords.clear();
+            }
+            "allkeys" => self.apply("~*")?,
+            "resetkeys" => self.keys.clear(),
+            "allchannels" => self.apply("&*")?,
+            "resetchannels" => self.channels.clear(),
+            "allcommands" => self.apply("+@all")?,
+            "nocommands" => self.apply("-@all")?,
+            "reset" => {
+                for rule in [
+                    "resetpass",
+                    "resetkeys",
+                    "resetchannels",
+                    "nocommands",
+                    "off",
+                ] {
+                    self.apply(rule)?;
+                }
+            }
+            _ => {
+                if let Some(password) = rule.strip_prefix('>') {
+                    self.passwords.insert(digest(password));
+                    self.nopass = false;
+                } else if let Some(password) = rule.strip_prefix('<') {
+                    self.passwords.remove(&digest(password));
+                } else if let Some(hash) = rule.strip_prefix('#') {
+                    if hash.len() != 64 || !hash.bytes().all(|b| b.is_ascii_hexdigit()) {
+                        return Err(invalid("The password hash must be exactly 64 characters and contain only lowercase hexadecimal characters"));
+                    }
+                    self.passwords.insert(hash.to_ascii_lowercase());
+                    self.nopass = false;
+                } else if let Some(hash) = rule.strip_prefix('!') {
+                    self.passwords.remove(&hash.to_ascii_lowercase());
+                } else if let Some(pattern) = rule.strip_prefix('~') {
+                    self.add_key_pattern(pattern, true, true);
+                } else if let Some((flags, pattern)) =
+                    rule.strip_prefix('%').and_then(|r| r.split_once('~'))
+                {
+                    let flags = flags.to_ascii_uppercase();
+                    if flags.is_empty() || !flags.chars().all(|c| c == 'R' || c == 'W') {
+                        return Err(invalid("Syntax error"));
+                    }
+                    self.add_key_pattern(pattern, flags.contains('R'), flags.contains('W'));
+                } else if let Some(pattern) = rule.strip_prefix('&') {
+                    if !self.channels.iter().any(|v| v == pattern) {
+                        self.channels.push(pattern.to_string());
+                    }
+                } else if let Some(name) = rule.strip_prefix('+') {
+                    let names = command_names(name)
+                        .ok_or_else(|| invalid("Unknown command or category name in ACL"))?;
+                    self.commands.extend(names);
+                } else if let Some(name) = rule.strip_prefix('-') {
+                    let names = command_names(name)
+                        .ok_or_else(|| invalid("Unknown command or category name in ACL"))?;
+                    for name in names {
+                        self.commands.remove(name);
+                    }
+                } else {
+                    return Err(invalid("Syntax error"));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn add_key_pattern(&mut self, pattern: &str, read: bool, write: bool) {
+        match self.keys.iter_mut().find(|key| key.pattern == pattern) {
+            Some(key) => {
+                key.read |= read;
+                key.write |= write;
+            }
+            None => self.keys.push(KeyPattern {
+                pattern: pattern.to_string(),
+                read,
+                write,
+            }),
+        }
+    }
+}
+
+// the commands of a "@category", or the command itself, None if there is no such one
+fn command_names(name: &str) -> Option<Vec<&'static str>> {
+    let name = name.to_ascii_lowercase();
+    match name.strip_prefix('@') {
+        Some("all") => Some(COMMAND_TABLE.iter().map(|spec| spec.name).collect()),
+        Some(category) if ACL_CATEGORIES.contains(&category) => Some(
+            COMMAND_TABLE
+                .iter()
+                .filter(|spec| spec.acl_categories().contains(&category))
+                .map(|spec| spec.name)
+                .collect(),
+        ),
+        Some(_) => None,
+        None => crate::cmd::lookup(&name).map(|spec| vec![spec.name]),
+    }
+}
+
+fn digest(password: &str) -> String {
+    format!("{:x}", Sha256::digest(password.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cmd::lookup;
+
+    use super::*;
+
+    fn rules(rules: &[&str]) -> Vec<String> {
+        rules.iter().map(|v| v.to_string()).collect()
+    }
+
+    #[test]
+    fn test_default_user() {
+        let acl = Acl::new(None);
+        assert!(acl.auto_login());
+        assert_eq!(acl.authenticate("default", "anything"), Ok(()));
+        assert_eq!(
+            acl.user("default").map(|user| user.describe()),
+            Some("user default on nopass ~* &* +@all".to_string())
+        );
+
+        let acl = Acl::new(Some("secret"));
+        assert!(!acl.auto_login());
+        assert_eq!(
+            acl.authenticate("default", "wrong"),
+            Err(AclError::WrongPass)
+        );
+        assert_eq!(acl.authenticate("default", "secret"), Ok(()));
+        assert_eq!(
+            acl.del_users(&rules(&["default"])),
+            Err(AclError::DefaultUser)
+        );
+    }
+
+    #[test]
+    fn test_set_user_and_check() {
+        let acl = Acl::new(None);
+        acl.set_user(
+            "alice",
+            &rules(&["on", ">pw", "~cache:*", "%R~shared:*", "+@read", "+set"]),
+        )
+        .unwrap();
+        assert_eq!(acl.authenticate("alice", "pw"), Ok(()));
+        assert_eq!(acl.authenticate("bob", "pw"), Err(AclError::WrongPass));
+
+        let get = lookup("get").unwrap();
+        let set = lookup("set").unwrap();
+        let key = |key: &'static str| [Bytes::from(key)];
+        assert_eq!(acl.check("alice", get, &key("cache:1"), &[]), Ok(()));
+        assert_eq!(acl.check("alice", get, &key("shared:1"), &[]), Ok(()));
+        assert_eq!(
+            acl.check("alice", set, &key("shared:1"), &[]),
+            Err(AclError::KeyDenied)
+        );
+        assert_eq!(
+            acl.check("alice", lookup("del").unwrap(), &key("cache:1"), &[]),
+            Err(AclError::CommandDenied(
+                "alice".to_string(),
+                "del".to_string()
+            ))
+        );
+        assert_eq!(
+            acl.check("alice", get, &key("other"), &[]),
+            Err(AclError::KeyDenied)
+        );
+        assert_eq!(acl.check("bob", get, &[], &[]), Err(AclError::NoAuth));
+
+        // an invalid rule leaves the user as it was
+        let before = acl.user("alice");
+        assert!(acl.set_user("alice", &rules(&["off", "+nope"])).is_err());
+        assert_eq!(acl.user("alice"), before);
+
+        acl.set_user("alice", &rules(&["reset"])).unwrap();
+        assert_eq!(acl.authenticate("alice", "pw"), Err(AclError::WrongPass));
+        assert_eq!(
+            acl.user("alice").map(|user| user.describe()),
+            Some("user alice off resetchannels -@all".to_string())
+        );
+        assert_eq!(acl.del_users(&rules(&["alice", "bob"])), Ok(1));
+    }
+
+    #[test]
+    fn test_channels() {
+        let acl = Acl::new(None);
+        acl.set_user("bob", &rules(&["on", "nopass", "+@pubsub", "&news.*"]))
+            .unwrap();
+        let channel = |channel: &'static str| [Bytes::from(channel)];
+        let publish = lookup("publish").unwrap();
+        let psubscribe = lookup("psubscribe").unwrap();
+        assert_eq!(
+            acl.check("bob", publish, &[], &channel("news.tech")),
+            Ok(())
+        );
+        assert_eq!(
+            acl.check("bob", publish, &[], &channel("sport")),
+            Err(AclError::ChannelDenied)
+        );
+        assert_eq!(
+            acl.check("bob", psubscribe, &[], &channel("news.*")),
+            Ok(())
+        );
+        assert_eq!(
+            acl.check("bob", psubscribe, &[], &channel("news.t*")),
+            Err(AclError::ChannelDenied)
+        );
+    }
+}
@@ -0,0 +1,495 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use std::time::Duration;
+
+use thiserror::Error;
+use tracing::{info, warn};
+
+use crate::cmd::{Command, CommandExecutor};
+use crate::{
+    parse_frame, parse_frame_length, AofFsync, Array, Backend, BulkString, RespEncode, RespError,
+    RespFrame,
+};
+
+use super::{now_ms, Stream, StreamId};
+
+const AOF_FSYNC_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Error, Debug)]
+pub enum AofError {
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Invalid append only file: {0}")]
+    InvalidFormat(String),
+    #[error("AOF is disabled")]
+    Disabled,
+    #[error("Background append only file rewriting already in progress")]
+    AlreadyInProgress,
+}
+
+/// The append only file: write commands in their RESP encoding, in execution order.
+#[derive(Debug)]
+pub struct Aof {
+    path: PathBuf,
+    fsync: AofFsync,
+    state: Mutex<AofState>,
+    rewrite_in_progress: AtomicBool,
+}
+
+#[derive(Debug)]
+struct AofState {
+    file: File,
+    // commands appended while a rewrite is running, they go to the end of the new file
+    rewrite_buf: Option<Vec<u8>>,
+    // the database selected by the file, None until a SELECT is appended
+    db: Option<usize>,
+}
+
+/// Exclusive access to the append only file. Write commands are executed while holding it
+/// so that the order in the file is the order in which they were applied.
+pub struct AofWriter<'a> {
+    fsync: AofFsync,
+    state: MutexGuard<'a, AofState>,
+}
+
+impl Aof {
+    fn open(path: PathBuf, fsync: AofFsync) -> Result<Self, AofError> {
+        let file = open_append(&path)?;
+        Ok(Self {
+            path,
+            fsync,
+            state: Mutex::new(AofState {
+                file,
+                rewrite_buf: None,
+                db: None,
+            }),
+            rewrite_in_progress: AtomicBool::new(false),
+        })
+    }
+
+    fn state(&self) -> MutexGuard<'_, AofState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    // flush the file to disk without blocking writers during the fsync
+    fn sync(&self) -> Result<(), AofError> {
+        let file = self.state().file.try_clone()?;
+        file.sync_data()?;
+        Ok(())
+    }
+}
+
+impl AofWriter<'_> {
+    pub fn append(&mut self, frame: RespFrame) -> Result<(), AofError> {
+        self.append_encoded(&frame.encode())
+    }
+
+    /// Append a SELECT of the database at `index`, unless it is selected already.
+    pub fn select(&mut self, index: usize) -> Result<(), AofError> {
+        if self.state.db == Some(index) {
+            return Ok(());
+        }
+        self.append(command("select", vec![bulk(&index.to_string())]))?;
+        self.state.db = Some(index);
+        Ok(())
+    }
+
+    /// Append a command already in its RESP encoding.
+    pub fn append_encoded(&mut self, buf: &[u8]) -> Result<(), AofError> {
+        self.state.file.write_all(buf)?;
+        if let Some(rewrite_buf) = self.state.rewrite_buf.as_mut() {
+            rewrite_buf.extend_from_slice(buf);
+        }
+        if self.fsync == AofFsync::Always {
+            self.state.file.sync_data()?;
+        }
+        Ok(())
+    }
+}
+
+impl Backend {
+    /// Open the configured append only file, replaying it if it exists.
+    /// Without one, the snapshot is loaded and written out as the initial append only file.
+    pub fn open_aof(&self) -> Result<usize, AofError> {
+        let config = self.config().aof.clone();
+        if !config.enabled {
+            return Err(AofError::Disabled);
+        }
+        let path = config.path();
+        let loaded = if path.exists() {
+            self.load_aof(&path)?
+        } else {
+            let loaded = self
+                .load()
+                .map_err(|e| AofError::InvalidFormat(e.to_string()))?;
+            write_atomically(&path, &self.rewrite_commands())?;
+            loaded
+        };
+        let aof = Aof::open(path, config.fsync)?;
+        if self.aof.set(aof).is_err() {
+            warn!("append only file is already open");
+        }
+        Ok(loaded)
+    }
+
+    /// Replay the commands of an append only file. Returns the number of replayed commands.
+    pub fn load_aof(&self, path: &Path) -> Result<usize, AofError> {
+        let data = fs::read(path)?;
+        let mut buf = data.as_slice();
+        let mut replayed = 0;
+        // the commands apply to the database selected last
+        let mut backend = self.clone();
+        while !buf.is_empty() {
+            let len = match parse_frame_length(buf) {
+                Ok(len) => len,
+                Err(RespError::NotComplete) => {
+                    // the server died in the middle of a write, drop the partial command
+                    warn!(
+                        "append only file {} is truncated, ignoring the last {} bytes",
+                        path.display(),
+                        buf.len()
+                    );
+                    break;
+                }
+                Err(e) => return Err(AofError::InvalidFormat(e.to_string())),
+            };
+            let frame = parse_frame(&mut &buf[..len])
+                .map_err(|e| AofError::InvalidFormat(e.to_string()))?;
+            buf = &buf[len..];
+
+            let cmd =
+                Command::try_from(frame).map_err(|e| AofError::InvalidFormat(e.to_string()))?;
+            match cmd {
+                Command::Select(cmd) => {
+                    backend = self.select(cmd.index()).ok_or_else(|| {
+                        AofError::InvalidFormat(format!("no database {}", cmd.index()))
+                    })?;
+                }
+                cmd => {
+                    if let RespFrame::Error(e) = cmd.execute(&backend) {
+                        warn!("error replaying append only file: {:?}", e);
+                    }
+                }
+            }
+            replayed += 1;
+        }
+        self.dirty.store(0, Ordering::Relaxed);
+        info!("Replayed {} commands from {}", replayed, path.display());
+        Ok(replayed)
+    }
+
+    /// Lock the append only file for a write command, None if AOF is disabled.
+    pub fn aof_writer(&self) -> Option<AofWriter<'_>> {
+        self.aof.get().map(|aof| AofWriter {
+            fsync: aof.fsync,
+            state: aof.state(),
+        })
+    }
+
+    pub fn aof_enabled(&self) -> bool {
+        self.aof.get().is_some()
+    }
+
+    /// Compact the append only file in a background thread, the caller does not wait for it.
+    pub fn bgrewriteaof(&self) -> Result<(), AofError> {
+        let aof = self.aof.get().ok_or(AofError::Disabled)?;
+        if aof
+            .rewrite_in_progress
+            .compare_exchange(false, true, Ordering::AcqRel, Ordering::Acquire)
+            .is_err()
+        {
+            return Err(AofError::AlreadyInProgress);
+        }
+
+        // no write command can run while the aof is locked, so the dump is consistent
+        // with the point where the rewrite buffer starts
+        let data = {
+            let mut state = aof.state();
+            state.rewrite_buf = Some(Vec::new());
+            // the commands appended next select their database after those of the new file
+            state.db = None;
+            self.rewrite_commands()
+        };
+
+        let backend = self.clone();
+        std::thread::spawn(move || {
+            let aof = backend.aof.get().expect("aof is open");
+            match rewrite(aof, &data) {
+                Ok(_) => info!("Background AOF rewrite finished successfully"),
+                Err(e) => {
+                    aof.state().rewrite_buf = None;
+                    warn!("Background AOF rewrite error: {}", e);
+                }
+            }
+            aof.rewrite_in_progress.store(false, Ordering::Release);
+        });
+        Ok(())
+    }
+
+    pub fn aof_rewrite_in_progress(&self) -> bool {
+        self.aof
+            .get()
+            .map(|aof| aof.rewrite_in_progress.load(Ordering::Acquire))
+            .unwrap_or_default()
+    }
+
+    /// Periodically fsync the append only file for the everysec policy.
+    pub async fn run_aof_fsync(self) {
+        let aof = match self.aof.get() {
+            Some(aof) if aof.fsync == AofFsync::Everysec => aof,
+            _ => return,
+        };
+        let mut ticker = tokio::time::interval(AOF_FSYNC_INTERVAL);
+        loop {
+            ticker.tick().await;
+            if let Err(e) = aof.sync() {
+                warn!("failed to fsync append only file: {}", e);
+            }
+        }
+    }
+
+    // the shortest sequence of commands which rebuilds the keyspaces of all the databases
+    fn rewrite_commands(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(4096);
+        for db in self.all_dbs().filter(|db| db.dbsize() > 0) {
+            let args = vec![bulk(&db.db().to_string())];
+            buf.extend(command("select", args).encode());
+            db.rewrite_keyspace(&mut buf);
+        }
+        buf
+    }
+
+    // the commands which rebuild the keyspace of the selected database
+    fn rewrite_keyspace(&self, buf: &mut Vec<u8>) {
+        let now = now_ms();
+        let mut keys = Vec::new();
+
+        for entry in self.keyspace().map.iter() {
+            let args = vec![
+                bulk(entry.key()),
+                BulkString::from(entry.value().clone()).into(),
+            ];
+            buf.extend(command("set", args).encode());
+            keys.push(entry.key().clone());
+        }
+        for entry in self.keyspace().hmap.iter() {
+            for field in entry.value().iter() {
+                let args = vec![bulk(entry.key()), bulk(field.key()), field.value().clone()];
+                buf.extend(command("hset", args).encode());
+            }
+            keys.push(entry.key().clone());
+        }
+        for entry in self.keyspace().list.iter() {
+            let mut args = vec![bulk(entry.key())];
+            args.extend(entry.value().iter().cloned());
+            buf.extend(command("rpush", args).encode());
+            keys.push(entry.key().clone());
+        }
+        for entry in self.keyspace().zset.iter() {
+            let mut args = vec![bulk(entry.key())];
+            for (member, score) in entry.value().range_by_rank(0, -1, false) {
+                args.push(bulk(&score.to_string()));
+                args.push(bulk(&member));
+            }
+            buf.extend(command("zadd", args).encode());
+            keys.push(entry.key().clone());
+        }
+        for entry in self.keyspace().stream.iter() {
+            rewrite_stream(buf, entry.key(), entry.value());
+            keys.push(entry.key().clone());
+        }
+
+        for key in keys {
+            // keys already expired are written too and removed right away when replayed
+            let at = self.keyspace().expires.get(&key).map(|v| *v.value());
+            if let Some(at) = at {
+                let at = at.max(now);
+                let args = vec![bulk(&key), bulk(&at.to_string())];
+                buf.extend(command("pexpireat", args).encode());
+            }
+        }
+    }
+}
+
+// the entries of a stream, then its groups with their consumers and pending entries
+fn rewrite_stream(buf: &mut Vec<u8>, key: &str, stream: &Stream) {
+    let bulks = |args: &[&str]| args.iter().map(|v| bulk(v)).collect::<Vec<_>>();
+    for (id, fields) in stream.entries() {
+        let mut args = bulks(&[key, &id.to_string()]);
+        args.extend(fields.iter().cloned());
+        buf.extend(command("xadd", args).encode());
+    }
+    if stream.is_empty() && stream.last_id() != StreamId::MIN {
+        // an entry trimmed right away leaves an empty stream with the last id
+        let id = stream.last_id().to_string();
+        let args = bulks(&[key, "maxlen", "0", &id, "", ""]);
+        buf.extend(command("xadd", args).encode());
+    }
+    for (name, group) in stream.groups() {
+        let id = group.last_delivered().to_string();
+        let args = bulks(&["create", key, name, &id, "mkstream"]);
+        buf.extend(command("xgroup", args).encode());
+        for consumer in group.consumers() {
+            let args = bulks(&["createconsumer", key, name, consumer]);
+            buf.extend(command("xgroup", args).encode());
+        }
+        for (id, pending) in group.pending() {
+            let (id, time) = (id.to_string(), pending.delivered.to_string());
+            let count = pending.deliveries.to_string();
+            let args = bulks(&[
+                key,
+                name,
+                &pending.consumer,
+                "0",
+                &id,
+                "time",
+                &time,
+                "retrycount",
+                &count,
+                "force",
+                "justid",
+            ]);
+            buf.extend(command("xclaim", args).encode());
+        }
+    }
+}
+
+fn rewrite(aof: &Aof, data: &[u8]) -> Result<(), AofError> {
+    let tmp = aof
+        .path
+        .with_extension(format!("rewrite-{}", std::process::id()));
+    let mut file = open_append(&tmp)?;
+    file.write_all(data)?;
+    file.sync_data()?;
+
+    // switch over to the new file, appending the commands executed in the meantime
+    let mut state = aof.state();
+    if let Some(rewrite_buf) = state.rewrite_buf.take() {
+        file.write_all(&rewrite_buf)?;
+    }
+    file.sync_data()?;
+    fs::rename(&tmp, &aof.path)?;
+    state.file = file;
+    Ok(())
+}
+
+fn open_append(path: &Path) -> std::io::Result<File> {
+    OpenOptions::new().create(true).append(true).open(path)
+}
+
+fn write_atomically(path: &Path, data: &[u8]) -> Result<(), AofError> {
+    let tmp = path.with_extension(format!("tmp-{}", std::process::id()));
+    let mut file = File::create(&tmp)?;
+    file.write_all(data)?;
+    file.sync_all()?;
+    fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+fn command(name: &str, args: Vec<RespFrame>) -> RespFrame {
+    let mut frames = Vec::with_capacity(args.len() + 1);
+    frames.push(bulk(name));
+    frames.extend(args);
+    Array::new(frames).into()
+}
+
+fn bulk(s: &str) -> RespFrame {
+    BulkString::new(s).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{AppConfig, XAddId, ZAddFlags};
+
+    use super::*;
+
+    fn test_config(name: &str) -> AppConfig {
+        let mut config = AppConfig::default();
+        config.aof.enabled = true;
+        config.aof.dir = std::env::temp_dir();
+        config.aof.filename = format!("simple-redis-{}-{}.aof", name, std::process::id());
+        config.rdb.save = vec![];
+        config
+    }
+
+    #[test]
+    fn test_append_and_replay() -> anyhow::Result<()> {
+        let config = test_config("replay");
+        let _ = fs::remove_file(config.aof.path());
+        let backend = Backend::with_config(config.clone());
+        backend.open_aof()?;
+
+        {
+            let mut writer = backend.aof_writer().expect("aof enabled");
+            writer.append(command("set", vec![bulk("key"), bulk("value")]))?;
+            writer.append(command("rpush", vec![bulk("list"), bulk("a"), bulk("b")]))?;
+        }
+        // a partial command at the end is ignored
+        let mut file = open_append(&config.aof.path())?;
+        file.write_all(b"*3\r\n$3\r\nset\r\n")?;
+
+        let replayed = Backend::with_config(config.clone());
+        assert_eq!(replayed.open_aof()?, 2);
+        assert_eq!(replayed.get("key"), Some("value".into()));
+        assert_eq!(replayed.lrange("list", 0, -1), vec![bulk("a"), bulk("b")]);
+
+        fs::remove_file(config.aof.path())?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_rewrite_commands_rebuild_keyspace() -> anyhow::Result<()> {
+        let backend = Backend::new();
+        backend.set("s".to_string(), "hello".into());
+        backend
+            .hset("h".to_string(), "f".to_string(), bulk("v"))
+            .unwrap();
+        backend
+            .rpush("l".to_string(), vec![bulk("a"), bulk("b")])
+            .unwrap();
+        backend
+            .zadd(
+                "z".to_string(),
+                vec![
+                    (1.5, "one".to_string()),
+                    (f64::NEG_INFINITY, "low".to_string()),
+                ],
+                ZAddFlags::default(),
+            )
+            .unwrap();
+        let fields = vec![bulk("f"), bulk("v")];
+        backend.xadd("x".to_string(), XAddId::Auto, fields, None, false)?;
+        backend.xgroup_create("x", "g".to_string(), Some(StreamId::MIN), false)?;
+        backend.xreadgroup("x", "g", "c", None, None, false)?;
+        backend.expire_at("s", now_ms() + 60_000, None);
+        let other = backend.select(3).unwrap();
+        other.set("s".to_string(), "other".into());
+
+        let config = test_config("rewrite");
+        fs::write(config.aof.path(), backend.rewrite_commands())?;
+        let rebuilt = Backend::with_config(config.clone());
+        assert_eq!(rebuilt.load_aof(&config.aof.path())?, 12);
+        assert_eq!(rebuilt.select(3).unwrap().get("s"), Some("other".into()));
+
+        assert_eq!(rebuilt.get("s"), Some("hello".into()));
+        assert!(rebuilt.pttl("s") > 50_000);
+        assert_eq!(rebuilt.hget("h", "f"), Some(bulk("v")));
+        assert_eq!(rebuilt.lrange("l", 0, -1).len(), 2);
+        assert_eq!(rebuilt.zscore("z", "low"), Some(f64::NEG_INFINITY));
+        assert_eq!(rebuilt.zscore("z", "one"), Some(1.5));
+        assert_eq!(
+            rebuilt
+                .xrange("x", StreamId::MIN, StreamId::MAX, None, false)
+                .len(),
+            1
+        );
+        let pending = rebuilt.with_group("x", "g", |g| g.pending().count())?;
+        assert_eq!(pending, 1);
+
+        fs::remove_file(config.aof.path())?;
+        Ok(())
+    }
+}
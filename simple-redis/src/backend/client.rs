@@ -0,0 +1,203 @@
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use dashmap::DashMap;
+use tokio::sync::Notify;
+
+use crate::{now_ms, Backend};
+
+/// The connected clients, as CLIENT LIST reports them.
+#[derive(Debug, Default)]
+pub struct Clients {
+    clients: DashMap<u64, Client>,
+    connected: AtomicUsize,
+    total_connections: AtomicU64,
+    rejected_connections: AtomicU64,
+}
+
+#[derive(Debug)]
+struct Client {
+    info: ClientInfo,
+    // notified to make the connection close
+    killed: Arc<Notify>,
+}
+
+/// What is known of a connection, kept up to date by the connection after each command.
+#[derive(Debug, Clone)]
+pub struct ClientInfo {
+    pub id: u64,
+    pub addr: SocketAddr,
+    pub name: Option<String>,
+    /// None until the client authenticated
+    pub user: Option<String>,
+    pub db: usize,
+    pub resp: u8,
+    /// O for a monitor, P for a subscriber, S for a replica, x in a transaction, N otherwise
+    pub flags: String,
+    pub sub: usize,
+    pub psub: usize,
+    /// number of queued commands, -1 outside of a transaction
+    pub multi: i64,
+    /// name of the last command run
+    pub cmd: String,
+    /// unix time in milliseconds at which the client connected
+    pub created: u64,
+    /// unix time in milliseconds of the last command
+    pub last_interaction: u64,
+}
+
+/// A connected client, counted and listed until it is dropped.
+#[derive(Debug)]
+pub struct ClientSlot {
+    backend: Backend,
+    id: u64,
+    killed: Arc<Notify>,
+}
+
+impl Backend {
+    /// Register a new client connection, None if `maxclients` clients are connected already.
+    pub fn connect_client(&self, id: u64, addr: SocketAddr) -> Option<ClientSlot> {
+        let maxclients = self.config().server.maxclients;
+        let clients = &self.clients;
+        clients.total_connections.fetch_add(1, Ordering::Relaxed);
+        let admitted = clients
+            .connected
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |n| {
+                (n < maxclients).then_some(n + 1)
+            });
+        if admitted.is_err() {
+            clients.rejected_connections.fetch_add(1, Ordering::Relaxed);
+            return None;
+        }
+        let now = now_ms();
+        let killed = Arc::new(Notify::new());
+        let info = ClientInfo {
+            id,
+            addr,
+            name: None,
+            user: None,
+            db: 0,
+            resp: 2,
+            flags: "N".to_string(),
+            sub: 0,
+            psub: 0,
+            multi: -1,
+            cmd: "NULL".to_string(),
+            created: now,
+            last_interaction: now,
+        };
+        let client = Client {
+            info,
+            killed: killed.clone(),
+        };
+        clients.clients.insert(id, client);
+        Some(ClientSlot {
+            backend: self.clone(),
+            id,
+            killed,
+        })
+    }
+
+    /// Number of client connections, replicas included.
+    pub fn connected_clients(&self) -> usize {
+        self.clients.connected.load(Ordering::Relaxed)
+    }
+
+    /// Number of connections accepted since the server started, refused ones included.
+    pub fn total_connections_received(&self) -> u64 {
+        self.clients.total_connections.load(Ordering::Relaxed)
+    }
+
+    /// Number of connections refused because of `maxclients`.
+    pub fn rejected_connections(&self) -> u64 {
+        self.clients.rejected_connections.load(Ordering::Relaxed)
+    }
+
+    /// The connected clients, by id.
+    pub fn client_list(&self) -> Vec<ClientInfo> {
+        let mut clients = self
+            .clients
+            .clients
+            .iter()
+            .map(|client| client.info.clone())
+            .collect::<Vec<_>>();
+        clients.sort_by_key(|info| info.id);
+        clients
+    }
+
+    /// Close the connections of the clients matching the filter. Returns the number of clients.
+    pub fn kill_clients(&self, filter: impl Fn(&ClientInfo) -> bool) -> usize {
+        self.clients
+            .clients
+            .iter()
+            .filter(|client| filter(&client.info))
+            .map(|client| client.killed.notify_one())
+            .count()
+    }
+}
+
+impl ClientSlot {
+    /// Update what is known of the client.
+    pub fn update(&self, f: impl FnOnce(&mut ClientInfo)) {
+        if let Some(mut client) = self.backend.clients.clients.get_mut(&self.id) {
+            f(&mut client.info);
+        }
+    }
+
+    /// Notified once the client is killed, the connection should then be closed.
+    pub fn killed(&self) -> Arc<Notify> {
+        self.killed.clone()
+    }
+}
+
+impl Drop for ClientSlot {
+    fn drop(&mut self) {
+        self.backend.clients.clients.remove(&self.id);
+        self.backend
+            .clients
+            .connected
+            .fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_maxclients() {
+        let backend = Backend::new();
+        backend
+            .set_config(&[("maxclients".to_string(), "2".to_string())])
+            .unwrap();
+        let addr = "127.0.0.1:6000".parse().unwrap();
+        let first = backend.connect_client(1, addr);
+        let second = backend.connect_client(2, addr);
+        assert!(first.is_some() && second.is_some());
+        assert!(backend.connect_client(3, addr).is_none());
+        drop(first);
+        assert_eq!(backend.connected_clients(), 1);
+        assert!(backend.connect_client(4, addr).is_some());
+        assert_eq!(backend.total_connections_received(), 4);
+        assert_eq!(backend.rejected_connections(), 1);
+    }
+
+    #[test]
+    fn test_client_list_and_kill() {
+        let backend = Backend::new();
+        let addr = "127.0.0.1:6000".parse().unwrap();
+        let first = backend.connect_client(1, addr).unwrap();
+        let _second = backend.connect_client(2, addr).unwrap();
+        first.update(|info| info.name = Some("worker".to_string()));
+        let list = backend.client_list();
+        assert_eq!(list.len(), 2);
+        assert_eq!(list[0].name.as_deref(), Some("worker"));
+
+        assert_eq!(backend.kill_clients(|info| info.id == 1), 1);
+        // the permit is stored until the connection waits for it
+        let killed = first.killed();
+        assert!(futures::FutureExt::now_or_never(killed.notified()).is_some());
+        assert_eq!(backend.kill_clients(|info| info.id == 3), 0);
+    }
+}
@@ -0,0 +1,383 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use thiserror::Error;
+use tracing::{info, warn};
+
+use crate::{Backend, ClusterConfig};
+
+/// Number of hash slots the keyspace of a cluster is split into.
+pub const CLUSTER_SLOTS: usize = 16384;
+
+#[derive(Error, Debug)]
+pub enum ClusterError {
+    #[error("Invalid cluster config: {0}")]
+    InvalidConfig(String),
+    #[error("Cluster support is disabled")]
+    Disabled,
+}
+
+// slots moved between a node and the other node of each migration
+type SlotMigrations<'a> = Vec<(u16, &'a ClusterNode)>;
+
+/// Why a request can't be served by this node, replied as an error to the client.
+#[derive(Debug, PartialEq)]
+pub enum Redirect {
+    /// the slot is served by another node
+    Moved(u16, String),
+    /// the slot is being migrated and the key is already on the target node
+    Ask(u16, String),
+    CrossSlot,
+    Unassigned,
+}
+
+/// The static topology of the cluster this node is part of.
+#[derive(Debug)]
+pub struct Cluster {
+    nodes: Vec<ClusterNode>,
+    // index of this node in nodes
+    myself: usize,
+    // index of the node serving each slot
+    slots: Vec<Option<usize>>,
+    // slots being moved between two nodes, with the index of the source and of the target
+    migrations: HashMap<u16, (usize, usize)>,
+}
+
+#[derive(Debug)]
+pub struct ClusterNode {
+    pub id: String,
+    pub host: String,
+    pub port: u16,
+    // index in the node list, which gives the config epoch
+    pub index: usize,
+}
+
+impl fmt::Display for Redirect {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Redirect::Moved(slot, addr) => write!(f, "MOVED {} {}", slot, addr),
+            Redirect::Ask(slot, addr) => write!(f, "ASK {} {}", slot, addr),
+            Redirect::CrossSlot => {
+                write!(f, "CROSSSLOT Keys in request don't hash to the same slot")
+            }
+            Redirect::Unassigned => write!(f, "CLUSTERDOWN Hash slot not served"),
+        }
+    }
+}
+
+impl Cluster {
+    pub fn new(config: &ClusterConfig) -> Result<Self, ClusterError> {
+        let nodes = config
+            .nodes
+            .iter()
+            .enumerate()
+            .map(|(index, node)| ClusterNode {
+                id: node.id.clone(),
+                host: node.host.clone(),
+                port: node.port,
+                index,
+            })
+            .collect::<Vec<_>>();
+        let index_of = |id: &str| {
+            nodes
+                .iter()
+                .position(|node| node.id == id)
+                .ok_or_else(|| ClusterError::InvalidConfig(format!("unknown node '{}'", id)))
+        };
+        let myself = index_of(&config.myself)?;
+
+        let mut slots = vec![None; CLUSTER_SLOTS];
+        let mut migrations = HashMap::new();
+        for (index, node) in config.nodes.iter().enumerate() {
+            for range in node.slots.iter() {
+                let (first, last) = parse_slot_range(range)?;
+                for slot in slots[first..=last].iter_mut() {
+                    if slot.is_some() {
+                        return Err(ClusterError::InvalidConfig(format!(
+                            "slots {} are assigned twice",
+                            range
+                        )));
+                    }
+                    *slot = Some(index);
+                }
+            }
+            for (slot, target) in node.migrating.iter() {
+                if slots.get(*slot as usize).copied().flatten() != Some(index) {
+                    return Err(ClusterError::InvalidConfig(format!(
+                        "node '{}' migrates slot {} which it does not serve",
+                        node.id, slot
+                    )));
+                }
+                migrations.insert(*slot, (index, index_of(target)?));
+            }
+        }
+        Ok(Self {
+            nodes,
+            myself,
+            slots,
+            migrations,
+        })
+    }
+
+    pub fn myself(&self) -> &ClusterNode {
+        &self.nodes[self.myself]
+    }
+
+    pub fn nodes(&self) -> &[ClusterNode] {
+        &self.nodes
+    }
+
+    /// The node serving a slot.
+    pub fn owner(&self, slot: u16) -> Option<&ClusterNode> {
+        self.slots[slot as usize].map(|i| &self.nodes[i])
+    }
+
+    /// The slots served by a node, as ranges of consecutive slots.
+    pub fn slot_ranges(&self, node: &ClusterNode) -> Vec<(u16, u16)> {
+        let mut ranges: Vec<(u16, u16)> = Vec::new();
+        for (slot, owner) in self.slots.iter().enumerate() {
+            if *owner != Some(node.index) {
+                continue;
+            }
+            let slot = slot as u16;
+            match ranges.last_mut() {
+                Some((_, last)) if *last + 1 == slot => *last = slot,
+                _ => ranges.push((slot, slot)),
+            }
+        }
+        ranges
+    }
+
+    /// Slots moved from the node to other nodes, and moved from other nodes to it.
+    pub fn migrations(&self, node: &ClusterNode) -> (SlotMigrations<'_>, SlotMigrations<'_>) {
+        let mut migrating = Vec::new();
+        let mut importing = Vec::new();
+        for (slot, (source, target)) in self.migrations.iter() {
+            if *source == node.index {
+                migrating.push((*slot, &self.nodes[*target]));
+            }
+            if *target == node.index {
+                importing.push((*slot, &self.nodes[*source]));
+            }
+        }
+        migrating.sort_by_key(|(slot, _)| *slot);
+        importing.sort_by_key(|(slot, _)| *slot);
+        (migrating, importing)
+    }
+
+    pub fn assigned_slots(&self) -> usize {
+        self.slots.iter().filter(|slot| slot.is_some()).count()
+    }
+}
+
+impl ClusterNode {
+    pub fn addr(&self) -> String {
+        format!("{}:{}", self.host, self.port)
+    }
+}
+
+impl Backend {
+    /// Join the cluster of the config, after which keys are only served by the node owning their slot.
+    pub fn enable_cluster(&self) -> Result<(), ClusterError> {
+        let config = self.config().cluster.clone();
+        if !config.enabled {
+            return Err(ClusterError::Disabled);
+        }
+        let cluster = Cluster::new(&config)?;
+        info!(
+            "Cluster node {} serving {} slots",
+            cluster.myself().id,
+            cluster
+                .slot_ranges(cluster.myself())
+                .iter()
+                .map(|(first, last)| (last - first) as usize + 1)
+                .sum::<usize>()
+        );
+        if self.cluster.set(cluster).is_err() {
+            warn!("cluster is already enabled");
+        }
+        Ok(())
+    }
+
+    pub fn cluster(&self) -> Option<&Cluster> {
+        self.cluster.get()
+    }
+
+    /// Check that the keys of a request are served by this node. `asking` is set by a client
+    /// redirected with ASK, which may then use a slot being imported.
+    pub fn route<K: AsRef<[u8]>>(&self, keys: &[K], asking: bool) -> Result<(), Redirect> {
+        let (Some(cluster), Some(first)) = (self.cluster.get(), keys.first()) else {
+            return Ok(());
+        };
+        let slot = key_hash_slot(first.as_ref());
+        if keys.iter().any(|key| key_hash_slot(key.as_ref()) != slot) {
+            return Err(Redirect::CrossSlot);
+        }
+        let owner = cluster.owner(slot).ok_or(Redirect::Unassigned)?;
+        let migration = cluster.migrations.get(&slot);
+
+        if owner.index != cluster.myself {
+            return match migration {
+                Some((_, target)) if *target == cluster.myself && asking => Ok(()),
+                _ => Err(Redirect::Moved(slot, owner.addr())),
+            };
+        }
+        // keys already moved are looked up on the target node
+        match migration {
+            Some((_, target))
+                if keys
+                    .iter()
+                    .any(|key| !self.exists(&String::from_utf8_lossy(key.as_ref()))) =>
+            {
+                Err(Redirect::Ask(slot, cluster.nodes[*target].addr()))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// The slot of a key: CRC16 of the key, or of its hash tag, the part between the first `{` and
+/// the following `}` when it is not empty, so that related keys can be kept on the same node.
+pub fn key_hash_slot(key: &[u8]) -> u16 {
+    let tag = key.iter().position(|b| *b == b'{').and_then(|start| {
+        key[start + 1..]
+            .iter()
+            .position(|b| *b == b'}')
+            .filter(|len| *len > 0)
+            .map(|len| &key[start + 1..start + 1 + len])
+    });
+    crc16(tag.unwrap_or(key)) % CLUSTER_SLOTS as u16
+}
+
+// CRC16-CCITT (XMODEM), as used by Redis Cluster
+fn crc16(data: &[u8]) -> u16 {
+    let mut crc = 0u16;
+    for byte in data {
+        crc ^= (*byte as u16) << 8;
+        for _ in 0..8 {
+            crc = match crc & 0x8000 {
+                0 => crc << 1,
+                _ => (crc << 1) ^ 0x1021,
+            };
+        }
+    }
+    crc
+}
+
+fn parse_slot_range(range: &str) -> Result<(usize, usize), ClusterError> {
+    let err = || ClusterError::InvalidConfig(format!("invalid slot range '{}'", range));
+    let (first, last) = match range.split_once('-') {
+        Some((first, last)) => (first.trim(), last.trim()),
+        None => (range.trim(), range.trim()),
+    };
+    let first = first.parse::<usize>().map_err(|_| err())?;
+    let last = last.parse::<usize>().map_err(|_| err())?;
+    if first > last || last >= CLUSTER_SLOTS {
+        return Err(err());
+    }
+    Ok((first, last))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use crate::{AppConfig, ClusterNodeConfig};
+
+    use super::*;
+
+    fn node(id: &str, port: u16, slots: &[&str]) -> ClusterNodeConfig {
+        ClusterNodeConfig {
+            id: id.to_string(),
+            host: "127.0.0.1".to_string(),
+            port,
+            slots: slots.iter().map(|s| s.to_string()).collect(),
+            migrating: BTreeMap::new(),
+        }
+    }
+
+    fn cluster_backend(myself: &str) -> Backend {
+        let mut config = AppConfig::default();
+        config.cluster.enabled = true;
+        config.cluster.myself = myself.to_string();
+        let mut a = node("a", 7000, &["0-8191"]);
+        a.migrating.insert(3990, "b".to_string());
+        config.cluster.nodes = vec![a, node("b", 7001, &["8192-16383"])];
+        let backend = Backend::with_config(config);
+        backend.enable_cluster().unwrap();
+        backend
+    }
+
+    #[test]
+    fn test_key_hash_slot() {
+        assert_eq!(crc16(b"123456789"), 0x31c3);
+        assert_eq!(key_hash_slot(b"foo"), 12182);
+        assert_eq!(
+            key_hash_slot(b"{user1000}.following"),
+            key_hash_slot(b"user1000")
+        );
+        assert_eq!(key_hash_slot(b"foo{}{bar}"), key_hash_slot(b"foo{}{bar}"));
+        assert_ne!(key_hash_slot(b"foo{}{bar}"), key_hash_slot(b"bar"));
+        assert_eq!(key_hash_slot(b"foo{{bar}}zap"), key_hash_slot(b"{bar"));
+    }
+
+    #[test]
+    fn test_cluster_config() {
+        let backend = cluster_backend("a");
+        let cluster = backend.cluster().unwrap();
+        assert_eq!(cluster.assigned_slots(), CLUSTER_SLOTS);
+        assert_eq!(
+            cluster.slot_ranges(&cluster.nodes()[1]),
+            vec![(8192, 16383)]
+        );
+        let (migrating, importing) = cluster.migrations(cluster.myself());
+        assert_eq!(migrating.len(), 1);
+        assert!(importing.is_empty());
+
+        let mut config = ClusterConfig {
+            enabled: true,
+            myself: "a".to_string(),
+            nodes: vec![node("a", 7000, &["0-100"]), node("b", 7001, &["100"])],
+        };
+        assert!(Cluster::new(&config).is_err());
+        config.nodes[1].slots = vec!["16384".to_string()];
+        assert!(Cluster::new(&config).is_err());
+        config.myself = "c".to_string();
+        assert!(Cluster::new(&config).is_err());
+    }
+
+    #[test]
+    fn test_route() {
+        let backend = cluster_backend("a");
+        assert_eq!(backend.route::<&str>(&[], false), Ok(()));
+        // "bar" is slot 5061 and "foo" slot 12182
+        assert_eq!(backend.route(&["bar"], false), Ok(()));
+        assert_eq!(
+            backend.route(&["foo"], false),
+            Err(Redirect::Moved(12182, "127.0.0.1:7001".to_string()))
+        );
+        assert_eq!(
+            backend.route(&["bar", "foo"], false),
+            Err(Redirect::CrossSlot)
+        );
+
+        // slot 3990 is being moved to b, keys which are not here anymore are asked there
+        let key = (0..)
+            .map(|i| format!("key{}", i))
+            .find(|key| key_hash_slot(key.as_bytes()) == 3990)
+            .unwrap();
+        assert_eq!(
+            backend.route(&[&key], false),
+            Err(Redirect::Ask(3990, "127.0.0.1:7001".to_string()))
+        );
+        backend.set(key.clone(), "v".into());
+        assert_eq!(backend.route(&[&key], false), Ok(()));
+
+        let backend = cluster_backend("b");
+        assert_eq!(
+            backend.route(&[&key], false),
+            Err(Redirect::Moved(3990, "127.0.0.1:7000".to_string()))
+        );
+        assert_eq!(backend.route(&[&key], true), Ok(()));
+    }
+}
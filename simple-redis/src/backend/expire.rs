@@ -0,0 +1,207 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use tracing::debug;
+
+use crate::Backend;
+
+// how many expired keys an active expire cycle removes at most
+const ACTIVE_EXPIRE_CYCLE_KEYS: usize = 200;
+
+/// Condition flags of EXPIRE / PEXPIRE: NX, XX, GT, LT.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExpireCondition {
+    /// set expiry only when the key has no expiry
+    Nx,
+    /// set expiry only when the key has an existing expiry
+    Xx,
+    /// set expiry only when the new expiry is greater than current one
+    Gt,
+    /// set expiry only when the new expiry is less than current one
+    Lt,
+}
+
+pub fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+impl Backend {
+    /// Lazily expire the key: if its deadline has passed, remove it. Returns true if removed.
+    /// A key which is still alive counts as accessed.
+    pub fn expire_if_needed(&self, key: &str) -> bool {
+        // copy the deadline out so that the shard lock is released before removal
+        let deadline = self.keyspace().expires.get(key).map(|v| *v.value());
+        match deadline {
+            Some(at) if at <= now_ms() => {
+                debug!("key {} expired", key);
+                let removed = self.remove_key(key);
+                if removed {
+                    self.record_expired();
+                }
+                removed
+            }
+            _ => {
+                self.record_access(key);
+                false
+            }
+        }
+    }
+
+    /// Set the absolute expiry (unix time in milliseconds) of a key.
+    /// Returns false if the key does not exist or the condition is not met.
+    pub fn expire_at(&self, key: &str, at: u64, condition: Option<ExpireCondition>) -> bool {
+        if !self.exists(key) {
+            return false;
+        }
+
+        let current = self.keyspace().expires.get(key).map(|v| *v.value());
+        let allowed = match (condition, current) {
+            (None, _) => true,
+            (Some(ExpireCondition::Nx), current) => current.is_none(),
+            (Some(ExpireCondition::Xx), current) => current.is_some(),
+            // a key without ttl is treated as an infinite ttl
+            (Some(ExpireCondition::Gt), Some(current)) => at > current,
+            (Some(ExpireCondition::Gt), None) => false,
+            (Some(ExpireCondition::Lt), Some(current)) => at < current,
+            (Some(ExpireCondition::Lt), None) => true,
+        };
+        if !allowed {
+            return false;
+        }
+
+        if at <= now_ms() {
+            // a deadline in the past deletes the key right away
+            self.remove_key(key);
+        } else {
+            self.keyspace().expires.insert(key.to_string(), at);
+            self.touch(key);
+        }
+        true
+    }
+
+    /// Remove the expiry of a key. Returns true if the key had an expiry.
+    pub fn persist(&self, key: &str) -> bool {
+        self.expire_if_needed(key);
+        let removed = self.keyspace().expires.remove(key).is_some();
+        if removed {
+            self.touch(key);
+        }
+        removed
+    }
+
+    /// Remaining time to live in milliseconds; -2 if the key does not exist, -1 if it has no expiry.
+    pub fn pttl(&self, key: &str) -> i64 {
+        if !self.exists(key) {
+            return -2;
+        }
+        match self.keyspace().expires.get(key).map(|v| *v.value()) {
+            Some(at) => at.saturating_sub(now_ms()) as i64,
+            None => -1,
+        }
+    }
+
+    /// Absolute expiry of the key in unix milliseconds, None if the key does not exist or has no expiry.
+    pub fn expire_time(&self, key: &str) -> Option<u64> {
+        self.expire_if_needed(key);
+        self.keyspace().expires.get(key).map(|v| *v.value())
+    }
+
+    /// Actively remove keys whose ttl has passed, in every database. Returns the number of
+    /// removed keys.
+    pub fn active_expire_cycle(&self) -> usize {
+        let now = now_ms();
+        self.all_dbs()
+            .map(|db| {
+                let expired = db
+                    .keyspace()
+                    .expires
+                    .iter()
+                    .filter(|v| *v.value() <= now)
+                    .take(ACTIVE_EXPIRE_CYCLE_KEYS)
+                    .map(|v| v.key().clone())
+                    .collect::<Vec<_>>();
+                expired
+                    .iter()
+                    .filter(|key| db.expire_if_needed(key))
+                    .count()
+            })
+            .sum()
+    }
+
+    /// Run the active expire cycle every `interval` until the runtime shuts down.
+    pub async fn run_active_expire(self, interval: Duration) {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            let removed = {
+                let _guard = self.lock_shared();
+                let removed = self.active_expire_cycle();
+                // keys which are deleted are only measured here when nothing grows the keyspace
+                for db in self.all_dbs() {
+                    db.refresh_memory();
+                }
+                removed
+            };
+            if removed > 0 {
+                debug!("active expire cycle removed {} keys", removed);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expire_and_persist() {
+        let backend = Backend::new();
+        backend.set("key".to_string(), "value".into());
+        assert_eq!(backend.pttl("key"), -1);
+        assert_eq!(backend.pttl("missing"), -2);
+
+        assert!(backend.expire_at("key", now_ms() + 10_000, None));
+        let ttl = backend.pttl("key");
+        assert!(ttl > 9_000 && ttl <= 10_000);
+
+        assert!(!backend.expire_at("key", now_ms() + 1_000, Some(ExpireCondition::Nx)));
+        assert!(!backend.expire_at("key", now_ms() + 1_000, Some(ExpireCondition::Gt)));
+        assert!(backend.expire_at("key", now_ms() + 1_000, Some(ExpireCondition::Lt)));
+
+        assert!(backend.persist("key"));
+        assert!(!backend.persist("key"));
+        assert_eq!(backend.pttl("key"), -1);
+    }
+
+    #[test]
+    fn test_lazy_and_active_expire() {
+        let backend = Backend::new();
+        backend.set("a".to_string(), "1".into());
+        backend
+            .hset("b".to_string(), "f".to_string(), 1.into())
+            .unwrap();
+        backend
+            .keyspace()
+            .expires
+            .insert("a".to_string(), now_ms() - 1);
+        backend
+            .keyspace()
+            .expires
+            .insert("b".to_string(), now_ms() - 1);
+
+        assert_eq!(backend.get("a"), None);
+        assert_eq!(backend.active_expire_cycle(), 1);
+        assert!(!backend.keyspace().hmap.contains_key("b"));
+        assert!(backend.keyspace().expires.is_empty());
+    }
+
+    #[test]
+    fn test_expire_in_the_past_deletes_key() {
+        let backend = Backend::new();
+        backend.set("key".to_string(), "value".into());
+        assert!(backend.expire_at("key", now_ms() - 1, None));
+        assert!(!backend.exists("key"));
+    }
+}
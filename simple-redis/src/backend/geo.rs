@@ -0,0 +1,257 @@
+use thiserror::Error;
+
+use crate::Backend;
+
+// the bits of each coordinate in a geohash, 52 bits in total so that it is stored exactly
+// as the score of a sorted set
+const GEO_STEP: u32 = 26;
+// the latitudes which can be indexed, as the web mercator projection limits them
+const GEO_LAT_MIN: f64 = -85.05112878;
+const GEO_LAT_MAX: f64 = 85.05112878;
+const GEO_LON_MIN: f64 = -180.0;
+const GEO_LON_MAX: f64 = 180.0;
+const EARTH_RADIUS_IN_METERS: f64 = 6372797.560856;
+const GEOHASH_ALPHABET: &[u8] = b"0123456789bcdefghjkmnpqrstuvwxyz";
+
+// reported when parsing a request, as an argument error
+#[derive(Error, Debug, PartialEq)]
+pub enum GeoError {
+    #[error("invalid longitude,latitude pair {0:.6},{1:.6}")]
+    InvalidPoint(f64, f64),
+}
+
+/// A position on earth, in degrees.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GeoPoint {
+    pub lon: f64,
+    pub lat: f64,
+}
+
+/// The area searched by GEOSEARCH around its center, in meters.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GeoShape {
+    Radius(f64),
+    Box { width: f64, height: f64 },
+}
+
+/// A member found by GEOSEARCH.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GeoMatch {
+    pub member: String,
+    pub point: GeoPoint,
+    pub hash: u64,
+    /// distance to the center of the search, in meters
+    pub distance: f64,
+}
+
+impl GeoPoint {
+    /// A point which can be indexed, the poles excluded.
+    pub fn new(lon: f64, lat: f64) -> Result<Self, GeoError> {
+        let valid = (GEO_LON_MIN..=GEO_LON_MAX).contains(&lon)
+            && (GEO_LAT_MIN..=GEO_LAT_MAX).contains(&lat);
+        match valid {
+            true => Ok(Self { lon, lat }),
+            false => Err(GeoError::InvalidPoint(lon, lat)),
+        }
+    }
+
+    /// The 52 bit geohash of the point, stored as the score of its member.
+    pub fn hash(&self) -> u64 {
+        encode(self.lon, self.lat, (GEO_LAT_MIN, GEO_LAT_MAX))
+    }
+
+    /// The point at the center of the area of a geohash.
+    pub fn from_hash(hash: u64) -> Self {
+        let (lat, lon) = deinterleave(hash);
+        let cell = |i: u32, min: f64, max: f64| {
+            let size = (max - min) / (1u64 << GEO_STEP) as f64;
+            let lo = min + i as f64 * size;
+            (lo + size / 2.0).clamp(min, max)
+        };
+        Self {
+            lon: cell(lon, GEO_LON_MIN, GEO_LON_MAX),
+            lat: cell(lat, GEO_LAT_MIN, GEO_LAT_MAX),
+        }
+    }
+
+    /// The standard 11 characters geohash of the point, as GEOHASH reports it.
+    pub fn geohash_string(&self) -> String {
+        // the standard geohash covers all the latitudes
+        let hash = encode(self.lon, self.lat, (-90.0, 90.0));
+        (0..11)
+            .map(|i| {
+                // the last character only has 2 of the 52 bits, they are left out
+                let idx = match i {
+                    10 => 0,
+                    i => (hash >> (52 - (i + 1) * 5)) & 0x1f,
+                };
+                GEOHASH_ALPHABET[idx as usize] as char
+            })
+            .collect()
+    }
+
+    /// Distance to another point along the surface of the earth, in meters.
+    pub fn distance(&self, other: &GeoPoint) -> f64 {
+        let (lat1, lat2) = (self.lat.to_radians(), other.lat.to_radians());
+        let u = ((lat2 - lat1) / 2.0).sin();
+        let v = ((other.lon.to_radians() - self.lon.to_radians()) / 2.0).sin();
+        2.0 * EARTH_RADIUS_IN_METERS * (u * u + lat1.cos() * lat2.cos() * v * v).sqrt().asin()
+    }
+
+    // the distance to the point if it lies in the shape centered on self
+    fn distance_within(&self, point: &GeoPoint, shape: GeoShape) -> Option<f64> {
+        match shape {
+            GeoShape::Radius(radius) => Some(self.distance(point)).filter(|d| *d <= radius),
+            GeoShape::Box { width, height } => {
+                let lat_distance =
+                    EARTH_RADIUS_IN_METERS * (point.lat.to_radians() - self.lat.to_radians()).abs();
+                if lat_distance > height / 2.0 {
+                    return None;
+                }
+                let lon_distance = GeoPoint {
+                    lon: self.lon,
+                    lat: point.lat,
+                }
+                .distance(point);
+                if lon_distance > width / 2.0 {
+                    return None;
+                }
+                Some(self.distance(point))
+            }
+        }
+    }
+}
+
+impl Backend {
+    /// The position of each member of the geo set at key.
+    pub fn geopos(&self, key: &str, members: &[String]) -> Vec<Option<GeoPoint>> {
+        members
+            .iter()
+            .map(|member| {
+                self.zscore(key, member)
+                    .map(|score| GeoPoint::from_hash(score as u64))
+            })
+            .collect()
+    }
+
+    /// Distance between two members in meters, None if one of them is missing.
+    pub fn geodist(&self, key: &str, from: &str, to: &str) -> Option<f64> {
+        let points = self.geopos(key, &[from.to_string(), to.to_string()]);
+        match (points[0], points[1]) {
+            (Some(from), Some(to)) => Some(from.distance(&to)),
+            _ => None,
+        }
+    }
+
+    /// The members within the shape centered on `center`, in no particular order.
+    pub fn geosearch(&self, key: &str, center: GeoPoint, shape: GeoShape) -> Vec<GeoMatch> {
+        self.expire_if_needed(key);
+        let Some(zset) = self.keyspace().zset.get(key) else {
+            return vec![];
+        };
+        // every member is checked, geo sets are expected to hold a few thousands of places
+        zset.iter()
+            .filter_map(|(member, score)| {
+                let hash = score as u64;
+                let point = GeoPoint::from_hash(hash);
+                let distance = center.distance_within(&point, shape)?;
+                Some(GeoMatch {
+                    member: member.to_string(),
+                    point,
+                    hash,
+                    distance,
+                })
+            })
+            .collect()
+    }
+}
+
+// the geohash of a point, the latitudes being within `lat_range`
+fn encode(lon: f64, lat: f64, (lat_min, lat_max): (f64, f64)) -> u64 {
+    let scale = |v: f64, min: f64, max: f64| {
+        let offset = (v - min) / (max - min) * (1u64 << GEO_STEP) as f64;
+        (offset as u64).min((1 << GEO_STEP) - 1) as u32
+    };
+    interleave(
+        scale(lat, lat_min, lat_max),
+        scale(lon, GEO_LON_MIN, GEO_LON_MAX),
+    )
+}
+
+// the bits of x in the even positions and the bits of y in the odd ones
+fn interleave(x: u32, y: u32) -> u64 {
+    (0..32).fold(0, |hash, i| {
+        hash | (((x >> i) & 1) as u64) << (2 * i) | (((y >> i) & 1) as u64) << (2 * i + 1)
+    })
+}
+
+fn deinterleave(hash: u64) -> (u32, u32) {
+    (0..32).fold((0, 0), |(x, y), i| {
+        (
+            x | (((hash >> (2 * i)) & 1) as u32) << i,
+            y | (((hash >> (2 * i + 1)) & 1) as u32) << i,
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ZAddFlags;
+
+    use super::*;
+
+    fn point(lon: f64, lat: f64) -> GeoPoint {
+        GeoPoint::new(lon, lat).unwrap()
+    }
+
+    #[test]
+    fn test_geohash() {
+        // the scores and the hashes redis stores and reports for the same places
+        let palermo = point(13.361389, 38.115556);
+        assert_eq!(palermo.hash(), 3479099956230698);
+        assert_eq!(palermo.geohash_string(), "sqc8b49rny0");
+        let decoded = GeoPoint::from_hash(palermo.hash());
+        assert!((decoded.lon - 13.361389).abs() < 1e-5);
+        assert!((decoded.lat - 38.115556).abs() < 1e-5);
+
+        // redis measures between the positions as they are stored
+        let catania = GeoPoint::from_hash(point(15.087269, 37.502669).hash());
+        assert!((decoded.distance(&catania) - 166274.1516).abs() < 0.01);
+        assert_eq!(
+            GeoPoint::new(200.0, 10.0),
+            Err(GeoError::InvalidPoint(200.0, 10.0))
+        );
+        assert!(GeoPoint::new(0.0, 86.0).is_err());
+    }
+
+    #[test]
+    fn test_geosearch() {
+        let backend = Backend::new();
+        let places = [
+            ("palermo", point(13.361389, 38.115556)),
+            ("catania", point(15.087269, 37.502669)),
+        ];
+        let entries = places
+            .iter()
+            .map(|(name, point)| (point.hash() as f64, name.to_string()))
+            .collect();
+        backend
+            .zadd("sicily".to_string(), entries, ZAddFlags::default())
+            .unwrap();
+
+        let center = point(15.0, 37.0);
+        let found = backend.geosearch("sicily", center, GeoShape::Radius(100_000.0));
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].member, "catania");
+        assert!((found[0].distance - 56441.2645).abs() < 0.01);
+
+        let shape = GeoShape::Box {
+            width: 400_000.0,
+            height: 400_000.0,
+        };
+        assert_eq!(backend.geosearch("sicily", center, shape).len(), 2);
+        let dist = backend.geodist("sicily", "palermo", "catania").unwrap();
+        assert!((dist - 166274.1516).abs() < 0.01);
+        assert_eq!(backend.geodist("sicily", "palermo", "rome"), None);
+    }
+}
@@ -0,0 +1,395 @@
+use bytes::{BufMut, Bytes, BytesMut};
+use thiserror::Error;
+
+use crate::{Backend, StringError};
+
+// the layout of redis: 2^14 registers of 6 bits, for a standard error of 0.81%
+const HLL_P: u32 = 14;
+const HLL_Q: u32 = 64 - HLL_P;
+const HLL_REGISTERS: usize = 1 << HLL_P;
+const HLL_BITS: usize = 6;
+const HLL_REGISTER_MAX: u8 = (1 << HLL_BITS) - 1;
+// magic, encoding, 3 unused bytes and the cached cardinality
+const HLL_HDR_SIZE: usize = 16;
+const HLL_DENSE_SIZE: usize = HLL_HDR_SIZE + (HLL_REGISTERS * HLL_BITS).div_ceil(8);
+const HLL_DENSE: u8 = 0;
+const HLL_SPARSE: u8 = 1;
+// the sparse representation is kept until it would exceed this many bytes
+const HLL_SPARSE_MAX_BYTES: usize = 3000;
+const HLL_SPARSE_VAL_MAX_VALUE: u8 = 32;
+const HLL_SPARSE_VAL_MAX_LEN: usize = 4;
+const HLL_SPARSE_ZERO_MAX_LEN: usize = 64;
+const HLL_SPARSE_XZERO_MAX_LEN: usize = 16384;
+const HLL_ALPHA_INF: f64 = 0.721_347_520_444_481_7;
+const HLL_HASH_SEED: u64 = 0xadc83b19;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum HllError {
+    #[error("WRONGTYPE Key is not a valid HyperLogLog string value.")]
+    InvalidValue,
+    #[error("{0}")]
+    String(#[from] StringError),
+}
+
+/// The registers of a HyperLogLog, stored in a string with the sparse or the dense encoding
+/// of redis.
+#[derive(Debug, Clone, PartialEq)]
+struct HyperLogLog {
+    registers: Vec<u8>,
+}
+
+impl Default for HyperLogLog {
+    fn default() -> Self {
+        Self {
+            registers: vec![0; HLL_REGISTERS],
+        }
+    }
+}
+
+impl HyperLogLog {
+    fn decode(value: &[u8]) -> Result<Self, HllError> {
+        if value.len() < HLL_HDR_SIZE || &value[..4] != b"HYLL" {
+            return Err(HllError::InvalidValue);
+        }
+        let data = &value[HLL_HDR_SIZE..];
+        let registers = match value[4] {
+            HLL_DENSE if value.len() == HLL_DENSE_SIZE => {
+                (0..HLL_REGISTERS).map(|i| dense_get(data, i)).collect()
+            }
+            HLL_SPARSE => sparse_decode(data)?,
+            _ => return Err(HllError::InvalidValue),
+        };
+        Ok(Self { registers })
+    }
+
+    // the sparse encoding while it is small enough, the dense one otherwise
+    fn encode(&self) -> Bytes {
+        let sparse = self
+            .registers
+            .iter()
+            .all(|v| *v <= HLL_SPARSE_VAL_MAX_VALUE)
+            .then(|| sparse_encode(&self.registers))
+            .filter(|data| data.len() <= HLL_SPARSE_MAX_BYTES);
+        let (encoding, data) = match sparse {
+            Some(data) => (HLL_SPARSE, data),
+            None => {
+                let mut data = vec![0; HLL_DENSE_SIZE - HLL_HDR_SIZE];
+                for (i, v) in self.registers.iter().enumerate() {
+                    dense_set(&mut data, i, *v);
+                }
+                (HLL_DENSE, data)
+            }
+        };
+        let mut buf = BytesMut::with_capacity(HLL_HDR_SIZE + data.len());
+        buf.put_slice(b"HYLL");
+        buf.put_u8(encoding);
+        buf.put_bytes(0, 3);
+        // the cached cardinality is flagged as invalid, it is computed by PFCOUNT
+        buf.put_bytes(0, 7);
+        buf.put_u8(0x80);
+        buf.put_slice(&data);
+        buf.freeze()
+    }
+
+    /// Add an element. Returns whether a register changed.
+    fn add(&mut self, element: &[u8]) -> bool {
+        let hash = murmurhash64a(element, HLL_HASH_SEED);
+        let index = (hash as usize) & (HLL_REGISTERS - 1);
+        // the position of the first set bit, the bit above the 50 used ones ending the run
+        let count = ((hash >> HLL_P) | (1 << HLL_Q)).trailing_zeros() as u8 + 1;
+        if count > self.registers[index] {
+            self.registers[index] = count;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn merge(&mut self, other: &HyperLogLog) {
+        for (v, other) in self.registers.iter_mut().zip(&other.registers) {
+            *v = (*v).max(*other);
+        }
+    }
+
+    /// The estimated cardinality, with the estimator of redis which needs no bias correction.
+    fn count(&self) -> u64 {
+        let mut histogram = [0u32; 64];
+        for v in &self.registers {
+            histogram[*v as usize] += 1;
+        }
+        let m = HLL_REGISTERS as f64;
+        let mut z = m * tau((m - histogram[HLL_Q as usize + 1] as f64) / m);
+        for j in (1..=HLL_Q as usize).rev() {
+            z += histogram[j] as f64;
+            z *= 0.5;
+        }
+        z += m * sigma(histogram[0] as f64 / m);
+        (HLL_ALPHA_INF * m * m / z).round() as u64
+    }
+}
+
+impl Backend {
+    /// Add elements to the HyperLogLog at key, creating it if needed. Returns whether it changed.
+    pub fn pfadd(&self, key: &str, elements: &[Bytes]) -> Result<bool, HllError> {
+        self.update_string(key, |old| {
+            let (mut hll, mut changed) = match old {
+                Some(old) => (HyperLogLog::decode(old)?, false),
+                None => (HyperLogLog::default(), true),
+            };
+            for element in elements {
+                changed |= hll.add(element);
+            }
+            Ok((changed.then(|| hll.encode()), changed))
+        })
+    }
+
+    /// The estimated number of distinct elements added to the union of the HyperLogLogs at keys.
+    pub fn pfcount(&self, keys: &[String]) -> Result<u64, HllError> {
+        let values = keys.iter().filter_map(|key| self.get(key));
+        let mut union = HyperLogLog::default();
+        for value in values {
+            // a single HyperLogLog may carry its cardinality, cached by redis
+            if keys.len() == 1 && value.len() >= HLL_HDR_SIZE && value[15] & 0x80 == 0 {
+                HyperLogLog::decode(&value)?;
+                let card = u64::from_le_bytes(value[8..16].try_into().unwrap_or_default());
+                return Ok(card);
+            }
+            union.merge(&HyperLogLog::decode(&value)?);
+        }
+        Ok(union.count())
+    }
+
+    /// Store at dest the union of the HyperLogLogs at dest and at keys.
+    pub fn pfmerge(&self, dest: &str, keys: &[String]) -> Result<(), HllError> {
+        let mut union = HyperLogLog::default();
+        for key in keys {
+            if let Some(value) = self.get(key) {
+                union.merge(&HyperLogLog::decode(&value)?);
+            }
+        }
+        self.update_string(dest, |old| {
+            if let Some(old) = old {
+                union.merge(&HyperLogLog::decode(old)?);
+            }
+            Ok((Some(union.encode()), ()))
+        })
+    }
+}
+
+// registers are packed from the least significant bits, a register may span two bytes
+fn dense_get(data: &[u8], i: usize) -> u8 {
+    let (byte, bit) = (i * HLL_BITS / 8, i * HLL_BITS % 8);
+    let lo = data[byte] as u16 >> bit;
+    let hi = (data.get(byte + 1).copied().unwrap_or_default() as u16) << (8 - bit);
+    (lo | hi) as u8 & HLL_REGISTER_MAX
+}
+
+fn dense_set(data: &mut [u8], i: usize, v: u8) {
+    let (byte, bit) = (i * HLL_BITS / 8, i * HLL_BITS % 8);
+    data[byte] &= !(HLL_REGISTER_MAX << bit);
+    data[byte] |= v << bit;
+    if bit + HLL_BITS > 8 {
+        data[byte + 1] &= !(HLL_REGISTER_MAX >> (8 - bit));
+        data[byte + 1] |= v >> (8 - bit);
+    }
+}
+
+// the sparse encoding is a sequence of runs:
+// ZERO 00xxxxxx, up to 64 zero registers
+// XZERO 01xxxxxx yyyyyyyy, up to 16384 zero registers
+// VAL 1vvvvvxx, up to 4 registers of value 1 to 32
+fn sparse_decode(data: &[u8]) -> Result<Vec<u8>, HllError> {
+    let mut registers = Vec::with_capacity(HLL_REGISTERS);
+    let mut bytes = data.iter();
+    while let Some(&op) = bytes.next() {
+        let (value, len) = match op & 0xc0 {
+            0x00 => (0, (op & 0x3f) as usize + 1),
+            0x40 => {
+                let next = *bytes.next().ok_or(HllError::InvalidValue)?;
+                (0, (((op & 0x3f) as usize) << 8 | next as usize) + 1)
+            }
+            _ => (((op >> 2) & 0x1f) + 1, (op & 0x03) as usize + 1),
+        };
+        if registers.len() + len > HLL_REGISTERS {
+            return Err(HllError::InvalidValue);
+        }
+        registers.resize(registers.len() + len, value);
+    }
+    match registers.len() == HLL_REGISTERS {
+        true => Ok(registers),
+        false => Err(HllError::InvalidValue),
+    }
+}
+
+fn sparse_encode(registers: &[u8]) -> Vec<u8> {
+    let mut data = Vec::new();
+    let mut i = 0;
+    while i < registers.len() {
+        let value = registers[i];
+        let run = registers[i..].iter().take_while(|v| **v == value).count();
+        let mut left = run;
+        while left > 0 {
+            let len = match value {
+                0 if left > HLL_SPARSE_ZERO_MAX_LEN => {
+                    let len = left.min(HLL_SPARSE_XZERO_MAX_LEN);
+                    data.push(0x40 | ((len - 1) >> 8) as u8);
+                    data.push((len - 1) as u8);
+                    len
+                }
+                0 => {
+                    data.push((left - 1) as u8);
+                    left
+                }
+                value => {
+                    let len = left.min(HLL_SPARSE_VAL_MAX_LEN);
+                    data.push(0x80 | (value - 1) << 2 | (len - 1) as u8);
+                    len
+                }
+            };
+            left -= len;
+        }
+        i += run;
+    }
+    data
+}
+
+fn sigma(mut x: f64) -> f64 {
+    if x == 1.0 {
+        return f64::INFINITY;
+    }
+    let (mut y, mut z) = (1.0, x);
+    loop {
+        x *= x;
+        let previous = z;
+        z += x * y;
+        y += y;
+        if previous == z {
+            return z;
+        }
+    }
+}
+
+fn tau(mut x: f64) -> f64 {
+    if x == 0.0 || x == 1.0 {
+        return 0.0;
+    }
+    let (mut y, mut z) = (1.0, 1.0 - x);
+    loop {
+        x = x.sqrt();
+        let previous = z;
+        y *= 0.5;
+        z -= (1.0 - x).powi(2) * y;
+        if previous == z {
+            return z / 3.0;
+        }
+    }
+}
+
+// the hash function of redis, so that the same elements set the same registers
+fn murmurhash64a(key: &[u8], seed: u64) -> u64 {
+    const M: u64 = 0xc6a4a7935bd1e995;
+    const R: u32 = 47;
+    let mut h = seed ^ (key.len() as u64).wrapping_mul(M);
+    let mut chunks = key.chunks_exact(8);
+    for chunk in &mut chunks {
+        let mut k = u64::from_le_bytes(chunk.try_into().unwrap_or_default());
+        k = k.wrapping_mul(M);
+        k ^= k >> R;
+        k = k.wrapping_mul(M);
+        h ^= k;
+        h = h.wrapping_mul(M);
+    }
+    let tail = chunks.remainder();
+    if !tail.is_empty() {
+        for (i, b) in tail.iter().enumerate() {
+            h ^= (*b as u64) << (8 * i);
+        }
+        h = h.wrapping_mul(M);
+    }
+    h ^= h >> R;
+    h = h.wrapping_mul(M);
+    h ^= h >> R;
+    h
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::BulkString;
+
+    use super::*;
+
+    #[test]
+    fn test_hll_encodings() {
+        let mut hll = HyperLogLog::default();
+        for i in 0..100 {
+            hll.add(format!("element-{}", i).as_bytes());
+        }
+        let sparse = hll.encode();
+        assert_eq!(sparse[4], HLL_SPARSE);
+        assert_eq!(HyperLogLog::decode(&sparse), Ok(hll.clone()));
+
+        for i in 100..20_000 {
+            hll.add(format!("element-{}", i).as_bytes());
+        }
+        let dense = hll.encode();
+        assert_eq!((dense[4], dense.len()), (HLL_DENSE, HLL_DENSE_SIZE));
+        assert_eq!(HyperLogLog::decode(&dense), Ok(hll));
+        assert_eq!(
+            HyperLogLog::decode(b"HYLL\x01\0\0\0\0\0\0\0\0\0\0\x80\x7f"),
+            Err(HllError::InvalidValue)
+        );
+    }
+
+    #[test]
+    fn test_hll_error_bound() {
+        let mut hll = HyperLogLog::default();
+        assert_eq!(hll.count(), 0);
+        for (i, n) in [10u64, 1_000, 100_000].into_iter().enumerate() {
+            let start = [0, 10, 1_000][i];
+            for i in start..n {
+                hll.add(i.to_string().as_bytes());
+            }
+            let error = (hll.count() as f64 - n as f64).abs() / n as f64;
+            // three times the standard error
+            assert!(error < 0.0243, "{} counted as {}", n, hll.count());
+        }
+    }
+
+    #[test]
+    fn test_pfadd_pfcount_pfmerge() {
+        let backend = Backend::new();
+        let elements = |range: std::ops::Range<u32>| {
+            range
+                .map(|i| Bytes::from(i.to_string()))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(backend.pfadd("a", &elements(0..500)), Ok(true));
+        assert_eq!(backend.pfadd("a", &elements(0..500)), Ok(false));
+        assert_eq!(backend.pfadd("b", &elements(250..1000)), Ok(true));
+        assert_eq!(backend.pfadd("empty", &[]), Ok(true));
+
+        let count = backend
+            .pfcount(&["a".to_string(), "b".to_string()])
+            .unwrap();
+        assert!((980..=1020).contains(&count));
+        backend
+            .pfmerge("a", &["b".to_string(), "missing".to_string()])
+            .unwrap();
+        assert_eq!(backend.pfcount(&["a".to_string()]), Ok(count));
+
+        backend.set("s".to_string(), "value".into());
+        assert_eq!(backend.pfadd("s", &[]), Err(HllError::InvalidValue));
+
+        backend
+            .rpush("l".to_string(), vec![BulkString::new("a").into()])
+            .unwrap();
+        assert_eq!(
+            backend.pfadd("l", &[Bytes::from("x")]),
+            Err(HllError::String(StringError::WrongType))
+        );
+        assert_eq!(
+            backend.pfmerge("l", &[]),
+            Err(HllError::String(StringError::WrongType))
+        );
+    }
+}
@@ -0,0 +1,293 @@
+use std::collections::VecDeque;
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+use bytes::Bytes;
+use dashmap::DashMap;
+
+use crate::{glob_match, now_ms, Backend, RespFrame};
+
+use super::{SortedSet, Stream};
+
+/// A step of a SCAN iteration: the cursor to continue from, 0 once the iteration is complete,
+/// and the items of the step.
+pub type ScanStep<T> = (u64, Vec<T>);
+
+// a value of any type, moved as a whole by RENAME
+enum Value {
+    String(Bytes),
+    Hash(DashMap<String, RespFrame>),
+    List(VecDeque<RespFrame>),
+    ZSet(SortedSet),
+    Stream(Stream),
+}
+
+impl Backend {
+    /// Remove every key of the selected database, flagging the connections watching them.
+    pub fn flushdb(&self) {
+        for key in self.key_names() {
+            self.remove_key(&key);
+        }
+        self.keyspace().expires.clear();
+    }
+
+    /// Number of keys in the selected database, including expired keys not removed yet.
+    pub fn dbsize(&self) -> usize {
+        let keyspace = self.keyspace();
+        keyspace.map.len()
+            + keyspace.hmap.len()
+            + keyspace.list.len()
+            + keyspace.zset.len()
+            + keyspace.stream.len()
+    }
+
+    /// Number of keys with a ttl in the selected database.
+    pub fn volatile_keys(&self) -> usize {
+        self.keyspace().expires.len()
+    }
+
+    /// Remove the keys. Returns the number of keys which existed.
+    pub fn del(&self, keys: &[String]) -> usize {
+        keys.iter()
+            .filter(|key| {
+                self.expire_if_needed(key);
+                self.remove_key(key)
+            })
+            .count()
+    }
+
+    /// The keys matching a glob-style pattern.
+    pub fn keys(&self, pattern: &str) -> Vec<String> {
+        let now = now_ms();
+        self.key_names()
+            .into_iter()
+            .filter(|key| self.alive_at(key, now) && glob_match(pattern.as_bytes(), key.as_bytes()))
+            .collect()
+    }
+
+    /// A step of the iteration over the keys starting at `cursor`, with about `count` keys
+    /// before the keys matching `pattern` and of type `type_` are filtered out.
+    pub fn scan(
+        &self,
+        cursor: u64,
+        pattern: Option<&str>,
+        count: usize,
+        type_: Option<&str>,
+    ) -> ScanStep<String> {
+        let now = now_ms();
+        let keys = self.key_names().into_iter().map(|key| (key, ()));
+        let (cursor, keys) = scan_step(keys, cursor, count);
+        let keys = keys
+            .into_iter()
+            .map(|(key, _)| key)
+            .filter(|key| self.alive_at(key, now))
+            .filter(|key| {
+                pattern.is_none_or(|pattern| glob_match(pattern.as_bytes(), key.as_bytes()))
+            })
+            .filter(|key| type_.is_none_or(|type_| self.key_type(key) == Some(type_)))
+            .collect();
+        (cursor, keys)
+    }
+
+    /// Same as `scan`, over the fields of the hash at key.
+    pub fn hscan(
+        &self,
+        key: &str,
+        cursor: u64,
+        pattern: Option<&str>,
+        count: usize,
+    ) -> ScanStep<(String, RespFrame)> {
+        self.expire_if_needed(key);
+        let Some(hmap) = self.keyspace().hmap.get(key).map(|v| v.clone()) else {
+            return (0, vec![]);
+        };
+        let (cursor, mut fields) = scan_step(hmap.into_iter(), cursor, count);
+        if let Some(pattern) = pattern {
+            fields.retain(|(field, _)| glob_match(pattern.as_bytes(), field.as_bytes()));
+        }
+        (cursor, fields)
+    }
+
+    /// Move the value of `key` along with its ttl to `new_key`, replacing any value there unless
+    /// `nx`. Returns None if `key` does not exist, otherwise whether the value was moved.
+    pub fn rename(&self, key: &str, new_key: &str, nx: bool) -> Option<bool> {
+        if !self.exists(key) {
+            return None;
+        }
+        if key == new_key || (nx && self.exists(new_key)) {
+            return Some(!nx);
+        }
+        let ttl = self.keyspace().expires.remove(key).map(|(_, at)| at);
+        let value = self.take_value(key)?;
+        self.touch(key);
+        self.remove_key(new_key);
+        self.insert_value(new_key.to_string(), value);
+        if let Some(at) = ttl {
+            self.keyspace().expires.insert(new_key.to_string(), at);
+        }
+        self.touch(new_key);
+        if let Some(notify) = self.keyspace().key_waiters.get(new_key) {
+            notify.notify_waiters();
+        }
+        Some(true)
+    }
+
+    // the keys of every type, expired or not
+    fn key_names(&self) -> Vec<String> {
+        let keyspace = self.keyspace();
+        keyspace
+            .map
+            .iter()
+            .map(|v| v.key().clone())
+            .chain(keyspace.hmap.iter().map(|v| v.key().clone()))
+            .chain(keyspace.list.iter().map(|v| v.key().clone()))
+            .chain(keyspace.zset.iter().map(|v| v.key().clone()))
+            .chain(keyspace.stream.iter().map(|v| v.key().clone()))
+            .collect()
+    }
+
+    // whether the key has not expired at `now`, without removing it
+    fn alive_at(&self, key: &str, now: u64) -> bool {
+        self.keyspace()
+            .expires
+            .get(key)
+            .is_none_or(|at| *at.value() > now)
+    }
+
+    fn take_value(&self, key: &str) -> Option<Value> {
+        let keyspace = self.keyspace();
+        keyspace
+            .map
+            .remove(key)
+            .map(|(_, v)| Value::String(v))
+            .or_else(|| keyspace.hmap.remove(key).map(|(_, v)| Value::Hash(v)))
+            .or_else(|| keyspace.list.remove(key).map(|(_, v)| Value::List(v)))
+            .or_else(|| keyspace.zset.remove(key).map(|(_, v)| Value::ZSet(v)))
+            .or_else(|| keyspace.stream.remove(key).map(|(_, v)| Value::Stream(v)))
+    }
+
+    fn insert_value(&self, key: String, value: Value) {
+        let keyspace = self.keyspace();
+        match value {
+            Value::String(v) => {
+                keyspace.map.insert(key, v);
+            }
+            Value::Hash(v) => {
+                keyspace.hmap.insert(key, v);
+            }
+            Value::List(v) => {
+                keyspace.list.insert(key, v);
+            }
+            Value::ZSet(v) => {
+                keyspace.zset.insert(key, v);
+            }
+            Value::Stream(v) => {
+                keyspace.stream.insert(key, v);
+            }
+        }
+    }
+}
+
+// items are iterated in the order of a hash of their name and the cursor is the hash to go on
+// from, so that the items present during the whole iteration are returned exactly once
+fn scan_step<T>(
+    items: impl Iterator<Item = (String, T)>,
+    cursor: u64,
+    count: usize,
+) -> ScanStep<(String, T)> {
+    let mut items = items
+        .map(|item| (scan_position(&item.0), item))
+        .filter(|(position, _)| *position >= cursor)
+        .collect::<Vec<_>>();
+    items.sort_unstable_by_key(|(position, _)| *position);
+    // the items at the same position go in the same step, the cursor can't tell them apart
+    let mut end = count.max(1).min(items.len());
+    while end < items.len() && items[end].0 == items[end - 1].0 {
+        end += 1;
+    }
+    let cursor = items.get(end).map_or(0, |(position, _)| *position);
+    items.truncate(end);
+    (cursor, items.into_iter().map(|(_, item)| item).collect())
+}
+
+// 0 is the cursor which starts and ends an iteration, no item is at that position
+fn scan_position(name: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    name.hash(&mut hasher);
+    hasher.finish().max(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use crate::BulkString;
+
+    use super::*;
+
+    #[test]
+    fn test_scan_returns_every_key_once() {
+        let backend = Backend::new();
+        for i in 0..100 {
+            backend.set(format!("key:{}", i), "v".into());
+        }
+        backend
+            .hset(
+                "hash".to_string(),
+                "f".to_string(),
+                BulkString::new("v").into(),
+            )
+            .unwrap();
+
+        let (mut cursor, mut seen) = (0, HashSet::new());
+        loop {
+            let (next, keys) = backend.scan(cursor, Some("key:*"), 7, None);
+            for key in keys {
+                assert!(seen.insert(key));
+            }
+            // keys added during the iteration may or may not be returned
+            backend.set(format!("new:{}", next), "v".into());
+            if next == 0 {
+                break;
+            }
+            cursor = next;
+        }
+        assert_eq!(seen.len(), 100);
+
+        let (cursor, keys) = backend.scan(0, None, 1000, Some("hash"));
+        assert_eq!((cursor, keys), (0, vec!["hash".to_string()]));
+        assert_eq!(backend.hscan("hash", 0, Some("f*"), 10).1.len(), 1);
+        assert_eq!(backend.hscan("missing", 0, None, 10), (0, vec![]));
+    }
+
+    #[test]
+    fn test_rename_del_and_flushdb() {
+        let backend = Backend::new();
+        backend
+            .rpush("a".to_string(), vec![BulkString::new("x").into()])
+            .unwrap();
+        backend.expire_at("a", now_ms() + 60_000, None);
+        backend.set("b".to_string(), "v".into());
+
+        assert_eq!(backend.rename("missing", "c", false), None);
+        assert_eq!(backend.rename("a", "b", true), Some(false));
+        assert_eq!(backend.rename("a", "b", false), Some(true));
+        assert_eq!(backend.key_type("b"), Some("list"));
+        assert!(backend.pttl("b") > 0);
+        assert!(!backend.exists("a"));
+
+        let mut keys = backend.keys("*");
+        keys.sort();
+        assert_eq!(keys, vec!["b".to_string()]);
+        backend.set("c".to_string(), "v".into());
+        assert_eq!(backend.dbsize(), 2);
+        assert_eq!(backend.del(&["b".to_string(), "missing".to_string()]), 1);
+
+        let other = backend.select(1).expect("16 databases by default");
+        other.set("c".to_string(), "other".into());
+        backend.flushdb();
+        assert_eq!(backend.dbsize(), 0);
+        assert_eq!(other.get("c"), Some("other".into()));
+        backend.flushall();
+        assert_eq!(other.dbsize(), 0);
+    }
+}
@@ -0,0 +1,343 @@
+use std::collections::VecDeque;
+
+use thiserror::Error;
+
+use crate::{Backend, RespFrame};
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum ListError {
+    #[error("WRONGTYPE Operation against a key holding the wrong kind of value")]
+    WrongType,
+}
+
+// a missing list is created with the key locked, so that a concurrent SET can't store a string
+// between the type check and the push
+impl Backend {
+    pub fn lpush(&self, key: String, values: Vec<RespFrame>) -> Result<usize, ListError> {
+        self.push(key, values, true, true)
+    }
+
+    pub fn rpush(&self, key: String, values: Vec<RespFrame>) -> Result<usize, ListError> {
+        self.push(key, values, false, true)
+    }
+
+    /// Push only if the list exists, returns 0 otherwise.
+    pub fn lpushx(&self, key: String, values: Vec<RespFrame>) -> Result<usize, ListError> {
+        self.push(key, values, true, false)
+    }
+
+    /// Push only if the list exists, returns 0 otherwise.
+    pub fn rpushx(&self, key: String, values: Vec<RespFrame>) -> Result<usize, ListError> {
+        self.push(key, values, false, false)
+    }
+
+    fn push(
+        &self,
+        key: String,
+        values: Vec<RespFrame>,
+        left: bool,
+        create: bool,
+    ) -> Result<usize, ListError> {
+        self.expire_if_needed(&key);
+        let push_all = |list: &mut VecDeque<RespFrame>| {
+            for value in values {
+                if left {
+                    list.push_front(value);
+                } else {
+                    list.push_back(value);
+                }
+            }
+            list.len()
+        };
+        let len = match self.keyspace().list.get_mut(&key) {
+            Some(mut list) => push_all(&mut list),
+            None if !create => return self.missing_list(&key).map(|_| 0),
+            None => self
+                .create_key(&key, "list", || {
+                    push_all(&mut self.keyspace().list.entry(key.clone()).or_default())
+                })
+                .ok_or(ListError::WrongType)?,
+        };
+        self.touch(&key);
+        if let Some(notify) = self.keyspace().key_waiters.get(&key) {
+            notify.notify_waiters();
+        }
+        Ok(len)
+    }
+
+    pub fn lpop(&self, key: &str, count: usize) -> Result<Option<Vec<RespFrame>>, ListError> {
+        self.pop(key, count, true)
+    }
+
+    pub fn rpop(&self, key: &str, count: usize) -> Result<Option<Vec<RespFrame>>, ListError> {
+        self.pop(key, count, false)
+    }
+
+    fn pop(
+        &self,
+        key: &str,
+        count: usize,
+        left: bool,
+    ) -> Result<Option<Vec<RespFrame>>, ListError> {
+        self.expire_if_needed(key);
+        let (values, empty) = {
+            let Some(mut list) = self.keyspace().list.get_mut(key) else {
+                return self.missing_list(key).map(|_| None);
+            };
+            let n = count.min(list.len());
+            let values = if left {
+                list.drain(..n).collect::<Vec<_>>()
+            } else {
+                let start = list.len() - n;
+                list.drain(start..).rev().collect::<Vec<_>>()
+            };
+            (values, list.is_empty())
+        };
+        if !values.is_empty() {
+            self.touch(key);
+        }
+        if empty {
+            // an empty list is removed from the keyspace
+            self.keyspace()
+                .list
+                .remove_if(key, |_, list| list.is_empty());
+            self.keyspace().expires.remove(key);
+        }
+        Ok(Some(values))
+    }
+
+    pub fn llen(&self, key: &str) -> usize {
+        self.expire_if_needed(key);
+        self.keyspace()
+            .list
+            .get(key)
+            .map(|v| v.len())
+            .unwrap_or_default()
+    }
+
+    pub fn lrange(&self, key: &str, start: i64, stop: i64) -> Vec<RespFrame> {
+        self.expire_if_needed(key);
+        match self.keyspace().list.get(key) {
+            Some(list) => match normalize_range(start, stop, list.len()) {
+                Some((start, stop)) => list.range(start..=stop).cloned().collect(),
+                None => vec![],
+            },
+            None => vec![],
+        }
+    }
+
+    pub fn lindex(&self, key: &str, index: i64) -> Option<RespFrame> {
+        self.expire_if_needed(key);
+        let list = self.keyspace().list.get(key)?;
+        let index = normalize_index(index, list.len())?;
+        list.get(index).cloned()
+    }
+
+    /// Set the element at index. Returns None if the key does not exist,
+    /// Some(false) if the index is out of range.
+    pub fn lset(&self, key: &str, index: i64, value: RespFrame) -> Result<Option<bool>, ListError> {
+        self.expire_if_needed(key);
+        let Some(mut list) = self.keyspace().list.get_mut(key) else {
+            return self.missing_list(key).map(|_| None);
+        };
+        match normalize_index(index, list.len()) {
+            Some(index) => {
+                list[index] = value;
+                drop(list);
+                self.touch(key);
+                Ok(Some(true))
+            }
+            None => Ok(Some(false)),
+        }
+    }
+
+    /// Trim the list so that it only contains the specified inclusive range.
+    pub fn ltrim(&self, key: &str, start: i64, stop: i64) -> Result<(), ListError> {
+        self.expire_if_needed(key);
+        let empty = match self.keyspace().list.get_mut(key) {
+            Some(mut list) => {
+                match normalize_range(start, stop, list.len()) {
+                    Some((start, stop)) => {
+                        list.truncate(stop + 1);
+                        list.drain(..start);
+                    }
+                    None => list.clear(),
+                }
+                list.is_empty()
+            }
+            None => return self.missing_list(key),
+        };
+        self.touch(key);
+        if empty {
+            self.keyspace()
+                .list
+                .remove_if(key, |_, list| list.is_empty());
+            self.keyspace().expires.remove(key);
+        }
+        Ok(())
+    }
+
+    /// Remove `count` occurrences of value: from head to tail if count > 0,
+    /// from tail to head if count < 0, all of them if count == 0.
+    pub fn lrem(&self, key: &str, count: i64, value: &RespFrame) -> Result<usize, ListError> {
+        self.expire_if_needed(key);
+        let (removed, empty) = match self.keyspace().list.get_mut(key) {
+            Some(mut list) => {
+                let limit = if count == 0 {
+                    usize::MAX
+                } else {
+                    count.unsigned_abs() as usize
+                };
+                let mut removed = 0;
+                let mut kept = VecDeque::with_capacity(list.len());
+                if count >= 0 {
+                    for v in list.drain(..) {
+                        if removed < limit && &v == value {
+                            removed += 1;
+                        } else {
+                            kept.push_back(v);
+                        }
+                    }
+                } else {
+                    for v in list.drain(..).rev() {
+                        if removed < limit && &v == value {
+                            removed += 1;
+                        } else {
+                            kept.push_front(v);
+                        }
+                    }
+                }
+                *list = kept;
+                (removed, list.is_empty())
+            }
+            None => return self.missing_list(key).map(|_| 0),
+        };
+        if removed > 0 {
+            self.touch(key);
+        }
+        if empty {
+            self.keyspace()
+                .list
+                .remove_if(key, |_, list| list.is_empty());
+            self.keyspace().expires.remove(key);
+        }
+        Ok(removed)
+    }
+
+    // there is no list at key, which is an error if the key holds another type
+    fn missing_list(&self, key: &str) -> Result<(), ListError> {
+        match self.key_type(key) {
+            Some("list") | None => Ok(()),
+            Some(_) => Err(ListError::WrongType),
+        }
+    }
+}
+
+// convert a possibly negative index into an offset of a list of length len
+fn normalize_index(index: i64, len: usize) -> Option<usize> {
+    let index = if index < 0 { len as i64 + index } else { index };
+    if index < 0 || index >= len as i64 {
+        None
+    } else {
+        Some(index as usize)
+    }
+}
+
+// convert a possibly negative inclusive range into offsets of a list of length len
+pub(crate) fn normalize_range(start: i64, stop: i64, len: usize) -> Option<(usize, usize)> {
+    let len = len as i64;
+    let start = if start < 0 {
+        (len + start).max(0)
+    } else {
+        start
+    };
+    let stop = if stop < 0 {
+        len + stop
+    } else {
+        stop.min(len - 1)
+    };
+    if start > stop || start >= len {
+        None
+    } else {
+        Some((start as usize, stop as usize))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values(list: &[&str]) -> Vec<RespFrame> {
+        list.iter()
+            .map(|v| RespFrame::BulkString(v.as_bytes().into()))
+            .collect()
+    }
+
+    #[test]
+    fn test_push_pop_range() -> Result<(), ListError> {
+        let backend = Backend::new();
+        assert_eq!(backend.rpush("list".to_string(), values(&["a", "b"]))?, 2);
+        assert_eq!(backend.lpush("list".to_string(), values(&["c", "d"]))?, 4);
+        assert_eq!(backend.lrange("list", 0, -1), values(&["d", "c", "a", "b"]));
+        assert_eq!(backend.lrange("list", -2, 100), values(&["a", "b"]));
+        assert_eq!(backend.lrange("list", 3, 1), values(&[]));
+        assert_eq!(backend.lindex("list", -1), values(&["b"]).pop());
+
+        assert_eq!(backend.lpop("list", 1)?, Some(values(&["d"])));
+        assert_eq!(backend.rpop("list", 2)?, Some(values(&["b", "a"])));
+        assert_eq!(backend.rpop("list", 2)?, Some(values(&["c"])));
+        assert_eq!(backend.rpop("list", 1)?, None);
+        assert_eq!(backend.key_type("list"), None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_lset_ltrim_lrem() -> Result<(), ListError> {
+        let backend = Backend::new();
+        backend.rpush("list".to_string(), values(&["a", "b", "a", "c", "a"]))?;
+        assert_eq!(backend.lrem("list", -2, &values(&["a"])[0])?, 2);
+        assert_eq!(backend.lrange("list", 0, -1), values(&["a", "b", "c"]));
+
+        assert_eq!(
+            backend.lset("list", 1, values(&["x"]).remove(0))?,
+            Some(true)
+        );
+        assert_eq!(
+            backend.lset("list", 5, values(&["x"]).remove(0))?,
+            Some(false)
+        );
+        assert_eq!(backend.lset("missing", 0, values(&["x"]).remove(0))?, None);
+
+        backend.ltrim("list", 1, -1)?;
+        assert_eq!(backend.lrange("list", 0, -1), values(&["x", "c"]));
+        backend.ltrim("list", 5, 10)?;
+        assert_eq!(backend.llen("list"), 0);
+        assert_eq!(backend.key_type("list"), None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_wrong_type() -> Result<(), ListError> {
+        let backend = Backend::new();
+        backend.set("key".to_string(), "value".into());
+        let wrong_type = Some(ListError::WrongType);
+        assert_eq!(
+            backend.rpush("key".to_string(), values(&["a"])).err(),
+            wrong_type
+        );
+        assert_eq!(
+            backend.lpushx("key".to_string(), values(&["a"])).err(),
+            wrong_type
+        );
+        assert_eq!(backend.lpop("key", 1).err(), wrong_type);
+        assert_eq!(backend.lrem("key", 0, &values(&["a"])[0]).err(), wrong_type);
+        assert_eq!(backend.ltrim("key", 0, 1).err(), wrong_type);
+        // the string is left as is, no list was created next to it
+        assert_eq!(backend.key_type("key"), Some("string"));
+        assert!(!backend.keyspace().list.contains_key("key"));
+
+        assert_eq!(backend.rpushx("missing".to_string(), values(&["a"]))?, 0);
+        assert_eq!(backend.key_type("missing"), None);
+        Ok(())
+    }
+}
@@ -0,0 +1,449 @@
+use std::collections::hash_map::RandomState;
+use std::collections::HashSet;
+use std::hash::{BuildHasher, Hasher};
+use std::mem;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, PoisonError};
+
+use dashmap::DashMap;
+use thiserror::Error;
+use tracing::debug;
+
+use crate::{now_ms, Array, Backend, MaxMemoryPolicy, RespFrame};
+
+// rough overheads of a key and of an element of a collection, the usage is an estimate
+const KEY_OVERHEAD: u64 = 64;
+const ENTRY_OVERHEAD: u64 = 24;
+// the access counter of a new key, how fast it grows and how fast it decays, as in redis
+const LFU_INIT_VAL: u8 = 5;
+const LFU_LOG_FACTOR: f64 = 10.0;
+const LFU_DECAY_MS: u64 = 60_000;
+
+#[derive(Error, Debug)]
+pub enum MemoryError {
+    #[error("OOM command not allowed when used memory > 'maxmemory'.")]
+    OutOfMemory,
+}
+
+/// Approximate memory usage of each key, with the access statistics the eviction relies on.
+#[derive(Debug, Default)]
+pub struct Memory {
+    keys: DashMap<String, KeyUsage>,
+    used: AtomicU64,
+    // keys modified since they were last measured
+    pending: Mutex<HashSet<String>>,
+    // taken before `pending` while measuring, so that concurrent measures apply in order
+    pools: Mutex<Pools>,
+    evicted: AtomicU64,
+}
+
+// the keys in vectors, to sample them in constant time
+#[derive(Debug, Default)]
+struct Pools {
+    all: Vec<String>,
+    // the keys with a ttl
+    volatile: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct KeyUsage {
+    size: u64,
+    // unix time in milliseconds of the last access
+    access: u64,
+    // logarithmic access counter, decayed by the time since the last access
+    freq: u8,
+    // positions of the key in the pools
+    all: usize,
+    volatile: Option<usize>,
+}
+
+impl Backend {
+    /// Approximate number of bytes used by the keyspaces of all the databases.
+    pub fn used_memory(&self) -> u64 {
+        self.all_dbs()
+            .map(|db| {
+                db.refresh_memory();
+                db.memory().used.load(Ordering::Relaxed)
+            })
+            .sum()
+    }
+
+    /// Number of keys evicted since the server started.
+    pub fn evicted_keys(&self) -> u64 {
+        self.all_dbs()
+            .map(|db| db.memory().evicted.load(Ordering::Relaxed))
+            .sum()
+    }
+
+    /// Whether the keyspaces use more than `maxmemory`.
+    pub fn over_maxmemory(&self) -> bool {
+        let maxmemory = self.config().memory.maxmemory;
+        maxmemory > 0 && self.used_memory() > maxmemory
+    }
+
+    /// Evict keys of any database with the configured policy until the keyspaces fit in
+    /// `maxmemory`, calling `evicted` with the database and the key of each key evicted. Fails if
+    /// there is nothing left to evict.
+    pub fn free_memory(&self, mut evicted: impl FnMut(usize, &str)) -> Result<(), MemoryError> {
+        let config = self.config().memory.clone();
+        while self.over_maxmemory() {
+            let now = now_ms();
+            let (db, key) = self
+                .all_dbs()
+                .filter_map(|db| {
+                    let key = db.eviction_candidate(
+                        config.maxmemory_policy,
+                        config.maxmemory_samples,
+                        now,
+                    )?;
+                    Some((db, key))
+                })
+                .max_by_key(|(_, (_, score))| *score)
+                .map(|(db, (key, _))| (db, key))
+                .ok_or(MemoryError::OutOfMemory)?;
+            debug!("evicting key {} of db {}", key, db.db());
+            if !db.remove_key(&key) {
+                // the key is gone already, measuring it drops it from the pools
+                db.track_memory(&key);
+                continue;
+            }
+            db.memory().evicted.fetch_add(1, Ordering::Relaxed);
+            evicted(db.db(), &key);
+        }
+        Ok(())
+    }
+
+    /// The access counter of the key for the LFU policy, None if the key does not exist.
+    pub fn object_freq(&self, key: &str) -> Option<u8> {
+        self.refresh_memory();
+        let usage = self.live_usage(key)?;
+        Some(lfu_decay(usage.freq, usage.access, now_ms()))
+    }
+
+    /// Seconds since the key was last accessed, None if the key does not exist.
+    pub fn object_idletime(&self, key: &str) -> Option<u64> {
+        self.refresh_memory();
+        let usage = self.live_usage(key)?;
+        Some(now_ms().saturating_sub(usage.access) / 1000)
+    }
+
+    // the usage of a key which did not expire, without counting as an access
+    fn live_usage(&self, key: &str) -> Option<KeyUsage> {
+        if matches!(self.keyspace().expires.get(key).map(|v| *v.value()), Some(at) if at <= now_ms())
+        {
+            return None;
+        }
+        self.memory().keys.get(key).map(|v| *v.value())
+    }
+
+    // record an access to the key for the LRU and LFU policies
+    pub(super) fn record_access(&self, key: &str) {
+        if let Some(mut usage) = self.memory().keys.get_mut(key) {
+            let now = now_ms();
+            usage.freq = lfu_increment(lfu_decay(usage.freq, usage.access, now));
+            usage.access = now;
+        }
+    }
+
+    // the key was modified, it is measured again before the usage is next needed
+    pub(super) fn track_memory(&self, key: &str) {
+        self.memory()
+            .pending
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(key.to_string());
+    }
+
+    /// Measure the keys modified since the last refresh.
+    pub(super) fn refresh_memory(&self) {
+        let mut pools = self
+            .memory()
+            .pools
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        let pending = mem::take(
+            &mut *self
+                .memory()
+                .pending
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner),
+        );
+        for key in pending {
+            self.measure(&mut pools, key);
+        }
+    }
+
+    fn measure(&self, pools: &mut Pools, key: String) {
+        let size = self.value_size(&key);
+        let volatile = size.is_some() && self.keyspace().expires.contains_key(&key);
+        let old = self.memory().keys.get(&key).map(|v| *v.value());
+        match (old, size) {
+            (None, None) => {}
+            (Some(old), None) => {
+                self.memory().keys.remove(&key);
+                self.memory().used.fetch_sub(old.size, Ordering::Relaxed);
+                self.unpool(&mut pools.all, old.all, false);
+                if let Some(slot) = old.volatile {
+                    self.unpool(&mut pools.volatile, slot, true);
+                }
+            }
+            (old, Some(size)) => {
+                let mut usage = old.unwrap_or_else(|| {
+                    pools.all.push(key.clone());
+                    KeyUsage {
+                        size: 0,
+                        access: now_ms(),
+                        freq: LFU_INIT_VAL,
+                        all: pools.all.len() - 1,
+                        volatile: None,
+                    }
+                });
+                match (usage.volatile, volatile) {
+                    (None, true) => {
+                        pools.volatile.push(key.clone());
+                        usage.volatile = Some(pools.volatile.len() - 1);
+                    }
+                    (Some(slot), false) => {
+                        self.unpool(&mut pools.volatile, slot, true);
+                        usage.volatile = None;
+                    }
+                    _ => {}
+                }
+                self.memory().used.fetch_add(size, Ordering::Relaxed);
+                self.memory().used.fetch_sub(usage.size, Ordering::Relaxed);
+                usage.size = size;
+                self.memory().keys.insert(key, usage);
+            }
+        }
+    }
+
+    // remove the key at `slot` of a pool, the last key of the pool takes its place
+    fn unpool(&self, pool: &mut Vec<String>, slot: usize, volatile: bool) {
+        pool.swap_remove(slot);
+        let Some(moved) = pool.get(slot) else {
+            return;
+        };
+        if let Some(mut usage) = self.memory().keys.get_mut(moved) {
+            match volatile {
+                true => usage.volatile = Some(slot),
+                false => usage.all = slot,
+            }
+        }
+    }
+
+    // the best key to evict among a few random ones, with its score
+    fn eviction_candidate(
+        &self,
+        policy: MaxMemoryPolicy,
+        samples: usize,
+        now: u64,
+    ) -> Option<(String, u64)> {
+        let pools = self
+            .memory()
+            .pools
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        let pool = match policy {
+            MaxMemoryPolicy::NoEviction => return None,
+            policy if policy.is_volatile() => &pools.volatile,
+            _ => &pools.all,
+        };
+        if pool.is_empty() {
+            return None;
+        }
+        (0..samples.max(1))
+            .map(|_| &pool[random() as usize % pool.len()])
+            .map(|key| (key.clone(), self.eviction_score(key, policy, now)))
+            .max_by_key(|(_, score)| *score)
+    }
+
+    // the higher the score, the better the key is to evict
+    fn eviction_score(&self, key: &str, policy: MaxMemoryPolicy, now: u64) -> u64 {
+        match policy {
+            MaxMemoryPolicy::VolatileTtl => match self.keyspace().expires.get(key) {
+                Some(at) => u64::MAX - *at.value(),
+                None => 0,
+            },
+            policy => {
+                let Some(usage) = self.memory().keys.get(key).map(|v| *v.value()) else {
+                    return 0;
+                };
+                match policy {
+                    MaxMemoryPolicy::AllKeysLfu => {
+                        (u8::MAX - lfu_decay(usage.freq, usage.access, now)) as u64
+                    }
+                    _ => now.saturating_sub(usage.access),
+                }
+            }
+        }
+    }
+
+    fn memory(&self) -> &Memory {
+        &self.keyspace().memory
+    }
+
+    // the size of the value stored at key, None if there is none
+    fn value_size(&self, key: &str) -> Option<u64> {
+        let size = if let Some(value) = self.keyspace().map.get(key) {
+            value.len() as u64
+        } else if let Some(hmap) = self.keyspace().hmap.get(key) {
+            hmap.iter()
+                .map(|v| ENTRY_OVERHEAD + v.key().len() as u64 + frame_size(v.value()))
+                .sum()
+        } else if let Some(list) = self.keyspace().list.get(key) {
+            list.iter().map(|v| ENTRY_OVERHEAD + frame_size(v)).sum()
+        } else if let Some(zset) = self.keyspace().zset.get(key) {
+            // members are stored twice, by name and by score
+            zset.iter()
+                .map(|(member, _)| 2 * (ENTRY_OVERHEAD + member.len() as u64 + 8))
+                .sum()
+        } else if let Some(stream) = self.keyspace().stream.get(key) {
+            let entries = stream
+                .entries()
+                .map(|(_, fields)| ENTRY_OVERHEAD + 16 + fields.iter().map(frame_size).sum::<u64>())
+                .sum::<u64>();
+            let pending = stream
+                .groups()
+                .map(|(name, group)| {
+                    ENTRY_OVERHEAD + name.len() as u64 + group.pending().count() as u64 * 48
+                })
+                .sum::<u64>();
+            entries + pending
+        } else {
+            return None;
+        };
+        Some(KEY_OVERHEAD + key.len() as u64 + size)
+    }
+}
+
+fn frame_size(frame: &RespFrame) -> u64 {
+    match frame {
+        RespFrame::BulkString(s) => s.as_ref().len() as u64,
+        RespFrame::SimpleString(s) => s.len() as u64,
+        RespFrame::Array(Array(Some(items))) => {
+            items.iter().map(|v| ENTRY_OVERHEAD + frame_size(v)).sum()
+        }
+        _ => 8,
+    }
+}
+
+// the counter grows logarithmically: the higher it is, the less likely an access increments it
+fn lfu_increment(freq: u8) -> u8 {
+    if freq == u8::MAX {
+        return freq;
+    }
+    let base = freq.saturating_sub(LFU_INIT_VAL) as f64;
+    let p = 1.0 / (base * LFU_LOG_FACTOR + 1.0);
+    let r = (random() >> 11) as f64 / (1u64 << 53) as f64;
+    match r < p {
+        true => freq + 1,
+        false => freq,
+    }
+}
+
+// the counter loses one per decay period since the last access
+fn lfu_decay(freq: u8, access: u64, now: u64) -> u8 {
+    let periods = now.saturating_sub(access) / LFU_DECAY_MS;
+    freq.saturating_sub(periods.min(u8::MAX as u64) as u8)
+}
+
+fn random() -> u64 {
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u64(now_ms());
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{AppConfig, BulkString};
+
+    use super::*;
+
+    fn limited_backend(maxmemory: u64, policy: MaxMemoryPolicy) -> Backend {
+        let mut config = AppConfig::default();
+        config.memory.maxmemory = maxmemory;
+        config.memory.maxmemory_policy = policy;
+        // enough samples to always find the best candidate among a few keys
+        config.memory.maxmemory_samples = 1000;
+        Backend::with_config(config)
+    }
+
+    fn set(backend: &Backend, key: &str) {
+        backend.set(key.to_string(), vec![b'x'; 100].into());
+    }
+
+    #[test]
+    fn test_memory_accounting() {
+        let backend = Backend::new();
+        assert_eq!(backend.used_memory(), 0);
+        set(&backend, "key");
+        let used = backend.used_memory();
+        assert_eq!(used, KEY_OVERHEAD + 3 + 100);
+
+        backend
+            .hset(
+                "hash".to_string(),
+                "f".to_string(),
+                BulkString::new("v").into(),
+            )
+            .unwrap();
+        assert!(backend.used_memory() > used);
+        backend.flushall();
+        assert_eq!(backend.used_memory(), 0);
+        assert!(backend.memory().keys.is_empty());
+    }
+
+    #[test]
+    fn test_lru_and_ttl_eviction() {
+        let backend = limited_backend(3 * 200, MaxMemoryPolicy::AllKeysLru);
+        for key in ["a", "b", "c"] {
+            set(&backend, key);
+        }
+        assert!(!backend.over_maxmemory());
+        // "b" is the least recently used
+        backend.memory().keys.get_mut("b").unwrap().access -= 10_000;
+        set(&backend, "d");
+        let mut evicted = vec![];
+        backend
+            .free_memory(|_, key| evicted.push(key.to_string()))
+            .unwrap();
+        assert_eq!(evicted, vec!["b".to_string()]);
+        assert!(!backend.exists("b"));
+        assert_eq!(backend.evicted_keys(), 1);
+
+        let backend = limited_backend(3 * 200, MaxMemoryPolicy::VolatileTtl);
+        for key in ["a", "b", "c", "d"] {
+            set(&backend, key);
+        }
+        let now = now_ms();
+        backend.expire_at("c", now + 10_000, None);
+        backend.expire_at("d", now + 20_000, None);
+        let mut evicted = vec![];
+        backend
+            .free_memory(|_, key| evicted.push(key.to_string()))
+            .unwrap();
+        assert_eq!(evicted, vec!["c".to_string()]);
+
+        // only keys with a ttl may be evicted
+        set(&backend, "e");
+        set(&backend, "f");
+        assert!(backend.free_memory(|_, _| {}).is_err());
+        assert!(backend.exists("a"));
+    }
+
+    #[test]
+    fn test_lfu_counter() {
+        let backend = limited_backend(0, MaxMemoryPolicy::AllKeysLfu);
+        set(&backend, "key");
+        assert_eq!(backend.object_freq("key"), Some(LFU_INIT_VAL));
+        for _ in 0..100 {
+            backend.get("key");
+        }
+        assert!(backend.object_freq("key").unwrap() > LFU_INIT_VAL);
+        assert_eq!(backend.object_idletime("key"), Some(0));
+        assert_eq!(backend.object_freq("nope"), None);
+
+        assert_eq!(lfu_decay(10, 0, 3 * LFU_DECAY_MS), 7);
+        assert_eq!(lfu_increment(u8::MAX), u8::MAX);
+    }
+}
@@ -1,37 +1,165 @@
+use std::collections::HashMap;
+use std::collections::VecDeque;
 use std::ops::Deref;
-use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, OnceLock, PoisonError, RwLock, RwLockReadGuard};
 
+use bytes::Bytes;
+use dashmap::mapref::entry::Entry;
 use dashmap::DashMap;
+use thiserror::Error;
+use tokio::sync::Notify;
 
-use crate::RespFrame;
+use crate::{AppConfig, ConfigError, RespFrame};
 
+pub use self::acl::{Acl, AclError, User};
+pub use self::aof::{Aof, AofError, AofWriter};
+pub use self::client::{ClientInfo, ClientSlot, Clients};
+pub use self::cluster::{
+    key_hash_slot, Cluster, ClusterError, ClusterNode, Redirect, CLUSTER_SLOTS,
+};
+pub use self::expire::{now_ms, ExpireCondition};
+pub use self::geo::{GeoError, GeoMatch, GeoPoint, GeoShape};
+pub use self::glob::glob_match;
+pub use self::hyperloglog::HllError;
+pub use self::keyspace::ScanStep;
+pub use self::list::ListError;
+pub use self::memory::{Memory, MemoryError};
+pub use self::monitor::Monitors;
+pub use self::pubsub::{Broker, PubSubMessage, Subscriber};
+pub use self::rdb::RdbError;
+pub use self::replication::{CommandLog, PSyncReply, ReplicaStream, Replication, ReplicationError};
+pub use self::slowlog::{SlowLog, SlowLogEntry};
+pub use self::stats::{CommandStats, Stats};
+pub use self::stream::{
+    ClaimOptions, ConsumerGroup, GroupEntry, PendingEntry, Stream, StreamError, StreamFields,
+    StreamId, StreamTrim, TrimStrategy, XAddId,
+};
+pub use self::string::{BitOperator, SetTtl, StringError};
+pub use self::transaction::Watcher;
+pub use self::zset::{ScoreBound, SortedSet, ZAddFlags, ZSetError};
+
+mod acl;
+mod aof;
+mod client;
+mod cluster;
+mod expire;
+mod geo;
+mod glob;
+mod hyperloglog;
+mod keyspace;
+mod list;
+mod memory;
+mod monitor;
+mod pubsub;
+mod rdb;
+mod replication;
+mod script;
+mod slowlog;
+mod stats;
+mod stream;
+mod string;
+mod transaction;
+mod zset;
+
+/// A handle on the server state, running the keyspace commands against one of its databases.
 #[derive(Debug, Clone)]
-pub struct Backend(Arc<BackendInner>);
+pub struct Backend {
+    inner: Arc<BackendInner>,
+    // index of the selected database
+    db: usize,
+}
 
 #[derive(Debug)]
 pub struct BackendInner {
-    map: DashMap<String, RespFrame>,
+    // the logical databases, by index
+    dbs: Vec<Keyspace>,
+    // changed at runtime by CONFIG SET
+    config: RwLock<AppConfig>,
+    // number of changes since the last successful save
+    dirty: AtomicU64,
+    // unix time in seconds of the last successful save
+    last_save: AtomicU64,
+    bgsave_in_progress: AtomicBool,
+    // set once the append only file is open, write commands are logged from then on
+    aof: OnceLock<Aof>,
+    broker: Broker,
+    next_client_id: AtomicU64,
+    clients: Clients,
+    // unix time in milliseconds at which the server started
+    started: u64,
+    slowlog: SlowLog,
+    stats: Stats,
+    monitors: Monitors,
+    // taken shared by every command and exclusively by EXEC
+    exec_lock: RwLock<()>,
+    replication: Replication,
+    // set once the node joined its cluster, keys are then only served for the slots it owns
+    cluster: OnceLock<Cluster>,
+    // bodies of the scripts loaded with SCRIPT LOAD or EVAL, by SHA1 digest
+    scripts: DashMap<String, String>,
+    acl: Acl,
+}
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum HashError {
+    #[error("WRONGTYPE Operation against a key holding the wrong kind of value")]
+    WrongType,
+}
+
+// the keys of a logical database, along with what is tracked per key
+#[derive(Debug, Default)]
+struct Keyspace {
+    map: DashMap<String, Bytes>,
     hmap: DashMap<String, DashMap<String, RespFrame>>,
+    list: DashMap<String, VecDeque<RespFrame>>,
+    zset: DashMap<String, SortedSet>,
+    stream: DashMap<String, Stream>,
+    // clients blocked on a list or stream key wait for a push notification
+    key_waiters: DashMap<String, Arc<Notify>>,
+    // absolute unix timestamp in milliseconds at which the key expires
+    expires: DashMap<String, u64>,
+    // connections watching a key, each with the flag to raise when it is modified
+    watched: DashMap<String, HashMap<u64, Arc<AtomicBool>>>,
+    memory: Memory,
 }
 
 impl Deref for Backend {
     type Target = BackendInner;
     fn deref(&self) -> &Self::Target {
-        &self.0
+        &self.inner
     }
 }
 
 impl Default for Backend {
     fn default() -> Self {
-        Self(Arc::new(BackendInner::default()))
+        Self::with_config(AppConfig::default())
     }
 }
 
-impl Default for BackendInner {
-    fn default() -> Self {
+impl BackendInner {
+    fn new(config: AppConfig) -> Self {
         Self {
-            map: DashMap::new(),
-            hmap: DashMap::new(),
+            dbs: (0..config.server.databases.max(1))
+                .map(|_| Keyspace::default())
+                .collect(),
+            replication: Replication::new(config.replication.backlog_size),
+            cluster: OnceLock::new(),
+            scripts: DashMap::new(),
+            acl: Acl::new(config.server.requirepass.as_deref()),
+            config: RwLock::new(config),
+            dirty: AtomicU64::new(0),
+            last_save: AtomicU64::new(now_ms() / 1000),
+            bgsave_in_progress: AtomicBool::new(false),
+            aof: OnceLock::new(),
+            broker: Broker::default(),
+            next_client_id: AtomicU64::new(1),
+            clients: Clients::default(),
+            started: now_ms(),
+            slowlog: SlowLog::default(),
+            stats: Stats::default(),
+            monitors: Monitors::default(),
+            exec_lock: RwLock::new(()),
         }
     }
 }
@@ -41,26 +169,240 @@ impl Backend {
         Self::default()
     }
 
-    pub fn get(&self, key: &str) -> Option<RespFrame> {
-        self.map.get(key).map(|v| v.value().clone())
+    pub fn with_config(config: AppConfig) -> Self {
+        Self {
+            inner: Arc::new(BackendInner::new(config)),
+            db: 0,
+        }
+    }
+
+    /// A handle on the database at `index`, None if there is no such database.
+    pub fn select(&self, index: usize) -> Option<Self> {
+        (index < self.dbs.len()).then(|| Self {
+            inner: self.inner.clone(),
+            db: index,
+        })
+    }
+
+    /// Index of the selected database.
+    pub fn db(&self) -> usize {
+        self.db
+    }
+
+    /// Number of logical databases.
+    pub fn databases(&self) -> usize {
+        self.dbs.len()
     }
 
-    pub fn set(&self, key: String, value: RespFrame) {
-        self.map.insert(key, value);
+    pub fn config(&self) -> RwLockReadGuard<'_, AppConfig> {
+        self.inner
+            .config
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Change parameters of the config, applying them to the running server. Either all of
+    /// them are changed or none is.
+    pub fn set_config(&self, changes: &[(String, String)]) -> Result<(), ConfigError> {
+        let mut config = self
+            .inner
+            .config
+            .write()
+            .unwrap_or_else(PoisonError::into_inner);
+        let mut changed = config.clone();
+        for (name, value) in changes {
+            changed.set(name, value)?;
+        }
+        if changes.iter().any(|(name, _)| name == "requirepass") {
+            self.acl
+                .set_requirepass(changed.server.requirepass.as_deref());
+        }
+        *config = changed;
+        Ok(())
+    }
+
+    /// Seconds since the server started.
+    pub fn uptime(&self) -> u64 {
+        now_ms().saturating_sub(self.started) / 1000
+    }
+
+    /// Allocate the id of a new client connection.
+    pub fn next_client_id(&self) -> u64 {
+        self.next_client_id.fetch_add(1, Ordering::Relaxed)
+    }
+
+    pub fn get(&self, key: &str) -> Option<Bytes> {
+        self.expire_if_needed(key);
+        self.keyspace().map.get(key).map(|v| v.value().clone())
+    }
+
+    /// Set a string value, replacing any existing value (of any type) and its ttl.
+    pub fn set(&self, key: String, value: Bytes) {
+        self.expire_if_needed(&key);
+        let entry = self.keyspace().map.entry(key.clone());
+        self.store_string(entry, &key, value, SetTtl::Persist);
+        self.touch(&key);
     }
 
     pub fn hget(&self, key: &str, field: &str) -> Option<RespFrame> {
-        self.hmap
+        self.expire_if_needed(key);
+        self.keyspace()
+            .hmap
             .get(key)
             .and_then(|v| v.get(field).map(|v| v.value().clone()))
     }
 
-    pub fn hset(&self, key: String, field: String, value: RespFrame) {
-        let hmap = self.hmap.entry(key).or_default();
-        hmap.insert(field, value);
+    /// Set a field of the hash at key. Returns whether the field is new.
+    pub fn hset(&self, key: String, field: String, value: RespFrame) -> Result<bool, HashError> {
+        self.hmset(key, vec![(field, value)])
+            .map(|added| added == 1)
+    }
+
+    /// Set fields of the hash at key, creating it if needed. Returns the number of new fields.
+    pub fn hmset(&self, key: String, fields: Vec<(String, RespFrame)>) -> Result<usize, HashError> {
+        self.expire_if_needed(&key);
+        let set_all = |hmap: &DashMap<String, RespFrame>| {
+            fields
+                .into_iter()
+                .map(|(field, value)| hmap.insert(field, value))
+                .filter(Option::is_none)
+                .count()
+        };
+        let added = match self.keyspace().hmap.get(&key) {
+            Some(hmap) => set_all(&hmap),
+            None => self
+                .create_key(&key, "hash", || {
+                    set_all(&self.keyspace().hmap.entry(key.clone()).or_default())
+                })
+                .ok_or(HashError::WrongType)?,
+        };
+        self.touch(&key);
+        Ok(added)
     }
 
     pub fn hgetall(&self, key: &str) -> Option<DashMap<String, RespFrame>> {
-        self.hmap.get(key).map(|m| m.clone())
+        self.expire_if_needed(key);
+        self.keyspace().hmap.get(key).map(|m| m.clone())
+    }
+
+    pub fn exists(&self, key: &str) -> bool {
+        self.expire_if_needed(key);
+        self.contains_key(key)
+    }
+
+    /// Returns the type name of the value stored at key, as reported by TYPE.
+    pub fn key_type(&self, key: &str) -> Option<&'static str> {
+        self.expire_if_needed(key);
+        let keyspace = self.keyspace();
+        if keyspace.map.contains_key(key) {
+            Some("string")
+        } else if keyspace.hmap.contains_key(key) {
+            Some("hash")
+        } else if keyspace.list.contains_key(key) {
+            Some("list")
+        } else if keyspace.zset.contains_key(key) {
+            Some("zset")
+        } else if keyspace.stream.contains_key(key) {
+            Some("stream")
+        } else {
+            None
+        }
+    }
+
+    /// Returns true if the key holds a value other than a string.
+    pub fn is_non_string(&self, key: &str) -> bool {
+        matches!(self.key_type(key), Some(t) if t != "string")
+    }
+
+    // a key is created as another type than string with its entry in `map` held, as SET holds
+    // it, so that the entry locks the key across the keyspaces: no other write can create the key
+    // between the type check and `create`. No guard of another keyspace is held while taking it.
+    // Returns None if the key holds another type.
+    fn create_key<T>(&self, key: &str, kind: &str, create: impl FnOnce() -> T) -> Option<T> {
+        let lock = self.keyspace().map.entry(key.to_string());
+        if matches!(lock, Entry::Occupied(_)) || self.holds_other_type(key, kind) {
+            return None;
+        }
+        Some(create())
+    }
+
+    // whether the key holds a value of another type than `kind`, strings left out since the
+    // caller holds the entry of the key in `map`
+    fn holds_other_type(&self, key: &str, kind: &str) -> bool {
+        let keyspace = self.keyspace();
+        (kind != "hash" && keyspace.hmap.contains_key(key))
+            || (kind != "list" && keyspace.list.contains_key(key))
+            || (kind != "zset" && keyspace.zset.contains_key(key))
+            || (kind != "stream" && keyspace.stream.contains_key(key))
+    }
+
+    fn contains_key(&self, key: &str) -> bool {
+        let keyspace = self.keyspace();
+        keyspace.map.contains_key(key)
+            || keyspace.hmap.contains_key(key)
+            || keyspace.list.contains_key(key)
+            || keyspace.zset.contains_key(key)
+            || keyspace.stream.contains_key(key)
+    }
+
+    /// Remove every key of every database, flagging the connections watching them.
+    pub fn flushall(&self) {
+        for db in self.all_dbs() {
+            db.flushdb();
+        }
+    }
+
+    /// A handle on each database, by index.
+    pub fn all_dbs(&self) -> impl Iterator<Item = Backend> + '_ {
+        (0..self.databases()).filter_map(|index| self.select(index))
+    }
+
+    // remove the value of the key from every keyspace, but keep its ttl
+    fn remove_value(&self, key: &str) -> bool {
+        let keyspace = self.keyspace();
+        keyspace.map.remove(key).is_some()
+            | keyspace.hmap.remove(key).is_some()
+            | keyspace.list.remove(key).is_some()
+            | keyspace.zset.remove(key).is_some()
+            | keyspace.stream.remove(key).is_some()
+    }
+
+    // remove the key from every keyspace, including its ttl
+    fn remove_key(&self, key: &str) -> bool {
+        let removed = self.remove_value(key);
+        self.keyspace().expires.remove(key);
+        if removed {
+            self.touch(key);
+        }
+        removed
+    }
+
+    /// Get the notifier that is signaled whenever an element is pushed to the list or stream
+    /// at key.
+    pub fn key_notifier(&self, key: &str) -> Arc<Notify> {
+        self.keyspace()
+            .key_waiters
+            .entry(key.to_string())
+            .or_default()
+            .clone()
+    }
+
+    /// Drop the notifier of key if no other client is waiting on it.
+    pub fn release_key_notifier(&self, key: &str) {
+        self.keyspace()
+            .key_waiters
+            .remove_if(key, |_, notify| Arc::strong_count(notify) == 1);
+    }
+
+    // the selected database
+    fn keyspace(&self) -> &Keyspace {
+        &self.dbs[self.db]
+    }
+
+    // record a modification of key
+    fn touch(&self, key: &str) {
+        self.dirty.fetch_add(1, Ordering::Relaxed);
+        self.signal_modified(key);
+        self.track_memory(key);
     }
 }
@@ -0,0 +1,91 @@
+use std::fmt::Write as _;
+
+use bytes::Bytes;
+use tokio::sync::broadcast;
+
+use crate::{now_ms, Backend};
+
+// lines buffered for a monitor, a slower monitor misses the older ones
+const MONITOR_BUFFER: usize = 4096;
+
+/// Streams the commands run by every client to the connections which called MONITOR.
+#[derive(Debug)]
+pub struct Monitors {
+    sender: broadcast::Sender<String>,
+}
+
+impl Default for Monitors {
+    fn default() -> Self {
+        let (sender, _) = broadcast::channel(MONITOR_BUFFER);
+        Self { sender }
+    }
+}
+
+impl Backend {
+    /// Receive a line for every command run from now on.
+    pub fn monitor(&self) -> broadcast::Receiver<String> {
+        self.monitors.sender.subscribe()
+    }
+
+    /// Whether a connection is monitoring, the commands don't need to be formatted otherwise.
+    pub fn has_monitors(&self) -> bool {
+        self.monitors.sender.receiver_count() > 0
+    }
+
+    /// Send a command run by the client at `addr` to the monitors.
+    pub fn feed_monitors(&self, addr: &str, args: &[Bytes]) {
+        let now = now_ms();
+        let mut line = format!("{}.{:03}000 [{} {}]", now / 1000, now % 1000, self.db, addr);
+        for arg in args {
+            line.push(' ');
+            quote(&mut line, arg);
+        }
+        // there may be no monitor left by now
+        let _ = self.monitors.sender.send(line);
+    }
+}
+
+// quote an argument the way redis-cli reads it back
+fn quote(line: &mut String, arg: &[u8]) {
+    line.push('"');
+    for &c in arg {
+        match c {
+            b'\\' => line.push_str("\\\\"),
+            b'"' => line.push_str("\\\""),
+            b'\n' => line.push_str("\\n"),
+            b'\r' => line.push_str("\\r"),
+            b'\t' => line.push_str("\\t"),
+            0x07 => line.push_str("\\a"),
+            0x08 => line.push_str("\\b"),
+            c if c.is_ascii_graphic() || c == b' ' => line.push(c as char),
+            c => {
+                let _ = write!(line, "\\x{:02x}", c);
+            }
+        }
+    }
+    line.push('"');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_monitor_lines() {
+        let backend = Backend::new();
+        assert!(!backend.has_monitors());
+        let mut monitor = backend.monitor();
+        assert!(backend.has_monitors());
+        let args = [
+            Bytes::from("set"),
+            Bytes::from("k"),
+            Bytes::from("a \"b\"\n\x01"),
+        ];
+        backend
+            .select(2)
+            .unwrap()
+            .feed_monitors("127.0.0.1:6000", &args);
+        let line = monitor.try_recv().unwrap();
+        assert!(line.ends_with(r#" [2 127.0.0.1:6000] "set" "k" "a \"b\"\n\x01""#));
+    }
+}
@@ -0,0 +1,381 @@
+use std::collections::{BTreeSet, HashMap};
+
+use dashmap::DashMap;
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+
+use crate::{Array, Backend, BulkString, Push, RespFrame};
+
+use super::glob_match;
+
+type Subscribers = DashMap<String, HashMap<u64, UnboundedSender<PubSubMessage>>>;
+
+/// Routes published messages to the connections subscribed to a channel or a pattern.
+#[derive(Debug, Default)]
+pub struct Broker {
+    channels: Subscribers,
+    patterns: Subscribers,
+}
+
+/// Everything a subscribed connection receives: confirmations of its own (un)subscribe
+/// requests and the messages published to its channels.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PubSubMessage {
+    Subscribe {
+        channel: String,
+        count: usize,
+    },
+    Unsubscribe {
+        channel: Option<String>,
+        count: usize,
+    },
+    PSubscribe {
+        pattern: String,
+        count: usize,
+    },
+    PUnsubscribe {
+        pattern: Option<String>,
+        count: usize,
+    },
+    Message {
+        channel: String,
+        payload: RespFrame,
+    },
+    PMessage {
+        pattern: String,
+        channel: String,
+        payload: RespFrame,
+    },
+}
+
+/// The subscriptions of a connection. They are all dropped from the broker when it goes away.
+#[derive(Debug)]
+pub struct Subscriber {
+    id: u64,
+    backend: Backend,
+    sender: UnboundedSender<PubSubMessage>,
+    channels: BTreeSet<String>,
+    patterns: BTreeSet<String>,
+}
+
+impl Backend {
+    /// Publish a message, returns the number of receivers.
+    pub fn publish(&self, channel: &str, payload: RespFrame) -> usize {
+        let mut receivers = 0;
+        if let Some(subscribers) = self.broker.channels.get(channel) {
+            for sender in subscribers.values() {
+                let message = PubSubMessage::Message {
+                    channel: channel.to_string(),
+                    payload: payload.clone(),
+                };
+                receivers += sender.send(message).is_ok() as usize;
+            }
+        }
+        for entry in self.broker.patterns.iter() {
+            if !glob_match(entry.key().as_bytes(), channel.as_bytes()) {
+                continue;
+            }
+            for sender in entry.value().values() {
+                let message = PubSubMessage::PMessage {
+                    pattern: entry.key().clone(),
+                    channel: channel.to_string(),
+                    payload: payload.clone(),
+                };
+                receivers += sender.send(message).is_ok() as usize;
+            }
+        }
+        receivers
+    }
+
+    /// Channels with at least one subscriber, optionally filtered by a glob pattern.
+    pub fn pubsub_channels(&self, pattern: Option<&str>) -> Vec<String> {
+        let mut channels = self
+            .broker
+            .channels
+            .iter()
+            .map(|entry| entry.key().clone())
+            .filter(|channel| pattern.is_none_or(|p| glob_match(p.as_bytes(), channel.as_bytes())))
+            .collect::<Vec<_>>();
+        channels.sort();
+        channels
+    }
+
+    /// Number of subscribers of a channel, pattern subscribers are not counted.
+    pub fn pubsub_numsub(&self, channel: &str) -> usize {
+        self.broker
+            .channels
+            .get(channel)
+            .map(|subscribers| subscribers.len())
+            .unwrap_or_default()
+    }
+
+    /// Number of distinct subscribed patterns.
+    pub fn pubsub_numpat(&self) -> usize {
+        self.broker.patterns.len()
+    }
+}
+
+impl Subscriber {
+    /// Create the subscriber of a connection, messages for it arrive on the returned receiver.
+    pub fn new(backend: Backend, id: u64) -> (Self, UnboundedReceiver<PubSubMessage>) {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let subscriber = Self {
+            id,
+            backend,
+            sender,
+            channels: BTreeSet::new(),
+            patterns: BTreeSet::new(),
+        };
+        (subscriber, receiver)
+    }
+
+    /// Total number of channel and pattern subscriptions.
+    pub fn count(&self) -> usize {
+        self.channels.len() + self.patterns.len()
+    }
+
+    /// Number of channel subscriptions.
+    pub fn channels(&self) -> usize {
+        self.channels.len()
+    }
+
+    /// Number of pattern subscriptions.
+    pub fn patterns(&self) -> usize {
+        self.patterns.len()
+    }
+
+    pub fn is_subscribed(&self) -> bool {
+        self.count() > 0
+    }
+
+    pub fn subscribe(&mut self, channel: String) -> PubSubMessage {
+        if self.channels.insert(channel.clone()) {
+            add(
+                &self.backend.broker.channels,
+                &channel,
+                self.id,
+                &self.sender,
+            );
+        }
+        PubSubMessage::Subscribe {
+            channel,
+            count: self.count(),
+        }
+    }
+
+    pub fn psubscribe(&mut self, pattern: String) -> PubSubMessage {
+        if self.patterns.insert(pattern.clone()) {
+            add(
+                &self.backend.broker.patterns,
+                &pattern,
+                self.id,
+                &self.sender,
+            );
+        }
+        PubSubMessage::PSubscribe {
+            pattern,
+            count: self.count(),
+        }
+    }
+
+    /// Unsubscribe from the given channels, or from all of them if none is given.
+    pub fn unsubscribe(&mut self, channels: Vec<String>) -> Vec<PubSubMessage> {
+        let channels = match channels.is_empty() {
+            true => self.channels.iter().cloned().collect(),
+            false => channels,
+        };
+        if channels.is_empty() {
+            return vec![PubSubMessage::Unsubscribe {
+                channel: None,
+                count: self.count(),
+            }];
+        }
+        channels
+            .into_iter()
+            .map(|channel| {
+                if self.channels.remove(&channel) {
+                    remove(&self.backend.broker.channels, &channel, self.id);
+                }
+                PubSubMessage::Unsubscribe {
+                    channel: Some(channel),
+                    count: self.count(),
+                }
+            })
+            .collect()
+    }
+
+    /// Unsubscribe from the given patterns, or from all of them if none is given.
+    pub fn punsubscribe(&mut self, patterns: Vec<String>) -> Vec<PubSubMessage> {
+        let patterns = match patterns.is_empty() {
+            true => self.patterns.iter().cloned().collect(),
+            false => patterns,
+        };
+        if patterns.is_empty() {
+            return vec![PubSubMessage::PUnsubscribe {
+                pattern: None,
+                count: self.count(),
+            }];
+        }
+        patterns
+            .into_iter()
+            .map(|pattern| {
+                if self.patterns.remove(&pattern) {
+                    remove(&self.backend.broker.patterns, &pattern, self.id);
+                }
+                PubSubMessage::PUnsubscribe {
+                    pattern: Some(pattern),
+                    count: self.count(),
+                }
+            })
+            .collect()
+    }
+}
+
+impl Drop for Subscriber {
+    fn drop(&mut self) {
+        for channel in &self.channels {
+            remove(&self.backend.broker.channels, channel, self.id);
+        }
+        for pattern in &self.patterns {
+            remove(&self.backend.broker.patterns, pattern, self.id);
+        }
+    }
+}
+
+impl PubSubMessage {
+    /// Encode as a push frame for RESP3 clients, as a plain array for RESP2 ones.
+    pub fn into_frame(self, resp3: bool) -> RespFrame {
+        let items = match self {
+            PubSubMessage::Subscribe { channel, count } => {
+                vec![bulk("subscribe"), bulk(&channel), count_frame(count)]
+            }
+            PubSubMessage::Unsubscribe { channel, count } => {
+                vec![bulk("unsubscribe"), optional(channel), count_frame(count)]
+            }
+            PubSubMessage::PSubscribe { pattern, count } => {
+                vec![bulk("psubscribe"), bulk(&pattern), count_frame(count)]
+            }
+            PubSubMessage::PUnsubscribe { pattern, count } => {
+                vec![bulk("punsubscribe"), optional(pattern), count_frame(count)]
+            }
+            PubSubMessage::Message { channel, payload } => {
+                vec![bulk("message"), bulk(&channel), payload]
+            }
+            PubSubMessage::PMessage {
+                pattern,
+                channel,
+                payload,
+            } => vec![bulk("pmessage"), bulk(&pattern), bulk(&channel), payload],
+        };
+        match resp3 {
+            true => Push::new(items).into(),
+            false => Array::new(items).into(),
+        }
+    }
+}
+
+fn add(subscribers: &Subscribers, name: &str, id: u64, sender: &UnboundedSender<PubSubMessage>) {
+    subscribers
+        .entry(name.to_string())
+        .or_default()
+        .insert(id, sender.clone());
+}
+
+fn remove(subscribers: &Subscribers, name: &str, id: u64) {
+    if let Some(mut entry) = subscribers.get_mut(name) {
+        entry.remove(&id);
+    }
+    subscribers.remove_if(name, |_, v| v.is_empty());
+}
+
+fn bulk(s: &str) -> RespFrame {
+    BulkString::new(s).into()
+}
+
+fn optional(s: Option<String>) -> RespFrame {
+    match s {
+        Some(s) => bulk(&s),
+        None => BulkString::none().into(),
+    }
+}
+
+fn count_frame(count: usize) -> RespFrame {
+    RespFrame::Integer(count as i64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_publish_to_channels_and_patterns() {
+        let backend = Backend::new();
+        let (mut sub1, mut rx1) = Subscriber::new(backend.clone(), 1);
+        let (mut sub2, mut rx2) = Subscriber::new(backend.clone(), 2);
+
+        assert_eq!(
+            sub1.subscribe("news".to_string()),
+            PubSubMessage::Subscribe {
+                channel: "news".to_string(),
+                count: 1
+            }
+        );
+        sub2.psubscribe("n*".to_string());
+
+        assert_eq!(backend.publish("news", bulk("hello")), 2);
+        assert_eq!(backend.publish("nothing", bulk("hi")), 1);
+        assert_eq!(backend.publish("sport", bulk("hi")), 0);
+
+        assert_eq!(
+            rx1.try_recv().unwrap(),
+            PubSubMessage::Message {
+                channel: "news".to_string(),
+                payload: bulk("hello")
+            }
+        );
+        assert!(rx1.try_recv().is_err());
+        assert!(matches!(
+            rx2.try_recv().unwrap(),
+            PubSubMessage::PMessage { .. }
+        ));
+        assert!(matches!(
+            rx2.try_recv().unwrap(),
+            PubSubMessage::PMessage { .. }
+        ));
+
+        assert_eq!(backend.pubsub_channels(None), vec!["news".to_string()]);
+        assert_eq!(backend.pubsub_numsub("news"), 1);
+        assert_eq!(backend.pubsub_numpat(), 1);
+
+        drop(sub2);
+        assert_eq!(backend.pubsub_numpat(), 0);
+        assert_eq!(sub1.unsubscribe(vec![]).len(), 1);
+        assert!(!sub1.is_subscribed());
+        assert!(backend.pubsub_channels(None).is_empty());
+    }
+
+    #[test]
+    fn test_unsubscribe_without_subscriptions() {
+        let (mut sub, _rx) = Subscriber::new(Backend::new(), 1);
+        assert_eq!(
+            sub.unsubscribe(vec![]),
+            vec![PubSubMessage::Unsubscribe {
+                channel: None,
+                count: 0
+            }]
+        );
+    }
+
+    #[test]
+    fn test_message_frames() {
+        let message = PubSubMessage::Message {
+            channel: "news".to_string(),
+            payload: bulk("hello"),
+        };
+        let items = vec![bulk("message"), bulk("news"), bulk("hello")];
+        assert_eq!(
+            message.clone().into_frame(false),
+            Array::new(items.clone()).into()
+        );
+        assert_eq!(message.into_frame(true), Push::new(items).into());
+    }
+}
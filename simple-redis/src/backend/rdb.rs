@@ -0,0 +1,606 @@
+use std::collections::VecDeque;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+
+use bytes::{Buf, Bytes};
+use dashmap::DashMap;
+use thiserror::Error;
+use tracing::{info, warn};
+
+use crate::{parse_frame, Backend, RespEncode, RespFrame};
+
+use super::stream::{Consumer, ConsumerGroup, PendingEntry};
+use super::{now_ms, SortedSet, Stream, StreamId};
+
+// snapshot layout:
+//   MAGIC VERSION(u32) { OP_SELECTDB index(u32) { [OP_EXPIRE ms(u64)] TYPE key value }* }* OP_EOF
+// integers are little endian, strings and string values are u32 length prefixed and
+// RespFrame values are stored in their RESP encoding
+const MAGIC: &[u8] = b"SREDIS";
+const VERSION: u32 = 2;
+
+const OP_EXPIRE: u8 = 0xFC;
+const OP_SELECTDB: u8 = 0xFE;
+const OP_EOF: u8 = 0xFF;
+
+const TYPE_STRING: u8 = 0;
+const TYPE_HASH: u8 = 1;
+const TYPE_LIST: u8 = 2;
+const TYPE_ZSET: u8 = 3;
+const TYPE_STREAM: u8 = 4;
+
+// how often the save rules are checked
+const SNAPSHOT_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Error, Debug)]
+pub enum RdbError {
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Invalid snapshot: {0}")]
+    InvalidFormat(String),
+    #[error("Background save already in progress")]
+    AlreadyInProgress,
+}
+
+impl Backend {
+    /// Serialize all alive keys of every database into a snapshot.
+    pub fn dump(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(4096);
+        buf.extend_from_slice(MAGIC);
+        buf.extend_from_slice(&VERSION.to_le_bytes());
+        for db in self.all_dbs().filter(|db| db.dbsize() > 0) {
+            buf.push(OP_SELECTDB);
+            buf.extend_from_slice(&(db.db() as u32).to_le_bytes());
+            db.dump_keyspace(&mut buf);
+        }
+        buf.push(OP_EOF);
+        buf
+    }
+
+    // serialize the alive keys of the selected database
+    fn dump_keyspace(&self, buf: &mut Vec<u8>) {
+        let now = now_ms();
+        let write_header = |buf: &mut Vec<u8>, key: &str, type_: u8| -> bool {
+            let expire = self.keyspace().expires.get(key).map(|v| *v.value());
+            match expire {
+                Some(at) if at <= now => return false,
+                Some(at) => {
+                    buf.push(OP_EXPIRE);
+                    buf.extend_from_slice(&at.to_le_bytes());
+                }
+                None => {}
+            }
+            buf.push(type_);
+            write_str(buf, key);
+            true
+        };
+
+        for entry in self.keyspace().map.iter() {
+            if write_header(buf, entry.key(), TYPE_STRING) {
+                write_bytes(buf, entry.value());
+            }
+        }
+        for entry in self.keyspace().hmap.iter() {
+            if write_header(buf, entry.key(), TYPE_HASH) {
+                let fields = entry
+                    .value()
+                    .iter()
+                    .map(|v| (v.key().clone(), v.value().clone()))
+                    .collect::<Vec<_>>();
+                write_len(buf, fields.len());
+                for (field, value) in fields {
+                    write_str(buf, &field);
+                    buf.extend(value.encode());
+                }
+            }
+        }
+        for entry in self.keyspace().list.iter() {
+            if write_header(buf, entry.key(), TYPE_LIST) {
+                write_len(buf, entry.value().len());
+                for value in entry.value().iter() {
+                    buf.extend(value.clone().encode());
+                }
+            }
+        }
+        for entry in self.keyspace().zset.iter() {
+            if write_header(buf, entry.key(), TYPE_ZSET) {
+                let zset = entry.value();
+                write_len(buf, zset.len());
+                for (member, score) in zset.range_by_rank(0, -1, false) {
+                    write_str(buf, &member);
+                    buf.extend_from_slice(&score.to_le_bytes());
+                }
+            }
+        }
+        for entry in self.keyspace().stream.iter() {
+            if write_header(buf, entry.key(), TYPE_STREAM) {
+                write_stream(buf, entry.value());
+            }
+        }
+    }
+
+    /// Load keys from a snapshot, skipping the ones already expired. Returns the number of loaded keys.
+    pub fn restore(&self, data: &[u8]) -> Result<usize, RdbError> {
+        let mut buf = data;
+        if buf.len() < MAGIC.len() + 4 || !buf.starts_with(MAGIC) {
+            return Err(RdbError::InvalidFormat("bad magic".to_string()));
+        }
+        buf.advance(MAGIC.len());
+        let version = buf.get_u32_le();
+        if version != VERSION {
+            return Err(RdbError::InvalidFormat(format!(
+                "unsupported version {}",
+                version
+            )));
+        }
+
+        let now = now_ms();
+        let mut loaded = 0;
+        // keys go to the selected database until the snapshot selects another one
+        let mut db = self.clone();
+        loop {
+            let mut op = read_u8(&mut buf)?;
+            if op == OP_SELECTDB {
+                let index = read_len(&mut buf)?;
+                db = self
+                    .select(index)
+                    .ok_or_else(|| RdbError::InvalidFormat(format!("no database {}", index)))?;
+                continue;
+            }
+            let mut expire = None;
+            if op == OP_EXPIRE {
+                expire = Some(read_u64(&mut buf)?);
+                op = read_u8(&mut buf)?;
+            }
+            if op == OP_EOF {
+                break;
+            }
+
+            let key = read_str(&mut buf)?;
+            match op {
+                TYPE_STRING => {
+                    let value = read_bytes(&mut buf)?;
+                    db.keyspace().map.insert(key.clone(), value);
+                }
+                TYPE_HASH => {
+                    let len = read_len(&mut buf)?;
+                    let hmap = DashMap::with_capacity(len);
+                    for _ in 0..len {
+                        let field = read_str(&mut buf)?;
+                        hmap.insert(field, read_frame(&mut buf)?);
+                    }
+                    db.keyspace().hmap.insert(key.clone(), hmap);
+                }
+                TYPE_LIST => {
+                    let len = read_len(&mut buf)?;
+                    let mut list = VecDeque::with_capacity(len);
+                    for _ in 0..len {
+                        list.push_back(read_frame(&mut buf)?);
+                    }
+                    db.keyspace().list.insert(key.clone(), list);
+                }
+                TYPE_ZSET => {
+                    let len = read_len(&mut buf)?;
+                    let mut zset = SortedSet::default();
+                    for _ in 0..len {
+                        let member = read_str(&mut buf)?;
+                        zset.insert(member, f64::from_bits(read_u64(&mut buf)?));
+                    }
+                    db.keyspace().zset.insert(key.clone(), zset);
+                }
+                TYPE_STREAM => {
+                    let stream = read_stream(&mut buf)?;
+                    db.keyspace().stream.insert(key.clone(), stream);
+                }
+                _ => {
+                    return Err(RdbError::InvalidFormat(format!("unknown type {}", op)));
+                }
+            }
+
+            db.track_memory(&key);
+            match expire {
+                Some(at) if at <= now => {
+                    db.remove_value(&key);
+                }
+                Some(at) => {
+                    db.keyspace().expires.insert(key, at);
+                    loaded += 1;
+                }
+                None => loaded += 1,
+            }
+        }
+        Ok(loaded)
+    }
+
+    /// Synchronously write a snapshot to the configured file. No command runs while the keyspace
+    /// is dumped, so the file is a point-in-time snapshot.
+    pub fn save(&self) -> Result<(), RdbError> {
+        let (data, dirty) = {
+            let _guard = self.lock_exclusive();
+            (self.dump(), self.dirty.load(Ordering::Relaxed))
+        };
+        self.write_snapshot(&data, dirty)
+    }
+
+    /// Like [`Backend::save`], for a caller which already holds [`Backend::lock_exclusive`].
+    pub(crate) fn save_locked(&self) -> Result<(), RdbError> {
+        let dirty = self.dirty.load(Ordering::Relaxed);
+        self.write_snapshot(&self.dump(), dirty)
+    }
+
+    // `dirty` is the number of changes included in the snapshot
+    fn write_snapshot(&self, data: &[u8], dirty: u64) -> Result<(), RdbError> {
+        let path = self.config().rdb.path();
+        write_atomically(&path, data)?;
+        // changes made while saving stay dirty
+        self.dirty.fetch_sub(dirty, Ordering::Relaxed);
+        self.last_save.store(now_ms() / 1000, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Write a snapshot in a background thread, the caller does not wait for it.
+    pub fn bgsave(&self) -> Result<(), RdbError> {
+        if self
+            .bgsave_in_progress
+            .compare_exchange(false, true, Ordering::AcqRel, Ordering::Acquire)
+            .is_err()
+        {
+            return Err(RdbError::AlreadyInProgress);
+        }
+
+        let backend = self.clone();
+        std::thread::spawn(move || {
+            match backend.save() {
+                Ok(_) => info!("Background saving terminated with success"),
+                Err(e) => warn!("Background saving error: {}", e),
+            }
+            backend.bgsave_in_progress.store(false, Ordering::Release);
+        });
+        Ok(())
+    }
+
+    pub fn bgsave_in_progress(&self) -> bool {
+        self.bgsave_in_progress.load(Ordering::Acquire)
+    }
+
+    /// Unix time in seconds of the last successful save.
+    pub fn last_save(&self) -> u64 {
+        self.last_save.load(Ordering::Relaxed)
+    }
+
+    /// Number of changes since the last successful save.
+    pub fn changes_since_last_save(&self) -> u64 {
+        self.dirty.load(Ordering::Relaxed)
+    }
+
+    /// Load the configured snapshot file if it exists. Returns the number of loaded keys.
+    pub fn load(&self) -> Result<usize, RdbError> {
+        let path = self.config().rdb.path();
+        if !path.exists() {
+            return Ok(0);
+        }
+        let loaded = self.restore(&fs::read(&path)?)?;
+        self.dirty.store(0, Ordering::Relaxed);
+        info!("Loaded {} keys from {}", loaded, path.display());
+        Ok(loaded)
+    }
+
+    /// Check the save rules periodically and start a background save when one is met.
+    pub async fn run_snapshot(self) {
+        let mut ticker = tokio::time::interval(SNAPSHOT_CHECK_INTERVAL);
+        loop {
+            ticker.tick().await;
+            let dirty = self.dirty.load(Ordering::Relaxed);
+            let elapsed = (now_ms() / 1000).saturating_sub(self.last_save());
+            // the rules may be changed at runtime by CONFIG SET
+            let matched = self
+                .config()
+                .rdb
+                .save
+                .iter()
+                .find(|rule| dirty >= rule.changes && elapsed >= rule.seconds)
+                .copied();
+            if let Some(rule) = matched {
+                info!(
+                    "{} changes in {} seconds. Saving...",
+                    rule.changes, rule.seconds
+                );
+                if let Err(e) = self.bgsave() {
+                    warn!("failed to start background save: {}", e);
+                }
+            }
+        }
+    }
+}
+
+// write to a temp file first so that a crash never leaves a truncated snapshot behind
+fn write_atomically(path: &Path, data: &[u8]) -> Result<(), RdbError> {
+    let tmp = path.with_extension(format!("tmp-{}", std::process::id()));
+    let mut file = fs::File::create(&tmp)?;
+    file.write_all(data)?;
+    file.sync_all()?;
+    fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+fn write_len(buf: &mut Vec<u8>, len: usize) {
+    buf.extend_from_slice(&(len as u32).to_le_bytes());
+}
+
+fn write_str(buf: &mut Vec<u8>, s: &str) {
+    write_bytes(buf, s.as_bytes());
+}
+
+fn write_bytes(buf: &mut Vec<u8>, bytes: &[u8]) {
+    write_len(buf, bytes.len());
+    buf.extend_from_slice(bytes);
+}
+
+fn ensure(buf: &[u8], len: usize) -> Result<(), RdbError> {
+    if buf.len() < len {
+        return Err(RdbError::InvalidFormat(
+            "unexpected end of file".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+fn read_u8(buf: &mut &[u8]) -> Result<u8, RdbError> {
+    ensure(buf, 1)?;
+    Ok(buf.get_u8())
+}
+
+fn read_u64(buf: &mut &[u8]) -> Result<u64, RdbError> {
+    ensure(buf, 8)?;
+    Ok(buf.get_u64_le())
+}
+
+fn read_len(buf: &mut &[u8]) -> Result<usize, RdbError> {
+    ensure(buf, 4)?;
+    Ok(buf.get_u32_le() as usize)
+}
+
+fn read_str(buf: &mut &[u8]) -> Result<String, RdbError> {
+    let len = read_len(buf)?;
+    ensure(buf, len)?;
+    let s = String::from_utf8(buf[..len].to_vec())
+        .map_err(|e| RdbError::InvalidFormat(e.to_string()))?;
+    buf.advance(len);
+    Ok(s)
+}
+
+fn read_bytes(buf: &mut &[u8]) -> Result<Bytes, RdbError> {
+    let len = read_len(buf)?;
+    ensure(buf, len)?;
+    let bytes = Bytes::copy_from_slice(&buf[..len]);
+    buf.advance(len);
+    Ok(bytes)
+}
+
+fn read_frame(buf: &mut &[u8]) -> Result<RespFrame, RdbError> {
+    parse_frame(buf).map_err(|e| RdbError::InvalidFormat(e.to_string()))
+}
+
+// stream layout:
+//   last_id len { id len field* }* groups { name last_delivered len { id consumer delivered(u64)
+//   deliveries(u64) }* len { consumer seen(u64) }* }*
+// ids are two u64, ms then seq, the pending entries of the consumers follow from the group ones
+fn write_stream(buf: &mut Vec<u8>, stream: &Stream) {
+    write_id(buf, stream.last_id);
+    write_len(buf, stream.entries.len());
+    for (id, fields) in stream.entries.iter() {
+        write_id(buf, *id);
+        write_len(buf, fields.len());
+        for field in fields {
+            buf.extend(field.clone().encode());
+        }
+    }
+    write_len(buf, stream.groups.len());
+    for (name, group) in stream.groups.iter() {
+        write_str(buf, name);
+        write_id(buf, group.last_delivered);
+        write_len(buf, group.pending.len());
+        for (id, pending) in group.pending.iter() {
+            write_id(buf, *id);
+            write_str(buf, &pending.consumer);
+            buf.extend_from_slice(&pending.delivered.to_le_bytes());
+            buf.extend_from_slice(&pending.deliveries.to_le_bytes());
+        }
+        write_len(buf, group.consumers.len());
+        for (name, consumer) in group.consumers.iter() {
+            write_str(buf, name);
+            buf.extend_from_slice(&consumer.seen.to_le_bytes());
+        }
+    }
+}
+
+fn read_stream(buf: &mut &[u8]) -> Result<Stream, RdbError> {
+    let mut stream = Stream {
+        last_id: read_id(buf)?,
+        ..Default::default()
+    };
+    for _ in 0..read_len(buf)? {
+        let id = read_id(buf)?;
+        let fields = (0..read_len(buf)?)
+            .map(|_| read_frame(buf))
+            .collect::<Result<Vec<_>, _>>()?;
+        stream.entries.insert(id, fields);
+    }
+    for _ in 0..read_len(buf)? {
+        let name = read_str(buf)?;
+        let mut group = ConsumerGroup {
+            last_delivered: read_id(buf)?,
+            ..Default::default()
+        };
+        for _ in 0..read_len(buf)? {
+            let id = read_id(buf)?;
+            let pending = PendingEntry {
+                consumer: read_str(buf)?,
+                delivered: read_u64(buf)?,
+                deliveries: read_u64(buf)?,
+            };
+            group.pending.insert(id, pending);
+        }
+        for _ in 0..read_len(buf)? {
+            let name = read_str(buf)?;
+            let consumer = Consumer {
+                seen: read_u64(buf)?,
+                ..Default::default()
+            };
+            group.consumers.insert(name, consumer);
+        }
+        for (id, pending) in group.pending.iter() {
+            let consumer = group.consumers.entry(pending.consumer.clone()).or_default();
+            consumer.pending.insert(*id);
+        }
+        stream.groups.insert(name, group);
+    }
+    Ok(stream)
+}
+
+fn write_id(buf: &mut Vec<u8>, id: StreamId) {
+    buf.extend_from_slice(&id.ms.to_le_bytes());
+    buf.extend_from_slice(&id.seq.to_le_bytes());
+}
+
+fn read_id(buf: &mut &[u8]) -> Result<StreamId, RdbError> {
+    Ok(StreamId::new(read_u64(buf)?, read_u64(buf)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{AppConfig, BulkString, ScoreBound, XAddId, ZAddFlags};
+
+    use super::*;
+
+    #[test]
+    fn test_dump_and_restore() -> Result<(), RdbError> {
+        let backend = Backend::new();
+        backend.set("s".to_string(), "hello".into());
+        backend.set("i".to_string(), "-42".into());
+        backend
+            .hset(
+                "h".to_string(),
+                "f".to_string(),
+                BulkString::new("v").into(),
+            )
+            .unwrap();
+        backend
+            .rpush(
+                "l".to_string(),
+                vec![BulkString::new("a").into(), BulkString::new("b").into()],
+            )
+            .unwrap();
+        backend
+            .zadd(
+                "z".to_string(),
+                vec![(1.5, "one".to_string()), (f64::INFINITY, "inf".to_string())],
+                ZAddFlags::default(),
+            )
+            .unwrap();
+        let fields = vec![BulkString::new("f").into(), BulkString::new("v").into()];
+        backend
+            .xadd("x".to_string(), XAddId::Auto, fields, None, false)
+            .unwrap();
+        backend
+            .xgroup_create("x", "g".to_string(), Some(StreamId::MIN), false)
+            .unwrap();
+        backend
+            .xreadgroup("x", "g", "c", None, None, false)
+            .unwrap();
+        backend.expire_at("s", now_ms() + 60_000, None);
+        backend.set("gone".to_string(), "x".into());
+        backend
+            .keyspace()
+            .expires
+            .insert("gone".to_string(), now_ms() - 1);
+
+        backend
+            .select(15)
+            .unwrap()
+            .set("s".to_string(), "other".into());
+
+        let data = backend.dump();
+        let restored = Backend::new();
+        assert_eq!(restored.restore(&data)?, 7);
+        assert_eq!(restored.select(15).unwrap().get("s"), Some("other".into()));
+
+        assert_eq!(restored.get("s"), Some("hello".into()));
+        assert_eq!(restored.get("i"), Some("-42".into()));
+        assert!(restored.pttl("s") > 0);
+        assert_eq!(restored.pttl("i"), -1);
+        assert_eq!(restored.get("gone"), None);
+        assert_eq!(restored.hget("h", "f"), Some(BulkString::new("v").into()));
+        assert_eq!(restored.lrange("l", 0, -1).len(), 2);
+        assert_eq!(restored.zscore("z", "inf"), Some(f64::INFINITY));
+        assert_eq!(
+            restored.zcount("z", ScoreBound::inclusive(1.0), ScoreBound::inclusive(2.0)),
+            1
+        );
+        assert_eq!(restored.xlen("x"), 1);
+        let pending = restored
+            .with_group("x", "g", |g| g.pending_summary())
+            .unwrap();
+        assert_eq!(pending.2, vec![("c".to_string(), 1)]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_restore_rejects_corrupted_data() {
+        let backend = Backend::new();
+        assert!(backend.restore(b"REDIS0011").is_err());
+
+        backend.set("key".to_string(), "value".into());
+        let data = backend.dump();
+        assert!(Backend::new().restore(&data[..data.len() - 3]).is_err());
+    }
+
+    #[test]
+    fn test_save_and_load() -> Result<(), RdbError> {
+        let mut config = AppConfig::default();
+        config.rdb.dir = std::env::temp_dir();
+        config.rdb.dbfilename = format!("simple-redis-test-{}.rdb", std::process::id());
+
+        let backend = Backend::with_config(config.clone());
+        backend.set("key".to_string(), "value".into());
+        assert!(backend.dirty.load(Ordering::Relaxed) > 0);
+        backend.save()?;
+        assert_eq!(backend.dirty.load(Ordering::Relaxed), 0);
+
+        let loaded = Backend::with_config(config.clone());
+        assert_eq!(loaded.load()?, 1);
+        assert_eq!(loaded.get("key"), Some("value".into()));
+
+        fs::remove_file(config.rdb.path())?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_save_waits_for_running_commands() -> Result<(), RdbError> {
+        let mut config = AppConfig::default();
+        config.rdb.dir = std::env::temp_dir();
+        config.rdb.dbfilename = format!("simple-redis-test-wait-{}.rdb", std::process::id());
+        let backend = Backend::with_config(config.clone());
+
+        // a command is running, e.g. a transaction which sets both keys
+        let guard = backend.lock_exclusive();
+        backend.set("a".to_string(), "1".into());
+        let saving = std::thread::spawn({
+            let backend = backend.clone();
+            move || backend.save()
+        });
+        std::thread::sleep(std::time::Duration::from_millis(50));
+        assert!(!config.rdb.path().exists());
+        backend.set("b".to_string(), "2".into());
+        drop(guard);
+        saving.join().unwrap()?;
+
+        let loaded = Backend::with_config(config.clone());
+        assert_eq!(loaded.load()?, 2);
+        fs::remove_file(config.rdb.path())?;
+        Ok(())
+    }
+}
@@ -0,0 +1,758 @@
+use std::collections::hash_map::RandomState;
+use std::collections::VecDeque;
+use std::fmt::Write as _;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicU8, AtomicUsize, Ordering};
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use std::time::Duration;
+
+use bytes::{Bytes, BytesMut};
+use dashmap::DashMap;
+use thiserror::Error;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+use crate::cmd::{Command, CommandExecutor};
+use crate::{
+    parse_frame_bytes, parse_frame_length, Array, Backend, BulkString, RdbError, RespEncode,
+    RespError, RespFrame, SimpleString,
+};
+
+use super::{now_ms, AofWriter};
+
+const RECONNECT_INTERVAL: Duration = Duration::from_secs(1);
+const ACK_INTERVAL: Duration = Duration::from_secs(1);
+
+const LINK_CONNECTING: u8 = 0;
+const LINK_SYNC: u8 = 1;
+const LINK_CONNECTED: u8 = 2;
+
+#[derive(Error, Debug)]
+pub enum ReplicationError {
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Protocol error: {0}")]
+    Protocol(String),
+    #[error("Invalid snapshot: {0}")]
+    Rdb(#[from] RdbError),
+    #[error("Connection closed by master")]
+    Closed,
+}
+
+/// State of the leader/follower replication. A master streams its write commands to the
+/// connected replicas, a replica applies the stream of its master.
+#[derive(Debug)]
+pub struct Replication {
+    backlog: Mutex<Backlog>,
+    // set once a replica attached or this server became a replica, commands are recorded
+    // in the backlog from then on
+    active: AtomicBool,
+    replicas: DashMap<u64, Replica>,
+    // the master of this server, None for a master
+    master: Mutex<Option<MasterLink>>,
+    link_state: AtomicU8,
+    // unix time in milliseconds of the last data received from the master
+    last_io: AtomicU64,
+    // the database the commands of the master apply to, as selected by its stream
+    master_db: AtomicUsize,
+}
+
+/// The most recent part of the replication stream, from which a replica which was briefly
+/// disconnected resumes without a full resync.
+#[derive(Debug)]
+struct Backlog {
+    // identifies the history of the stream, an offset is only meaningful for a given replid
+    replid: String,
+    // number of bytes streamed since the history began
+    offset: u64,
+    buf: VecDeque<u8>,
+    capacity: usize,
+    // the database selected by the stream, None until a SELECT is streamed
+    db: Option<usize>,
+}
+
+#[derive(Debug)]
+struct Replica {
+    ip: String,
+    port: u16,
+    sender: UnboundedSender<Bytes>,
+    // offset acknowledged by the replica and unix time in milliseconds of the ack
+    ack_offset: u64,
+    last_ack: u64,
+}
+
+#[derive(Debug)]
+struct MasterLink {
+    host: String,
+    port: u16,
+    task: JoinHandle<()>,
+}
+
+/// Exclusive access to everything a write command is propagated to: the append only file and
+/// the replicas. Commands are executed while holding it so that the order is the same everywhere.
+pub struct CommandLog<'a> {
+    // the database the commands apply to
+    db: usize,
+    aof: Option<AofWriter<'a>>,
+    replication: Option<(MutexGuard<'a, Backlog>, &'a DashMap<u64, Replica>)>,
+}
+
+/// Reply to a PSYNC, the stream then goes on with the commands of the master.
+#[derive(Debug)]
+pub struct PSyncReply {
+    pub frame: RespFrame,
+    pub stream: ReplicaStream,
+}
+
+/// The replication stream of a connected replica. It is unregistered when dropped.
+#[derive(Debug)]
+pub struct ReplicaStream {
+    id: u64,
+    backend: Backend,
+    receiver: UnboundedReceiver<Bytes>,
+}
+
+impl Replication {
+    pub(super) fn new(backlog_size: usize) -> Self {
+        Self {
+            backlog: Mutex::new(Backlog {
+                replid: new_replid(),
+                offset: 0,
+                buf: VecDeque::new(),
+                capacity: backlog_size,
+                db: None,
+            }),
+            active: AtomicBool::new(false),
+            replicas: DashMap::new(),
+            master: Mutex::new(None),
+            link_state: AtomicU8::new(LINK_CONNECTING),
+            last_io: AtomicU64::new(0),
+            master_db: AtomicUsize::new(0),
+        }
+    }
+
+    fn backlog(&self) -> MutexGuard<'_, Backlog> {
+        self.backlog.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn master(&self) -> MutexGuard<'_, Option<MasterLink>> {
+        self.master.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl Backlog {
+    fn append(&mut self, data: &[u8]) {
+        self.offset += data.len() as u64;
+        self.buf.extend(data);
+        if self.buf.len() > self.capacity {
+            let excess = self.buf.len() - self.capacity;
+            self.buf.drain(..excess);
+        }
+    }
+
+    // the stream following the first `offset` bytes, if the backlog still has it
+    fn range_from(&self, offset: u64) -> Option<Bytes> {
+        let start = self.offset - self.buf.len() as u64;
+        if offset < start || offset > self.offset {
+            return None;
+        }
+        let skip = (offset - start) as usize;
+        Some(
+            self.buf
+                .iter()
+                .skip(skip)
+                .copied()
+                .collect::<Vec<_>>()
+                .into(),
+        )
+    }
+
+    fn reset(&mut self, replid: String, offset: u64) {
+        self.replid = replid;
+        self.offset = offset;
+        self.buf.clear();
+        self.db = None;
+    }
+}
+
+impl CommandLog<'_> {
+    /// Switch the database the next commands apply to.
+    pub fn set_db(&mut self, db: usize) {
+        self.db = db;
+    }
+
+    pub fn append(&mut self, frame: RespFrame) {
+        self.append_encoded(&frame.encode());
+    }
+
+    /// Propagate a command already in its RESP encoding, preceded by a SELECT wherever another
+    /// database is selected.
+    pub fn append_encoded(&mut self, buf: &[u8]) {
+        self.append_aof(buf);
+        if let Some((backlog, replicas)) = self.replication.as_mut() {
+            if backlog.db != Some(self.db) {
+                let select = command(&["select", &self.db.to_string()]).encode();
+                stream(backlog, replicas, &select);
+                backlog.db = Some(self.db);
+            }
+            stream(backlog, replicas, buf);
+        }
+    }
+
+    /// Propagate a part of the replication stream of the master as received, the replicas of
+    /// this server get the very same stream.
+    pub fn append_replicated(&mut self, buf: &[u8]) {
+        self.append_aof(buf);
+        if let Some((backlog, replicas)) = self.replication.as_mut() {
+            stream(backlog, replicas, buf);
+        }
+    }
+
+    /// Follow a SELECT of the replication stream of the master. The append only file selects
+    /// the database along with the next command.
+    pub fn select_replicated(&mut self, db: usize, buf: &[u8]) {
+        self.db = db;
+        if let Some((backlog, replicas)) = self.replication.as_mut() {
+            stream(backlog, replicas, buf);
+        }
+    }
+
+    fn append_aof(&mut self, buf: &[u8]) {
+        if let Some(aof) = self.aof.as_mut() {
+            if let Err(e) = aof.select(self.db).and_then(|_| aof.append_encoded(buf)) {
+                warn!("failed to append to aof: {}", e);
+            }
+        }
+    }
+}
+
+fn stream(backlog: &mut Backlog, replicas: &DashMap<u64, Replica>, buf: &[u8]) {
+    backlog.append(buf);
+    let data = Bytes::copy_from_slice(buf);
+    for replica in replicas.iter() {
+        // a replica which went away is unregistered by its connection
+        let _ = replica.sender.send(data.clone());
+    }
+}
+
+impl ReplicaStream {
+    /// The next part of the stream, None once the replica has been dropped by the master.
+    pub async fn recv(&mut self) -> Option<Bytes> {
+        self.receiver.recv().await
+    }
+}
+
+impl Drop for ReplicaStream {
+    fn drop(&mut self) {
+        self.backend.replication.replicas.remove(&self.id);
+    }
+}
+
+impl Backend {
+    /// Lock everything write commands are propagated to, None if they are not propagated.
+    pub fn command_log(&self) -> Option<CommandLog<'_>> {
+        let aof = self.aof_writer();
+        let replication = self
+            .replication
+            .active
+            .load(Ordering::Acquire)
+            .then(|| (self.replication.backlog(), &self.replication.replicas));
+        if aof.is_none() && replication.is_none() {
+            return None;
+        }
+        Some(CommandLog {
+            db: self.db(),
+            aof,
+            replication,
+        })
+    }
+
+    pub fn is_replica(&self) -> bool {
+        self.replication.master().is_some()
+    }
+
+    /// Start replicating the given master, or stop replicating with None. Returns false if
+    /// this server already replicates that master.
+    pub fn replicaof(&self, master: Option<(String, u16)>) -> bool {
+        let mut link = self.replication.master();
+        match (&*link, &master) {
+            (Some(current), Some((host, port)))
+                if current.host == *host && current.port == *port =>
+            {
+                return false;
+            }
+            (None, None) => return false,
+            _ => {}
+        }
+        if let Some(current) = link.take() {
+            current.task.abort();
+        }
+        match master {
+            Some((host, port)) => {
+                info!("Replicating {}:{}", host, port);
+                self.replication
+                    .link_state
+                    .store(LINK_CONNECTING, Ordering::Release);
+                self.replication.active.store(true, Ordering::Release);
+                let task = tokio::spawn(self.clone().run_replication(host.clone(), port));
+                *link = Some(MasterLink { host, port, task });
+            }
+            None => {
+                // a new history begins, the replicas of the former master can't resume from it
+                info!("Replication stopped, this server is now a master");
+                let mut backlog = self.replication.backlog();
+                let offset = backlog.offset;
+                backlog.reset(new_replid(), offset);
+            }
+        }
+        true
+    }
+
+    /// Attach a replica, resuming from the backlog when it has the requested part of the stream
+    /// and sending a snapshot otherwise. `offset` is the first byte the replica wants.
+    pub fn psync(&self, id: u64, ip: String, port: u16, replid: &str, offset: i64) -> PSyncReply {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let stream = ReplicaStream {
+            id,
+            backend: self.clone(),
+            receiver,
+        };
+        let replica = Replica {
+            ip,
+            port,
+            sender: sender.clone(),
+            ack_offset: 0,
+            last_ack: now_ms(),
+        };
+
+        if self.replication.active.load(Ordering::Acquire) && offset > 0 {
+            let backlog = self.replication.backlog();
+            if let Some(data) = (backlog.replid == replid)
+                .then(|| backlog.range_from(offset as u64 - 1))
+                .flatten()
+            {
+                info!("Partial resync of replica {} from offset {}", id, offset);
+                let frame = SimpleString::new(format!("CONTINUE {}", backlog.replid)).into();
+                let _ = sender.send(data);
+                self.replication.replicas.insert(id, replica);
+                return PSyncReply { frame, stream };
+            }
+        }
+
+        // no command runs while the snapshot is taken, so it is consistent with the offset
+        let _guard = self.lock_exclusive();
+        let mut backlog = self.replication.backlog();
+        let data = self.dump();
+        // the replica starts with no database selected
+        backlog.db = None;
+        let mut payload = BytesMut::with_capacity(data.len() + 16);
+        payload.extend_from_slice(format!("${}\r\n", data.len()).as_bytes());
+        payload.extend_from_slice(&data);
+        let _ = sender.send(payload.freeze());
+
+        info!("Full resync of replica {} at offset {}", id, backlog.offset);
+        self.replication.active.store(true, Ordering::Release);
+        self.replication.replicas.insert(id, replica);
+        let frame =
+            SimpleString::new(format!("FULLRESYNC {} {}", backlog.replid, backlog.offset)).into();
+        PSyncReply { frame, stream }
+    }
+
+    /// Record the offset acknowledged by a replica.
+    pub fn replica_ack(&self, id: u64, offset: u64) {
+        if let Some(mut replica) = self.replication.replicas.get_mut(&id) {
+            replica.ack_offset = offset;
+            replica.last_ack = now_ms();
+        }
+    }
+
+    /// The replication section of INFO.
+    pub fn replication_info(&self) -> String {
+        let mut info = String::from("# Replication\r\n");
+        let now = now_ms();
+        match &*self.replication.master() {
+            Some(master) => {
+                let state = self.replication.link_state.load(Ordering::Acquire);
+                let last_io = self.replication.last_io.load(Ordering::Acquire);
+                let _ =
+                    write!(
+                    info,
+                    "role:slave\r\nmaster_host:{}\r\nmaster_port:{}\r\nmaster_link_status:{}\r\n\
+                     master_last_io_seconds_ago:{}\r\nmaster_sync_in_progress:{}\r\n\
+                     slave_repl_offset:{}\r\nslave_read_only:1\r\n",
+                    master.host,
+                    master.port,
+                    if state == LINK_CONNECTED { "up" } else { "down" },
+                    match last_io {
+                        0 => -1,
+                        at => (now.saturating_sub(at) / 1000) as i64,
+                    },
+                    (state == LINK_SYNC) as u8,
+                    self.replication.backlog().offset,
+                );
+            }
+            None => info.push_str("role:master\r\n"),
+        }
+
+        let _ = write!(
+            info,
+            "connected_slaves:{}\r\n",
+            self.replication.replicas.len()
+        );
+        let mut replicas = self
+            .replication
+            .replicas
+            .iter()
+            .map(|replica| {
+                format!(
+                    "ip={},port={},state=online,offset={},lag={}",
+                    replica.ip,
+                    replica.port,
+                    replica.ack_offset,
+                    now.saturating_sub(replica.last_ack) / 1000
+                )
+            })
+            .collect::<Vec<_>>();
+        replicas.sort();
+        for (i, replica) in replicas.iter().enumerate() {
+            let _ = write!(info, "slave{}:{}\r\n", i, replica);
+        }
+
+        let backlog = self.replication.backlog();
+        let _ = write!(
+            info,
+            "master_replid:{}\r\nmaster_repl_offset:{}\r\nrepl_backlog_active:{}\r\n\
+             repl_backlog_size:{}\r\nrepl_backlog_first_byte_offset:{}\r\nrepl_backlog_histlen:{}\r\n",
+            backlog.replid,
+            backlog.offset,
+            self.replication.active.load(Ordering::Acquire) as u8,
+            backlog.capacity,
+            backlog.offset - backlog.buf.len() as u64 + 1,
+            backlog.buf.len(),
+        );
+        info
+    }
+
+    // keep replicating the master, reconnecting whenever the link breaks
+    async fn run_replication(self, host: String, port: u16) {
+        loop {
+            if let Err(e) = self.sync_with_master(&host, port).await {
+                warn!("replication link with {}:{} broken: {}", host, port, e);
+            }
+            self.replication
+                .link_state
+                .store(LINK_CONNECTING, Ordering::Release);
+            tokio::time::sleep(RECONNECT_INTERVAL).await;
+        }
+    }
+
+    async fn sync_with_master(&self, host: &str, port: u16) -> Result<(), ReplicationError> {
+        let mut master = MasterConnection {
+            stream: TcpStream::connect((host, port)).await?,
+            buf: BytesMut::new(),
+        };
+        master.stream.set_nodelay(true)?;
+        self.replication
+            .link_state
+            .store(LINK_SYNC, Ordering::Release);
+
+        master.request(&["ping"]).await?;
+        let listening_port = self.config().server.port.to_string();
+        master
+            .request(&["replconf", "listening-port", &listening_port])
+            .await?;
+        master.request(&["replconf", "capa", "psync2"]).await?;
+
+        // ask to resume from the end of the stream received so far
+        let (replid, offset) = {
+            let backlog = self.replication.backlog();
+            (backlog.replid.clone(), backlog.offset)
+        };
+        let reply = master
+            .request(&["psync", &replid, &(offset + 1).to_string()])
+            .await?;
+        let reply = match reply {
+            RespFrame::SimpleString(s) => s.0,
+            frame => return Err(ReplicationError::Protocol(format!("{:?}", frame))),
+        };
+        let mut parts = reply.split_whitespace();
+        match (parts.next(), parts.next(), parts.next()) {
+            (Some("FULLRESYNC"), Some(replid), Some(offset)) => {
+                let offset = offset
+                    .parse()
+                    .map_err(|_| ReplicationError::Protocol(reply.clone()))?;
+                let data = master.read_payload().await?;
+                // the keyspace is replaced as a whole, and the replicas of this server follow
+                let _guard = self.lock_exclusive();
+                self.flushall();
+                let loaded = self.restore(&data)?;
+                self.replication.backlog().reset(replid.to_string(), offset);
+                self.replication.master_db.store(0, Ordering::Release);
+                self.replication.replicas.clear();
+                info!("Full resync with master: loaded {} keys", loaded);
+            }
+            (Some("CONTINUE"), replid, _) => {
+                if let Some(replid) = replid {
+                    self.replication.backlog().replid = replid.to_string();
+                }
+                info!("Partial resync with master from offset {}", offset + 1);
+            }
+            _ => return Err(ReplicationError::Protocol(reply)),
+        }
+
+        self.replication
+            .link_state
+            .store(LINK_CONNECTED, Ordering::Release);
+        self.replication.last_io.store(now_ms(), Ordering::Release);
+        let mut ticker = tokio::time::interval(ACK_INTERVAL);
+        loop {
+            tokio::select! {
+                ret = master.read_frame() => {
+                    let (frame, data) = ret?;
+                    self.replication.last_io.store(now_ms(), Ordering::Release);
+                    if is_getack(&frame) {
+                        let offset = self.replication.backlog().offset;
+                        master.send_ack(offset).await?;
+                    }
+                    self.apply(frame, &data);
+                }
+                _ = ticker.tick() => {
+                    let offset = self.replication.backlog().offset;
+                    master.send_ack(offset).await?;
+                }
+            }
+        }
+    }
+
+    // apply a command of the master, propagating it as received so that the offsets match
+    fn apply(&self, frame: RespFrame, data: &[u8]) {
+        let _guard = self.lock_shared();
+        let db = self.replication.master_db.load(Ordering::Acquire);
+        let backend = self.select(db).unwrap_or_else(|| self.clone());
+        let mut log = backend.command_log();
+        if is_getack(&frame) {
+            if let Some(log) = log.as_mut() {
+                log.append_replicated(data);
+            }
+            return;
+        }
+        match Command::try_from(frame) {
+            Ok(Command::Select(cmd)) => match self.select(cmd.index()) {
+                Some(selected) => {
+                    let db = selected.db();
+                    self.replication.master_db.store(db, Ordering::Release);
+                    if let Some(log) = log.as_mut() {
+                        log.select_replicated(db, data);
+                    }
+                    return;
+                }
+                None => warn!("replicated SELECT of a missing database {}", cmd.index()),
+            },
+            Ok(cmd) => {
+                if let RespFrame::Error(e) = cmd.execute(&backend) {
+                    warn!("error applying replicated command: {:?}", e);
+                }
+            }
+            Err(e) => warn!("invalid replicated command: {}", e),
+        }
+        if let Some(log) = log.as_mut() {
+            log.append_replicated(data);
+        }
+    }
+}
+
+// the connection of a replica to its master
+struct MasterConnection {
+    stream: TcpStream,
+    buf: BytesMut,
+}
+
+impl MasterConnection {
+    async fn request(&mut self, args: &[&str]) -> Result<RespFrame, ReplicationError> {
+        self.stream.write_all(&command(args).encode()).await?;
+        match self.read_frame().await? {
+            (RespFrame::Error(e), _) => Err(ReplicationError::Protocol(e.0)),
+            (frame, _) => Ok(frame),
+        }
+    }
+
+    async fn send_ack(&mut self, offset: u64) -> Result<(), ReplicationError> {
+        let frame = command(&["replconf", "ack", &offset.to_string()]);
+        self.stream.write_all(&frame.encode()).await?;
+        Ok(())
+    }
+
+    // the next frame with its encoding
+    async fn read_frame(&mut self) -> Result<(RespFrame, Bytes), ReplicationError> {
+        loop {
+            match parse_frame_length(&self.buf) {
+                Ok(len) => {
+                    let data = self.buf.split_to(len).freeze();
+                    let frame = parse_frame_bytes(&data)
+                        .map_err(|e| ReplicationError::Protocol(e.to_string()))?;
+                    return Ok((frame, data));
+                }
+                Err(RespError::NotComplete) => self.fill().await?,
+                Err(e) => return Err(ReplicationError::Protocol(e.to_string())),
+            }
+        }
+    }
+
+    // the snapshot of a full resync: a bulk string without the trailing CRLF
+    async fn read_payload(&mut self) -> Result<Bytes, ReplicationError> {
+        let header = loop {
+            if let Some(end) = self.buf.windows(2).position(|w| w == b"\r\n") {
+                let header = self.buf.split_to(end + 2);
+                break String::from_utf8_lossy(&header[..end]).to_string();
+            }
+            self.fill().await?;
+        };
+        let len = header
+            .strip_prefix('$')
+            .and_then(|len| len.parse::<usize>().ok())
+            .ok_or_else(|| ReplicationError::Protocol(header.clone()))?;
+        while self.buf.len() < len {
+            self.fill().await?;
+        }
+        let data = self.buf.split_to(len).freeze();
+        Ok(data)
+    }
+
+    async fn fill(&mut self) -> Result<(), ReplicationError> {
+        match self.stream.read_buf(&mut self.buf).await? {
+            0 => Err(ReplicationError::Closed),
+            _ => Ok(()),
+        }
+    }
+}
+
+// REPLCONF GETACK, which a master sends to learn the offset of its replicas
+fn is_getack(frame: &RespFrame) -> bool {
+    let RespFrame::Array(Array(Some(items))) = frame else {
+        return false;
+    };
+    let arg = |i: usize| match items.get(i) {
+        Some(RespFrame::BulkString(s)) => Some(s.as_ref().to_ascii_lowercase()),
+        _ => None,
+    };
+    arg(0).as_deref() == Some(b"replconf") && arg(1).as_deref() == Some(b"getack")
+}
+
+fn command(args: &[&str]) -> RespFrame {
+    Array::new(
+        args.iter()
+            .map(|arg| BulkString::new(*arg).into())
+            .collect::<Vec<RespFrame>>(),
+    )
+    .into()
+}
+
+// 40 random hex characters
+fn new_replid() -> String {
+    let state = RandomState::new();
+    let mut replid = String::with_capacity(48);
+    for i in 0..3u64 {
+        let mut hasher = state.build_hasher();
+        hasher.write_u64(now_ms() ^ i);
+        let _ = write!(replid, "{:016x}", hasher.finish());
+    }
+    replid.truncate(40);
+    replid
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn set(key: &str, value: &str) -> RespFrame {
+        command(&["set", key, value])
+    }
+
+    #[test]
+    fn test_backlog_keeps_the_end_of_the_stream() {
+        let mut backlog = Backlog {
+            replid: new_replid(),
+            offset: 0,
+            buf: VecDeque::new(),
+            capacity: 8,
+            db: None,
+        };
+        assert_eq!(backlog.replid.len(), 40);
+        backlog.append(b"hello");
+        backlog.append(b"world");
+        assert_eq!(backlog.offset, 10);
+        assert_eq!(backlog.buf.len(), 8);
+        assert_eq!(backlog.range_from(10), Some(Bytes::new()));
+        assert_eq!(backlog.range_from(5), Some(Bytes::from_static(b"world")));
+        assert_eq!(backlog.range_from(2), Some(Bytes::from_static(b"lloworld")));
+        assert_eq!(backlog.range_from(1), None);
+        assert_eq!(backlog.range_from(11), None);
+    }
+
+    #[tokio::test]
+    async fn test_psync_full_then_partial() {
+        let backend = Backend::new();
+        backend.set("key".to_string(), "value".into());
+
+        let mut reply = backend.psync(1, "127.0.0.1".to_string(), 6380, "?", -1);
+        let RespFrame::SimpleString(frame) = &reply.frame else {
+            panic!("PSYNC should reply with a simple string");
+        };
+        assert!(frame.starts_with("FULLRESYNC "));
+        let payload = reply.stream.recv().await.unwrap();
+        assert!(payload.starts_with(b"$"));
+        let replid = frame.split(' ').nth(1).unwrap().to_string();
+
+        // writes go to the replica and to the backlog, after selecting their database
+        let select = command(&["select", "0"]).encode();
+        backend.command_log().unwrap().append(set("a", "1"));
+        assert_eq!(reply.stream.recv().await.unwrap(), select);
+        assert_eq!(reply.stream.recv().await.unwrap(), set("a", "1").encode());
+        let offset = (select.len() + set("a", "1").encode().len()) as i64;
+        drop(reply);
+        assert_eq!(backend.replication.replicas.len(), 0);
+
+        backend.command_log().unwrap().append(set("b", "2"));
+        let mut reply = backend.psync(2, "127.0.0.1".to_string(), 6380, &replid, offset + 1);
+        assert_eq!(
+            reply.frame,
+            SimpleString::new(format!("CONTINUE {}", replid)).into()
+        );
+        assert_eq!(reply.stream.recv().await.unwrap(), set("b", "2").encode());
+        let other = backend.select(2).unwrap();
+        other.command_log().unwrap().append(set("c", "3"));
+        let select = command(&["select", "2"]).encode();
+        assert_eq!(reply.stream.recv().await.unwrap(), select);
+
+        // an unknown history needs a full resync
+        let reply = backend.psync(3, "127.0.0.1".to_string(), 6381, "other", offset + 1);
+        assert!(matches!(reply.frame, RespFrame::SimpleString(s) if s.starts_with("FULLRESYNC")));
+        assert!(backend
+            .replication_info()
+            .contains("connected_slaves:2\r\n"));
+    }
+
+    #[tokio::test]
+    async fn test_replica_rejoins_as_master() {
+        let backend = Backend::new();
+        assert!(backend
+            .replication_info()
+            .starts_with("# Replication\r\nrole:master\r\n"));
+        assert!(backend.command_log().is_none());
+
+        assert!(backend.replicaof(Some(("127.0.0.1".to_string(), 1))));
+        assert!(!backend.replicaof(Some(("127.0.0.1".to_string(), 1))));
+        assert!(backend.is_replica());
+        let info = backend.replication_info();
+        assert!(info.contains("role:slave\r\n"));
+        assert!(info.contains("master_link_status:down\r\n"));
+
+        let replid = backend.replication.backlog().replid.clone();
+        assert!(backend.replicaof(None));
+        assert!(!backend.is_replica());
+        assert_ne!(backend.replication.backlog().replid, replid);
+    }
+}
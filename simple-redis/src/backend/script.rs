@@ -0,0 +1,386 @@
+use std::cell::RefCell;
+use std::fmt;
+
+use mlua::{Lua, LuaOptions, StdLib, Table, Value, Variadic};
+use tracing::{debug, info, warn};
+
+use crate::cmd::lookup;
+use crate::{Array, Backend, BulkString, Null, RespFrame, SimpleError, SimpleString};
+
+// an error reply of redis.call, raised as a Lua error and replied as is if the script fails
+#[derive(Debug)]
+struct CallError(String);
+
+impl fmt::Display for CallError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for CallError {}
+
+impl Backend {
+    /// Cache a script, returning its SHA1 digest.
+    pub fn script_load(&self, body: &str) -> String {
+        let sha = sha1_hex(body.as_bytes());
+        self.scripts.insert(sha.clone(), body.to_string());
+        sha
+    }
+
+    pub fn script_get(&self, sha: &str) -> Option<String> {
+        self.scripts
+            .get(&sha.to_ascii_lowercase())
+            .map(|v| v.value().clone())
+    }
+
+    pub fn script_exists(&self, sha: &str) -> bool {
+        self.scripts.contains_key(&sha.to_ascii_lowercase())
+    }
+
+    pub fn script_flush(&self) {
+        self.scripts.clear();
+    }
+
+    /// Run a script with the KEYS and ARGV tables. `call` executes the commands requested with
+    /// redis.call / redis.pcall, so that the caller decides how they are locked and propagated.
+    ///
+    /// Every script gets a fresh interpreter, nothing a script does outlives it.
+    pub fn eval(
+        &self,
+        body: &str,
+        keys: Vec<RespFrame>,
+        args: Vec<RespFrame>,
+        call: impl FnMut(RespFrame) -> RespFrame,
+    ) -> RespFrame {
+        let sha = self.script_load(body);
+        let lua = match Lua::new_with(
+            StdLib::TABLE | StdLib::STRING | StdLib::MATH,
+            LuaOptions::default(),
+        ) {
+            Ok(lua) => lua,
+            Err(e) => return SimpleError::new(format!("ERR {}", e)).into(),
+        };
+        let call = RefCell::new(call);
+
+        let ret = lua.scope(|scope| {
+            let globals = lua.globals();
+            globals.set("KEYS", to_lua_args(&lua, keys)?)?;
+            globals.set("ARGV", to_lua_args(&lua, args)?)?;
+
+            let redis = lua.create_table()?;
+            // the call failing raises an error, the protected call returns it as a table instead
+            let call_fn = scope.create_function(|lua, args: Variadic<Value>| {
+                let frame = command(&args)?;
+                match run(&mut *call.borrow_mut(), frame) {
+                    RespFrame::Error(e) => Err(mlua::Error::external(CallError(e.0))),
+                    ret => to_lua(lua, ret),
+                }
+            })?;
+            let pcall_fn = scope.create_function(|lua, args: Variadic<Value>| {
+                let frame = match command(&args) {
+                    Ok(frame) => frame,
+                    Err(e) => return error_table(lua, &e.to_string()),
+                };
+                to_lua(lua, run(&mut *call.borrow_mut(), frame))
+            })?;
+            redis.set("call", call_fn)?;
+            redis.set("pcall", pcall_fn)?;
+            redis.set(
+                "error_reply",
+                lua.create_function(|lua, msg: String| error_table(lua, &msg))?,
+            )?;
+            redis.set(
+                "status_reply",
+                lua.create_function(|lua, msg: String| {
+                    let table = lua.create_table()?;
+                    table.set("ok", msg)?;
+                    Ok(Value::Table(table))
+                })?,
+            )?;
+            redis.set(
+                "sha1hex",
+                lua.create_function(|_, s: mlua::String| Ok(sha1_hex(s.as_bytes())))?,
+            )?;
+            redis.set(
+                "log",
+                lua.create_function(|_, (level, msg): (i64, String)| {
+                    match level {
+                        0 => debug!("script: {}", msg),
+                        3 => warn!("script: {}", msg),
+                        _ => info!("script: {}", msg),
+                    }
+                    Ok(())
+                })?,
+            )?;
+            for (i, level) in ["LOG_DEBUG", "LOG_VERBOSE", "LOG_NOTICE", "LOG_WARNING"]
+                .iter()
+                .enumerate()
+            {
+                redis.set(*level, i)?;
+            }
+            globals.set("redis", redis)?;
+
+            let ret = lua.load(body).set_name("=user_script").eval::<Value>()?;
+            Ok(from_lua(ret))
+        });
+
+        match ret {
+            Ok(ret) => ret,
+            Err(e) => match call_error(&e) {
+                Some(e) => SimpleError::new(e).into(),
+                None => SimpleError::new(format!("ERR {} script: {}", e, sha)).into(),
+            },
+        }
+    }
+}
+
+// run a command of a script, refusing the ones which make no sense within a script
+fn run(call: &mut impl FnMut(RespFrame) -> RespFrame, frame: RespFrame) -> RespFrame {
+    let name = match &frame {
+        RespFrame::Array(Array(Some(items))) => match items.first() {
+            Some(RespFrame::BulkString(name)) => {
+                String::from_utf8_lossy(name.as_ref()).to_ascii_lowercase()
+            }
+            _ => String::new(),
+        },
+        _ => String::new(),
+    };
+    match lookup(&name) {
+        Some(spec) if spec.has_flag("noscript") => {
+            SimpleError::new("ERR This Redis command is not allowed from script").into()
+        }
+        Some(_) => call(frame),
+        None => SimpleError::new("ERR Unknown Redis command called from script").into(),
+    }
+}
+
+// the request of redis.call, whose arguments must be strings or numbers
+fn command(args: &[Value]) -> mlua::Result<RespFrame> {
+    if args.is_empty() {
+        return Err(mlua::Error::external(CallError(
+            "ERR Please specify at least one argument for this redis lib call".to_string(),
+        )));
+    }
+    let args = args
+        .iter()
+        .map(|arg| match arg {
+            Value::String(s) => Ok(BulkString::new(s.as_bytes()).into()),
+            Value::Integer(i) => Ok(BulkString::new(i.to_string()).into()),
+            Value::Number(n) => Ok(BulkString::new(format_number(*n)).into()),
+            _ => Err(mlua::Error::external(CallError(
+                "ERR Lua redis lib command arguments must be strings or integers".to_string(),
+            ))),
+        })
+        .collect::<mlua::Result<Vec<RespFrame>>>()?;
+    Ok(Array::new(args).into())
+}
+
+// the reply of a command as a Lua value, following the RESP2 conversions of Redis
+fn to_lua(lua: &Lua, frame: RespFrame) -> mlua::Result<Value<'_>> {
+    let value = match frame {
+        RespFrame::Integer(i) => Value::Integer(i),
+        RespFrame::BulkString(BulkString(Some(s))) => Value::String(lua.create_string(s)?),
+        RespFrame::BulkString(BulkString(None)) | RespFrame::Array(Array(None)) => {
+            Value::Boolean(false)
+        }
+        RespFrame::Null(_) => Value::Boolean(false),
+        RespFrame::Boolean(b) => Value::Boolean(b),
+        RespFrame::Double(d) => Value::String(lua.create_string(format_number(d))?),
+        RespFrame::SimpleString(s) => {
+            let table = lua.create_table()?;
+            table.set("ok", s.0)?;
+            Value::Table(table)
+        }
+        RespFrame::Error(e) => error_table(lua, &e.0)?,
+        RespFrame::Array(Array(Some(items))) | RespFrame::Set(crate::Set(items)) => {
+            lua_array(lua, items)?
+        }
+        RespFrame::Push(push) => lua_array(lua, push.0)?,
+        RespFrame::Map(map) => {
+            let items = map
+                .0
+                .into_iter()
+                .flat_map(|(k, v)| [BulkString::from(k).into(), v])
+                .collect();
+            lua_array(lua, items)?
+        }
+    };
+    Ok(value)
+}
+
+fn lua_array(lua: &Lua, items: Vec<RespFrame>) -> mlua::Result<Value<'_>> {
+    let table = lua.create_table_with_capacity(items.len(), 0)?;
+    for (i, item) in items.into_iter().enumerate() {
+        table.raw_set(i + 1, to_lua(lua, item)?)?;
+    }
+    Ok(Value::Table(table))
+}
+
+fn to_lua_args(lua: &Lua, args: Vec<RespFrame>) -> mlua::Result<Table<'_>> {
+    let table = lua.create_table_with_capacity(args.len(), 0)?;
+    for (i, arg) in args.into_iter().enumerate() {
+        table.raw_set(i + 1, to_lua(lua, arg)?)?;
+    }
+    Ok(table)
+}
+
+fn error_table<'lua>(lua: &'lua Lua, msg: &str) -> mlua::Result<Value<'lua>> {
+    let table = lua.create_table()?;
+    table.set("err", msg)?;
+    Ok(Value::Table(table))
+}
+
+// the value returned by a script as a reply: numbers are truncated to integers, false is a null,
+// and a table is an array up to its first nil, unless it is an error or a status reply
+fn from_lua(value: Value) -> RespFrame {
+    match value {
+        Value::Integer(i) => RespFrame::Integer(i),
+        Value::Number(n) => RespFrame::Integer(n as i64),
+        Value::String(s) => BulkString::new(s.as_bytes()).into(),
+        Value::Boolean(true) => RespFrame::Integer(1),
+        Value::Table(table) => {
+            if let Ok(err) = table.raw_get::<_, String>("err") {
+                return SimpleError::new(err).into();
+            }
+            if let Ok(ok) = table.raw_get::<_, String>("ok") {
+                return SimpleString::new(ok).into();
+            }
+            let items = table
+                .sequence_values::<Value>()
+                .map_while(|v| v.ok())
+                .map(from_lua)
+                .collect::<Vec<_>>();
+            Array::new(items).into()
+        }
+        _ => RespFrame::Null(Null),
+    }
+}
+
+// the error reply of a redis.call which made the script fail
+fn call_error(e: &mlua::Error) -> Option<String> {
+    match e {
+        mlua::Error::CallbackError { cause, .. } => call_error(cause),
+        e => e.downcast_ref::<CallError>().map(|e| e.0.clone()),
+    }
+}
+
+// numbers as Lua prints them, integral ones without a fraction
+fn format_number(n: f64) -> String {
+    match n.fract() == 0.0 && n.abs() < 1e15 {
+        true => format!("{}", n as i64),
+        false => format!("{}", n),
+    }
+}
+
+fn sha1_hex(data: &[u8]) -> String {
+    sha1_smol::Sha1::from(data).digest().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cmd::{Command, CommandExecutor};
+
+    use super::*;
+
+    fn eval(backend: &Backend, body: &str, keys: &[&str], args: &[&str]) -> RespFrame {
+        let frames = |v: &[&str]| {
+            v.iter()
+                .map(|s| BulkString::new(*s).into())
+                .collect::<Vec<RespFrame>>()
+        };
+        backend.eval(
+            body,
+            frames(keys),
+            frames(args),
+            |frame| match Command::try_from(frame) {
+                Ok(cmd) => cmd.execute(backend),
+                Err(e) => e.into(),
+            },
+        )
+    }
+
+    #[test]
+    fn test_eval_conversions() {
+        let backend = Backend::new();
+        assert_eq!(
+            eval(&backend, "return 1.9", &[], &[]),
+            RespFrame::Integer(1)
+        );
+        assert_eq!(
+            eval(&backend, "return {1, 'a', false, 2, nil, 3}", &[], &[]),
+            Array::new(vec![
+                RespFrame::Integer(1),
+                BulkString::new("a").into(),
+                RespFrame::Null(Null),
+                RespFrame::Integer(2)
+            ])
+            .into()
+        );
+        assert_eq!(
+            eval(&backend, "return redis.status_reply('FINE')", &[], &[]),
+            SimpleString::new("FINE").into()
+        );
+        assert_eq!(
+            eval(&backend, "return {err = 'MY error'}", &[], &[]),
+            SimpleError::new("MY error").into()
+        );
+        assert_eq!(
+            eval(&backend, "return {KEYS[1], ARGV[2]}", &["k"], &["a", "b"]),
+            Array::new(vec![
+                BulkString::new("k").into(),
+                BulkString::new("b").into()
+            ])
+            .into()
+        );
+        assert_eq!(
+            eval(&backend, "return redis.sha1hex('')", &[], &[]),
+            BulkString::new("da39a3ee5e6b4b0d3255bfef95601890afd80709").into()
+        );
+    }
+
+    #[test]
+    fn test_eval_calls_commands() {
+        let backend = Backend::new();
+        let body = "redis.call('set', KEYS[1], ARGV[1]); return redis.call('get', KEYS[1])";
+        assert_eq!(
+            eval(&backend, body, &["key"], &["10"]),
+            BulkString::new("10").into()
+        );
+        assert_eq!(
+            eval(&backend, "return redis.call('get', 'nope')", &[], &[]),
+            RespFrame::Null(Null)
+        );
+
+        // a failing call makes the script fail with its error, unless it is protected
+        let body = "return redis.call('lpushx', KEYS[1], 'a')";
+        let RespFrame::Error(e) = eval(&backend, body, &["key"], &[]) else {
+            panic!("the script should fail");
+        };
+        assert!(e.starts_with("WRONGTYPE"));
+        let body = "local ret = redis.pcall('lpushx', KEYS[1], 'a'); return ret['err']";
+        assert!(matches!(
+            eval(&backend, body, &["key"], &[]),
+            RespFrame::BulkString(s) if s.as_ref().starts_with(b"WRONGTYPE")
+        ));
+
+        assert_eq!(
+            eval(&backend, "return redis.call('multi')", &[], &[]),
+            SimpleError::new("ERR This Redis command is not allowed from script").into()
+        );
+        let RespFrame::Error(e) = eval(&backend, "return x +", &[], &[]) else {
+            panic!("the script should not compile");
+        };
+        assert!(e.starts_with("ERR ") && e.contains("user_script:1:"));
+    }
+
+    #[test]
+    fn test_script_cache() {
+        let backend = Backend::new();
+        let sha = backend.script_load("return 1");
+        assert_eq!(sha, "e0e1f9fabfc9d4800c877a703b823ac0578ff8db");
+        assert!(backend.script_exists(&sha.to_ascii_uppercase()));
+        assert_eq!(backend.script_get(&sha), Some("return 1".to_string()));
+        backend.script_flush();
+        assert!(!backend.script_exists(&sha));
+    }
+}
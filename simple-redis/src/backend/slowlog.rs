@@ -0,0 +1,146 @@
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use std::time::Duration;
+
+use bytes::Bytes;
+
+use crate::{now_ms, Backend};
+
+// an entry keeps at most that many arguments and that many bytes of each argument
+const SLOWLOG_MAX_ARGC: usize = 32;
+const SLOWLOG_MAX_ARGLEN: usize = 128;
+
+/// The latest commands which ran for longer than `slowlog-log-slower-than`.
+#[derive(Debug, Default)]
+pub struct SlowLog {
+    // newest first
+    entries: Mutex<VecDeque<SlowLogEntry>>,
+    next_id: AtomicU64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SlowLogEntry {
+    pub id: u64,
+    /// unix time in seconds at which the command ran
+    pub timestamp: u64,
+    /// in microseconds
+    pub duration: u64,
+    pub args: Vec<Bytes>,
+    pub addr: String,
+    pub name: String,
+}
+
+impl Backend {
+    /// Log a command which ran for `duration` if it is slower than the configured threshold.
+    pub fn slowlog_record(&self, args: &[Bytes], duration: Duration, addr: String, name: String) {
+        let config = self.config().slowlog.clone();
+        let duration = duration.as_micros() as u64;
+        if config.log_slower_than < 0 || duration < config.log_slower_than as u64 {
+            return;
+        }
+        let entry = SlowLogEntry {
+            id: self.slowlog.next_id.fetch_add(1, Ordering::Relaxed),
+            timestamp: now_ms() / 1000,
+            duration,
+            args: trim_args(args),
+            addr,
+            name,
+        };
+        let mut entries = self.slowlog.entries();
+        entries.push_front(entry);
+        entries.truncate(config.max_len);
+    }
+
+    /// The `count` newest entries, newest first.
+    pub fn slowlog_get(&self, count: usize) -> Vec<SlowLogEntry> {
+        self.slowlog.entries().iter().take(count).cloned().collect()
+    }
+
+    pub fn slowlog_len(&self) -> usize {
+        self.slowlog.entries().len()
+    }
+
+    pub fn slowlog_reset(&self) {
+        self.slowlog.entries().clear();
+    }
+}
+
+impl SlowLog {
+    fn entries(&self) -> MutexGuard<'_, VecDeque<SlowLogEntry>> {
+        self.entries.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+// long commands are logged with a note of what was left out, like redis does
+fn trim_args(args: &[Bytes]) -> Vec<Bytes> {
+    let kept = match args.len() > SLOWLOG_MAX_ARGC {
+        true => SLOWLOG_MAX_ARGC - 1,
+        false => args.len(),
+    };
+    let mut trimmed = args[..kept]
+        .iter()
+        .map(|arg| match arg.len() > SLOWLOG_MAX_ARGLEN {
+            true => {
+                let more = arg.len() - SLOWLOG_MAX_ARGLEN;
+                let mut arg = arg[..SLOWLOG_MAX_ARGLEN].to_vec();
+                arg.extend_from_slice(format!("... ({} more bytes)", more).as_bytes());
+                Bytes::from(arg)
+            }
+            false => arg.clone(),
+        })
+        .collect::<Vec<_>>();
+    if kept < args.len() {
+        let more = args.len() - kept;
+        trimmed.push(Bytes::from(format!("... ({} more arguments)", more)));
+    }
+    trimmed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_slowlog() {
+        let backend = Backend::new();
+        let args = [Bytes::from("get"), Bytes::from("k")];
+        let record = |micros| {
+            backend.slowlog_record(
+                &args,
+                Duration::from_micros(micros),
+                "127.0.0.1:6000".to_string(),
+                String::new(),
+            )
+        };
+        record(100);
+        assert_eq!(backend.slowlog_len(), 0);
+        record(20_000);
+        record(30_000);
+        let entries = backend.slowlog_get(10);
+        assert_eq!(entries.len(), 2);
+        assert_eq!((entries[0].id, entries[0].duration), (1, 30_000));
+
+        backend
+            .set_config(&[("slowlog-max-len".to_string(), "1".to_string())])
+            .unwrap();
+        record(40_000);
+        assert_eq!(backend.slowlog_len(), 1);
+        backend.slowlog_reset();
+        assert_eq!(backend.slowlog_len(), 0);
+    }
+
+    #[test]
+    fn test_slowlog_trims_args() {
+        let args = (0..40)
+            .map(|i| Bytes::from(i.to_string()))
+            .chain([Bytes::from("x".repeat(200))])
+            .collect::<Vec<_>>();
+        let trimmed = trim_args(&args);
+        assert_eq!(trimmed.len(), 32);
+        assert_eq!(trimmed[31], Bytes::from("... (10 more arguments)"));
+
+        let trimmed = trim_args(&args[40..]);
+        assert!(trimmed[0].ends_with(b"x... (72 more bytes)"));
+    }
+}
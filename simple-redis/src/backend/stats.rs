@@ -0,0 +1,86 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use dashmap::DashMap;
+
+use crate::Backend;
+
+/// Counters reported by the stats and commandstats sections of INFO.
+#[derive(Debug, Default)]
+pub struct Stats {
+    commands: DashMap<String, CommandStats>,
+    expired_keys: AtomicU64,
+}
+
+/// The calls of a command since the server started.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CommandStats {
+    pub calls: u64,
+    /// total time spent running the command, in microseconds
+    pub usec: u64,
+    /// calls which replied with an error
+    pub failed_calls: u64,
+}
+
+impl Backend {
+    /// Count a call of the command `name` which ran for `duration`.
+    pub fn record_call(&self, name: &str, duration: Duration, failed: bool) {
+        let mut stats = self.stats.commands.entry(name.to_string()).or_default();
+        stats.calls += 1;
+        stats.usec += duration.as_micros() as u64;
+        stats.failed_calls += failed as u64;
+    }
+
+    /// The stats of the commands called at least once, by name.
+    pub fn command_stats(&self) -> Vec<(String, CommandStats)> {
+        let mut stats = self
+            .stats
+            .commands
+            .iter()
+            .map(|v| (v.key().clone(), *v.value()))
+            .collect::<Vec<_>>();
+        stats.sort_by(|(a, _), (b, _)| a.cmp(b));
+        stats
+    }
+
+    /// Number of commands called since the server started.
+    pub fn total_commands_processed(&self) -> u64 {
+        self.stats.commands.iter().map(|v| v.calls).sum()
+    }
+
+    /// Number of keys removed because their ttl passed.
+    pub fn expired_keys(&self) -> u64 {
+        self.stats.expired_keys.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn record_expired(&self) {
+        self.stats.expired_keys.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_command_stats() {
+        let backend = Backend::new();
+        backend.record_call("set", Duration::from_micros(10), false);
+        backend.record_call("set", Duration::from_micros(30), true);
+        backend.record_call("get", Duration::from_micros(5), false);
+        let stats = backend.command_stats();
+        assert_eq!(stats[0].0, "get");
+        assert_eq!(
+            stats[1],
+            (
+                "set".to_string(),
+                CommandStats {
+                    calls: 2,
+                    usec: 40,
+                    failed_calls: 1
+                }
+            )
+        );
+        assert_eq!(backend.total_commands_processed(), 3);
+    }
+}
@@ -0,0 +1,925 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+use thiserror::Error;
+
+use crate::{now_ms, Backend, RespFrame};
+
+#[derive(Error, Debug, PartialEq)]
+pub enum StreamError {
+    #[error("ERR Invalid stream ID specified as stream command argument")]
+    InvalidId,
+    #[error("ERR The ID specified in XADD is equal or smaller than the target stream top item")]
+    IdTooSmall,
+    #[error("ERR The ID specified in XADD must be greater than 0-0")]
+    ZeroId,
+    #[error("ERR The stream has exhausted the last possible ID, unable to add more items")]
+    Exhausted,
+    #[error("NOGROUP No such key '{0}' or consumer group '{1}'")]
+    NoGroup(String, String),
+    #[error("BUSYGROUP Consumer Group name already exists")]
+    BusyGroup,
+    #[error("ERR The XGROUP subcommand requires the key to exist. Note that for CREATE you may want to use the MKSTREAM option to create an empty stream automatically.")]
+    NoKey,
+}
+
+/// The id of a stream entry: a unix time in milliseconds and a sequence number within it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct StreamId {
+    pub ms: u64,
+    pub seq: u64,
+}
+
+/// The id requested by XADD, the parts given as `*` are generated.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum XAddId {
+    Auto,
+    AutoSeq(u64),
+    Explicit(StreamId),
+}
+
+/// How XADD / XTRIM trim a stream, from its oldest entries.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StreamTrim {
+    pub strategy: TrimStrategy,
+    /// the most entries removed at once
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TrimStrategy {
+    /// keep at most this many entries
+    MaxLen(usize),
+    /// remove the entries with a smaller id
+    MinId(StreamId),
+}
+
+/// Options of XCLAIM.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ClaimOptions {
+    /// the idle time of the claimed entries in milliseconds, instead of 0
+    pub idle: Option<u64>,
+    /// the delivery time of the claimed entries as a unix time in milliseconds
+    pub time: Option<u64>,
+    /// the delivery count of the claimed entries, instead of incrementing it
+    pub retry_count: Option<u64>,
+    /// claim the entries which are not pending, as long as they exist
+    pub force: bool,
+    /// reply with the ids only, without incrementing the delivery count
+    pub just_id: bool,
+    /// the last delivered id of the group, if greater than the current one
+    pub last_id: Option<StreamId>,
+}
+
+/// Field value pairs of an entry, flattened as they are replied.
+pub type StreamFields = Vec<RespFrame>;
+
+/// An entry as read by a consumer group, an entry deleted since its delivery has no fields.
+pub type GroupEntry = (StreamId, Option<StreamFields>);
+
+/// An append only log of entries ordered by id, read by consumer groups.
+#[derive(Debug, Clone, Default)]
+pub struct Stream {
+    pub(super) entries: BTreeMap<StreamId, StreamFields>,
+    // the id of the last entry ever added, later ids must be greater
+    pub(super) last_id: StreamId,
+    pub(super) groups: BTreeMap<String, ConsumerGroup>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ConsumerGroup {
+    // the last entry delivered to a consumer of the group
+    pub(super) last_delivered: StreamId,
+    // entries delivered and not acknowledged yet
+    pub(super) pending: BTreeMap<StreamId, PendingEntry>,
+    pub(super) consumers: BTreeMap<String, Consumer>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PendingEntry {
+    pub consumer: String,
+    /// unix time in milliseconds of the last delivery
+    pub delivered: u64,
+    pub deliveries: u64,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Consumer {
+    // unix time in milliseconds of the last read or claim
+    pub(super) seen: u64,
+    // the ids of the group pending entries delivered to this consumer
+    pub(super) pending: BTreeSet<StreamId>,
+}
+
+/// Summary of the pending entries of a group: the count, the smallest and the greatest id, and
+/// the count of each consumer.
+pub type PendingSummary = (usize, Option<(StreamId, StreamId)>, Vec<(String, usize)>);
+
+impl StreamId {
+    pub const MIN: StreamId = StreamId { ms: 0, seq: 0 };
+    pub const MAX: StreamId = StreamId {
+        ms: u64::MAX,
+        seq: u64::MAX,
+    };
+
+    pub fn new(ms: u64, seq: u64) -> Self {
+        Self { ms, seq }
+    }
+
+    /// Parse `ms-seq`, or `ms` alone with `seq` as the sequence number.
+    pub fn parse(s: &str, seq: u64) -> Result<Self, StreamError> {
+        let (ms, seq) = match s.split_once('-') {
+            Some((ms, seq)) => (ms, seq.parse().map_err(|_| StreamError::InvalidId)?),
+            None => (s, seq),
+        };
+        let ms = ms.parse().map_err(|_| StreamError::InvalidId)?;
+        Ok(Self { ms, seq })
+    }
+
+    /// The smallest id greater than this one.
+    pub fn next(self) -> Option<Self> {
+        match (self.ms, self.seq) {
+            (_, seq) if seq < u64::MAX => Some(Self::new(self.ms, seq + 1)),
+            (ms, _) if ms < u64::MAX => Some(Self::new(ms + 1, 0)),
+            _ => None,
+        }
+    }
+
+    /// The greatest id smaller than this one.
+    pub fn prev(self) -> Option<Self> {
+        match (self.ms, self.seq) {
+            (_, seq) if seq > 0 => Some(Self::new(self.ms, seq - 1)),
+            (ms, _) if ms > 0 => Some(Self::new(ms - 1, u64::MAX)),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for StreamId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}-{}", self.ms, self.seq)
+    }
+}
+
+impl Stream {
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn last_id(&self) -> StreamId {
+        self.last_id
+    }
+
+    pub fn entries(&self) -> impl Iterator<Item = (&StreamId, &StreamFields)> {
+        self.entries.iter()
+    }
+
+    pub fn groups(&self) -> impl Iterator<Item = (&String, &ConsumerGroup)> {
+        self.groups.iter()
+    }
+
+    // whether the stream is the same as a missing one
+    fn is_pristine(&self) -> bool {
+        self.entries.is_empty() && self.groups.is_empty() && self.last_id == StreamId::MIN
+    }
+
+    /// Append an entry, generating the parts of its id which are not given.
+    pub fn add(&mut self, id: XAddId, fields: StreamFields) -> Result<StreamId, StreamError> {
+        let last = self.last_id;
+        let id = match id {
+            XAddId::Auto => match now_ms() {
+                ms if ms > last.ms => StreamId::new(ms, 0),
+                _ => last.next().ok_or(StreamError::Exhausted)?,
+            },
+            XAddId::AutoSeq(ms) if ms > last.ms => StreamId::new(ms, 0),
+            XAddId::AutoSeq(ms) if ms == last.ms => last
+                .next()
+                .filter(|id| id.ms == ms)
+                .ok_or(StreamError::IdTooSmall)?,
+            XAddId::AutoSeq(_) => return Err(StreamError::IdTooSmall),
+            XAddId::Explicit(StreamId::MIN) => return Err(StreamError::ZeroId),
+            XAddId::Explicit(id) if id <= last => return Err(StreamError::IdTooSmall),
+            XAddId::Explicit(id) => id,
+        };
+        self.entries.insert(id, fields);
+        self.last_id = id;
+        Ok(id)
+    }
+
+    /// Remove the oldest entries per `trim`. Returns the number of removed entries.
+    pub fn trim(&mut self, trim: &StreamTrim) -> usize {
+        let limit = trim.limit.filter(|limit| *limit > 0).unwrap_or(usize::MAX);
+        let mut removed = 0;
+        while removed < limit {
+            let Some(first) = self.entries.first_key_value().map(|(id, _)| *id) else {
+                break;
+            };
+            let remove = match trim.strategy {
+                TrimStrategy::MaxLen(max) => self.entries.len() > max,
+                TrimStrategy::MinId(min) => first < min,
+            };
+            if !remove {
+                break;
+            }
+            self.entries.remove(&first);
+            removed += 1;
+        }
+        removed
+    }
+
+    /// The entries with ids between `start` and `end` inclusive, in reverse order if `rev`.
+    pub fn range(
+        &self,
+        start: StreamId,
+        end: StreamId,
+        count: Option<usize>,
+        rev: bool,
+    ) -> Vec<(StreamId, StreamFields)> {
+        if start > end {
+            return vec![];
+        }
+        let range = self.entries.range(start..=end);
+        let count = count.unwrap_or(usize::MAX);
+        let entry = |(id, fields): (&StreamId, &StreamFields)| (*id, fields.clone());
+        match rev {
+            true => range.rev().take(count).map(entry).collect(),
+            false => range.take(count).map(entry).collect(),
+        }
+    }
+
+    /// Create a group which delivers the entries after `id`, the last entry if None.
+    pub fn create_group(&mut self, name: String, id: Option<StreamId>) -> Result<(), StreamError> {
+        if self.groups.contains_key(&name) {
+            return Err(StreamError::BusyGroup);
+        }
+        let group = ConsumerGroup {
+            last_delivered: id.unwrap_or(self.last_id),
+            ..Default::default()
+        };
+        self.groups.insert(name, group);
+        Ok(())
+    }
+
+    pub fn destroy_group(&mut self, name: &str) -> bool {
+        self.groups.remove(name).is_some()
+    }
+
+    pub fn group(&self, name: &str) -> Option<&ConsumerGroup> {
+        self.groups.get(name)
+    }
+
+    pub fn group_mut(&mut self, name: &str) -> Option<&mut ConsumerGroup> {
+        self.groups.get_mut(name)
+    }
+
+    /// Deliver entries to a consumer of a group: the entries never delivered to the group if `id`
+    /// is None, otherwise the entries pending for the consumer after `id`.
+    pub fn read_group(
+        &mut self,
+        group: &str,
+        consumer: &str,
+        id: Option<StreamId>,
+        count: Option<usize>,
+        no_ack: bool,
+    ) -> Option<Vec<GroupEntry>> {
+        let group = self.groups.get_mut(group)?;
+        let now = now_ms();
+        let count = count.filter(|count| *count > 0).unwrap_or(usize::MAX);
+        group.consumer(consumer).seen = now;
+        let Some(id) = id else {
+            let start = group.last_delivered.next()?;
+            let entries = self
+                .entries
+                .range(start..)
+                .take(count)
+                .map(|(id, fields)| (*id, Some(fields.clone())))
+                .collect::<Vec<_>>();
+            for (id, _) in entries.iter() {
+                group.last_delivered = *id;
+                if !no_ack {
+                    group.deliver(*id, consumer, now, None);
+                }
+            }
+            return Some(entries);
+        };
+
+        let ids = match id.next() {
+            Some(start) => group
+                .consumer(consumer)
+                .pending
+                .range(start..)
+                .take(count)
+                .copied()
+                .collect::<Vec<_>>(),
+            None => vec![],
+        };
+        let entries = ids
+            .into_iter()
+            .map(|id| {
+                if let Some(pending) = group.pending.get_mut(&id) {
+                    pending.delivered = now;
+                    pending.deliveries += 1;
+                }
+                (id, self.entries.get(&id).cloned())
+            })
+            .collect();
+        Some(entries)
+    }
+
+    /// Change the owner of pending entries which are idle for at least `min_idle` milliseconds.
+    pub fn claim(
+        &mut self,
+        group: &str,
+        consumer: &str,
+        min_idle: u64,
+        ids: &[StreamId],
+        options: &ClaimOptions,
+    ) -> Option<Vec<GroupEntry>> {
+        let group = self.groups.get_mut(group)?;
+        let now = now_ms();
+        if let Some(last_id) = options.last_id {
+            group.last_delivered = group.last_delivered.max(last_id);
+        }
+        let delivered = match (options.time, options.idle) {
+            (Some(time), _) => time,
+            (None, Some(idle)) => now.saturating_sub(idle),
+            (None, None) => now,
+        };
+        group.consumer(consumer).seen = now;
+
+        let mut claimed = vec![];
+        for id in ids {
+            let Some(fields) = self.entries.get(id) else {
+                // a deleted entry can't be delivered anymore
+                group.acknowledge(id);
+                continue;
+            };
+            let deliveries = match group.pending.get(id) {
+                Some(pending) if now.saturating_sub(pending.delivered) < min_idle => continue,
+                Some(pending) => pending.deliveries,
+                None if options.force => 0,
+                None => continue,
+            };
+            let deliveries = match (options.retry_count, options.just_id) {
+                (Some(count), _) => count,
+                (None, true) => deliveries,
+                (None, false) => deliveries + 1,
+            };
+            group.acknowledge(id);
+            group.deliver(*id, consumer, delivered, Some(deliveries));
+            claimed.push((*id, (!options.just_id).then(|| fields.clone())));
+        }
+        Some(claimed)
+    }
+}
+
+impl ConsumerGroup {
+    pub fn last_delivered(&self) -> StreamId {
+        self.last_delivered
+    }
+
+    pub fn set_last_delivered(&mut self, id: StreamId) {
+        self.last_delivered = id;
+    }
+
+    pub fn pending(&self) -> impl Iterator<Item = (&StreamId, &PendingEntry)> {
+        self.pending.iter()
+    }
+
+    pub fn consumers(&self) -> impl Iterator<Item = &String> {
+        self.consumers.keys()
+    }
+
+    /// Create a consumer. Returns false if it exists already.
+    pub fn create_consumer(&mut self, name: &str) -> bool {
+        if self.consumers.contains_key(name) {
+            return false;
+        }
+        self.consumer(name);
+        true
+    }
+
+    /// Delete a consumer along with its pending entries. Returns the number of pending entries.
+    pub fn delete_consumer(&mut self, name: &str) -> usize {
+        let Some(consumer) = self.consumers.remove(name) else {
+            return 0;
+        };
+        for id in consumer.pending.iter() {
+            self.pending.remove(id);
+        }
+        consumer.pending.len()
+    }
+
+    /// Remove an entry from the pending entries. Returns false if it was not pending.
+    pub fn acknowledge(&mut self, id: &StreamId) -> bool {
+        let Some(pending) = self.pending.remove(id) else {
+            return false;
+        };
+        if let Some(consumer) = self.consumers.get_mut(&pending.consumer) {
+            consumer.pending.remove(id);
+        }
+        true
+    }
+
+    pub fn pending_summary(&self) -> PendingSummary {
+        let bounds = match (
+            self.pending.first_key_value(),
+            self.pending.last_key_value(),
+        ) {
+            (Some((first, _)), Some((last, _))) => Some((*first, *last)),
+            _ => None,
+        };
+        let consumers = self
+            .consumers
+            .iter()
+            .filter(|(_, consumer)| !consumer.pending.is_empty())
+            .map(|(name, consumer)| (name.clone(), consumer.pending.len()))
+            .collect();
+        (self.pending.len(), bounds, consumers)
+    }
+
+    /// The pending entries between `start` and `end` which are idle for at least `min_idle`
+    /// milliseconds, with their consumer, idle time and delivery count.
+    pub fn pending_range(
+        &self,
+        start: StreamId,
+        end: StreamId,
+        count: usize,
+        min_idle: u64,
+        consumer: Option<&str>,
+    ) -> Vec<(StreamId, String, u64, u64)> {
+        if start > end {
+            return vec![];
+        }
+        let now = now_ms();
+        self.pending
+            .range(start..=end)
+            .map(|(id, pending)| (id, pending, now.saturating_sub(pending.delivered)))
+            .filter(|(_, pending, idle)| {
+                *idle >= min_idle && consumer.is_none_or(|name| pending.consumer == name)
+            })
+            .take(count)
+            .map(|(id, pending, idle)| (*id, pending.consumer.clone(), idle, pending.deliveries))
+            .collect()
+    }
+
+    fn consumer(&mut self, name: &str) -> &mut Consumer {
+        if !self.consumers.contains_key(name) {
+            let consumer = Consumer {
+                seen: now_ms(),
+                pending: BTreeSet::new(),
+            };
+            self.consumers.insert(name.to_string(), consumer);
+        }
+        self.consumers
+            .get_mut(name)
+            .expect("the consumer was just created")
+    }
+
+    // add a pending entry for the consumer, counting one more delivery unless `deliveries` is given
+    fn deliver(&mut self, id: StreamId, consumer: &str, delivered: u64, deliveries: Option<u64>) {
+        let previous = self.pending.get(&id).map(|pending| pending.deliveries);
+        self.acknowledge(&id);
+        let deliveries = deliveries.unwrap_or(previous.unwrap_or_default() + 1);
+        self.consumer(consumer).pending.insert(id);
+        self.pending.insert(
+            id,
+            PendingEntry {
+                consumer: consumer.to_string(),
+                delivered,
+                deliveries,
+            },
+        );
+    }
+}
+
+impl Backend {
+    /// Append an entry to the stream at key, creating the stream unless `no_mkstream`.
+    /// Returns None if the stream does not exist and is not created.
+    pub fn xadd(
+        &self,
+        key: String,
+        id: XAddId,
+        fields: StreamFields,
+        trim: Option<StreamTrim>,
+        no_mkstream: bool,
+    ) -> Result<Option<StreamId>, StreamError> {
+        self.expire_if_needed(&key);
+        if no_mkstream && !self.keyspace().stream.contains_key(&key) {
+            return Ok(None);
+        }
+        let ret = {
+            let mut stream = self.keyspace().stream.entry(key.clone()).or_default();
+            stream.add(id, fields).inspect(|_| {
+                if let Some(trim) = trim {
+                    stream.trim(&trim);
+                }
+            })
+        };
+        let id = match ret {
+            Ok(id) => id,
+            Err(e) => {
+                // a stream created for the entry which could not be added is not kept
+                self.keyspace()
+                    .stream
+                    .remove_if(&key, |_, stream| stream.is_pristine());
+                return Err(e);
+            }
+        };
+        self.touch(&key);
+        if let Some(notify) = self.keyspace().key_waiters.get(&key) {
+            notify.notify_waiters();
+        }
+        Ok(Some(id))
+    }
+
+    pub fn xlen(&self, key: &str) -> usize {
+        self.expire_if_needed(key);
+        self.keyspace()
+            .stream
+            .get(key)
+            .map(|v| v.len())
+            .unwrap_or_default()
+    }
+
+    pub fn xrange(
+        &self,
+        key: &str,
+        start: StreamId,
+        end: StreamId,
+        count: Option<usize>,
+        rev: bool,
+    ) -> Vec<(StreamId, StreamFields)> {
+        self.expire_if_needed(key);
+        match self.keyspace().stream.get(key) {
+            Some(stream) => stream.range(start, end, count, rev),
+            None => vec![],
+        }
+    }
+
+    pub fn xtrim(&self, key: &str, trim: &StreamTrim) -> usize {
+        self.expire_if_needed(key);
+        let removed = match self.keyspace().stream.get_mut(key) {
+            Some(mut stream) => stream.trim(trim),
+            None => 0,
+        };
+        if removed > 0 {
+            self.touch(key);
+        }
+        removed
+    }
+
+    /// The id of the last entry added to the stream at key.
+    pub fn stream_last_id(&self, key: &str) -> Option<StreamId> {
+        self.expire_if_needed(key);
+        self.keyspace()
+            .stream
+            .get(key)
+            .map(|stream| stream.last_id())
+    }
+
+    /// Create a consumer group, delivering the entries after `id`, or the entries added from now
+    /// on if None.
+    pub fn xgroup_create(
+        &self,
+        key: &str,
+        group: String,
+        id: Option<StreamId>,
+        mkstream: bool,
+    ) -> Result<(), StreamError> {
+        self.expire_if_needed(key);
+        if !mkstream && !self.keyspace().stream.contains_key(key) {
+            return Err(StreamError::NoKey);
+        }
+        self.keyspace()
+            .stream
+            .entry(key.to_string())
+            .or_default()
+            .create_group(group, id)?;
+        self.touch(key);
+        Ok(())
+    }
+
+    pub fn xgroup_destroy(&self, key: &str, group: &str) -> Result<bool, StreamError> {
+        let destroyed = self.update_stream(key, |stream| Ok(stream.destroy_group(group)))?;
+        if destroyed {
+            self.touch(key);
+        }
+        Ok(destroyed)
+    }
+
+    pub fn xgroup_setid(
+        &self,
+        key: &str,
+        group: &str,
+        id: Option<StreamId>,
+    ) -> Result<(), StreamError> {
+        self.update_group(key, group, |group, last_id| {
+            group.set_last_delivered(id.unwrap_or(last_id));
+        })?;
+        self.touch(key);
+        Ok(())
+    }
+
+    pub fn xgroup_create_consumer(
+        &self,
+        key: &str,
+        group: &str,
+        consumer: &str,
+    ) -> Result<bool, StreamError> {
+        let created = self.update_group(key, group, |group, _| group.create_consumer(consumer))?;
+        if created {
+            self.touch(key);
+        }
+        Ok(created)
+    }
+
+    pub fn xgroup_delete_consumer(
+        &self,
+        key: &str,
+        group: &str,
+        consumer: &str,
+    ) -> Result<usize, StreamError> {
+        let pending = self.update_group(key, group, |group, _| group.delete_consumer(consumer))?;
+        self.touch(key);
+        Ok(pending)
+    }
+
+    /// Read entries as `consumer` of `group`, see `Stream::read_group`.
+    pub fn xreadgroup(
+        &self,
+        key: &str,
+        group: &str,
+        consumer: &str,
+        id: Option<StreamId>,
+        count: Option<usize>,
+        no_ack: bool,
+    ) -> Result<Vec<GroupEntry>, StreamError> {
+        let entries = self.update_stream(key, |stream| {
+            stream
+                .read_group(group, consumer, id, count, no_ack)
+                .ok_or_else(|| StreamError::NoGroup(key.to_string(), group.to_string()))
+        })?;
+        self.touch(key);
+        Ok(entries)
+    }
+
+    /// Acknowledge entries of a group. Returns the number of entries which were pending.
+    pub fn xack(&self, key: &str, group: &str, ids: &[StreamId]) -> usize {
+        let acked = self
+            .update_group(key, group, |group, _| {
+                ids.iter().filter(|id| group.acknowledge(id)).count()
+            })
+            .unwrap_or_default();
+        if acked > 0 {
+            self.touch(key);
+        }
+        acked
+    }
+
+    pub fn xclaim(
+        &self,
+        key: &str,
+        group: &str,
+        consumer: &str,
+        min_idle: u64,
+        ids: &[StreamId],
+        options: &ClaimOptions,
+    ) -> Result<Vec<GroupEntry>, StreamError> {
+        let claimed = self.update_stream(key, |stream| {
+            stream
+                .claim(group, consumer, min_idle, ids, options)
+                .ok_or_else(|| StreamError::NoGroup(key.to_string(), group.to_string()))
+        })?;
+        self.touch(key);
+        Ok(claimed)
+    }
+
+    /// Run `f` with a consumer group, without modifying it.
+    pub fn with_group<R>(
+        &self,
+        key: &str,
+        group: &str,
+        f: impl FnOnce(&ConsumerGroup) -> R,
+    ) -> Result<R, StreamError> {
+        self.expire_if_needed(key);
+        self.keyspace()
+            .stream
+            .get(key)
+            .and_then(|stream| stream.group(group).map(f))
+            .ok_or_else(|| StreamError::NoGroup(key.to_string(), group.to_string()))
+    }
+
+    // run f with the stream at key, the stream must exist
+    fn update_stream<R>(
+        &self,
+        key: &str,
+        f: impl FnOnce(&mut Stream) -> Result<R, StreamError>,
+    ) -> Result<R, StreamError> {
+        self.expire_if_needed(key);
+        match self.keyspace().stream.get_mut(key) {
+            Some(mut stream) => f(&mut stream),
+            None => Err(StreamError::NoKey),
+        }
+    }
+
+    // run f with a consumer group and the last id of its stream
+    fn update_group<R>(
+        &self,
+        key: &str,
+        group: &str,
+        f: impl FnOnce(&mut ConsumerGroup, StreamId) -> R,
+    ) -> Result<R, StreamError> {
+        let no_group = || StreamError::NoGroup(key.to_string(), group.to_string());
+        self.update_stream(key, |stream| {
+            let last_id = stream.last_id();
+            stream
+                .group_mut(group)
+                .map(|group| f(group, last_id))
+                .ok_or_else(no_group)
+        })
+        .map_err(|_| no_group())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::BulkString;
+
+    use super::*;
+
+    fn fields(values: &[&str]) -> StreamFields {
+        values.iter().map(|v| BulkString::new(*v).into()).collect()
+    }
+
+    #[test]
+    fn test_stream_ids() {
+        assert_eq!(StreamId::parse("5-3", 0), Ok(StreamId::new(5, 3)));
+        assert_eq!(
+            StreamId::parse("5", u64::MAX),
+            Ok(StreamId::new(5, u64::MAX))
+        );
+        assert_eq!(StreamId::parse("5-x", 0), Err(StreamError::InvalidId));
+        assert_eq!(StreamId::new(5, u64::MAX).next(), Some(StreamId::new(6, 0)));
+        assert_eq!(StreamId::new(5, 0).prev(), Some(StreamId::new(4, u64::MAX)));
+        assert_eq!(StreamId::MAX.next(), None);
+
+        let mut stream = Stream::default();
+        let f = fields(&["f", "v"]);
+        assert_eq!(
+            stream.add(XAddId::Explicit(StreamId::MIN), f.clone()),
+            Err(StreamError::ZeroId)
+        );
+        assert_eq!(
+            stream.add(XAddId::AutoSeq(0), f.clone()),
+            Ok(StreamId::new(0, 1))
+        );
+        assert_eq!(
+            stream.add(XAddId::Explicit(StreamId::new(5, 0)), f.clone()),
+            Ok(StreamId::new(5, 0))
+        );
+        assert_eq!(
+            stream.add(XAddId::AutoSeq(5), f.clone()),
+            Ok(StreamId::new(5, 1))
+        );
+        assert_eq!(
+            stream.add(XAddId::AutoSeq(4), f.clone()),
+            Err(StreamError::IdTooSmall)
+        );
+        let id = stream.add(XAddId::Auto, f).unwrap();
+        assert!(id.ms > 5 && id.seq == 0);
+        assert_eq!(stream.len(), 4);
+    }
+
+    #[test]
+    fn test_stream_range_and_trim() {
+        let backend = Backend::new();
+        for i in 1..=5 {
+            let id = XAddId::Explicit(StreamId::new(i, 0));
+            backend
+                .xadd(
+                    "s".to_string(),
+                    id,
+                    fields(&["i", &i.to_string()]),
+                    None,
+                    false,
+                )
+                .unwrap();
+        }
+        let ids = |entries: Vec<(StreamId, StreamFields)>| {
+            entries.into_iter().map(|(id, _)| id.ms).collect::<Vec<_>>()
+        };
+        let range = backend.xrange("s", StreamId::new(2, 0), StreamId::MAX, Some(2), false);
+        assert_eq!(ids(range), vec![2, 3]);
+        let range = backend.xrange("s", StreamId::MIN, StreamId::new(4, 0), None, true);
+        assert_eq!(ids(range), vec![4, 3, 2, 1]);
+
+        let trim = StreamTrim {
+            strategy: TrimStrategy::MaxLen(3),
+            limit: None,
+        };
+        assert_eq!(backend.xtrim("s", &trim), 2);
+        let trim = StreamTrim {
+            strategy: TrimStrategy::MinId(StreamId::new(5, 0)),
+            limit: Some(1),
+        };
+        assert_eq!(backend.xtrim("s", &trim), 1);
+        assert_eq!(backend.xlen("s"), 2);
+
+        let ret = backend.xadd("s".to_string(), XAddId::AutoSeq(1), vec![], None, false);
+        assert_eq!(ret, Err(StreamError::IdTooSmall));
+        let ret = backend.xadd("new".to_string(), XAddId::Auto, vec![], None, true);
+        assert_eq!(ret, Ok(None));
+        let ret = backend.xadd(
+            "new".to_string(),
+            XAddId::Explicit(StreamId::MIN),
+            vec![],
+            None,
+            false,
+        );
+        assert_eq!(ret, Err(StreamError::ZeroId));
+        assert_eq!(backend.key_type("new"), None);
+    }
+
+    #[test]
+    fn test_consumer_groups() {
+        let backend = Backend::new();
+        assert_eq!(
+            backend.xgroup_create("s", "g".to_string(), None, false),
+            Err(StreamError::NoKey)
+        );
+        backend
+            .xgroup_create("s", "g".to_string(), None, true)
+            .unwrap();
+        assert_eq!(
+            backend.xgroup_create("s", "g".to_string(), None, false),
+            Err(StreamError::BusyGroup)
+        );
+        for i in 1..=3 {
+            let id = XAddId::Explicit(StreamId::new(i, 0));
+            backend
+                .xadd("s".to_string(), id, fields(&["f", "v"]), None, false)
+                .unwrap();
+        }
+
+        let read = backend
+            .xreadgroup("s", "g", "alice", None, Some(2), false)
+            .unwrap();
+        assert_eq!(read.len(), 2);
+        let read = backend
+            .xreadgroup("s", "g", "bob", None, None, false)
+            .unwrap();
+        assert_eq!(read, vec![(StreamId::new(3, 0), Some(fields(&["f", "v"])))]);
+        assert!(backend
+            .xreadgroup("s", "g", "bob", None, None, false)
+            .unwrap()
+            .is_empty());
+
+        // the history of a consumer is its pending entries
+        let read = backend
+            .xreadgroup("s", "g", "alice", Some(StreamId::MIN), None, false)
+            .unwrap();
+        assert_eq!(read.len(), 2);
+        assert_eq!(
+            backend.xack("s", "g", &[StreamId::new(1, 0), StreamId::new(9, 0)]),
+            1
+        );
+
+        let (count, bounds, consumers) = backend
+            .with_group("s", "g", |g| g.pending_summary())
+            .unwrap();
+        assert_eq!(count, 2);
+        assert_eq!(bounds, Some((StreamId::new(2, 0), StreamId::new(3, 0))));
+        assert_eq!(
+            consumers,
+            vec![("alice".to_string(), 1), ("bob".to_string(), 1)]
+        );
+        let pending = backend
+            .with_group("s", "g", |g| {
+                g.pending_range(StreamId::MIN, StreamId::MAX, 10, 0, Some("alice"))
+            })
+            .unwrap();
+        assert_eq!(pending[0].0, StreamId::new(2, 0));
+        // delivered once, then read again from the history
+        assert_eq!(pending[0].3, 2);
+
+        let options = ClaimOptions::default();
+        let claimed = backend
+            .xclaim("s", "g", "bob", 0, &[StreamId::new(2, 0)], &options)
+            .unwrap();
+        assert_eq!(claimed.len(), 1);
+        let claimed = backend
+            .xclaim("s", "g", "bob", 60_000, &[StreamId::new(3, 0)], &options)
+            .unwrap();
+        assert!(claimed.is_empty());
+        assert_eq!(backend.xgroup_delete_consumer("s", "g", "bob"), Ok(2));
+        assert_eq!(
+            backend.xreadgroup("s", "nope", "bob", None, None, false),
+            Err(StreamError::NoGroup("s".to_string(), "nope".to_string()))
+        );
+    }
+}
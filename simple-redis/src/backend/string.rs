@@ -0,0 +1,478 @@
+use bytes::{Bytes, BytesMut};
+use dashmap::mapref::entry::Entry;
+use thiserror::Error;
+
+use crate::{now_ms, Backend};
+
+use super::list::normalize_range;
+
+// the maximum length of a string, as the proto-max-bulk-len default of redis
+const MAX_STRING_LEN: usize = 512 * 1024 * 1024;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum StringError {
+    #[error("WRONGTYPE Operation against a key holding the wrong kind of value")]
+    WrongType,
+    #[error("ERR value is not an integer or out of range")]
+    NotInteger,
+    #[error("ERR value is not a valid float")]
+    NotFloat,
+    #[error("ERR increment or decrement would overflow")]
+    Overflow,
+    #[error("ERR increment would produce NaN or Infinity")]
+    NotFinite,
+    #[error("ERR string exceeds maximum allowed size (proto-max-bulk-len)")]
+    TooLarge,
+}
+
+/// The operation of BITOP.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BitOperator {
+    And,
+    Or,
+    Xor,
+    Not,
+}
+
+/// The ttl of a string stored by SET.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SetTtl {
+    /// the key does not expire
+    Persist,
+    /// absolute unix timestamp in milliseconds, a past one removes the key
+    At(u64),
+    /// the ttl of the key is kept if the key is alive
+    Keep,
+}
+
+impl Backend {
+    /// Add delta to the integer stored at key, a missing key counting as 0. Returns the new value.
+    pub fn incr_by(&self, key: &str, delta: i64) -> Result<i64, StringError> {
+        self.update_string(key, |old| {
+            let old = match old {
+                Some(old) => parse_integer(old).ok_or(StringError::NotInteger)?,
+                None => 0,
+            };
+            let value = old.checked_add(delta).ok_or(StringError::Overflow)?;
+            Ok((Some(value.to_string().into()), value))
+        })
+    }
+
+    /// Add delta to the float stored at key, a missing key counting as 0. Returns the new value
+    /// as it is stored.
+    pub fn incr_by_float(&self, key: &str, delta: f64) -> Result<Bytes, StringError> {
+        self.update_string(key, |old| {
+            let old = match old {
+                Some(old) => parse_float(old).ok_or(StringError::NotFloat)?,
+                None => 0.0,
+            };
+            let value = old + delta;
+            if !value.is_finite() {
+                return Err(StringError::NotFinite);
+            }
+            let value = Bytes::from(value.to_string());
+            Ok((Some(value.clone()), value))
+        })
+    }
+
+    /// Append value to the string at key, creating it if needed. Returns the new length.
+    pub fn append(&self, key: &str, value: &[u8]) -> Result<usize, StringError> {
+        self.update_string(key, |old| {
+            let old = old.unwrap_or_default();
+            if old.len() + value.len() > MAX_STRING_LEN {
+                return Err(StringError::TooLarge);
+            }
+            let mut buf = BytesMut::with_capacity(old.len() + value.len());
+            buf.extend_from_slice(old);
+            buf.extend_from_slice(value);
+            Ok((Some(buf.freeze()), old.len() + value.len()))
+        })
+    }
+
+    /// The substring of the string at key between two inclusive, possibly negative, offsets.
+    pub fn getrange(&self, key: &str, start: i64, end: i64) -> Bytes {
+        match self.get(key) {
+            Some(value) => match normalize_range(start, end, value.len()) {
+                Some((start, end)) => value.slice(start..=end),
+                None => Bytes::new(),
+            },
+            None => Bytes::new(),
+        }
+    }
+
+    /// Overwrite the string at key from offset, padding it with zero bytes if it is shorter.
+    /// Returns the new length.
+    pub fn setrange(&self, key: &str, offset: usize, value: &[u8]) -> Result<usize, StringError> {
+        self.update_string(key, |old| {
+            let old = old.unwrap_or_default();
+            // an empty value leaves the string untouched, and does not create the key
+            if value.is_empty() {
+                return Ok((None, old.len()));
+            }
+            if offset + value.len() > MAX_STRING_LEN {
+                return Err(StringError::TooLarge);
+            }
+            let mut buf = BytesMut::from(old);
+            if buf.len() < offset + value.len() {
+                buf.resize(offset + value.len(), 0);
+            }
+            buf[offset..offset + value.len()].copy_from_slice(value);
+            let len = buf.len();
+            Ok((Some(buf.freeze()), len))
+        })
+    }
+
+    pub fn strlen(&self, key: &str) -> usize {
+        self.get(key).map(|v| v.len()).unwrap_or_default()
+    }
+
+    /// Remove the string at key, returning it.
+    pub fn getdel(&self, key: &str) -> Option<Bytes> {
+        self.expire_if_needed(key);
+        let (_, value) = self.keyspace().map.remove(key)?;
+        self.remove_key(key);
+        self.touch(key);
+        Some(value)
+    }
+
+    /// Set a string value as SET does with its options: `condition` is given whether the key
+    /// exists and tells if the value is stored. Returns whether it was stored along with the
+    /// previous string, an error if `get` is set and the key holds another type. The entry of the
+    /// key stays locked from the checks to the store, so that two SET NX can't both succeed.
+    pub fn set_with(
+        &self,
+        key: String,
+        value: Bytes,
+        ttl: SetTtl,
+        get: bool,
+        condition: impl FnOnce(bool) -> bool,
+    ) -> Result<(bool, Option<Bytes>), StringError> {
+        self.expire_if_needed(&key);
+        let (stored, old) = {
+            let entry = self.keyspace().map.entry(key.clone());
+            let old = match &entry {
+                Entry::Occupied(entry) => Some(entry.get().clone()),
+                Entry::Vacant(_) => None,
+            };
+            let other = old.is_none() && self.holds_other_type(&key, "string");
+            if get && other {
+                return Err(StringError::WrongType);
+            }
+            let stored = condition(old.is_some() || other);
+            if stored {
+                self.store_string(entry, &key, value, ttl);
+            }
+            (stored, old)
+        };
+        if stored {
+            self.touch(&key);
+        }
+        Ok((stored, old))
+    }
+
+    /// Set several strings at once.
+    pub fn mset(&self, pairs: Vec<(String, Bytes)>) {
+        for (key, value) in pairs {
+            self.set(key, value);
+        }
+    }
+
+    /// Set several strings at once, unless any of the keys exists. Returns whether they were set.
+    pub fn msetnx(&self, pairs: Vec<(String, Bytes)>) -> bool {
+        if pairs.iter().any(|(key, _)| self.exists(key)) {
+            return false;
+        }
+        self.mset(pairs);
+        true
+    }
+
+    /// Set or clear the bit at offset of the string at key, growing it as needed.
+    /// Returns the previous value of the bit.
+    pub fn setbit(&self, key: &str, offset: usize, bit: bool) -> Result<u8, StringError> {
+        self.update_string(key, |old| {
+            let old = old.unwrap_or_default();
+            let byte = offset / 8;
+            if byte >= MAX_STRING_LEN {
+                return Err(StringError::TooLarge);
+            }
+            let mut buf = BytesMut::from(old);
+            if buf.len() <= byte {
+                buf.resize(byte + 1, 0);
+            }
+            // bits are numbered from the most significant bit of the first byte
+            let mask = 0x80 >> (offset % 8);
+            let previous = u8::from(buf[byte] & mask != 0);
+            if bit {
+                buf[byte] |= mask;
+            } else {
+                buf[byte] &= !mask;
+            }
+            Ok((Some(buf.freeze()), previous))
+        })
+    }
+
+    pub fn getbit(&self, key: &str, offset: usize) -> u8 {
+        match self.get(key) {
+            Some(value) if offset / 8 < value.len() => {
+                u8::from(value[offset / 8] & (0x80 >> (offset % 8)) != 0)
+            }
+            _ => 0,
+        }
+    }
+
+    /// Count the bits set in the string at key, within an inclusive range of bytes, or of bits
+    /// if `bit` is set.
+    pub fn bitcount(&self, key: &str, range: Option<(i64, i64, bool)>) -> usize {
+        let Some(value) = self.get(key) else {
+            return 0;
+        };
+        match range {
+            None => count_ones(&value),
+            Some((start, end, false)) => match normalize_range(start, end, value.len()) {
+                Some((start, end)) => count_ones(&value[start..=end]),
+                None => 0,
+            },
+            Some((start, end, true)) => match normalize_range(start, end, value.len() * 8) {
+                Some((start, end)) => (start..=end)
+                    .filter(|i| value[i / 8] & (0x80 >> (i % 8)) != 0)
+                    .count(),
+                None => 0,
+            },
+        }
+    }
+
+    /// Store at dest the result of a bitwise operation between the strings at keys, missing
+    /// keys and the end of shorter strings counting as zero bytes. Returns the length of the
+    /// result, an empty result removes dest.
+    pub fn bitop(&self, op: BitOperator, dest: String, keys: &[String]) -> usize {
+        let values = keys
+            .iter()
+            .map(|key| self.get(key).unwrap_or_default())
+            .collect::<Vec<_>>();
+        let len = values.iter().map(|v| v.len()).max().unwrap_or_default();
+        let result = (0..len)
+            .map(|i| {
+                let mut bytes = values.iter().map(|v| v.get(i).copied().unwrap_or_default());
+                let first = bytes.next().unwrap_or_default();
+                match op {
+                    BitOperator::And => bytes.fold(first, |acc, b| acc & b),
+                    BitOperator::Or => bytes.fold(first, |acc, b| acc | b),
+                    BitOperator::Xor => bytes.fold(first, |acc, b| acc ^ b),
+                    BitOperator::Not => !first,
+                }
+            })
+            .collect::<Vec<_>>();
+        if result.is_empty() {
+            self.expire_if_needed(&dest);
+            self.remove_key(&dest);
+        } else {
+            self.set(dest, result.into());
+        }
+        len
+    }
+
+    // store a string in the entry of key, replacing a value of another type, the caller touches
+    // the key once the entry is released
+    pub(super) fn store_string(
+        &self,
+        entry: Entry<'_, String, Bytes>,
+        key: &str,
+        value: Bytes,
+        ttl: SetTtl,
+    ) {
+        let keyspace = self.keyspace();
+        keyspace.hmap.remove(key);
+        keyspace.list.remove(key);
+        keyspace.zset.remove(key);
+        keyspace.stream.remove(key);
+        match ttl {
+            SetTtl::Persist => {
+                keyspace.expires.remove(key);
+            }
+            SetTtl::At(at) if at <= now_ms() => {
+                keyspace.expires.remove(key);
+                if let Entry::Occupied(entry) = entry {
+                    entry.remove();
+                }
+                return;
+            }
+            SetTtl::At(at) => {
+                keyspace.expires.insert(key.to_string(), at);
+            }
+            SetTtl::Keep => {}
+        }
+        entry.insert(value);
+    }
+
+    // apply f to the string at key while holding the lock of its entry, so that concurrent
+    // updates of the key are not lost and no other type can be stored at the key between the
+    // type check and the change, then store the value f returns, if any
+    pub(super) fn update_string<T, E: From<StringError>>(
+        &self,
+        key: &str,
+        f: impl FnOnce(Option<&[u8]>) -> Result<(Option<Bytes>, T), E>,
+    ) -> Result<T, E> {
+        self.expire_if_needed(key);
+        let (stored, ret) = match self.keyspace().map.entry(key.to_string()) {
+            Entry::Occupied(mut entry) => {
+                let (value, ret) = f(Some(entry.get()))?;
+                (value.map(|v| entry.insert(v)).is_some(), ret)
+            }
+            Entry::Vacant(_) if self.holds_other_type(key, "string") => {
+                return Err(StringError::WrongType.into());
+            }
+            Entry::Vacant(entry) => {
+                let (value, ret) = f(None)?;
+                (value.map(|v| entry.insert(v)).is_some(), ret)
+            }
+        };
+        if stored {
+            self.touch(key);
+        }
+        Ok(ret)
+    }
+}
+
+/// Parse a string holding a 64 bit signed integer, in the strict format redis stores integers
+/// in: no sign other than a leading minus, no leading zeros and no spaces.
+pub(crate) fn parse_integer(value: &[u8]) -> Option<i64> {
+    let digits = value.strip_prefix(b"-").unwrap_or(value);
+    match digits {
+        [] => None,
+        [b'0'] => (value.len() == 1).then_some(0),
+        [b'0', ..] => None,
+        _ if digits.iter().all(u8::is_ascii_digit) => std::str::from_utf8(value).ok()?.parse().ok(),
+        _ => None,
+    }
+}
+
+/// Parse a string holding a finite float, spaces are not allowed.
+pub(crate) fn parse_float(value: &[u8]) -> Option<f64> {
+    let value = std::str::from_utf8(value).ok()?;
+    if value.is_empty()
+        || value.starts_with(char::is_whitespace)
+        || value.ends_with(char::is_whitespace)
+    {
+        return None;
+    }
+    value.parse::<f64>().ok().filter(|v| v.is_finite())
+}
+
+fn count_ones(bytes: &[u8]) -> usize {
+    bytes.iter().map(|b| b.count_ones() as usize).sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::BulkString;
+
+    use super::*;
+
+    #[test]
+    fn test_incr_by() {
+        let backend = Backend::new();
+        assert_eq!(backend.incr_by("n", 5), Ok(5));
+        assert_eq!(backend.incr_by("n", -7), Ok(-2));
+        assert_eq!(backend.get("n"), Some("-2".into()));
+
+        backend.set("n".to_string(), i64::MAX.to_string().into());
+        assert_eq!(backend.incr_by("n", 1), Err(StringError::Overflow));
+        backend.set("s".to_string(), "012".into());
+        assert_eq!(backend.incr_by("s", 1), Err(StringError::NotInteger));
+        assert_eq!(backend.get("s"), Some("012".into()));
+
+        assert_eq!(backend.incr_by_float("f", 10.5), Ok("10.5".into()));
+        assert_eq!(backend.incr_by_float("f", 0.1), Ok("10.6".into()));
+        assert_eq!(backend.incr_by_float("f", -5.6), Ok("5".into()));
+        assert_eq!(backend.incr_by_float("s", 1.0), Ok("13".into()));
+        assert_eq!(
+            backend.incr_by_float("f", f64::INFINITY),
+            Err(StringError::NotFinite)
+        );
+    }
+
+    #[test]
+    fn test_ranges() {
+        let backend = Backend::new();
+        assert_eq!(backend.append("s", b"Hello"), Ok(5));
+        assert_eq!(backend.append("s", b" World"), Ok(11));
+        assert_eq!(backend.getrange("s", 0, 4), Bytes::from("Hello"));
+        assert_eq!(backend.getrange("s", -3, -1), Bytes::from("rld"));
+        assert_eq!(backend.getrange("s", 5, 2), Bytes::new());
+        assert_eq!(backend.setrange("s", 6, b"Redis"), Ok(11));
+        assert_eq!(backend.get("s"), Some("Hello Redis".into()));
+
+        assert_eq!(backend.setrange("pad", 3, b"x"), Ok(4));
+        assert_eq!(backend.get("pad"), Some(Bytes::from(&b"\0\0\0x"[..])));
+        assert_eq!(backend.setrange("none", 3, b""), Ok(0));
+        assert!(!backend.exists("none"));
+
+        assert_eq!(backend.strlen("s"), 11);
+        assert_eq!(backend.getdel("s"), Some("Hello Redis".into()));
+        assert_eq!(backend.getdel("s"), None);
+    }
+
+    #[test]
+    fn test_bits() {
+        let backend = Backend::new();
+        assert_eq!(backend.setbit("b", 7, true), Ok(0));
+        assert_eq!(backend.setbit("b", 7, true), Ok(1));
+        assert_eq!(backend.get("b"), Some(Bytes::from(&b"\x01"[..])));
+        assert_eq!(backend.getbit("b", 7), 1);
+        assert_eq!(backend.getbit("b", 100), 0);
+
+        backend.set("s".to_string(), "foobar".into());
+        assert_eq!(backend.bitcount("s", None), 26);
+        assert_eq!(backend.bitcount("s", Some((1, 1, false))), 6);
+        assert_eq!(backend.bitcount("s", Some((5, 30, true))), 17);
+
+        backend.set("a".to_string(), "abc".into());
+        backend.set("c".to_string(), "a".into());
+        let keys = ["a".to_string(), "c".to_string()];
+        assert_eq!(backend.bitop(BitOperator::And, "d".to_string(), &keys), 3);
+        assert_eq!(backend.get("d"), Some(Bytes::from(&b"a\0\0"[..])));
+        assert_eq!(
+            backend.bitop(BitOperator::Not, "d".to_string(), &keys[1..]),
+            1
+        );
+        assert_eq!(backend.get("d"), Some(Bytes::from(&b"\x9e"[..])));
+        assert_eq!(
+            backend.bitop(BitOperator::Or, "d".to_string(), &["x".to_string()]),
+            0
+        );
+        assert!(!backend.exists("d"));
+    }
+
+    #[test]
+    fn test_update_wrong_type() {
+        let backend = Backend::new();
+        backend
+            .rpush("l".to_string(), vec![BulkString::new("a").into()])
+            .unwrap();
+        assert_eq!(backend.incr_by("l", 1), Err(StringError::WrongType));
+        assert_eq!(backend.incr_by_float("l", 1.0), Err(StringError::WrongType));
+        assert_eq!(backend.append("l", b"x"), Err(StringError::WrongType));
+        assert_eq!(backend.setrange("l", 0, b"x"), Err(StringError::WrongType));
+        assert_eq!(backend.setbit("l", 0, true), Err(StringError::WrongType));
+        assert_eq!(backend.key_type("l"), Some("list"));
+    }
+
+    #[test]
+    fn test_parse_integer() {
+        assert_eq!(parse_integer(b"0"), Some(0));
+        assert_eq!(parse_integer(b"-42"), Some(-42));
+        assert_eq!(parse_integer(b"-9223372036854775808"), Some(i64::MIN));
+        for invalid in [
+            &b""[..],
+            b"-",
+            b"-0",
+            b"01",
+            b"+1",
+            b" 1",
+            b"1.0",
+            b"9223372036854775808",
+        ] {
+            assert_eq!(parse_integer(invalid), None);
+        }
+    }
+}
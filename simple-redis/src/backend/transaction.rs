@@ -0,0 +1,132 @@
+use std::collections::BTreeSet;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, PoisonError, RwLockReadGuard, RwLockWriteGuard};
+
+use crate::Backend;
+
+/// The keys watched by a connection for optimistic locking. They are unwatched when it goes away.
+#[derive(Debug)]
+pub struct Watcher {
+    id: u64,
+    backend: Backend,
+    // each key with the index of its database
+    keys: BTreeSet<(usize, String)>,
+    // set when any of the watched keys is modified
+    dirty: Arc<AtomicBool>,
+}
+
+impl Backend {
+    /// Shared access to the keyspace for running a single command.
+    pub fn lock_shared(&self) -> RwLockReadGuard<'_, ()> {
+        self.exec_lock
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Exclusive access to the keyspace, e.g. for EXEC which runs several commands atomically.
+    pub fn lock_exclusive(&self) -> RwLockWriteGuard<'_, ()> {
+        self.exec_lock
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    // flag the connections watching key
+    pub(super) fn signal_modified(&self, key: &str) {
+        if let Some(watchers) = self.keyspace().watched.get(key) {
+            for dirty in watchers.values() {
+                dirty.store(true, Ordering::Release);
+            }
+        }
+    }
+}
+
+impl Watcher {
+    pub fn new(backend: Backend, id: u64) -> Self {
+        Self {
+            id,
+            backend,
+            keys: BTreeSet::new(),
+            dirty: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Watch the key of the database at index `db`.
+    pub fn watch(&mut self, db: usize, key: String) {
+        if self.keys.insert((db, key.clone())) {
+            self.backend.dbs[db]
+                .watched
+                .entry(key)
+                .or_default()
+                .insert(self.id, self.dirty.clone());
+        }
+    }
+
+    /// Forget all watched keys, as done by UNWATCH, EXEC and DISCARD.
+    pub fn unwatch(&mut self) {
+        for (db, key) in std::mem::take(&mut self.keys) {
+            let watched = &self.backend.dbs[db].watched;
+            if let Some(mut watchers) = watched.get_mut(&key) {
+                watchers.remove(&self.id);
+            }
+            watched.remove_if(&key, |_, watchers| watchers.is_empty());
+        }
+        self.dirty.store(false, Ordering::Release);
+    }
+
+    /// Whether a watched key was modified since it was watched.
+    pub fn is_dirty(&self) -> bool {
+        self.dirty.load(Ordering::Acquire)
+    }
+}
+
+impl Drop for Watcher {
+    fn drop(&mut self) {
+        self.unwatch();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::now_ms;
+
+    use super::*;
+
+    #[test]
+    fn test_watch_detects_modification() {
+        let backend = Backend::new();
+        let mut watcher = Watcher::new(backend.clone(), 1);
+        watcher.watch(0, "key".to_string());
+        assert!(!watcher.is_dirty());
+
+        backend.set("other".to_string(), "v".into());
+        assert!(!watcher.is_dirty());
+        // the same key of another database
+        let other = backend.select(1).unwrap();
+        other.set("key".to_string(), "v".into());
+        assert!(!watcher.is_dirty());
+        backend.set("key".to_string(), "v".into());
+        assert!(watcher.is_dirty());
+
+        watcher.unwatch();
+        assert!(!watcher.is_dirty());
+        assert!(backend.keyspace().watched.is_empty());
+    }
+
+    #[test]
+    fn test_watch_detects_expiration() {
+        let backend = Backend::new();
+        backend.set("key".to_string(), "v".into());
+        let mut watcher = Watcher::new(backend.clone(), 1);
+        watcher.watch(0, "key".to_string());
+
+        backend
+            .keyspace()
+            .expires
+            .insert("key".to_string(), now_ms() - 1);
+        assert!(!backend.exists("key"));
+        assert!(watcher.is_dirty());
+
+        drop(watcher);
+        assert!(backend.keyspace().watched.is_empty());
+    }
+}
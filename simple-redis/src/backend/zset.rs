@@ -0,0 +1,515 @@
+use std::cmp::Ordering;
+use std::collections::{BTreeSet, HashMap};
+use std::ops::Bound;
+
+use thiserror::Error;
+
+use crate::Backend;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum ZSetError {
+    #[error("WRONGTYPE Operation against a key holding the wrong kind of value")]
+    WrongType,
+}
+
+/// A sorted set: members ordered by score, ties broken lexicographically by member.
+#[derive(Debug, Default, Clone)]
+pub struct SortedSet {
+    scores: HashMap<String, f64>,
+    ordered: BTreeSet<ScoreMember>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct ScoreMember {
+    score: f64,
+    member: String,
+}
+
+/// A min / max score of ZRANGEBYSCORE and friends, e.g. `1.5`, `(1.5`, `-inf`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ScoreBound {
+    pub value: f64,
+    pub exclusive: bool,
+}
+
+/// Flags of ZADD which control how existing members are updated.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ZAddFlags {
+    /// only add new members
+    pub nx: bool,
+    /// only update existing members
+    pub xx: bool,
+    /// only update when the new score is greater than the current one
+    pub gt: bool,
+    /// only update when the new score is less than the current one
+    pub lt: bool,
+}
+
+impl Eq for ScoreMember {}
+
+impl Ord for ScoreMember {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.score
+            .total_cmp(&other.score)
+            .then_with(|| self.member.cmp(&other.member))
+    }
+}
+
+impl PartialOrd for ScoreMember {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl ScoreBound {
+    pub fn inclusive(value: f64) -> Self {
+        Self {
+            value,
+            exclusive: false,
+        }
+    }
+
+    fn above_min(&self, score: f64) -> bool {
+        if self.exclusive {
+            score > self.value
+        } else {
+            score >= self.value
+        }
+    }
+
+    fn below_max(&self, score: f64) -> bool {
+        if self.exclusive {
+            score < self.value
+        } else {
+            score <= self.value
+        }
+    }
+}
+
+impl SortedSet {
+    pub fn len(&self) -> usize {
+        self.scores.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.scores.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, f64)> {
+        self.scores
+            .iter()
+            .map(|(member, score)| (member.as_str(), *score))
+    }
+
+    pub fn score(&self, member: &str) -> Option<f64> {
+        self.scores.get(member).copied()
+    }
+
+    /// Insert or update a member. Returns true if the member is new.
+    pub fn insert(&mut self, member: String, score: f64) -> bool {
+        // -0.0 and 0.0 must be the same score
+        let score = score + 0.0;
+        let old = self.scores.insert(member.clone(), score);
+        if let Some(old) = old {
+            self.ordered.remove(&ScoreMember {
+                score: old,
+                member: member.clone(),
+            });
+        }
+        self.ordered.insert(ScoreMember { score, member });
+        old.is_none()
+    }
+
+    pub fn remove(&mut self, member: &str) -> bool {
+        match self.scores.remove(member) {
+            Some(score) => {
+                self.ordered.remove(&ScoreMember {
+                    score,
+                    member: member.to_string(),
+                });
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// 0-based rank of the member, in ascending order unless `rev`.
+    pub fn rank(&self, member: &str, rev: bool) -> Option<usize> {
+        let score = self.score(member)?;
+        let target = ScoreMember {
+            score,
+            member: member.to_string(),
+        };
+        let rank = self.ordered.range(..&target).count();
+        Some(if rev { self.len() - 1 - rank } else { rank })
+    }
+
+    /// Members in the inclusive rank range, negative ranks count from the end.
+    pub fn range_by_rank(&self, start: i64, stop: i64, rev: bool) -> Vec<(String, f64)> {
+        let len = self.len() as i64;
+        let start = if start < 0 {
+            (len + start).max(0)
+        } else {
+            start
+        };
+        let stop = if stop < 0 {
+            len + stop
+        } else {
+            stop.min(len - 1)
+        };
+        if start > stop || start >= len {
+            return vec![];
+        }
+        let (skip, take) = (start as usize, (stop - start + 1) as usize);
+        if rev {
+            self.collect(self.ordered.iter().rev().skip(skip).take(take))
+        } else {
+            self.collect(self.ordered.iter().skip(skip).take(take))
+        }
+    }
+
+    /// Members with a score between min and max. `limit` is (offset, count), a negative
+    /// count returns all the remaining members.
+    pub fn range_by_score(
+        &self,
+        min: ScoreBound,
+        max: ScoreBound,
+        rev: bool,
+        limit: Option<(usize, i64)>,
+    ) -> Vec<(String, f64)> {
+        let (offset, count) = limit.unwrap_or((0, -1));
+        let count = if count < 0 {
+            usize::MAX
+        } else {
+            count as usize
+        };
+        let iter = self
+            .ordered
+            .range((Bound::Included(Self::lowest(min.value)), Bound::Unbounded))
+            .skip_while(|v| !min.above_min(v.score))
+            .take_while(|v| max.below_max(v.score));
+        if rev {
+            let members = iter.collect::<Vec<_>>();
+            self.collect(members.into_iter().rev().skip(offset).take(count))
+        } else {
+            self.collect(iter.skip(offset).take(count))
+        }
+    }
+
+    pub fn count(&self, min: ScoreBound, max: ScoreBound) -> usize {
+        self.range_by_score(min, max, false, None).len()
+    }
+
+    // the smallest possible entry with the given score
+    fn lowest(score: f64) -> ScoreMember {
+        ScoreMember {
+            score,
+            member: String::new(),
+        }
+    }
+
+    fn collect<'a>(&self, iter: impl Iterator<Item = &'a ScoreMember>) -> Vec<(String, f64)> {
+        iter.map(|v| (v.member.clone(), v.score)).collect()
+    }
+}
+
+impl Backend {
+    /// Add or update members. Returns (added, changed) where changed also counts updated scores.
+    pub fn zadd(
+        &self,
+        key: String,
+        entries: Vec<(f64, String)>,
+        flags: ZAddFlags,
+    ) -> Result<(usize, usize), ZSetError> {
+        self.expire_if_needed(&key);
+        let (mut added, mut changed) = (0, 0);
+        let empty = self.update_zset(&key, |zset| {
+            for (score, member) in entries {
+                match zset.score(&member) {
+                    Some(_) if flags.nx => {}
+                    None if flags.xx => {}
+                    Some(old) => {
+                        let update = old != score
+                            && (!flags.gt || score > old)
+                            && (!flags.lt || score < old);
+                        if update {
+                            zset.insert(member, score);
+                            changed += 1;
+                        }
+                    }
+                    None => {
+                        zset.insert(member, score);
+                        added += 1;
+                        changed += 1;
+                    }
+                }
+            }
+            zset.is_empty()
+        })?;
+        if empty {
+            // e.g. ZADD XX on a missing key must not create an empty set
+            self.keyspace()
+                .zset
+                .remove_if(&key, |_, zset| zset.is_empty());
+        }
+        if changed > 0 {
+            self.touch(&key);
+        }
+        Ok((added, changed))
+    }
+
+    /// Increment the score of member by delta. Returns None if the update is prevented by
+    /// the flags, or NaN (without updating) if the result is not a number.
+    pub fn zincrby(
+        &self,
+        key: String,
+        member: String,
+        delta: f64,
+        flags: ZAddFlags,
+    ) -> Result<Option<f64>, ZSetError> {
+        self.expire_if_needed(&key);
+        let ret = self.update_zset(&key, |zset| {
+            let old = zset.score(&member);
+            let score = old.unwrap_or(0.0) + delta;
+            let allowed = match old {
+                Some(old) => !flags.nx && (!flags.gt || score > old) && (!flags.lt || score < old),
+                None => !flags.xx,
+            };
+            if score.is_nan() {
+                Some(f64::NAN)
+            } else if allowed {
+                zset.insert(member, score);
+                Some(score)
+            } else {
+                None
+            }
+        })?;
+        self.keyspace()
+            .zset
+            .remove_if(&key, |_, zset| zset.is_empty());
+        if matches!(ret, Some(score) if !score.is_nan()) {
+            self.touch(&key);
+        }
+        Ok(ret)
+    }
+
+    pub fn zrem(&self, key: &str, members: &[String]) -> Result<usize, ZSetError> {
+        self.expire_if_needed(key);
+        let (removed, empty) = match self.keyspace().zset.get_mut(key) {
+            Some(mut zset) => {
+                let removed = members.iter().filter(|m| zset.remove(m)).count();
+                (removed, zset.is_empty())
+            }
+            // there is no sorted set at key, which is an error if the key holds another type
+            None => match self.key_type(key) {
+                Some("zset") | None => (0, false),
+                Some(_) => return Err(ZSetError::WrongType),
+            },
+        };
+        if removed > 0 {
+            self.touch(key);
+        }
+        if empty {
+            self.keyspace()
+                .zset
+                .remove_if(key, |_, zset| zset.is_empty());
+            self.keyspace().expires.remove(key);
+        }
+        Ok(removed)
+    }
+
+    // apply f to the sorted set at key, a missing one being created with the key locked so that
+    // a concurrent write can't store another type between the type check and the change
+    fn update_zset<T>(
+        &self,
+        key: &str,
+        f: impl FnOnce(&mut SortedSet) -> T,
+    ) -> Result<T, ZSetError> {
+        match self.keyspace().zset.get_mut(key) {
+            Some(mut zset) => Ok(f(&mut zset)),
+            None => self
+                .create_key(key, "zset", || {
+                    f(&mut self.keyspace().zset.entry(key.to_string()).or_default())
+                })
+                .ok_or(ZSetError::WrongType),
+        }
+    }
+
+    pub fn zscore(&self, key: &str, member: &str) -> Option<f64> {
+        self.expire_if_needed(key);
+        self.keyspace()
+            .zset
+            .get(key)
+            .and_then(|zset| zset.score(member))
+    }
+
+    pub fn zcard(&self, key: &str) -> usize {
+        self.expire_if_needed(key);
+        self.keyspace()
+            .zset
+            .get(key)
+            .map(|zset| zset.len())
+            .unwrap_or_default()
+    }
+
+    pub fn zrank(&self, key: &str, member: &str, rev: bool) -> Option<usize> {
+        self.expire_if_needed(key);
+        self.keyspace()
+            .zset
+            .get(key)
+            .and_then(|zset| zset.rank(member, rev))
+    }
+
+    pub fn zrange(&self, key: &str, start: i64, stop: i64, rev: bool) -> Vec<(String, f64)> {
+        self.expire_if_needed(key);
+        self.keyspace()
+            .zset
+            .get(key)
+            .map(|zset| zset.range_by_rank(start, stop, rev))
+            .unwrap_or_default()
+    }
+
+    pub fn zrange_by_score(
+        &self,
+        key: &str,
+        min: ScoreBound,
+        max: ScoreBound,
+        rev: bool,
+        limit: Option<(usize, i64)>,
+    ) -> Vec<(String, f64)> {
+        self.expire_if_needed(key);
+        self.keyspace()
+            .zset
+            .get(key)
+            .map(|zset| zset.range_by_score(min, max, rev, limit))
+            .unwrap_or_default()
+    }
+
+    pub fn zcount(&self, key: &str, min: ScoreBound, max: ScoreBound) -> usize {
+        self.expire_if_needed(key);
+        self.keyspace()
+            .zset
+            .get(key)
+            .map(|zset| zset.count(min, max))
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn members(entries: &[(&str, f64)]) -> Vec<(String, f64)> {
+        entries.iter().map(|(m, s)| (m.to_string(), *s)).collect()
+    }
+
+    #[test]
+    fn test_sorted_set_order_and_rank() {
+        let mut zset = SortedSet::default();
+        assert!(zset.insert("b".to_string(), 1.0));
+        assert!(zset.insert("a".to_string(), 1.0));
+        assert!(zset.insert("c".to_string(), 0.5));
+        assert!(!zset.insert("c".to_string(), 2.0));
+
+        assert_eq!(
+            zset.range_by_rank(0, -1, false),
+            members(&[("a", 1.0), ("b", 1.0), ("c", 2.0)])
+        );
+        assert_eq!(zset.range_by_rank(0, 0, true), members(&[("c", 2.0)]));
+        assert_eq!(zset.rank("b", false), Some(1));
+        assert_eq!(zset.rank("a", true), Some(2));
+        assert_eq!(zset.rank("x", false), None);
+
+        assert!(zset.remove("a"));
+        assert!(!zset.remove("a"));
+        assert_eq!(zset.len(), 2);
+    }
+
+    #[test]
+    fn test_sorted_set_range_by_score() {
+        let mut zset = SortedSet::default();
+        for (i, m) in ["a", "b", "c", "d"].iter().enumerate() {
+            zset.insert(m.to_string(), i as f64);
+        }
+        let all = (
+            ScoreBound::inclusive(f64::NEG_INFINITY),
+            ScoreBound::inclusive(f64::INFINITY),
+        );
+        assert_eq!(zset.range_by_score(all.0, all.1, false, None).len(), 4);
+
+        let min = ScoreBound {
+            value: 1.0,
+            exclusive: true,
+        };
+        let max = ScoreBound::inclusive(3.0);
+        assert_eq!(
+            zset.range_by_score(min, max, false, None),
+            members(&[("c", 2.0), ("d", 3.0)])
+        );
+        assert_eq!(
+            zset.range_by_score(all.0, all.1, true, Some((1, 2))),
+            members(&[("c", 2.0), ("b", 1.0)])
+        );
+        assert_eq!(zset.count(min, max), 2);
+    }
+
+    #[test]
+    fn test_zadd_flags() {
+        let backend = Backend::new();
+        let entries = vec![(1.0, "a".to_string()), (2.0, "b".to_string())];
+        assert_eq!(
+            backend.zadd("z".to_string(), entries, ZAddFlags::default()),
+            Ok((2, 2))
+        );
+
+        let flags = ZAddFlags {
+            gt: true,
+            ..Default::default()
+        };
+        let entries = vec![(0.5, "a".to_string()), (3.0, "b".to_string())];
+        assert_eq!(backend.zadd("z".to_string(), entries, flags), Ok((0, 1)));
+        assert_eq!(backend.zscore("z", "a"), Some(1.0));
+        assert_eq!(backend.zscore("z", "b"), Some(3.0));
+
+        let flags = ZAddFlags {
+            xx: true,
+            ..Default::default()
+        };
+        let entries = vec![(1.0, "a".to_string())];
+        assert_eq!(
+            backend.zadd("missing".to_string(), entries, flags),
+            Ok((0, 0))
+        );
+        assert_eq!(backend.key_type("missing"), None);
+
+        assert_eq!(
+            backend.zincrby("z".to_string(), "a".to_string(), 2.5, ZAddFlags::default()),
+            Ok(Some(3.5))
+        );
+        assert_eq!(
+            backend.zrem("z", &["a".to_string(), "b".to_string()]),
+            Ok(2)
+        );
+        assert_eq!(backend.key_type("z"), None);
+    }
+
+    #[test]
+    fn test_zset_wrong_type() {
+        let backend = Backend::new();
+        backend.set("s".to_string(), "v".into());
+        let entries = vec![(1.0, "a".to_string())];
+        assert_eq!(
+            backend.zadd("s".to_string(), entries, ZAddFlags::default()),
+            Err(ZSetError::WrongType)
+        );
+        assert_eq!(
+            backend.zincrby("s".to_string(), "a".to_string(), 1.0, ZAddFlags::default()),
+            Err(ZSetError::WrongType)
+        );
+        assert_eq!(
+            backend.zrem("s", &["a".to_string()]),
+            Err(ZSetError::WrongType)
+        );
+        assert_eq!(backend.key_type("s"), Some("string"));
+    }
+}
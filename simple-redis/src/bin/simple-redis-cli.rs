@@ -0,0 +1,364 @@
+use std::io::Write;
+use std::path::PathBuf;
+
+use anyhow::{anyhow, Result};
+use bytes::Bytes;
+use clap::{ArgAction, Parser};
+use rustyline::error::ReadlineError;
+use rustyline::DefaultEditor;
+
+use simple_redis::client::{Client, ClientError};
+use simple_redis::{RespEncode, RespFrame};
+
+const HISTORY_FILE: &str = ".simple_redis_cli_history";
+
+/// An interactive client for simple-redis. A command given as arguments is run once
+/// instead of starting the prompt.
+#[derive(Debug, Parser)]
+#[command(version, about, disable_help_flag = true)]
+struct Args {
+    #[arg(short = 'h', long, default_value = "127.0.0.1")]
+    host: String,
+    #[arg(short, long, default_value_t = 6379)]
+    port: u16,
+    /// password of the default user, or of --user
+    #[arg(short = 'a', long = "pass")]
+    password: Option<String>,
+    #[arg(long)]
+    user: Option<String>,
+    /// database to select once connected
+    #[arg(short = 'n', default_value_t = 0)]
+    db: usize,
+    /// switch the connection to RESP3
+    #[arg(short = '3')]
+    resp3: bool,
+    /// print the replies as the RESP frames sent by the server
+    #[arg(long)]
+    raw: bool,
+    /// Print help
+    #[arg(long, action = ArgAction::Help)]
+    help: Option<bool>,
+    #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+    command: Vec<String>,
+}
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> Result<()> {
+    let args = Args::parse();
+    let mut client = match connect(&args).await {
+        Ok(client) => Some(client),
+        Err(e) => {
+            eprintln!(
+                "Could not connect to simple-redis at {}:{}: {}",
+                args.host, args.port, e
+            );
+            std::process::exit(1);
+        }
+    };
+    if !args.command.is_empty() {
+        let command = args.command.iter().map(|arg| Bytes::from(arg.clone()));
+        run(&args, &mut client, command.collect()).await?;
+        return Ok(());
+    }
+
+    let mut editor = DefaultEditor::new()?;
+    let history = std::env::var_os("HOME").map(|home| PathBuf::from(home).join(HISTORY_FILE));
+    if let Some(path) = &history {
+        // there is no history on the first run
+        let _ = editor.load_history(path);
+    }
+    let mut db = args.db;
+    loop {
+        let prompt = match (&client, db) {
+            (None, _) => "not connected> ".to_string(),
+            (Some(_), 0) => format!("{}:{}> ", args.host, args.port),
+            (Some(_), db) => format!("{}:{}[{}]> ", args.host, args.port, db),
+        };
+        let line = match editor.readline(&prompt) {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted | ReadlineError::Eof) => break,
+            Err(e) => return Err(e.into()),
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        editor.add_history_entry(line.as_str())?;
+        let command = match split_line(&line) {
+            Ok(command) => command,
+            Err(e) => {
+                println!("{}", e);
+                continue;
+            }
+        };
+        let name = String::from_utf8_lossy(&command[0]).to_ascii_lowercase();
+        match name.as_str() {
+            "quit" | "exit" => break,
+            "select" if client.is_some() => {
+                let selected = std::str::from_utf8(&command[command.len() - 1])?.parse();
+                // the prompt shows the database once the server accepted it
+                if run(&args, &mut client, command.clone()).await? {
+                    db = selected.unwrap_or(db);
+                }
+            }
+            _ => {
+                run(&args, &mut client, command).await?;
+            }
+        }
+    }
+    if let Some(path) = &history {
+        editor.save_history(path)?;
+    }
+    Ok(())
+}
+
+// connect and prepare the connection as asked by the flags
+async fn connect(args: &Args) -> Result<Client, ClientError> {
+    let mut client = Client::connect((args.host.as_str(), args.port)).await?;
+    match (&args.user, &args.password) {
+        (Some(user), Some(password)) => client.auth_user(user, password).await?,
+        (None, Some(password)) => client.auth(password).await?,
+        _ => {}
+    }
+    if args.resp3 {
+        client.hello(3).await?;
+    }
+    if args.db != 0 {
+        client.select(args.db).await?;
+    }
+    Ok(client)
+}
+
+// run a command and print its reply, true unless it is an error, a lost connection is
+// established again on the next command
+async fn run(args: &Args, client: &mut Option<Client>, command: Vec<Bytes>) -> Result<bool> {
+    if client.is_none() {
+        match connect(args).await {
+            Ok(connected) => *client = Some(connected),
+            Err(e) => {
+                println!("Could not connect: {}", e);
+                return Ok(false);
+            }
+        }
+    }
+    let Some(conn) = client else {
+        return Ok(false);
+    };
+    let name = String::from_utf8_lossy(&command[0]).to_ascii_lowercase();
+    let error = match conn.request(command).await {
+        // the connection then streams frames until it is closed
+        Ok(frame) if matches!(name.as_str(), "subscribe" | "psubscribe" | "monitor") => {
+            print_reply(frame, args.raw)?;
+            loop {
+                match conn.read().await {
+                    Ok(frame) => print_reply(frame, args.raw)?,
+                    Err(e) => break e,
+                }
+            }
+        }
+        Ok(frame) => {
+            let accepted = !matches!(frame, RespFrame::Error(_));
+            print_reply(frame, args.raw)?;
+            return Ok(accepted);
+        }
+        Err(e) => e,
+    };
+    println!("Error: {}", error);
+    if matches!(error, ClientError::Io(_) | ClientError::Closed) {
+        *client = None;
+    }
+    Ok(false)
+}
+
+fn print_reply(frame: RespFrame, raw: bool) -> std::io::Result<()> {
+    let mut stdout = std::io::stdout().lock();
+    match raw {
+        true => stdout.write_all(&frame.encode())?,
+        false => writeln!(stdout, "{}", format_reply(frame))?,
+    }
+    stdout.flush()
+}
+
+// a reply as redis-cli shows it, nested arrays are indented under their index
+fn format_reply(frame: RespFrame) -> String {
+    match frame {
+        RespFrame::SimpleString(s) => s.as_str().to_string(),
+        RespFrame::Error(e) => format!("(error) {}", *e),
+        RespFrame::Integer(n) => format!("(integer) {}", n),
+        RespFrame::BulkString(s) => match &*s {
+            Some(bytes) => quote(bytes),
+            None => "(nil)".to_string(),
+        },
+        RespFrame::Null(_) => "(nil)".to_string(),
+        RespFrame::Boolean(b) => format!("({})", b),
+        RespFrame::Double(n) => format!("(double) {}", n),
+        RespFrame::Array(items) => match &*items {
+            Some(items) => format_items(items.clone()),
+            None => "(nil)".to_string(),
+        },
+        RespFrame::Set(items) => format_items(items.to_vec()),
+        RespFrame::Push(items) => format_items(items.to_vec()),
+        RespFrame::Map(map) if map.is_empty() => "(empty hash)".to_string(),
+        RespFrame::Map(map) => {
+            let items = map.iter().map(|(key, value)| {
+                format!(
+                    "{} => {}",
+                    quote(key.as_bytes()),
+                    format_reply(value.clone())
+                )
+            });
+            indent(items.collect(), "#")
+        }
+    }
+}
+
+fn format_items(items: Vec<RespFrame>) -> String {
+    match items.is_empty() {
+        true => "(empty array)".to_string(),
+        false => indent(items.into_iter().map(format_reply).collect(), ")"),
+    }
+}
+
+fn indent(items: Vec<String>, marker: &str) -> String {
+    let width = items.len().to_string().len();
+    let mut lines = Vec::new();
+    for (i, item) in items.iter().enumerate() {
+        let prefix = format!("{:>width$}{} ", i + 1, marker, width = width);
+        for (j, line) in item.lines().enumerate() {
+            match j {
+                0 => lines.push(format!("{}{}", prefix, line)),
+                _ => lines.push(format!("{:pad$}{}", "", line, pad = prefix.len())),
+            }
+        }
+    }
+    lines.join("\n")
+}
+
+// a bulk string in double quotes, with the bytes which are not printable escaped
+fn quote(bytes: &[u8]) -> String {
+    let mut quoted = String::from("\"");
+    for &b in bytes {
+        match b {
+            b'\\' => quoted.push_str("\\\\"),
+            b'"' => quoted.push_str("\\\""),
+            b'\n' => quoted.push_str("\\n"),
+            b'\r' => quoted.push_str("\\r"),
+            b'\t' => quoted.push_str("\\t"),
+            b if b.is_ascii_graphic() || b == b' ' => quoted.push(b as char),
+            b => quoted.push_str(&format!("\\x{:02x}", b)),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+// split a line into arguments, double quoted ones take the same escapes as `quote` prints
+fn split_line(line: &str) -> Result<Vec<Bytes>> {
+    let invalid = || anyhow!("Invalid argument(s)");
+    let mut args = Vec::new();
+    let mut chars = line.chars().peekable();
+    loop {
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        let Some(first) = chars.next() else {
+            break;
+        };
+        let mut arg = Vec::new();
+        match first {
+            '"' => loop {
+                match chars.next().ok_or_else(invalid)? {
+                    '"' => break,
+                    '\\' => match chars.next().ok_or_else(invalid)? {
+                        'n' => arg.push(b'\n'),
+                        'r' => arg.push(b'\r'),
+                        't' => arg.push(b'\t'),
+                        'x' => {
+                            let hex = [chars.next(), chars.next()]
+                                .into_iter()
+                                .collect::<Option<String>>()
+                                .ok_or_else(invalid)?;
+                            arg.push(u8::from_str_radix(&hex, 16).map_err(|_| invalid())?);
+                        }
+                        c => push_char(&mut arg, c),
+                    },
+                    c => push_char(&mut arg, c),
+                }
+            },
+            '\'' => loop {
+                match chars.next().ok_or_else(invalid)? {
+                    '\'' => break,
+                    '\\' if chars.peek() == Some(&'\'') => {
+                        chars.next();
+                        arg.push(b'\'');
+                    }
+                    c => push_char(&mut arg, c),
+                }
+            },
+            c => {
+                push_char(&mut arg, c);
+                while let Some(c) = chars.next_if(|c| !c.is_whitespace()) {
+                    push_char(&mut arg, c);
+                }
+                args.push(arg.into());
+                continue;
+            }
+        }
+        // a closing quote must end the argument
+        if chars.peek().is_some_and(|c| !c.is_whitespace()) {
+            return Err(invalid());
+        }
+        args.push(arg.into());
+    }
+    Ok(args)
+}
+
+fn push_char(arg: &mut Vec<u8>, c: char) {
+    arg.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+    use simple_redis::{Array, BulkString, SimpleError};
+
+    use super::*;
+
+    #[test]
+    fn test_split_line() -> Result<()> {
+        assert_eq!(
+            split_line(r#"  set "a key" 'it\'s' "\x00\n" "#)?,
+            vec![
+                Bytes::from("set"),
+                Bytes::from("a key"),
+                Bytes::from("it's"),
+                Bytes::from(&b"\x00\n"[..])
+            ]
+        );
+        assert!(split_line(r#"get "key"#).is_err());
+        assert!(split_line(r#"get "a"b"#).is_err());
+        assert!(split_line("   ")?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_format_reply() {
+        let items: Vec<RespFrame> = (1..=10)
+            .map(|i| BulkString::new(format!("v{}", i)).into())
+            .collect();
+        let nested = Array::new(vec![
+            RespFrame::Integer(1),
+            Array::new(vec![
+                BulkString::new("a\"b").into(),
+                BulkString::none().into(),
+            ])
+            .into(),
+        ]);
+        assert_eq!(
+            format_reply(nested.into()),
+            "1) (integer) 1\n2) 1) \"a\\\"b\"\n   2) (nil)"
+        );
+        assert!(format_reply(Array::new(items).into()).starts_with(" 1) \"v1\"\n 2) \"v2\""));
+        assert_eq!(format_reply(Array::new(vec![]).into()), "(empty array)");
+        assert_eq!(
+            format_reply(SimpleError::new("ERR unknown").into()),
+            "(error) ERR unknown"
+        );
+    }
+}
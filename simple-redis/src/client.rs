@@ -0,0 +1,784 @@
+use std::collections::HashMap;
+
+use bytes::Bytes;
+use futures::SinkExt;
+use thiserror::Error;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::{TcpStream, ToSocketAddrs};
+use tokio_stream::StreamExt;
+use tokio_util::codec::Framed;
+
+use crate::network::RespFrameCodec;
+use crate::{Array, BulkString, PubSubMessage, RespFrame};
+
+#[derive(Error, Debug)]
+pub enum ClientError {
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Protocol error: {0}")]
+    Protocol(String),
+    /// an error reply, such as "WRONGTYPE ..."
+    #[error("{0}")]
+    Server(String),
+    #[error("Unexpected reply: {0:?}")]
+    UnexpectedReply(RespFrame),
+    #[error("Connection closed by server")]
+    Closed,
+}
+
+/// The arguments of a request, each of them sent as a bulk string. Tuples mix arguments
+/// of different types, such as `("set", key, 10)`.
+pub trait ToArgs {
+    fn write_args(&self, args: &mut Vec<RespFrame>);
+}
+
+/// Conversion of a reply to the value returned by a typed command.
+pub trait FromReply: Sized {
+    fn from_reply(frame: RespFrame) -> Result<Self, ClientError>;
+}
+
+/// An async connection to a redis compatible server, over TCP or any other stream.
+#[derive(Debug)]
+pub struct Client<S = TcpStream> {
+    framed: Framed<S, RespFrameCodec>,
+}
+
+/// Requests sent at once, their replies are read in order after all of them were written.
+#[derive(Debug, Default)]
+pub struct Pipeline {
+    requests: Vec<RespFrame>,
+}
+
+/// A connection in subscribed mode, which receives the messages published to its channels
+/// and patterns.
+#[derive(Debug)]
+pub struct Subscription<S = TcpStream> {
+    client: Client<S>,
+}
+
+impl Client<TcpStream> {
+    /// Connect to the server at `addr`.
+    pub async fn connect(addr: impl ToSocketAddrs) -> Result<Self, ClientError> {
+        let stream = TcpStream::connect(addr).await?;
+        // requests are flushed once written, there is no point in delaying them
+        stream.set_nodelay(true)?;
+        Ok(Self::new(stream))
+    }
+}
+
+impl<S> Client<S>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    /// A client over an established connection, such as a TLS stream.
+    pub fn new(stream: S) -> Self {
+        Self {
+            framed: Framed::new(stream, RespFrameCodec::new(false)),
+        }
+    }
+
+    /// Send a request without waiting for its reply.
+    pub async fn send(&mut self, args: impl ToArgs) -> Result<(), ClientError> {
+        self.framed.send(request(args)).await.map_err(codec_error)
+    }
+
+    /// The next frame sent by the server: the reply to a request, or what MONITOR streams.
+    pub async fn read(&mut self) -> Result<RespFrame, ClientError> {
+        match self.framed.next().await {
+            Some(frame) => frame.map_err(codec_error),
+            None => Err(ClientError::Closed),
+        }
+    }
+
+    /// Send a request and return its reply as is, an error reply included.
+    pub async fn request(&mut self, args: impl ToArgs) -> Result<RespFrame, ClientError> {
+        self.send(args).await?;
+        self.read().await
+    }
+
+    /// Send a request and convert its reply, an error reply becomes [`ClientError::Server`].
+    pub async fn call<T: FromReply>(&mut self, args: impl ToArgs) -> Result<T, ClientError> {
+        T::from_reply(self.request(args).await?)
+    }
+
+    /// Write all the requests of the pipeline, then read their replies, error replies included.
+    pub async fn pipeline(&mut self, pipeline: Pipeline) -> Result<Vec<RespFrame>, ClientError> {
+        let count = pipeline.requests.len();
+        for frame in pipeline.requests {
+            self.framed.feed(frame).await.map_err(codec_error)?;
+        }
+        self.framed.flush().await.map_err(codec_error)?;
+        let mut replies = Vec::with_capacity(count);
+        for _ in 0..count {
+            replies.push(self.read().await?);
+        }
+        Ok(replies)
+    }
+
+    /// Subscribe to `channels`, the connection is then only used to receive messages.
+    pub async fn subscribe(
+        mut self,
+        channels: impl ToArgs,
+    ) -> Result<Subscription<S>, ClientError> {
+        self.send(("subscribe", channels)).await?;
+        Ok(Subscription { client: self })
+    }
+
+    /// Subscribe to the channels matching `patterns`.
+    pub async fn psubscribe(
+        mut self,
+        patterns: impl ToArgs,
+    ) -> Result<Subscription<S>, ClientError> {
+        self.send(("psubscribe", patterns)).await?;
+        Ok(Subscription { client: self })
+    }
+
+    /// SET key value EX seconds
+    pub async fn set_ex(
+        &mut self,
+        key: &str,
+        value: impl ToArgs,
+        seconds: u64,
+    ) -> Result<(), ClientError> {
+        self.call(("set", key, value, "ex", seconds)).await
+    }
+
+    /// SET key value NX, false if the key already exists.
+    pub async fn set_nx(&mut self, key: &str, value: impl ToArgs) -> Result<bool, ClientError> {
+        self.call(("set", key, value, "nx")).await
+    }
+}
+
+impl Pipeline {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Queue a request.
+    pub fn add(&mut self, args: impl ToArgs) -> &mut Self {
+        self.requests.push(request(args));
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.requests.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.requests.is_empty()
+    }
+}
+
+impl<S> Subscription<S>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    /// The next message, or the confirmation of a (un)subscribe request of this connection.
+    pub async fn next_message(&mut self) -> Result<PubSubMessage, ClientError> {
+        let frame = self.client.read().await?;
+        pubsub_message(frame)
+    }
+
+    pub async fn subscribe(&mut self, channels: impl ToArgs) -> Result<(), ClientError> {
+        self.client.send(("subscribe", channels)).await
+    }
+
+    pub async fn psubscribe(&mut self, patterns: impl ToArgs) -> Result<(), ClientError> {
+        self.client.send(("psubscribe", patterns)).await
+    }
+
+    /// Unsubscribe from `channels`, or from all the channels when empty.
+    pub async fn unsubscribe(&mut self, channels: impl ToArgs) -> Result<(), ClientError> {
+        self.client.send(("unsubscribe", channels)).await
+    }
+
+    /// Unsubscribe from `patterns`, or from all the patterns when empty.
+    pub async fn punsubscribe(&mut self, patterns: impl ToArgs) -> Result<(), ClientError> {
+        self.client.send(("punsubscribe", patterns)).await
+    }
+}
+
+// a typed helper per command, the arguments of the request are given as a tuple
+macro_rules! commands {
+    ($($(#[$doc:meta])* $method:ident($($arg:ident: $ty:ty),*) -> $ret:ty = $args:expr;)*) => {
+        impl<S> Client<S>
+        where
+            S: AsyncRead + AsyncWrite + Unpin,
+        {
+            $(
+                $(#[$doc])*
+                pub async fn $method(&mut self, $($arg: $ty),*) -> Result<$ret, ClientError> {
+                    self.call($args).await
+                }
+            )*
+        }
+    };
+}
+
+commands! {
+    // connection
+    ping() -> String = ("ping",);
+    echo(message: &str) -> Bytes = ("echo", message);
+    auth(password: &str) -> () = ("auth", password);
+    /// AUTH username password, for an acl user.
+    auth_user(username: &str, password: &str) -> () = ("auth", username, password);
+    /// HELLO protover, replies are RESP3 frames once switched to 3.
+    hello(protover: i64) -> RespFrame = ("hello", protover);
+    select(db: usize) -> () = ("select", db);
+    client_id() -> i64 = ("client", "id");
+    client_getname() -> Option<String> = ("client", "getname");
+    client_setname(name: &str) -> () = ("client", "setname", name);
+    client_list() -> String = ("client", "list");
+
+    // keys
+    del(keys: &[&str]) -> i64 = ("del", keys);
+    exists(keys: &[&str]) -> i64 = ("exists", keys);
+    expire(key: &str, seconds: i64) -> bool = ("expire", key, seconds);
+    expireat(key: &str, timestamp: i64) -> bool = ("expireat", key, timestamp);
+    pexpire(key: &str, milliseconds: i64) -> bool = ("pexpire", key, milliseconds);
+    pexpireat(key: &str, timestamp: i64) -> bool = ("pexpireat", key, timestamp);
+    persist(key: &str) -> bool = ("persist", key);
+    ttl(key: &str) -> i64 = ("ttl", key);
+    pttl(key: &str) -> i64 = ("pttl", key);
+    /// TYPE key
+    key_type(key: &str) -> String = ("type", key);
+    rename(key: &str, newkey: &str) -> () = ("rename", key, newkey);
+    renamenx(key: &str, newkey: &str) -> bool = ("renamenx", key, newkey);
+    keys(pattern: &str) -> Vec<String> = ("keys", pattern);
+    /// SCAN cursor MATCH pattern, the next cursor and a batch of keys.
+    scan(cursor: u64, pattern: &str) -> (u64, Vec<String>) = ("scan", cursor, "match", pattern);
+    object_encoding(key: &str) -> Option<String> = ("object", "encoding", key);
+
+    // strings
+    get(key: &str) -> Option<Bytes> = ("get", key);
+    set(key: &str, value: impl ToArgs) -> () = ("set", key, value);
+    getdel(key: &str) -> Option<Bytes> = ("getdel", key);
+    getrange(key: &str, start: i64, end: i64) -> Bytes = ("getrange", key, start, end);
+    setrange(key: &str, offset: usize, value: impl ToArgs) -> i64 = ("setrange", key, offset, value);
+    append(key: &str, value: impl ToArgs) -> i64 = ("append", key, value);
+    strlen(key: &str) -> i64 = ("strlen", key);
+    incr(key: &str) -> i64 = ("incr", key);
+    incrby(key: &str, increment: i64) -> i64 = ("incrby", key, increment);
+    incrbyfloat(key: &str, increment: f64) -> f64 = ("incrbyfloat", key, increment);
+    decr(key: &str) -> i64 = ("decr", key);
+    decrby(key: &str, decrement: i64) -> i64 = ("decrby", key, decrement);
+    mget(keys: &[&str]) -> Vec<Option<Bytes>> = ("mget", keys);
+    mset(pairs: &[(&str, &str)]) -> () = ("mset", pairs);
+    msetnx(pairs: &[(&str, &str)]) -> bool = ("msetnx", pairs);
+    getbit(key: &str, offset: u64) -> i64 = ("getbit", key, offset);
+    setbit(key: &str, offset: u64, value: i64) -> i64 = ("setbit", key, offset, value);
+    bitcount(key: &str) -> i64 = ("bitcount", key);
+    bitop(operation: &str, destkey: &str, keys: &[&str]) -> i64 = ("bitop", operation, destkey, keys);
+
+    // hashes
+    hget(key: &str, field: &str) -> Option<Bytes> = ("hget", key, field);
+    hset(key: &str, field: &str, value: impl ToArgs) -> i64 = ("hset", key, field, value);
+    hmset(key: &str, pairs: &[(&str, &str)]) -> () = ("hmset", key, pairs);
+    hmget(key: &str, fields: &[&str]) -> Vec<Option<Bytes>> = ("hmget", key, fields);
+    hgetall(key: &str) -> HashMap<String, Bytes> = ("hgetall", key);
+    hscan(key: &str, cursor: u64) -> (u64, Vec<Bytes>) = ("hscan", key, cursor);
+
+    // lists
+    lpush(key: &str, elements: &[&str]) -> i64 = ("lpush", key, elements);
+    rpush(key: &str, elements: &[&str]) -> i64 = ("rpush", key, elements);
+    lpushx(key: &str, elements: &[&str]) -> i64 = ("lpushx", key, elements);
+    rpushx(key: &str, elements: &[&str]) -> i64 = ("rpushx", key, elements);
+    lpop(key: &str) -> Option<Bytes> = ("lpop", key);
+    rpop(key: &str) -> Option<Bytes> = ("rpop", key);
+    /// BLPOP key [key ...] timeout, the key and the element, None on timeout.
+    blpop(keys: &[&str], timeout: f64) -> Option<(String, Bytes)> = ("blpop", keys, timeout);
+    brpop(keys: &[&str], timeout: f64) -> Option<(String, Bytes)> = ("brpop", keys, timeout);
+    lrange(key: &str, start: i64, stop: i64) -> Vec<Bytes> = ("lrange", key, start, stop);
+    llen(key: &str) -> i64 = ("llen", key);
+    lindex(key: &str, index: i64) -> Option<Bytes> = ("lindex", key, index);
+    lset(key: &str, index: i64, element: &str) -> () = ("lset", key, index, element);
+    lrem(key: &str, count: i64, element: &str) -> i64 = ("lrem", key, count, element);
+    ltrim(key: &str, start: i64, stop: i64) -> () = ("ltrim", key, start, stop);
+
+    // sorted sets
+    zadd(key: &str, members: &[(f64, &str)]) -> i64 = ("zadd", key, members);
+    zscore(key: &str, member: &str) -> Option<f64> = ("zscore", key, member);
+    zincrby(key: &str, increment: f64, member: &str) -> f64 = ("zincrby", key, increment, member);
+    zcard(key: &str) -> i64 = ("zcard", key);
+    /// ZCOUNT key min max, the bounds as redis takes them, such as "-inf" or "(1".
+    zcount(key: &str, min: &str, max: &str) -> i64 = ("zcount", key, min, max);
+    zrange(key: &str, start: i64, stop: i64) -> Vec<String> = ("zrange", key, start, stop);
+    zrevrange(key: &str, start: i64, stop: i64) -> Vec<String> = ("zrevrange", key, start, stop);
+    zrangebyscore(key: &str, min: &str, max: &str) -> Vec<String> = ("zrangebyscore", key, min, max);
+    zrevrangebyscore(key: &str, max: &str, min: &str) -> Vec<String> = ("zrevrangebyscore", key, max, min);
+    zrank(key: &str, member: &str) -> Option<i64> = ("zrank", key, member);
+    zrevrank(key: &str, member: &str) -> Option<i64> = ("zrevrank", key, member);
+    zrem(key: &str, members: &[&str]) -> i64 = ("zrem", key, members);
+
+    // streams
+    /// XADD key id field value [field value ...], the id of the new entry.
+    xadd(key: &str, id: &str, fields: &[(&str, &str)]) -> String = ("xadd", key, id, fields);
+    xlen(key: &str) -> i64 = ("xlen", key);
+    /// XRANGE key start end, each entry as its id and its fields and values.
+    xrange(key: &str, start: &str, end: &str) -> Vec<(String, Vec<Bytes>)> = ("xrange", key, start, end);
+    xrevrange(key: &str, end: &str, start: &str) -> Vec<(String, Vec<Bytes>)> = ("xrevrange", key, end, start);
+    xtrim(key: &str, maxlen: usize) -> i64 = ("xtrim", key, "maxlen", maxlen);
+    /// XREAD STREAMS key [key ...] id [id ...]
+    xread(keys: &[&str], ids: &[&str]) -> RespFrame = ("xread", "streams", keys, ids);
+    xgroup_create(key: &str, group: &str, id: &str) -> () = ("xgroup", "create", key, group, id);
+    /// XREADGROUP GROUP group consumer STREAMS key [key ...] id [id ...]
+    xreadgroup(group: &str, consumer: &str, keys: &[&str], ids: &[&str]) -> RespFrame =
+        ("xreadgroup", "group", group, consumer, "streams", keys, ids);
+    xack(key: &str, group: &str, ids: &[&str]) -> i64 = ("xack", key, group, ids);
+    xpending(key: &str, group: &str) -> RespFrame = ("xpending", key, group);
+    xclaim(key: &str, group: &str, consumer: &str, min_idle_time: u64, ids: &[&str]) -> RespFrame =
+        ("xclaim", key, group, consumer, min_idle_time, ids);
+
+    // geo
+    /// GEOADD key longitude latitude member [...]
+    geoadd(key: &str, members: &[(f64, f64, &str)]) -> i64 = ("geoadd", key, members);
+    geodist(key: &str, member1: &str, member2: &str, unit: &str) -> Option<f64> =
+        ("geodist", key, member1, member2, unit);
+    /// GEOPOS key member [member ...], the longitude and latitude of each member.
+    geopos(key: &str, members: &[&str]) -> Vec<Option<(f64, f64)>> = ("geopos", key, members);
+    geohash(key: &str, members: &[&str]) -> Vec<Option<String>> = ("geohash", key, members);
+    /// GEOSEARCH key FROMLONLAT longitude latitude BYRADIUS radius unit ASC
+    geosearch_radius(key: &str, longitude: f64, latitude: f64, radius: f64, unit: &str) -> Vec<String> =
+        ("geosearch", key, "fromlonlat", longitude, latitude, "byradius", radius, unit, "asc");
+
+    // hyperloglog
+    pfadd(key: &str, elements: &[&str]) -> bool = ("pfadd", key, elements);
+    pfcount(keys: &[&str]) -> i64 = ("pfcount", keys);
+    pfmerge(destkey: &str, keys: &[&str]) -> () = ("pfmerge", destkey, keys);
+
+    // pub/sub
+    publish(channel: &str, message: impl ToArgs) -> i64 = ("publish", channel, message);
+    pubsub_channels(pattern: &str) -> Vec<String> = ("pubsub", "channels", pattern);
+    pubsub_numsub(channels: &[&str]) -> HashMap<String, i64> = ("pubsub", "numsub", channels);
+    pubsub_numpat() -> i64 = ("pubsub", "numpat");
+
+    // transactions, the commands queued in between reply with QUEUED, send them with `call`
+    multi() -> () = ("multi",);
+    /// EXEC, the replies of the queued commands, None if a watched key changed.
+    exec() -> Option<Vec<RespFrame>> = ("exec",);
+    discard() -> () = ("discard",);
+    watch(keys: &[&str]) -> () = ("watch", keys);
+    unwatch() -> () = ("unwatch",);
+
+    // scripting
+    eval(script: &str, keys: &[&str], args: &[&str]) -> RespFrame = ("eval", script, keys.len(), keys, args);
+    evalsha(sha1: &str, keys: &[&str], args: &[&str]) -> RespFrame = ("evalsha", sha1, keys.len(), keys, args);
+    script_load(script: &str) -> String = ("script", "load", script);
+    script_exists(sha1s: &[&str]) -> Vec<bool> = ("script", "exists", sha1s);
+    script_flush() -> () = ("script", "flush");
+
+    // server
+    dbsize() -> i64 = ("dbsize",);
+    flushdb() -> () = ("flushdb",);
+    flushall() -> () = ("flushall",);
+    info(section: &str) -> String = ("info", section);
+    lastsave() -> i64 = ("lastsave",);
+    save() -> () = ("save",);
+    bgsave() -> String = ("bgsave",);
+    bgrewriteaof() -> String = ("bgrewriteaof",);
+    config_get(parameter: &str) -> HashMap<String, String> = ("config", "get", parameter);
+    config_set(parameter: &str, value: &str) -> () = ("config", "set", parameter, value);
+    config_rewrite() -> () = ("config", "rewrite");
+    slowlog_get(count: usize) -> RespFrame = ("slowlog", "get", count);
+    slowlog_len() -> i64 = ("slowlog", "len");
+    slowlog_reset() -> () = ("slowlog", "reset");
+    command_count() -> i64 = ("command", "count");
+    acl_whoami() -> String = ("acl", "whoami");
+    acl_users() -> Vec<String> = ("acl", "users");
+    acl_setuser(username: &str, rules: &[&str]) -> () = ("acl", "setuser", username, rules);
+    acl_deluser(usernames: &[&str]) -> i64 = ("acl", "deluser", usernames);
+    replicaof(host: &str, port: u16) -> () = ("replicaof", host, port);
+    /// REPLICAOF NO ONE
+    replicaof_no_one() -> () = ("replicaof", "no", "one");
+    cluster_info() -> String = ("cluster", "info");
+    cluster_nodes() -> String = ("cluster", "nodes");
+    cluster_keyslot(key: &str) -> i64 = ("cluster", "keyslot", key);
+}
+
+fn request(args: impl ToArgs) -> RespFrame {
+    let mut frames = Vec::new();
+    args.write_args(&mut frames);
+    Array::new(frames).into()
+}
+
+// the codec reports i/o errors through anyhow
+fn codec_error(e: anyhow::Error) -> ClientError {
+    match e.downcast::<std::io::Error>() {
+        Ok(e) => ClientError::Io(e),
+        Err(e) => ClientError::Protocol(e.to_string()),
+    }
+}
+
+// the same frame in RESP2 and RESP3, an array or a push
+fn pubsub_message(frame: RespFrame) -> Result<PubSubMessage, ClientError> {
+    let items = match &frame {
+        RespFrame::Array(Array(Some(items))) => items.clone(),
+        RespFrame::Push(push) => push.0.clone(),
+        _ => return Err(ClientError::UnexpectedReply(frame)),
+    };
+    let unexpected = || ClientError::UnexpectedReply(frame.clone());
+    let mut items = items.into_iter();
+    let kind = String::from_reply(items.next().ok_or_else(unexpected)?)?;
+    let mut next = || items.next().ok_or_else(unexpected);
+    let message = match kind.to_ascii_lowercase().as_str() {
+        "subscribe" => PubSubMessage::Subscribe {
+            channel: String::from_reply(next()?)?,
+            count: i64::from_reply(next()?)? as usize,
+        },
+        "unsubscribe" => PubSubMessage::Unsubscribe {
+            channel: Option::from_reply(next()?)?,
+            count: i64::from_reply(next()?)? as usize,
+        },
+        "psubscribe" => PubSubMessage::PSubscribe {
+            pattern: String::from_reply(next()?)?,
+            count: i64::from_reply(next()?)? as usize,
+        },
+        "punsubscribe" => PubSubMessage::PUnsubscribe {
+            pattern: Option::from_reply(next()?)?,
+            count: i64::from_reply(next()?)? as usize,
+        },
+        "message" => PubSubMessage::Message {
+            channel: String::from_reply(next()?)?,
+            payload: next()?,
+        },
+        "pmessage" => PubSubMessage::PMessage {
+            pattern: String::from_reply(next()?)?,
+            channel: String::from_reply(next()?)?,
+            payload: next()?,
+        },
+        _ => return Err(unexpected()),
+    };
+    Ok(message)
+}
+
+impl ToArgs for str {
+    fn write_args(&self, args: &mut Vec<RespFrame>) {
+        args.push(BulkString::new(self.as_bytes()).into());
+    }
+}
+
+impl ToArgs for String {
+    fn write_args(&self, args: &mut Vec<RespFrame>) {
+        self.as_str().write_args(args);
+    }
+}
+
+impl ToArgs for Bytes {
+    fn write_args(&self, args: &mut Vec<RespFrame>) {
+        args.push(BulkString::from(self.clone()).into());
+    }
+}
+
+macro_rules! number_args {
+    ($($ty:ty),*) => {
+        $(
+            impl ToArgs for $ty {
+                fn write_args(&self, args: &mut Vec<RespFrame>) {
+                    self.to_string().write_args(args);
+                }
+            }
+        )*
+    };
+}
+
+number_args!(i32, i64, u16, u32, u64, usize, f64);
+
+impl<T: ToArgs + ?Sized> ToArgs for &T {
+    fn write_args(&self, args: &mut Vec<RespFrame>) {
+        (**self).write_args(args);
+    }
+}
+
+impl<T: ToArgs> ToArgs for [T] {
+    fn write_args(&self, args: &mut Vec<RespFrame>) {
+        self.iter().for_each(|item| item.write_args(args));
+    }
+}
+
+impl<T: ToArgs, const N: usize> ToArgs for [T; N] {
+    fn write_args(&self, args: &mut Vec<RespFrame>) {
+        self.as_slice().write_args(args);
+    }
+}
+
+impl<T: ToArgs> ToArgs for Vec<T> {
+    fn write_args(&self, args: &mut Vec<RespFrame>) {
+        self.as_slice().write_args(args);
+    }
+}
+
+macro_rules! tuple_args {
+    ($($name:ident),+) => {
+        impl<$($name: ToArgs),+> ToArgs for ($($name,)+) {
+            #[allow(non_snake_case)]
+            fn write_args(&self, args: &mut Vec<RespFrame>) {
+                let ($($name,)+) = self;
+                $($name.write_args(args);)+
+            }
+        }
+    };
+}
+
+tuple_args!(A);
+tuple_args!(A, B);
+tuple_args!(A, B, C);
+tuple_args!(A, B, C, D);
+tuple_args!(A, B, C, D, E);
+tuple_args!(A, B, C, D, E, F);
+tuple_args!(A, B, C, D, E, F, G);
+tuple_args!(A, B, C, D, E, F, G, H);
+tuple_args!(A, B, C, D, E, F, G, H, I);
+
+impl FromReply for RespFrame {
+    fn from_reply(frame: RespFrame) -> Result<Self, ClientError> {
+        match frame {
+            RespFrame::Error(e) => Err(ClientError::Server(e.0)),
+            frame => Ok(frame),
+        }
+    }
+}
+
+impl FromReply for () {
+    fn from_reply(frame: RespFrame) -> Result<Self, ClientError> {
+        RespFrame::from_reply(frame).map(|_| ())
+    }
+}
+
+impl FromReply for Bytes {
+    fn from_reply(frame: RespFrame) -> Result<Self, ClientError> {
+        match RespFrame::from_reply(frame)? {
+            RespFrame::BulkString(BulkString(Some(bytes))) => Ok(bytes),
+            RespFrame::SimpleString(s) => Ok(s.0.into()),
+            frame => Err(ClientError::UnexpectedReply(frame)),
+        }
+    }
+}
+
+impl FromReply for String {
+    fn from_reply(frame: RespFrame) -> Result<Self, ClientError> {
+        match RespFrame::from_reply(frame)? {
+            RespFrame::BulkString(BulkString(Some(bytes))) => String::from_utf8(bytes.to_vec())
+                .map_err(|_| ClientError::UnexpectedReply(BulkString::from(bytes).into())),
+            RespFrame::SimpleString(s) => Ok(s.0),
+            frame => Err(ClientError::UnexpectedReply(frame)),
+        }
+    }
+}
+
+impl FromReply for i64 {
+    fn from_reply(frame: RespFrame) -> Result<Self, ClientError> {
+        match RespFrame::from_reply(frame)? {
+            RespFrame::Integer(n) => Ok(n),
+            // cursors and a few RESP2 replies are numbers in bulk strings
+            RespFrame::BulkString(BulkString(Some(bytes))) => parse_number(bytes),
+            frame => Err(ClientError::UnexpectedReply(frame)),
+        }
+    }
+}
+
+impl FromReply for u64 {
+    fn from_reply(frame: RespFrame) -> Result<Self, ClientError> {
+        match RespFrame::from_reply(frame)? {
+            RespFrame::Integer(n) if n >= 0 => Ok(n as u64),
+            RespFrame::BulkString(BulkString(Some(bytes))) => parse_number(bytes),
+            frame => Err(ClientError::UnexpectedReply(frame)),
+        }
+    }
+}
+
+impl FromReply for f64 {
+    fn from_reply(frame: RespFrame) -> Result<Self, ClientError> {
+        match RespFrame::from_reply(frame)? {
+            RespFrame::Double(n) => Ok(n),
+            RespFrame::Integer(n) => Ok(n as f64),
+            // doubles are bulk strings in RESP2
+            RespFrame::BulkString(BulkString(Some(bytes))) => parse_number(bytes),
+            frame => Err(ClientError::UnexpectedReply(frame)),
+        }
+    }
+}
+
+impl FromReply for bool {
+    fn from_reply(frame: RespFrame) -> Result<Self, ClientError> {
+        match RespFrame::from_reply(frame)? {
+            RespFrame::Boolean(b) => Ok(b),
+            RespFrame::Integer(n) => Ok(n != 0),
+            // SET NX replies OK or nil
+            RespFrame::SimpleString(_) => Ok(true),
+            RespFrame::Null(_) | RespFrame::BulkString(BulkString(None)) => Ok(false),
+            frame => Err(ClientError::UnexpectedReply(frame)),
+        }
+    }
+}
+
+impl<T: FromReply> FromReply for Option<T> {
+    fn from_reply(frame: RespFrame) -> Result<Self, ClientError> {
+        match RespFrame::from_reply(frame)? {
+            RespFrame::Null(_)
+            | RespFrame::BulkString(BulkString(None))
+            | RespFrame::Array(Array(None)) => Ok(None),
+            frame => T::from_reply(frame).map(Some),
+        }
+    }
+}
+
+impl<T: FromReply> FromReply for Vec<T> {
+    fn from_reply(frame: RespFrame) -> Result<Self, ClientError> {
+        match RespFrame::from_reply(frame)? {
+            RespFrame::Array(Array(Some(items))) => items.into_iter().map(T::from_reply).collect(),
+            RespFrame::Set(set) => set.0.into_iter().map(T::from_reply).collect(),
+            RespFrame::Push(push) => push.0.into_iter().map(T::from_reply).collect(),
+            RespFrame::Null(_) | RespFrame::Array(Array(None)) => Ok(vec![]),
+            frame => Err(ClientError::UnexpectedReply(frame)),
+        }
+    }
+}
+
+impl<T: FromReply> FromReply for HashMap<String, T> {
+    fn from_reply(frame: RespFrame) -> Result<Self, ClientError> {
+        match RespFrame::from_reply(frame)? {
+            RespFrame::Map(map) => map
+                .0
+                .into_iter()
+                .map(|(key, value)| Ok((key, T::from_reply(value)?)))
+                .collect(),
+            // RESP2 maps are arrays of keys and values
+            RespFrame::Array(Array(Some(items))) if items.len() % 2 == 0 => {
+                let mut items = items.into_iter();
+                let mut map = HashMap::new();
+                while let (Some(key), Some(value)) = (items.next(), items.next()) {
+                    map.insert(String::from_reply(key)?, T::from_reply(value)?);
+                }
+                Ok(map)
+            }
+            frame => Err(ClientError::UnexpectedReply(frame)),
+        }
+    }
+}
+
+impl<A: FromReply, B: FromReply> FromReply for (A, B) {
+    fn from_reply(frame: RespFrame) -> Result<Self, ClientError> {
+        match RespFrame::from_reply(frame)? {
+            RespFrame::Array(Array(Some(items))) if items.len() == 2 => {
+                let mut items = items.into_iter();
+                let (a, b) = (items.next().unwrap(), items.next().unwrap());
+                Ok((A::from_reply(a)?, B::from_reply(b)?))
+            }
+            frame => Err(ClientError::UnexpectedReply(frame)),
+        }
+    }
+}
+
+fn parse_number<T: std::str::FromStr>(bytes: Bytes) -> Result<T, ClientError> {
+    std::str::from_utf8(&bytes)
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .ok_or_else(|| ClientError::UnexpectedReply(BulkString::from(bytes).into()))
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::{duplex, DuplexStream};
+
+    use crate::{network, Backend, SimpleString};
+
+    use super::*;
+
+    // a client served by its own connection task, as the server does for a tcp client
+    fn connect(backend: &Backend) -> Client<DuplexStream> {
+        let (client, server) = duplex(64 * 1024);
+        let addr = "127.0.0.1:6000".parse().unwrap();
+        tokio::spawn(network::stream_handler(server, addr, backend.clone()));
+        Client::new(client)
+    }
+
+    #[tokio::test]
+    async fn test_typed_commands() -> Result<(), ClientError> {
+        let backend = Backend::new();
+        let mut client = connect(&backend);
+        assert_eq!(client.ping().await?, "PONG");
+        client.set("a", 10).await?;
+        assert_eq!(client.incrby("a", 5).await?, 15);
+        assert_eq!(client.get("a").await?, Some(Bytes::from("15")));
+        assert_eq!(
+            client.mget(&["a", "missing"]).await?,
+            vec![Some(Bytes::from("15")), None]
+        );
+        assert!(client.set_nx("b", "x").await?);
+        assert!(!client.set_nx("b", "y").await?);
+        assert_eq!(client.del(&["a", "b", "c"]).await?, 2);
+
+        assert_eq!(client.hset("h", "f1", "v1").await?, 1);
+        client.hmset("h", &[("f1", "v0"), ("f2", "v2")]).await?;
+        let fields = client.hgetall("h").await?;
+        assert_eq!(fields.get("f2"), Some(&Bytes::from("v2")));
+        assert_eq!(client.zadd("z", &[(1.5, "m1"), (2.0, "m2")]).await?, 2);
+        assert_eq!(client.zscore("z", "m1").await?, Some(1.5));
+        assert_eq!(client.zrange("z", 0, -1).await?, vec!["m1", "m2"]);
+        assert_eq!(client.rpush("l", &["x", "y"]).await?, 2);
+        assert_eq!(
+            client.blpop(&["l"], 0.1).await?,
+            Some(("l".to_string(), Bytes::from("x")))
+        );
+
+        let err = client.lpush("h", &["x"]).await.unwrap_err();
+        assert!(matches!(err, ClientError::Server(e) if e.starts_with("WRONGTYPE")));
+        // the connection is still usable after an error reply
+        assert_eq!(client.key_type("h").await?, "hash");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_pipeline() -> Result<(), ClientError> {
+        let backend = Backend::new();
+        let mut client = connect(&backend);
+        let mut pipeline = Pipeline::new();
+        pipeline
+            .add(("set", "k", "v"))
+            .add(["incr", "k"])
+            .add(("get", "k"));
+        assert_eq!(pipeline.len(), 3);
+        let replies = client.pipeline(pipeline).await?;
+        assert_eq!(replies.len(), 3);
+        assert_eq!(replies[0], SimpleString::new("OK").into());
+        assert!(matches!(&replies[1], RespFrame::Error(_)));
+        assert_eq!(replies[2], BulkString::new("v").into());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_subscription() -> Result<(), ClientError> {
+        let backend = Backend::new();
+        let mut publisher = connect(&backend);
+        let mut subscription = connect(&backend).subscribe(&["news", "sport"]).await?;
+        assert_eq!(
+            subscription.next_message().await?,
+            PubSubMessage::Subscribe {
+                channel: "news".to_string(),
+                count: 1
+            }
+        );
+        subscription.next_message().await?;
+        subscription.psubscribe("w*").await?;
+        subscription.next_message().await?;
+
+        assert_eq!(publisher.publish("weather", "sunny").await?, 1);
+        assert_eq!(
+            subscription.next_message().await?,
+            PubSubMessage::PMessage {
+                pattern: "w*".to_string(),
+                channel: "weather".to_string(),
+                payload: BulkString::new("sunny").into(),
+            }
+        );
+        subscription.unsubscribe(["news"]).await?;
+        assert_eq!(
+            subscription.next_message().await?,
+            PubSubMessage::Unsubscribe {
+                channel: Some("news".to_string()),
+                count: 2
+            }
+        );
+        Ok(())
+    }
+}
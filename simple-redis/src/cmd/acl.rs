@@ -0,0 +1,194 @@
+use crate::cmd::{
+    extract_args, not_in_context, parse_string, validate_command_at_least, AclCommand,
+    AclSubcommand, CommandError, CommandExecutor, ACL_CATEGORIES, COMMAND_TABLE, RESP_OK,
+};
+use crate::{Array, Backend, BulkString, Map, Null, RespFrame, SimpleError, User};
+
+// WHOAMI needs the user of the connection, the other subcommands can run anywhere
+impl CommandExecutor for AclCommand {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        self.execute_with(backend, None)
+    }
+}
+
+impl AclCommand {
+    /// Run the subcommand for a connection authenticated as `user`.
+    pub fn execute_with(self, backend: &Backend, user: Option<&str>) -> RespFrame {
+        let acl = backend.acl();
+        match self.sub {
+            AclSubcommand::SetUser(username, rules) => match acl.set_user(&username, &rules) {
+                Ok(()) => RESP_OK.clone(),
+                Err(e) => SimpleError::new(e.to_string()).into(),
+            },
+            AclSubcommand::GetUser(username) => match acl.user(&username) {
+                Some(user) => describe_user(&user),
+                None => RespFrame::Null(Null),
+            },
+            AclSubcommand::DelUser(usernames) => match acl.del_users(&usernames) {
+                Ok(n) => RespFrame::Integer(n as i64),
+                Err(e) => SimpleError::new(e.to_string()).into(),
+            },
+            AclSubcommand::WhoAmI => match user {
+                Some(user) => BulkString::new(user).into(),
+                None => not_in_context("acl|whoami"),
+            },
+            AclSubcommand::List => bulk_array(acl.users().iter().map(|user| user.describe())),
+            AclSubcommand::Users => {
+                bulk_array(acl.users().iter().map(|user| user.name().to_string()))
+            }
+            AclSubcommand::Cat(None) => bulk_array(ACL_CATEGORIES.iter().map(|v| v.to_string())),
+            AclSubcommand::Cat(Some(category)) => {
+                let category = category.to_ascii_lowercase();
+                if !ACL_CATEGORIES.contains(&category.as_str()) {
+                    return SimpleError::new(format!("ERR Unknown category '{}'", category)).into();
+                }
+                let names = COMMAND_TABLE
+                    .iter()
+                    .filter(|spec| spec.acl_categories().contains(&category.as_str()))
+                    .map(|spec| spec.name.to_string());
+                bulk_array(names)
+            }
+        }
+    }
+}
+
+// flags, passwords, commands, keys, channels and selectors of the user
+fn describe_user(user: &User) -> RespFrame {
+    let mut flags = vec![if user.enabled() { "on" } else { "off" }.to_string()];
+    if user.nopass() {
+        flags.push("nopass".to_string());
+    }
+    let mut map = Map::new();
+    map.insert("flags".to_string(), bulk_array(flags.into_iter()));
+    map.insert(
+        "passwords".to_string(),
+        bulk_array(user.passwords().map(|v| v.to_string())),
+    );
+    map.insert(
+        "commands".to_string(),
+        BulkString::new(user.describe_commands()).into(),
+    );
+    map.insert(
+        "keys".to_string(),
+        BulkString::new(user.describe_keys()).into(),
+    );
+    map.insert(
+        "channels".to_string(),
+        BulkString::new(user.describe_channels()).into(),
+    );
+    map.insert("selectors".to_string(), Array::new(vec![]).into());
+    map.into()
+}
+
+fn bulk_array(items: impl Iterator<Item = String>) -> RespFrame {
+    Array::new(
+        items
+            .map(|item| BulkString::new(item).into())
+            .collect::<Vec<RespFrame>>(),
+    )
+    .into()
+}
+
+impl TryFrom<Array> for AclCommand {
+    type Error = CommandError;
+    fn try_from(value: Array) -> Result<Self, Self::Error> {
+        validate_command_at_least(&value, &["acl"], 1)?;
+        let mut args = extract_args(value, 1)?.into_iter();
+        let sub = parse_string(args.next())?.to_ascii_lowercase();
+        let args = args
+            .map(|v| parse_string(Some(v)))
+            .collect::<Result<Vec<_>, _>>()?;
+        let sub = match (sub.as_str(), args.len()) {
+            ("setuser", 1..) => {
+                let mut args = args.into_iter();
+                let username = args.next().unwrap_or_default();
+                AclSubcommand::SetUser(username, args.collect())
+            }
+            ("getuser", 1) => AclSubcommand::GetUser(args.into_iter().next().unwrap_or_default()),
+            ("deluser", 1..) => AclSubcommand::DelUser(args),
+            ("whoami", 0) => AclSubcommand::WhoAmI,
+            ("list", 0) => AclSubcommand::List,
+            ("users", 0) => AclSubcommand::Users,
+            ("cat", 0 | 1) => AclSubcommand::Cat(args.into_iter().next()),
+            (sub, _) => {
+                return Err(CommandError::InvalidArgument(format!(
+                    "unknown subcommand or wrong number of arguments for '{}'",
+                    sub
+                )))
+            }
+        };
+        Ok(AclCommand { sub })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use bytes::BytesMut;
+
+    use crate::RespDecode;
+
+    use super::*;
+
+    #[test]
+    fn test_acl_try_from_array() -> Result<()> {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(
+            b"*5\r\n$3\r\nACL\r\n$7\r\nSETUSER\r\n$5\r\nalice\r\n$2\r\non\r\n$3\r\n>pw\r\n",
+        );
+        let frame = Array::decode(&mut buf)?;
+        let cmd = AclCommand::try_from(frame)?;
+        assert_eq!(
+            cmd.sub,
+            AclSubcommand::SetUser(
+                "alice".to_string(),
+                vec!["on".to_string(), ">pw".to_string()]
+            )
+        );
+
+        buf.extend_from_slice(b"*3\r\n$3\r\nacl\r\n$6\r\nwhoami\r\n$1\r\nx\r\n");
+        let frame = Array::decode(&mut buf)?;
+        assert!(AclCommand::try_from(frame).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_acl_commands() {
+        let backend = Backend::new();
+        let acl = |sub| AclCommand { sub };
+        let rules = vec!["on".to_string(), "~*".to_string(), "+get".to_string()];
+        assert_eq!(
+            acl(AclSubcommand::SetUser("alice".to_string(), rules)).execute(&backend),
+            RESP_OK.clone()
+        );
+        assert_eq!(
+            acl(AclSubcommand::Users).execute(&backend),
+            Array::new(vec![
+                BulkString::new("alice").into(),
+                BulkString::new("default").into(),
+            ])
+            .into()
+        );
+        let RespFrame::Map(map) =
+            acl(AclSubcommand::GetUser("alice".to_string())).execute(&backend)
+        else {
+            panic!("GETUSER should reply with a map");
+        };
+        assert_eq!(
+            map.get("commands"),
+            Some(&BulkString::new("-@all +get").into())
+        );
+        assert_eq!(
+            acl(AclSubcommand::WhoAmI).execute_with(&backend, Some("alice")),
+            BulkString::new("alice").into()
+        );
+        assert_eq!(
+            acl(AclSubcommand::DelUser(vec!["default".to_string()])).execute(&backend),
+            SimpleError::new("ERR The 'default' user cannot be removed").into()
+        );
+        assert_eq!(
+            acl(AclSubcommand::Cat(Some("nope".to_string()))).execute(&backend),
+            SimpleError::new("ERR Unknown category 'nope'").into()
+        );
+    }
+}
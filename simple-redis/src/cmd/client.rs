@@ -0,0 +1,246 @@
+use crate::cmd::{
+    extract_args, not_in_context, parse_i64, parse_string, validate_command_at_least,
+    ClientCommand, ClientKill, ClientSubcommand, CommandError, CommandExecutor, RESP_OK,
+};
+use crate::{now_ms, Array, Backend, BulkString, ClientInfo, Null, RespFrame, SimpleError};
+
+// the name and the id of the client are part of the state of a connection
+impl CommandExecutor for ClientCommand {
+    fn execute(self, _backend: &Backend) -> RespFrame {
+        not_in_context("client")
+    }
+}
+
+impl ClientCommand {
+    /// Run the subcommand for the connection `id`, named `name`.
+    pub fn execute_with(self, backend: &Backend, id: u64, name: &mut Option<String>) -> RespFrame {
+        match self.sub {
+            ClientSubcommand::List => {
+                let now = now_ms();
+                let lines = backend
+                    .client_list()
+                    .iter()
+                    .map(|info| client_line(info, now))
+                    .collect::<Vec<_>>();
+                BulkString::new(lines.join("\n")).into()
+            }
+            ClientSubcommand::Info => match backend.client_list().iter().find(|c| c.id == id) {
+                Some(info) => BulkString::new(client_line(info, now_ms())).into(),
+                None => RespFrame::Null(Null),
+            },
+            ClientSubcommand::Id => RespFrame::Integer(id as i64),
+            ClientSubcommand::GetName => match name {
+                Some(name) => BulkString::new(name.as_str()).into(),
+                None => RespFrame::Null(Null),
+            },
+            ClientSubcommand::SetName(setname) => {
+                if setname.bytes().any(|c| !c.is_ascii_graphic()) {
+                    return SimpleError::new(
+                        "ERR Client names cannot contain spaces, newlines or special characters.",
+                    )
+                    .into();
+                }
+                // an empty name removes the name
+                *name = (!setname.is_empty()).then_some(setname);
+                RESP_OK.clone()
+            }
+            ClientSubcommand::Kill(kill) => {
+                let killed = backend.kill_clients(|info| {
+                    kill.id.is_none_or(|v| v == info.id)
+                        && kill
+                            .addr
+                            .as_ref()
+                            .is_none_or(|v| *v == info.addr.to_string())
+                        && kill
+                            .user
+                            .as_ref()
+                            .is_none_or(|v| info.user.as_ref() == Some(v))
+                        && !(kill.skipme && info.id == id)
+                });
+                match (kill.legacy, killed) {
+                    (true, 0) => SimpleError::new("ERR No such client").into(),
+                    (true, _) => RESP_OK.clone(),
+                    (false, killed) => RespFrame::Integer(killed as i64),
+                }
+            }
+        }
+    }
+}
+
+// a line of CLIENT LIST
+fn client_line(info: &ClientInfo, now: u64) -> String {
+    format!(
+        "id={} addr={} name={} age={} idle={} flags={} db={} sub={} psub={} multi={} cmd={} user={} resp={}",
+        info.id,
+        info.addr,
+        info.name.as_deref().unwrap_or_default(),
+        now.saturating_sub(info.created) / 1000,
+        now.saturating_sub(info.last_interaction) / 1000,
+        info.flags,
+        info.db,
+        info.sub,
+        info.psub,
+        info.multi,
+        info.cmd,
+        info.user.as_deref().unwrap_or_default(),
+        info.resp
+    )
+}
+
+impl TryFrom<Array> for ClientCommand {
+    type Error = CommandError;
+    fn try_from(value: Array) -> Result<Self, Self::Error> {
+        validate_command_at_least(&value, &["client"], 1)?;
+        let mut args = extract_args(value, 1)?.into_iter();
+        let sub = parse_string(args.next())?.to_ascii_lowercase();
+        let args = args.collect::<Vec<_>>();
+        let sub = match (sub.as_str(), args.len()) {
+            ("list", 0) => ClientSubcommand::List,
+            ("info", 0) => ClientSubcommand::Info,
+            ("id", 0) => ClientSubcommand::Id,
+            ("getname", 0) => ClientSubcommand::GetName,
+            ("setname", 1) => ClientSubcommand::SetName(parse_string(args.into_iter().next())?),
+            ("kill", 1) => ClientSubcommand::Kill(ClientKill {
+                id: None,
+                addr: Some(parse_string(args.into_iter().next())?),
+                user: None,
+                skipme: false,
+                legacy: true,
+            }),
+            ("kill", n) if n > 0 && n % 2 == 0 => ClientSubcommand::Kill(parse_kill_filters(args)?),
+            (sub, _) => {
+                return Err(CommandError::InvalidArgument(format!(
+                    "unknown subcommand or wrong number of arguments for '{}'",
+                    sub
+                )))
+            }
+        };
+        Ok(ClientCommand { sub })
+    }
+}
+
+fn parse_kill_filters(args: Vec<RespFrame>) -> Result<ClientKill, CommandError> {
+    let mut kill = ClientKill {
+        id: None,
+        addr: None,
+        user: None,
+        skipme: true,
+        legacy: false,
+    };
+    let mut args = args.into_iter();
+    while let Some(filter) = args.next() {
+        match parse_string(Some(filter))?.to_ascii_lowercase().as_str() {
+            "id" => {
+                let id = parse_i64(args.next())?;
+                kill.id = Some(u64::try_from(id).map_err(|_| {
+                    CommandError::InvalidArgument("client-id should be greater than 0".to_string())
+                })?);
+            }
+            "addr" => kill.addr = Some(parse_string(args.next())?),
+            "user" => kill.user = Some(parse_string(args.next())?),
+            "skipme" => {
+                kill.skipme = match parse_string(args.next())?.to_ascii_lowercase().as_str() {
+                    "yes" => true,
+                    "no" => false,
+                    _ => return Err(CommandError::InvalidArgument("syntax error".to_string())),
+                }
+            }
+            _ => return Err(CommandError::InvalidArgument("syntax error".to_string())),
+        }
+    }
+    Ok(kill)
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use bytes::BytesMut;
+
+    use crate::RespDecode;
+
+    use super::*;
+
+    #[test]
+    fn test_client_try_from_array() -> Result<()> {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(
+            b"*6\r\n$6\r\nclient\r\n$4\r\nKILL\r\n$2\r\nID\r\n$1\r\n5\r\n$6\r\nSKIPME\r\n$2\r\nno\r\n",
+        );
+        let frame = Array::decode(&mut buf)?;
+        assert_eq!(
+            ClientCommand::try_from(frame)?.sub,
+            ClientSubcommand::Kill(ClientKill {
+                id: Some(5),
+                addr: None,
+                user: None,
+                skipme: false,
+                legacy: false,
+            })
+        );
+
+        buf.extend_from_slice(b"*3\r\n$6\r\nclient\r\n$4\r\nlist\r\n$4\r\nfull\r\n");
+        let frame = Array::decode(&mut buf)?;
+        assert!(ClientCommand::try_from(frame).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_client_list_setname_kill() {
+        let backend = Backend::new();
+        let client = |id, name: &mut Option<String>, sub| {
+            ClientCommand { sub }.execute_with(&backend, id, name)
+        };
+        let addr = "127.0.0.1:6000".parse().unwrap();
+        let _first = backend.connect_client(1, addr).unwrap();
+        let second = backend
+            .connect_client(2, "127.0.0.1:6001".parse().unwrap())
+            .unwrap();
+
+        let mut name = None;
+        assert_eq!(
+            client(1, &mut name, ClientSubcommand::SetName("a b".to_string())),
+            SimpleError::new(
+                "ERR Client names cannot contain spaces, newlines or special characters."
+            )
+            .into()
+        );
+        assert_eq!(
+            client(
+                1,
+                &mut name,
+                ClientSubcommand::SetName("worker".to_string())
+            ),
+            RESP_OK.clone()
+        );
+        assert_eq!(name.as_deref(), Some("worker"));
+        second.update(|info| info.name = Some("other".to_string()));
+
+        let RespFrame::BulkString(list) = client(1, &mut name, ClientSubcommand::List) else {
+            panic!("CLIENT LIST should reply with a bulk string");
+        };
+        let list = String::from_utf8_lossy(list.as_ref()).to_string();
+        assert_eq!(list.lines().count(), 2);
+        assert!(list
+            .lines()
+            .nth(1)
+            .unwrap()
+            .starts_with("id=2 addr=127.0.0.1:6001 name=other "));
+
+        let kill = |addr: &str| ClientKill {
+            id: None,
+            addr: Some(addr.to_string()),
+            user: None,
+            skipme: true,
+            legacy: false,
+        };
+        // the calling client is skipped by default
+        assert_eq!(
+            client(1, &mut name, ClientSubcommand::Kill(kill("127.0.0.1:6000"))),
+            RespFrame::Integer(0)
+        );
+        assert_eq!(
+            client(1, &mut name, ClientSubcommand::Kill(kill("127.0.0.1:6001"))),
+            RespFrame::Integer(1)
+        );
+    }
+}
@@ -0,0 +1,283 @@
+use std::fmt::Write as _;
+
+use crate::cmd::{
+    extract_args, not_in_context, parse_string, validate_command, validate_command_at_least,
+    Asking, ClusterCommand, ClusterSubcommand, CommandError, CommandExecutor,
+};
+use crate::{
+    key_hash_slot, Array, Backend, BulkString, Cluster, ClusterNode, Map, RespFrame, SimpleError,
+    CLUSTER_SLOTS,
+};
+
+impl CommandExecutor for ClusterCommand {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let Some(cluster) = backend.cluster() else {
+            return SimpleError::new("ERR This instance has cluster support disabled").into();
+        };
+        match self.sub {
+            ClusterSubcommand::Slots => slots(cluster),
+            ClusterSubcommand::Shards => shards(cluster),
+            ClusterSubcommand::Nodes => BulkString::new(nodes(cluster)).into(),
+            ClusterSubcommand::Info => BulkString::new(info(cluster)).into(),
+            ClusterSubcommand::MyId => BulkString::new(cluster.myself().id.as_str()).into(),
+            ClusterSubcommand::KeySlot(key) => {
+                RespFrame::Integer(key_hash_slot(key.as_bytes()) as i64)
+            }
+        }
+    }
+}
+
+// ASKING only applies to the next command of the connection
+impl CommandExecutor for Asking {
+    fn execute(self, _backend: &Backend) -> RespFrame {
+        not_in_context("asking")
+    }
+}
+
+// [first slot, last slot, [host, port, id]] for each range of slots
+fn slots(cluster: &Cluster) -> RespFrame {
+    let mut ranges = cluster
+        .nodes()
+        .iter()
+        .flat_map(|node| {
+            cluster
+                .slot_ranges(node)
+                .into_iter()
+                .map(move |(first, last)| (first, last, node))
+        })
+        .collect::<Vec<_>>();
+    ranges.sort_by_key(|(first, _, _)| *first);
+    let ranges = ranges
+        .into_iter()
+        .map(|(first, last, node)| {
+            Array::new(vec![
+                RespFrame::Integer(first as i64),
+                RespFrame::Integer(last as i64),
+                Array::new(vec![
+                    BulkString::new(node.host.as_str()).into(),
+                    RespFrame::Integer(node.port as i64),
+                    BulkString::new(node.id.as_str()).into(),
+                ])
+                .into(),
+            ])
+            .into()
+        })
+        .collect::<Vec<RespFrame>>();
+    Array::new(ranges).into()
+}
+
+// a map of the slots and the nodes of each shard, there is a single node per shard
+fn shards(cluster: &Cluster) -> RespFrame {
+    let shards = cluster
+        .nodes()
+        .iter()
+        .map(|node| {
+            let slots = cluster
+                .slot_ranges(node)
+                .into_iter()
+                .flat_map(|(first, last)| {
+                    [
+                        RespFrame::Integer(first as i64),
+                        RespFrame::Integer(last as i64),
+                    ]
+                })
+                .collect::<Vec<_>>();
+            let mut shard = Map::new();
+            shard.insert("slots".to_string(), Array::new(slots).into());
+            shard.insert(
+                "nodes".to_string(),
+                Array::new(vec![shard_node(node)]).into(),
+            );
+            shard.into()
+        })
+        .collect::<Vec<RespFrame>>();
+    Array::new(shards).into()
+}
+
+fn shard_node(node: &ClusterNode) -> RespFrame {
+    let mut map = Map::new();
+    map.insert("id".to_string(), BulkString::new(node.id.as_str()).into());
+    map.insert("port".to_string(), RespFrame::Integer(node.port as i64));
+    map.insert("ip".to_string(), BulkString::new(node.host.as_str()).into());
+    map.insert(
+        "endpoint".to_string(),
+        BulkString::new(node.host.as_str()).into(),
+    );
+    map.insert("role".to_string(), BulkString::new("master").into());
+    map.insert("replication-offset".to_string(), RespFrame::Integer(0));
+    map.insert("health".to_string(), BulkString::new("online").into());
+    map.into()
+}
+
+// one line per node:
+// <id> <ip:port@cport> <flags> <master> <ping-sent> <pong-recv> <config-epoch> <link-state> <slot> ...
+fn nodes(cluster: &Cluster) -> String {
+    let mut ret = String::new();
+    for node in cluster.nodes() {
+        let flags = match node.index == cluster.myself().index {
+            true => "myself,master",
+            false => "master",
+        };
+        let _ = write!(
+            ret,
+            "{} {}:{}@{} {} - 0 0 {} connected",
+            node.id,
+            node.host,
+            node.port,
+            node.port as u32 + 10000,
+            flags,
+            node.index + 1
+        );
+        for (first, last) in cluster.slot_ranges(node) {
+            match first == last {
+                true => write!(ret, " {}", first),
+                false => write!(ret, " {}-{}", first, last),
+            }
+            .ok();
+        }
+        let (migrating, importing) = cluster.migrations(node);
+        for (slot, target) in migrating {
+            let _ = write!(ret, " [{}->-{}]", slot, target.id);
+        }
+        for (slot, source) in importing {
+            let _ = write!(ret, " [{}-<-{}]", slot, source.id);
+        }
+        ret.push('\n');
+    }
+    ret
+}
+
+fn info(cluster: &Cluster) -> String {
+    let assigned = cluster.assigned_slots();
+    let size = cluster
+        .nodes()
+        .iter()
+        .filter(|node| !cluster.slot_ranges(node).is_empty())
+        .count();
+    format!(
+        "cluster_enabled:1\r\ncluster_state:{}\r\ncluster_slots_assigned:{}\r\ncluster_slots_ok:{}\r\n\
+         cluster_slots_pfail:0\r\ncluster_slots_fail:0\r\ncluster_known_nodes:{}\r\ncluster_size:{}\r\n\
+         cluster_current_epoch:{}\r\ncluster_my_epoch:{}\r\n",
+        if assigned == CLUSTER_SLOTS { "ok" } else { "fail" },
+        assigned,
+        assigned,
+        cluster.nodes().len(),
+        size,
+        cluster.nodes().len(),
+        cluster.myself().index + 1,
+    )
+}
+
+impl TryFrom<Array> for ClusterCommand {
+    type Error = CommandError;
+    fn try_from(value: Array) -> Result<Self, Self::Error> {
+        validate_command_at_least(&value, &["cluster"], 1)?;
+        let mut args = extract_args(value, 1)?.into_iter();
+        let sub = parse_string(args.next())?.to_ascii_lowercase();
+        let key = args.next();
+        let sub = match (sub.as_str(), key, args.next()) {
+            ("slots", None, _) => ClusterSubcommand::Slots,
+            ("shards", None, _) => ClusterSubcommand::Shards,
+            ("nodes", None, _) => ClusterSubcommand::Nodes,
+            ("info", None, _) => ClusterSubcommand::Info,
+            ("myid", None, _) => ClusterSubcommand::MyId,
+            ("keyslot", Some(key), None) => ClusterSubcommand::KeySlot(parse_string(Some(key))?),
+            (sub, _, _) => {
+                return Err(CommandError::InvalidArgument(format!(
+                    "unknown subcommand or wrong number of arguments for '{}'",
+                    sub
+                )))
+            }
+        };
+        Ok(ClusterCommand { sub })
+    }
+}
+
+impl TryFrom<Array> for Asking {
+    type Error = CommandError;
+    fn try_from(value: Array) -> Result<Self, Self::Error> {
+        validate_command(&value, &["asking"], 0)?;
+        Ok(Asking)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use anyhow::Result;
+    use bytes::BytesMut;
+
+    use crate::{AppConfig, ClusterNodeConfig, RespDecode};
+
+    use super::*;
+
+    fn cluster_backend() -> Backend {
+        let node = |id: &str, port: u16, slots: &[&str]| ClusterNodeConfig {
+            id: id.to_string(),
+            host: "127.0.0.1".to_string(),
+            port,
+            slots: slots.iter().map(|s| s.to_string()).collect(),
+            migrating: BTreeMap::new(),
+        };
+        let mut config = AppConfig::default();
+        config.cluster.enabled = true;
+        config.cluster.myself = "b".to_string();
+        config.cluster.nodes = vec![
+            node("a", 7000, &["0-99", "200-8191"]),
+            node("b", 7001, &["100-199", "8192-16383"]),
+        ];
+        config.cluster.nodes[0]
+            .migrating
+            .insert(300, "b".to_string());
+        let backend = Backend::with_config(config);
+        backend.enable_cluster().unwrap();
+        backend
+    }
+
+    #[test]
+    fn test_cluster_try_from_array() -> Result<()> {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(b"*3\r\n$7\r\ncluster\r\n$7\r\nKEYSLOT\r\n$3\r\nfoo\r\n");
+        let frame = Array::decode(&mut buf)?;
+        let cmd = ClusterCommand::try_from(frame)?;
+        assert_eq!(cmd.sub, ClusterSubcommand::KeySlot("foo".to_string()));
+        assert_eq!(cmd.execute(&cluster_backend()), RespFrame::Integer(12182));
+
+        buf.extend_from_slice(b"*3\r\n$7\r\ncluster\r\n$5\r\nslots\r\n$3\r\nfoo\r\n");
+        let frame = Array::decode(&mut buf)?;
+        assert!(ClusterCommand::try_from(frame).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_cluster_topology() {
+        let backend = cluster_backend();
+        let cmd = ClusterCommand {
+            sub: ClusterSubcommand::Slots,
+        };
+        let RespFrame::Array(Array(Some(ranges))) = cmd.execute(&backend) else {
+            panic!("CLUSTER SLOTS should reply with an array");
+        };
+        assert_eq!(ranges.len(), 4);
+        let RespFrame::Array(Array(Some(range))) = &ranges[1] else {
+            panic!("each range should be an array");
+        };
+        assert_eq!(range[..2], [100.into(), 199.into()]);
+
+        assert_eq!(
+            nodes(backend.cluster().unwrap()),
+            "a 127.0.0.1:7000@17000 master - 0 0 1 connected 0-99 200-8191 [300->-b]\n\
+             b 127.0.0.1:7001@17001 myself,master - 0 0 2 connected 100-199 8192-16383 [300-<-a]\n"
+        );
+        assert!(info(backend.cluster().unwrap()).contains("cluster_state:ok\r\n"));
+
+        let cmd = ClusterCommand {
+            sub: ClusterSubcommand::Info,
+        };
+        assert!(matches!(
+            cmd.execute(&Backend::new()),
+            RespFrame::Error(e) if e.contains("cluster support disabled")
+        ));
+    }
+}
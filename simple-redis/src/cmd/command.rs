@@ -0,0 +1,147 @@
+use crate::cmd::{
+    extract_args, lookup, parse_string, validate_command_at_least, CommandError, CommandExecutor,
+    CommandSpec, Commands, CommandsSubcommand, COMMAND_TABLE,
+};
+use crate::{Array, Backend, BulkString, Map, RespFrame, SimpleString};
+
+impl CommandExecutor for Commands {
+    fn execute(self, _backend: &Backend) -> RespFrame {
+        match self.sub {
+            CommandsSubcommand::All => {
+                Array::new(COMMAND_TABLE.iter().map(info).collect::<Vec<_>>()).into()
+            }
+            CommandsSubcommand::Count => RespFrame::Integer(COMMAND_TABLE.len() as i64),
+            CommandsSubcommand::List => Array::new(
+                COMMAND_TABLE
+                    .iter()
+                    .map(|spec| BulkString::new(spec.name).into())
+                    .collect::<Vec<RespFrame>>(),
+            )
+            .into(),
+            // unknown commands get a null entry
+            CommandsSubcommand::Info(names) => Array::new(
+                names
+                    .iter()
+                    .map(|name| match lookup(&name.to_ascii_lowercase()) {
+                        Some(spec) => info(spec),
+                        None => Array::none().into(),
+                    })
+                    .collect::<Vec<_>>(),
+            )
+            .into(),
+            // there is no documentation for the commands, clients only need a valid reply
+            CommandsSubcommand::Docs(_) => Map::new().into(),
+        }
+    }
+}
+
+// [name, arity, flags, first key, last key, step, acl categories, tips, key specs, subcommands]
+fn info(spec: &CommandSpec) -> RespFrame {
+    let flags = spec
+        .flags
+        .iter()
+        .map(|flag| SimpleString::new(*flag).into())
+        .collect::<Vec<RespFrame>>();
+    let categories = spec
+        .acl_categories()
+        .into_iter()
+        .map(|category| SimpleString::new(format!("@{}", category)).into())
+        .collect::<Vec<RespFrame>>();
+    Array::new(vec![
+        BulkString::new(spec.name).into(),
+        RespFrame::Integer(spec.arity),
+        Array::new(flags).into(),
+        RespFrame::Integer(spec.first_key),
+        RespFrame::Integer(spec.last_key),
+        RespFrame::Integer(spec.step),
+        Array::new(categories).into(),
+        Array::new(vec![]).into(),
+        Array::new(vec![]).into(),
+        Array::new(vec![]).into(),
+    ])
+    .into()
+}
+
+impl TryFrom<Array> for Commands {
+    type Error = CommandError;
+    fn try_from(value: Array) -> Result<Self, Self::Error> {
+        validate_command_at_least(&value, &["command"], 0)?;
+        let mut args = extract_args(value, 1)?.into_iter();
+        let Some(sub) = args.next() else {
+            return Ok(Commands {
+                sub: CommandsSubcommand::All,
+            });
+        };
+        let sub = parse_string(Some(sub))?.to_ascii_lowercase();
+        let names = args
+            .map(|v| parse_string(Some(v)))
+            .collect::<Result<Vec<_>, _>>()?;
+        let sub = match sub.as_str() {
+            "count" if names.is_empty() => CommandsSubcommand::Count,
+            "list" if names.is_empty() => CommandsSubcommand::List,
+            "info" => CommandsSubcommand::Info(names),
+            "docs" => CommandsSubcommand::Docs(names),
+            sub => {
+                return Err(CommandError::InvalidArgument(format!(
+                    "unknown subcommand or wrong number of arguments for '{}'",
+                    sub
+                )))
+            }
+        };
+        Ok(Commands { sub })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use bytes::BytesMut;
+
+    use crate::RespDecode;
+
+    use super::*;
+
+    #[test]
+    fn test_command_try_from_array() -> Result<()> {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(b"*4\r\n$7\r\ncommand\r\n$4\r\nINFO\r\n$3\r\nget\r\n$3\r\nfoo\r\n");
+        let frame = Array::decode(&mut buf)?;
+        let cmd = Commands::try_from(frame)?;
+        assert_eq!(
+            cmd.sub,
+            CommandsSubcommand::Info(vec!["get".to_string(), "foo".to_string()])
+        );
+
+        buf.extend_from_slice(b"*3\r\n$7\r\ncommand\r\n$5\r\ncount\r\n$1\r\nx\r\n");
+        let frame = Array::decode(&mut buf)?;
+        assert!(Commands::try_from(frame).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_command_info() {
+        let backend = Backend::new();
+        let cmd = Commands {
+            sub: CommandsSubcommand::Info(vec!["GET".to_string(), "foo".to_string()]),
+        };
+        let RespFrame::Array(Array(Some(items))) = cmd.execute(&backend) else {
+            panic!("COMMAND INFO should reply with an array");
+        };
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[1], Array::none().into());
+        let RespFrame::Array(Array(Some(get))) = &items[0] else {
+            panic!("COMMAND INFO should describe get");
+        };
+        assert_eq!(get[0], BulkString::new("get").into());
+        assert_eq!(get[1], RespFrame::Integer(2));
+        assert_eq!(get[3..6], [1.into(), 1.into(), 1.into()]);
+
+        let cmd = Commands {
+            sub: CommandsSubcommand::Count,
+        };
+        assert_eq!(
+            cmd.execute(&backend),
+            RespFrame::Integer(COMMAND_TABLE.len() as i64)
+        );
+    }
+}
@@ -0,0 +1,131 @@
+use crate::cmd::{
+    extract_args, parse_string, validate_command_at_least, CommandError, CommandExecutor,
+    ConfigCommand, ConfigSubcommand, RESP_OK,
+};
+use crate::{
+    glob_match, Array, Backend, BulkString, Map, RespFrame, SimpleError, CONFIG_PARAMETERS,
+};
+
+impl CommandExecutor for ConfigCommand {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match self.sub {
+            ConfigSubcommand::Get(patterns) => {
+                let config = backend.config();
+                let mut map = Map::new();
+                for name in CONFIG_PARAMETERS {
+                    if patterns
+                        .iter()
+                        .any(|pattern| glob_match(pattern.as_bytes(), name.as_bytes()))
+                    {
+                        let value = config.get(name).unwrap_or_default();
+                        map.insert(name.to_string(), BulkString::new(value).into());
+                    }
+                }
+                map.into()
+            }
+            ConfigSubcommand::Set(changes) => match backend.set_config(&changes) {
+                Ok(()) => RESP_OK.clone(),
+                Err(e) => SimpleError::new(e.to_string()).into(),
+            },
+            ConfigSubcommand::Rewrite => match backend.config().rewrite() {
+                Ok(()) => RESP_OK.clone(),
+                Err(e) => SimpleError::new(format!("ERR {}", e)).into(),
+            },
+        }
+    }
+}
+
+impl TryFrom<Array> for ConfigCommand {
+    type Error = CommandError;
+    fn try_from(value: Array) -> Result<Self, Self::Error> {
+        validate_command_at_least(&value, &["config"], 1)?;
+        let mut args = extract_args(value, 1)?.into_iter();
+        let sub = parse_string(args.next())?.to_ascii_lowercase();
+        let args = args
+            .map(|v| parse_string(Some(v)))
+            .collect::<Result<Vec<_>, _>>()?;
+        // parameter names are case insensitive
+        let sub = match (sub.as_str(), args.len()) {
+            ("get", 1..) => {
+                ConfigSubcommand::Get(args.into_iter().map(|v| v.to_ascii_lowercase()).collect())
+            }
+            ("set", n) if n > 0 && n % 2 == 0 => ConfigSubcommand::Set(
+                args.chunks(2)
+                    .map(|pair| (pair[0].to_ascii_lowercase(), pair[1].clone()))
+                    .collect(),
+            ),
+            ("rewrite", 0) => ConfigSubcommand::Rewrite,
+            (sub, _) => {
+                return Err(CommandError::InvalidArgument(format!(
+                    "unknown subcommand or wrong number of arguments for '{}'",
+                    sub
+                )))
+            }
+        };
+        Ok(ConfigCommand { sub })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use bytes::BytesMut;
+
+    use crate::RespDecode;
+
+    use super::*;
+
+    #[test]
+    fn test_config_try_from_array() -> Result<()> {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(
+            b"*5\r\n$6\r\nCONFIG\r\n$3\r\nSET\r\n$9\r\nMAXMEMORY\r\n$3\r\n1mb\r\n$7\r\ntimeout\r\n",
+        );
+        let frame = Array::decode(&mut buf)?;
+        assert!(ConfigCommand::try_from(frame).is_err());
+
+        buf.extend_from_slice(
+            b"*4\r\n$6\r\nconfig\r\n$3\r\nset\r\n$9\r\nMAXMEMORY\r\n$3\r\n1mb\r\n",
+        );
+        let frame = Array::decode(&mut buf)?;
+        assert_eq!(
+            ConfigCommand::try_from(frame)?.sub,
+            ConfigSubcommand::Set(vec![("maxmemory".to_string(), "1mb".to_string())])
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_config_get_set() {
+        let backend = Backend::new();
+        let config = |sub| ConfigCommand { sub }.execute(&backend);
+        let set = |pairs: &[(&str, &str)]| {
+            ConfigSubcommand::Set(
+                pairs
+                    .iter()
+                    .map(|(name, value)| (name.to_string(), value.to_string()))
+                    .collect(),
+            )
+        };
+        assert_eq!(config(set(&[("maxmemory", "1000")])), RESP_OK.clone());
+        assert_eq!(backend.config().memory.maxmemory, 1000);
+
+        // nothing is changed when one of the parameters is invalid
+        assert_eq!(
+            config(set(&[("maxmemory", "2000"), ("port", "6380")])),
+            SimpleError::new("ERR CONFIG SET failed (possibly related to argument 'port') - can't set immutable config").into()
+        );
+        assert_eq!(backend.config().memory.maxmemory, 1000);
+
+        let RespFrame::Map(map) = config(ConfigSubcommand::Get(vec!["maxmemory*".to_string()]))
+        else {
+            panic!("CONFIG GET should reply with a map");
+        };
+        assert_eq!(map.len(), 3);
+        assert_eq!(map.get("maxmemory"), Some(&BulkString::new("1000").into()));
+
+        assert_eq!(config(set(&[("requirepass", "pw")])), RESP_OK.clone());
+        assert!(!backend.acl().auto_login());
+        assert!(backend.acl().authenticate("default", "pw").is_ok());
+    }
+}
@@ -0,0 +1,212 @@
+use crate::cmd::{
+    extract_args, not_in_context, parse_i64, parse_string, validate_command_at_least, Auth,
+    CommandError, CommandExecutor, Hello, RESP_OK,
+};
+use crate::{Array, Backend, BulkString, Map, RespFrame, SimpleError};
+
+// the protocol is part of the state of a connection, so HELLO is handled by the connection
+impl CommandExecutor for Hello {
+    fn execute(self, _backend: &Backend) -> RespFrame {
+        not_in_context("hello")
+    }
+}
+
+// the user is part of the state of a connection, so AUTH is handled by the connection
+impl CommandExecutor for Auth {
+    fn execute(self, _backend: &Backend) -> RespFrame {
+        not_in_context("auth")
+    }
+}
+
+impl Auth {
+    /// Authenticate the connection as a user, the default one if no username is given.
+    pub fn execute_with(self, backend: &Backend, user: &mut Option<String>) -> RespFrame {
+        let username = match self.username {
+            Some(username) => username,
+            None if backend.acl().auto_login() => {
+                return SimpleError::new("ERR AUTH <password> called without any password configured for the default user. Are you sure your configuration is correct?").into();
+            }
+            None => "default".to_string(),
+        };
+        match backend.acl().authenticate(&username, &self.password) {
+            Ok(()) => {
+                *user = Some(username);
+                RESP_OK.clone()
+            }
+            Err(e) => SimpleError::new(e.to_string()).into(),
+        }
+    }
+}
+
+impl Hello {
+    /// Switch the protocol of the connection `id` and reply with the server properties.
+    /// The connection must be authenticated already, or authenticate with the AUTH option.
+    pub fn execute_with(
+        self,
+        backend: &Backend,
+        id: u64,
+        resp3: &mut bool,
+        name: &mut Option<String>,
+        user: &mut Option<String>,
+    ) -> RespFrame {
+        if let Some(protover) = self.protover {
+            if !(2..=3).contains(&protover) {
+                return SimpleError::new("NOPROTO unsupported protocol version").into();
+            }
+        }
+        match self.auth {
+            Some((username, password)) => {
+                if let Err(e) = backend.acl().authenticate(&username, &password) {
+                    return SimpleError::new(e.to_string()).into();
+                }
+                *user = Some(username);
+            }
+            None if user.is_none() => {
+                return SimpleError::new("NOAUTH HELLO must be called with the client already authenticated, otherwise the HELLO <proto> AUTH <user> <pass> option can be used to authenticate the client and select the RESP protocol version at the same time").into();
+            }
+            None => {}
+        }
+
+        if let Some(protover) = self.protover {
+            *resp3 = protover == 3;
+        }
+        if let Some(setname) = self.setname {
+            *name = Some(setname);
+        }
+
+        let mut map = Map::new();
+        map.insert("server".to_string(), BulkString::new("redis").into());
+        map.insert(
+            "version".to_string(),
+            BulkString::new(env!("CARGO_PKG_VERSION")).into(),
+        );
+        map.insert(
+            "proto".to_string(),
+            RespFrame::Integer(if *resp3 { 3 } else { 2 }),
+        );
+        map.insert("id".to_string(), RespFrame::Integer(id as i64));
+        let mode = match backend.cluster() {
+            Some(_) => "cluster",
+            None => "standalone",
+        };
+        map.insert("mode".to_string(), BulkString::new(mode).into());
+        let role = match backend.is_replica() {
+            true => "replica",
+            false => "master",
+        };
+        map.insert("role".to_string(), BulkString::new(role).into());
+        map.insert("modules".to_string(), Array::new(vec![]).into());
+        map.into()
+    }
+}
+
+// AUTH [username] password
+impl TryFrom<Array> for Auth {
+    type Error = CommandError;
+    fn try_from(value: Array) -> Result<Self, Self::Error> {
+        validate_command_at_least(&value, &["auth"], 1)?;
+        let mut args = extract_args(value, 1)?.into_iter();
+        let (username, password) = match (args.next(), args.next(), args.next()) {
+            (password, None, None) => (None, parse_string(password)?),
+            (username, password, None) => (Some(parse_string(username)?), parse_string(password)?),
+            _ => return Err(CommandError::InvalidArgument("syntax error".to_string())),
+        };
+        Ok(Auth { username, password })
+    }
+}
+
+impl TryFrom<Array> for Hello {
+    type Error = CommandError;
+    fn try_from(value: Array) -> Result<Self, Self::Error> {
+        validate_command_at_least(&value, &["hello"], 0)?;
+        let mut args = extract_args(value, 1)?.into_iter();
+        let mut hello = Hello {
+            protover: None,
+            auth: None,
+            setname: None,
+        };
+        let Some(protover) = args.next() else {
+            return Ok(hello);
+        };
+        hello.protover = Some(parse_i64(Some(protover)).map_err(|_| {
+            CommandError::InvalidArgument(
+                "Protocol version is not an integer or out of range".to_string(),
+            )
+        })?);
+
+        while let Some(arg) = args.next() {
+            match parse_string(Some(arg))?.to_ascii_lowercase().as_str() {
+                "auth" => {
+                    let username = parse_string(args.next())?;
+                    let password = parse_string(args.next())?;
+                    hello.auth = Some((username, password));
+                }
+                "setname" => hello.setname = Some(parse_string(args.next())?),
+                option => {
+                    return Err(CommandError::InvalidArgument(format!(
+                        "Syntax error in HELLO option '{}'",
+                        option
+                    )))
+                }
+            }
+        }
+        Ok(hello)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use bytes::BytesMut;
+
+    use crate::{RespDecode, RespEncode};
+
+    use super::*;
+
+    #[test]
+    fn test_hello_try_from_array() -> Result<()> {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(b"*4\r\n$5\r\nhello\r\n$1\r\n3\r\n$7\r\nSETNAME\r\n$3\r\ncli\r\n");
+        let frame = Array::decode(&mut buf)?;
+        let cmd = Hello::try_from(frame)?;
+        assert_eq!(cmd.protover, Some(3));
+        assert_eq!(cmd.setname, Some("cli".to_string()));
+
+        buf.extend_from_slice(b"*3\r\n$5\r\nhello\r\n$1\r\n3\r\n$4\r\nauth\r\n");
+        let frame = Array::decode(&mut buf)?;
+        assert!(Hello::try_from(frame).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_hello_switches_protocol() {
+        let backend = Backend::new();
+        let (mut resp3, mut name, mut user) = (false, None, Some("default".to_string()));
+        let cmd = Hello {
+            protover: Some(3),
+            auth: None,
+            setname: Some("cli".to_string()),
+        };
+        let RespFrame::Map(map) = cmd.execute_with(&backend, 7, &mut resp3, &mut name, &mut user)
+        else {
+            panic!("HELLO should reply with a map");
+        };
+        assert!(resp3);
+        assert_eq!(name, Some("cli".to_string()));
+        assert_eq!(map.get("proto"), Some(&RespFrame::Integer(3)));
+        assert_eq!(map.get("id"), Some(&RespFrame::Integer(7)));
+        assert_eq!(map.get("mode"), Some(&BulkString::new("standalone").into()));
+
+        let cmd = Hello {
+            protover: Some(4),
+            auth: None,
+            setname: None,
+        };
+        assert_eq!(
+            cmd.execute_with(&backend, 7, &mut resp3, &mut name, &mut user)
+                .encode(),
+            b"-NOPROTO unsupported protocol version\r\n"
+        );
+        assert!(resp3);
+    }
+}
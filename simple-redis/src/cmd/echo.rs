@@ -1,5 +1,5 @@
-use crate::{Backend, Array, RespFrame, SimpleString};
-use crate::cmd::{CommandError, CommandExecutor, Echo, extract_args, validate_command};
+use crate::cmd::{extract_args, validate_command, CommandError, CommandExecutor, Echo, Ping};
+use crate::{Array, Backend, BulkString, RespFrame, SimpleString};
 
 impl CommandExecutor for Echo {
     fn execute(self, _backend: &Backend) -> RespFrame {
@@ -7,6 +7,23 @@ impl CommandExecutor for Echo {
     }
 }
 
+impl CommandExecutor for Ping {
+    fn execute(self, _backend: &Backend) -> RespFrame {
+        match self.message {
+            Some(message) => message,
+            None => SimpleString::new("PONG").into(),
+        }
+    }
+}
+
+impl Ping {
+    /// The reply of a RESP2 connection in subscribed mode, where only pub/sub frames are expected.
+    pub fn execute_subscribed(self) -> RespFrame {
+        let message = self.message.unwrap_or_else(|| BulkString::new("").into());
+        Array::new(vec![BulkString::new("pong").into(), message]).into()
+    }
+}
+
 impl TryFrom<Array> for Echo {
     type Error = CommandError;
     fn try_from(value: Array) -> Result<Self, Self::Error> {
@@ -23,6 +40,23 @@ impl TryFrom<Array> for Echo {
     }
 }
 
+impl TryFrom<Array> for Ping {
+    type Error = CommandError;
+    fn try_from(value: Array) -> Result<Self, Self::Error> {
+        let n_args = value.0.as_ref().map(|v| v.len()).unwrap_or_default();
+        if n_args > 2 {
+            return Err(CommandError::InvalidArgument(
+                "ping command takes at most 1 argument".to_string(),
+            ));
+        }
+        validate_command(&value, &["ping"], n_args.saturating_sub(1))?;
+        let mut args = extract_args(value, 1)?.into_iter();
+        Ok(Ping {
+            message: args.next(),
+        })
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use anyhow::Result;
@@ -65,4 +99,29 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_ping() -> Result<()> {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(b"*1\r\n$4\r\nPING\r\n");
+        let frame = Array::decode(&mut buf)?;
+        let cmd: Ping = frame.try_into()?;
+        assert_eq!(
+            cmd.execute(&Backend::default()),
+            RespFrame::SimpleString(SimpleString::new("PONG"))
+        );
+
+        buf.extend_from_slice(b"*2\r\n$4\r\nping\r\n$2\r\nhi\r\n");
+        let frame = Array::decode(&mut buf)?;
+        let cmd: Ping = frame.try_into()?;
+        assert_eq!(
+            cmd.execute_subscribed(),
+            Array::new(vec![
+                BulkString::new("pong").into(),
+                BulkString::new("hi").into()
+            ])
+            .into()
+        );
+        Ok(())
+    }
 }
@@ -0,0 +1,264 @@
+use crate::cmd::{
+    extract_args, parse_i64, parse_string, validate_command, CommandError, CommandExecutor, Expire,
+    ExpireAt, PExpire, PExpireAt, PTtl, Persist, Ttl,
+};
+use crate::{now_ms, Array, Backend, ExpireCondition, RespFrame};
+
+impl CommandExecutor for Expire {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let at = deadline(self.seconds.saturating_mul(1000));
+        RespFrame::Integer(backend.expire_at(&self.key, at, self.condition) as i64)
+    }
+}
+
+impl CommandExecutor for PExpire {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let at = deadline(self.milliseconds);
+        RespFrame::Integer(backend.expire_at(&self.key, at, self.condition) as i64)
+    }
+}
+
+impl CommandExecutor for ExpireAt {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let at = self.timestamp.saturating_mul(1000).max(0) as u64;
+        RespFrame::Integer(backend.expire_at(&self.key, at, self.condition) as i64)
+    }
+}
+
+impl CommandExecutor for PExpireAt {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let at = self.timestamp.max(0) as u64;
+        RespFrame::Integer(backend.expire_at(&self.key, at, self.condition) as i64)
+    }
+}
+
+impl CommandExecutor for Ttl {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let ttl = backend.pttl(&self.key);
+        if ttl < 0 {
+            return RespFrame::Integer(ttl);
+        }
+        // round to the nearest second like redis does
+        RespFrame::Integer((ttl + 500) / 1000)
+    }
+}
+
+impl CommandExecutor for PTtl {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        RespFrame::Integer(backend.pttl(&self.key))
+    }
+}
+
+impl CommandExecutor for Persist {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        RespFrame::Integer(backend.persist(&self.key) as i64)
+    }
+}
+
+// convert a relative ttl in milliseconds into an absolute unix timestamp
+fn deadline(ttl: i64) -> u64 {
+    let now = now_ms() as i64;
+    now.saturating_add(ttl).max(0) as u64
+}
+
+fn parse_expire_args(
+    value: Array,
+    name: &'static str,
+) -> Result<(String, i64, Option<ExpireCondition>), CommandError> {
+    let n_args = value.0.as_ref().map(|v| v.len()).unwrap_or_default();
+    if !(3..=4).contains(&n_args) {
+        return Err(CommandError::InvalidArgument(format!(
+            "{} command requires 2 or 3 arguments",
+            name
+        )));
+    }
+    validate_command(&value, &[name], n_args - 1)?;
+    let mut args = extract_args(value, 1)?.into_iter();
+    let key = parse_string(args.next())?;
+    let ttl = parse_i64(args.next())?;
+    let condition = match args.next() {
+        Some(option) => match parse_string(Some(option))?.to_ascii_lowercase().as_str() {
+            "nx" => Some(ExpireCondition::Nx),
+            "xx" => Some(ExpireCondition::Xx),
+            "gt" => Some(ExpireCondition::Gt),
+            "lt" => Some(ExpireCondition::Lt),
+            option => {
+                return Err(CommandError::InvalidArgument(format!(
+                    "Unsupported option {}",
+                    option
+                )))
+            }
+        },
+        None => None,
+    };
+    Ok((key, ttl, condition))
+}
+
+fn parse_key(value: Array, name: &'static str) -> Result<String, CommandError> {
+    validate_command(&value, &[name], 1)?;
+    let mut args = extract_args(value, 1)?.into_iter();
+    parse_string(args.next())
+}
+
+impl TryFrom<Array> for Expire {
+    type Error = CommandError;
+    fn try_from(value: Array) -> Result<Self, Self::Error> {
+        let (key, seconds, condition) = parse_expire_args(value, "expire")?;
+        Ok(Expire {
+            key,
+            seconds,
+            condition,
+        })
+    }
+}
+
+impl TryFrom<Array> for PExpire {
+    type Error = CommandError;
+    fn try_from(value: Array) -> Result<Self, Self::Error> {
+        let (key, milliseconds, condition) = parse_expire_args(value, "pexpire")?;
+        Ok(PExpire {
+            key,
+            milliseconds,
+            condition,
+        })
+    }
+}
+
+impl TryFrom<Array> for ExpireAt {
+    type Error = CommandError;
+    fn try_from(value: Array) -> Result<Self, Self::Error> {
+        let (key, timestamp, condition) = parse_expire_args(value, "expireat")?;
+        Ok(ExpireAt {
+            key,
+            timestamp,
+            condition,
+        })
+    }
+}
+
+impl TryFrom<Array> for PExpireAt {
+    type Error = CommandError;
+    fn try_from(value: Array) -> Result<Self, Self::Error> {
+        let (key, timestamp, condition) = parse_expire_args(value, "pexpireat")?;
+        Ok(PExpireAt {
+            key,
+            timestamp,
+            condition,
+        })
+    }
+}
+
+impl TryFrom<Array> for Ttl {
+    type Error = CommandError;
+    fn try_from(value: Array) -> Result<Self, Self::Error> {
+        Ok(Ttl {
+            key: parse_key(value, "ttl")?,
+        })
+    }
+}
+
+impl TryFrom<Array> for PTtl {
+    type Error = CommandError;
+    fn try_from(value: Array) -> Result<Self, Self::Error> {
+        Ok(PTtl {
+            key: parse_key(value, "pttl")?,
+        })
+    }
+}
+
+impl TryFrom<Array> for Persist {
+    type Error = CommandError;
+    fn try_from(value: Array) -> Result<Self, Self::Error> {
+        Ok(Persist {
+            key: parse_key(value, "persist")?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use bytes::BytesMut;
+
+    use crate::RespDecode;
+
+    use super::*;
+
+    #[test]
+    fn test_expire_try_from_array() -> Result<()> {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(b"*4\r\n$6\r\nexpire\r\n$3\r\nkey\r\n$2\r\n10\r\n$2\r\nNX\r\n");
+        let frame = Array::decode(&mut buf)?;
+        let result = Expire::try_from(frame)?;
+        assert_eq!(result.key, "key");
+        assert_eq!(result.seconds, 10);
+        assert_eq!(result.condition, Some(ExpireCondition::Nx));
+
+        buf.extend_from_slice(b"*3\r\n$7\r\npexpire\r\n$3\r\nkey\r\n$3\r\nabc\r\n");
+        let frame = Array::decode(&mut buf)?;
+        assert!(PExpire::try_from(frame).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_expire_ttl_persist_commands() -> Result<()> {
+        let backend = Backend::new();
+        backend.set("key".to_string(), "value".into());
+
+        let cmd = Ttl {
+            key: "key".to_string(),
+        };
+        assert_eq!(cmd.execute(&backend), RespFrame::Integer(-1));
+
+        let cmd = Expire {
+            key: "key".to_string(),
+            seconds: 100,
+            condition: None,
+        };
+        assert_eq!(cmd.execute(&backend), RespFrame::Integer(1));
+
+        let cmd = Ttl {
+            key: "key".to_string(),
+        };
+        assert_eq!(cmd.execute(&backend), RespFrame::Integer(100));
+
+        let cmd = PExpire {
+            key: "key".to_string(),
+            milliseconds: 5000,
+            condition: Some(ExpireCondition::Gt),
+        };
+        assert_eq!(cmd.execute(&backend), RespFrame::Integer(0));
+
+        let cmd = PExpireAt {
+            key: "key".to_string(),
+            timestamp: now_ms() as i64 + 50_000,
+            condition: None,
+        };
+        assert_eq!(cmd.execute(&backend), RespFrame::Integer(1));
+        assert!(backend.pttl("key") > 40_000);
+
+        let cmd = Persist {
+            key: "key".to_string(),
+        };
+        assert_eq!(cmd.execute(&backend), RespFrame::Integer(1));
+
+        let cmd = PTtl {
+            key: "key".to_string(),
+        };
+        assert_eq!(cmd.execute(&backend), RespFrame::Integer(-1));
+
+        let cmd = ExpireAt {
+            key: "key".to_string(),
+            timestamp: 1,
+            condition: None,
+        };
+        assert_eq!(cmd.execute(&backend), RespFrame::Integer(1));
+        assert!(!backend.exists("key"));
+
+        let cmd = PTtl {
+            key: "missing".to_string(),
+        };
+        assert_eq!(cmd.execute(&backend), RespFrame::Integer(-2));
+        Ok(())
+    }
+}
@@ -0,0 +1,478 @@
+use super::zset::{check_zset_type, zset_error};
+use crate::cmd::{
+    extract_args, parse_i64, parse_string, validate_command_at_least, CommandError,
+    CommandExecutor, GeoAdd, GeoDist, GeoFrom, GeoHash, GeoPos, GeoSearch, GeoSort,
+};
+use crate::{
+    Array, Backend, BulkString, GeoPoint, GeoShape, Null, RespFrame, SimpleError, ZAddFlags,
+};
+
+impl CommandExecutor for GeoAdd {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let entries = self
+            .entries
+            .into_iter()
+            .map(|(point, member)| (point.hash() as f64, member))
+            .collect();
+        match backend.zadd(self.key, entries, self.flags) {
+            Ok((added, changed)) => {
+                RespFrame::Integer(if self.ch { changed } else { added } as i64)
+            }
+            Err(e) => zset_error(e),
+        }
+    }
+}
+
+impl CommandExecutor for GeoDist {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        if let Some(err) = check_zset_type(backend, &self.key) {
+            return err;
+        }
+        match backend.geodist(&self.key, &self.from, &self.to) {
+            Some(distance) => distance_reply(distance / self.unit),
+            None => RespFrame::Null(Null),
+        }
+    }
+}
+
+impl CommandExecutor for GeoPos {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        if let Some(err) = check_zset_type(backend, &self.key) {
+            return err;
+        }
+        let points = backend
+            .geopos(&self.key, &self.members)
+            .into_iter()
+            .map(|point| match point {
+                Some(point) => coord_reply(point),
+                None => Array::none().into(),
+            })
+            .collect::<Vec<_>>();
+        Array::new(points).into()
+    }
+}
+
+impl CommandExecutor for GeoHash {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        if let Some(err) = check_zset_type(backend, &self.key) {
+            return err;
+        }
+        let hashes = backend
+            .geopos(&self.key, &self.members)
+            .into_iter()
+            .map(|point| match point {
+                Some(point) => BulkString::new(point.geohash_string()).into(),
+                None => RespFrame::Null(Null),
+            })
+            .collect::<Vec<_>>();
+        Array::new(hashes).into()
+    }
+}
+
+impl CommandExecutor for GeoSearch {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        if let Some(err) = check_zset_type(backend, &self.key) {
+            return err;
+        }
+        let center = match self.from {
+            GeoFrom::Point(point) => point,
+            GeoFrom::Member(member) => match backend.geopos(&self.key, &[member])[0] {
+                Some(point) => point,
+                None => {
+                    return SimpleError::new("ERR could not decode requested zset member").into()
+                }
+            },
+        };
+        let mut found = backend.geosearch(&self.key, center, self.shape);
+
+        // COUNT returns the nearest members, unless ANY of them will do
+        let sort = match (self.sort, self.count) {
+            (None, Some((_, false))) => Some(GeoSort::Asc),
+            (sort, _) => sort,
+        };
+        match sort {
+            Some(GeoSort::Asc) => found.sort_by(|a, b| a.distance.total_cmp(&b.distance)),
+            Some(GeoSort::Desc) => found.sort_by(|a, b| b.distance.total_cmp(&a.distance)),
+            None => {}
+        }
+        if let Some((count, _)) = self.count {
+            found.truncate(count);
+        }
+
+        let with_any = self.with_coord || self.with_dist || self.with_hash;
+        let found = found
+            .into_iter()
+            .map(|found| {
+                let member = BulkString::new(found.member).into();
+                if !with_any {
+                    return member;
+                }
+                let mut item = vec![member];
+                if self.with_dist {
+                    item.push(distance_reply(found.distance / self.unit));
+                }
+                if self.with_hash {
+                    item.push(RespFrame::Integer(found.hash as i64));
+                }
+                if self.with_coord {
+                    item.push(coord_reply(found.point));
+                }
+                Array::new(item).into()
+            })
+            .collect::<Vec<RespFrame>>();
+        Array::new(found).into()
+    }
+}
+
+// distances are replied with a precision of 0.1 millimeter of the unit
+fn distance_reply(distance: f64) -> RespFrame {
+    BulkString::new(format!("{:.4}", distance)).into()
+}
+
+fn coord_reply(point: GeoPoint) -> RespFrame {
+    Array::new(vec![
+        BulkString::new(point.lon.to_string()).into(),
+        BulkString::new(point.lat.to_string()).into(),
+    ])
+    .into()
+}
+
+fn parse_float(frame: Option<RespFrame>) -> Result<f64, CommandError> {
+    parse_string(frame)?
+        .parse::<f64>()
+        .ok()
+        .filter(|v| v.is_finite())
+        .ok_or_else(|| CommandError::InvalidArgument("value is not a valid float".to_string()))
+}
+
+fn parse_point(lon: Option<RespFrame>, lat: Option<RespFrame>) -> Result<GeoPoint, CommandError> {
+    GeoPoint::new(parse_float(lon)?, parse_float(lat)?)
+        .map_err(|e| CommandError::InvalidArgument(e.to_string()))
+}
+
+// the number of meters in a unit
+fn parse_unit(frame: Option<RespFrame>) -> Result<f64, CommandError> {
+    match parse_string(frame)?.to_ascii_lowercase().as_str() {
+        "m" => Ok(1.0),
+        "km" => Ok(1000.0),
+        "ft" => Ok(0.3048),
+        "mi" => Ok(1609.34),
+        _ => Err(CommandError::InvalidArgument(
+            "unsupported unit provided. please use M, KM, FT, MI".to_string(),
+        )),
+    }
+}
+
+fn parse_members(args: impl Iterator<Item = RespFrame>) -> Result<Vec<String>, CommandError> {
+    args.map(|member| parse_string(Some(member))).collect()
+}
+
+impl TryFrom<Array> for GeoAdd {
+    type Error = CommandError;
+    fn try_from(value: Array) -> Result<Self, Self::Error> {
+        validate_command_at_least(&value, &["geoadd"], 4)?;
+        let mut args = extract_args(value, 1)?.into_iter().peekable();
+        let key = parse_string(args.next())?;
+
+        let (mut flags, mut ch) = (ZAddFlags::default(), false);
+        while let Some(RespFrame::BulkString(option)) = args.peek() {
+            match option.as_ref().to_ascii_lowercase().as_slice() {
+                b"nx" => flags.nx = true,
+                b"xx" => flags.xx = true,
+                b"ch" => ch = true,
+                _ => break,
+            }
+            args.next();
+        }
+        if flags.nx && flags.xx {
+            return Err(CommandError::InvalidArgument(
+                "XX and NX options at the same time are not compatible".to_string(),
+            ));
+        }
+
+        let args = args.collect::<Vec<_>>();
+        if args.is_empty() || args.len() % 3 != 0 {
+            return Err(CommandError::InvalidArgument("syntax error".to_string()));
+        }
+        let mut entries = Vec::with_capacity(args.len() / 3);
+        let mut args = args.into_iter();
+        while let (Some(lon), Some(lat), Some(member)) = (args.next(), args.next(), args.next()) {
+            entries.push((
+                parse_point(Some(lon), Some(lat))?,
+                parse_string(Some(member))?,
+            ));
+        }
+        Ok(GeoAdd {
+            key,
+            entries,
+            flags,
+            ch,
+        })
+    }
+}
+
+impl TryFrom<Array> for GeoDist {
+    type Error = CommandError;
+    fn try_from(value: Array) -> Result<Self, Self::Error> {
+        validate_command_at_least(&value, &["geodist"], 3)?;
+        let mut args = extract_args(value, 1)?.into_iter();
+        let key = parse_string(args.next())?;
+        let from = parse_string(args.next())?;
+        let to = parse_string(args.next())?;
+        let unit = match (args.next(), args.next()) {
+            (None, _) => 1.0,
+            (unit, None) => parse_unit(unit)?,
+            _ => return Err(CommandError::InvalidArgument("syntax error".to_string())),
+        };
+        Ok(GeoDist {
+            key,
+            from,
+            to,
+            unit,
+        })
+    }
+}
+
+impl TryFrom<Array> for GeoPos {
+    type Error = CommandError;
+    fn try_from(value: Array) -> Result<Self, Self::Error> {
+        validate_command_at_least(&value, &["geopos"], 1)?;
+        let mut args = extract_args(value, 1)?.into_iter();
+        let key = parse_string(args.next())?;
+        Ok(GeoPos {
+            key,
+            members: parse_members(args)?,
+        })
+    }
+}
+
+impl TryFrom<Array> for GeoHash {
+    type Error = CommandError;
+    fn try_from(value: Array) -> Result<Self, Self::Error> {
+        validate_command_at_least(&value, &["geohash"], 1)?;
+        let mut args = extract_args(value, 1)?.into_iter();
+        let key = parse_string(args.next())?;
+        Ok(GeoHash {
+            key,
+            members: parse_members(args)?,
+        })
+    }
+}
+
+impl TryFrom<Array> for GeoSearch {
+    type Error = CommandError;
+    fn try_from(value: Array) -> Result<Self, Self::Error> {
+        validate_command_at_least(&value, &["geosearch"], 6)?;
+        let mut args = extract_args(value, 1)?.into_iter();
+        let key = parse_string(args.next())?;
+
+        let (mut from, mut shape, mut unit) = (None, None, 1.0);
+        let (mut sort, mut count, mut any) = (None, None, false);
+        let (mut with_coord, mut with_dist, mut with_hash) = (false, false, false);
+        let syntax_error = || CommandError::InvalidArgument("syntax error".to_string());
+        while let Some(option) = args.next() {
+            match parse_string(Some(option))?.to_ascii_lowercase().as_str() {
+                "frommember" if from.is_none() => {
+                    from = Some(GeoFrom::Member(parse_string(args.next())?))
+                }
+                "fromlonlat" if from.is_none() => {
+                    from = Some(GeoFrom::Point(parse_point(args.next(), args.next())?))
+                }
+                "frommember" | "fromlonlat" => {
+                    return Err(CommandError::InvalidArgument(
+                        "exactly one of FROMMEMBER or FROMLONLAT can be specified for GEOSEARCH"
+                            .to_string(),
+                    ))
+                }
+                "byradius" if shape.is_none() => {
+                    let radius = parse_float(args.next())?;
+                    if radius < 0.0 {
+                        return Err(CommandError::InvalidArgument(
+                            "radius cannot be negative".to_string(),
+                        ));
+                    }
+                    unit = parse_unit(args.next())?;
+                    shape = Some(GeoShape::Radius(radius * unit));
+                }
+                "bybox" if shape.is_none() => {
+                    let (width, height) = (parse_float(args.next())?, parse_float(args.next())?);
+                    if width < 0.0 || height < 0.0 {
+                        return Err(CommandError::InvalidArgument(
+                            "height or width cannot be negative".to_string(),
+                        ));
+                    }
+                    unit = parse_unit(args.next())?;
+                    shape = Some(GeoShape::Box {
+                        width: width * unit,
+                        height: height * unit,
+                    });
+                }
+                "byradius" | "bybox" => {
+                    return Err(CommandError::InvalidArgument(
+                        "exactly one of BYRADIUS and BYBOX can be specified for GEOSEARCH"
+                            .to_string(),
+                    ))
+                }
+                "asc" => sort = Some(GeoSort::Asc),
+                "desc" => sort = Some(GeoSort::Desc),
+                "count" => {
+                    let n = parse_i64(args.next())?;
+                    if n <= 0 {
+                        return Err(CommandError::InvalidArgument(
+                            "COUNT must be > 0".to_string(),
+                        ));
+                    }
+                    count = Some(n as usize);
+                }
+                "any" => any = true,
+                "withcoord" => with_coord = true,
+                "withdist" => with_dist = true,
+                "withhash" => with_hash = true,
+                _ => return Err(syntax_error()),
+            }
+        }
+
+        let Some(from) = from else {
+            return Err(CommandError::InvalidArgument(
+                "exactly one of FROMMEMBER or FROMLONLAT can be specified for GEOSEARCH"
+                    .to_string(),
+            ));
+        };
+        let Some(shape) = shape else {
+            return Err(CommandError::InvalidArgument(
+                "exactly one of BYRADIUS and BYBOX can be specified for GEOSEARCH".to_string(),
+            ));
+        };
+        if any && count.is_none() {
+            return Err(CommandError::InvalidArgument(
+                "the ANY argument requires COUNT argument".to_string(),
+            ));
+        }
+        Ok(GeoSearch {
+            key,
+            from,
+            shape,
+            unit,
+            sort,
+            count: count.map(|count| (count, any)),
+            with_coord,
+            with_dist,
+            with_hash,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use bytes::BytesMut;
+
+    use crate::RespDecode;
+
+    use super::*;
+
+    fn bulk(s: &str) -> RespFrame {
+        RespFrame::BulkString(s.into())
+    }
+
+    #[test]
+    fn test_geosearch_try_from_array() -> Result<()> {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(
+            b"*11\r\n$9\r\ngeosearch\r\n$6\r\nsicily\r\n$10\r\nFROMLONLAT\r\n$2\r\n15\r\n$2\r\n37\r\n$8\r\nBYRADIUS\r\n$3\r\n200\r\n$2\r\nkm\r\n$5\r\nCOUNT\r\n$1\r\n1\r\n$8\r\nWITHDIST\r\n",
+        );
+        let frame = Array::decode(&mut buf)?;
+        let cmd = GeoSearch::try_from(frame)?;
+        assert_eq!(
+            cmd.from,
+            GeoFrom::Point(GeoPoint {
+                lon: 15.0,
+                lat: 37.0
+            })
+        );
+        assert_eq!(cmd.shape, GeoShape::Radius(200_000.0));
+        assert_eq!(cmd.count, Some((1, false)));
+        assert!(cmd.with_dist && !cmd.with_coord);
+
+        buf.extend_from_slice(
+            b"*6\r\n$9\r\ngeosearch\r\n$6\r\nsicily\r\n$10\r\nFROMMEMBER\r\n$7\r\nPalermo\r\n$3\r\nASC\r\n$3\r\nANY\r\n",
+        );
+        let frame = Array::decode(&mut buf)?;
+        assert!(GeoSearch::try_from(frame).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_geo_commands() {
+        let backend = Backend::new();
+        let cmd = GeoAdd {
+            key: "sicily".to_string(),
+            entries: vec![
+                (
+                    GeoPoint::new(13.361389, 38.115556).unwrap(),
+                    "Palermo".to_string(),
+                ),
+                (
+                    GeoPoint::new(15.087269, 37.502669).unwrap(),
+                    "Catania".to_string(),
+                ),
+            ],
+            flags: ZAddFlags::default(),
+            ch: false,
+        };
+        assert_eq!(cmd.execute(&backend), RespFrame::Integer(2));
+
+        let cmd = GeoDist {
+            key: "sicily".to_string(),
+            from: "Palermo".to_string(),
+            to: "Catania".to_string(),
+            unit: 1000.0,
+        };
+        assert_eq!(cmd.execute(&backend), bulk("166.2742"));
+
+        let cmd = GeoHash {
+            key: "sicily".to_string(),
+            members: vec!["Palermo".to_string(), "Rome".to_string()],
+        };
+        assert_eq!(
+            cmd.execute(&backend),
+            Array::new(vec![bulk("sqc8b49rny0"), RespFrame::Null(Null)]).into()
+        );
+
+        let cmd = GeoSearch {
+            key: "sicily".to_string(),
+            from: GeoFrom::Point(GeoPoint::new(15.0, 37.0).unwrap()),
+            shape: GeoShape::Radius(200_000.0),
+            unit: 1000.0,
+            sort: Some(GeoSort::Desc),
+            count: None,
+            with_coord: false,
+            with_dist: true,
+            with_hash: false,
+        };
+        assert_eq!(
+            cmd.execute(&backend),
+            Array::new(vec![
+                Array::new(vec![bulk("Palermo"), bulk("190.4424")]).into(),
+                Array::new(vec![bulk("Catania"), bulk("56.4413")]).into(),
+            ])
+            .into()
+        );
+
+        let cmd = GeoSearch {
+            key: "sicily".to_string(),
+            from: GeoFrom::Member("Rome".to_string()),
+            shape: GeoShape::Radius(1.0),
+            unit: 1.0,
+            sort: None,
+            count: None,
+            with_coord: false,
+            with_dist: false,
+            with_hash: false,
+        };
+        assert_eq!(
+            cmd.execute(&backend),
+            SimpleError::new("ERR could not decode requested zset member").into()
+        );
+    }
+}
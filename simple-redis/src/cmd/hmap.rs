@@ -1,13 +1,16 @@
 use anyhow::Result;
 
-use crate::{Backend, BulkString, Array, RespFrame, Null};
 use crate::cmd::{
-    CommandError, CommandExecutor, extract_args, HGet, HGetAll, HMGet, HMSet, HSet,
-    RESP_OK, validate_command,
+    extract_args, validate_command, CommandError, CommandExecutor, HGet, HGetAll, HMGet, HMSet,
+    HSet, RESP_OK, RESP_WRONGTYPE,
 };
+use crate::{Array, Backend, BulkString, HashError, Null, RespFrame, SimpleError};
 
 impl CommandExecutor for HGet {
     fn execute(self, backend: &Backend) -> RespFrame {
+        if let Some(err) = check_hash_type(backend, &self.key) {
+            return err;
+        }
         backend
             .hget(&self.key, &self.field)
             .unwrap_or_else(|| RespFrame::Null(Null))
@@ -16,6 +19,9 @@ impl CommandExecutor for HGet {
 
 impl CommandExecutor for HGetAll {
     fn execute(self, backend: &Backend) -> RespFrame {
+        if let Some(err) = check_hash_type(backend, &self.key) {
+            return err;
+        }
         let hmap = backend.hgetall(&self.key);
         match hmap {
             Some(hmap) => {
@@ -41,39 +47,42 @@ impl CommandExecutor for HGetAll {
 
 impl CommandExecutor for HSet {
     fn execute(self, backend: &Backend) -> RespFrame {
-        backend.hset(self.key.clone(), self.field.clone(), self.value.clone());
-        RESP_OK.clone()
+        match backend.hset(self.key, self.field, self.value) {
+            Ok(added) => RespFrame::Integer(added as i64),
+            Err(e) => hash_error(e),
+        }
     }
 }
 
 impl CommandExecutor for HMSet {
     fn execute(self, backend: &Backend) -> RespFrame {
         let fields = self.fields.0.unwrap();
-        for i in (0..fields.len()).step_by(2) {
-            if let (Some(RespFrame::BulkString(key)), Some(RespFrame::BulkString(value))) =
-                (fields.get(i), fields.get(i + 1))
-            {
-                backend.hset(
-                    self.key.clone(),
+        let mut pairs = Vec::with_capacity(fields.len() / 2);
+        for pair in fields.chunks_exact(2) {
+            if let [RespFrame::BulkString(key), RespFrame::BulkString(value)] = pair {
+                pairs.push((
                     String::from_utf8_lossy(key.as_ref()).to_string(),
                     RespFrame::BulkString(value.clone()),
-                );
+                ));
             }
         }
-        RESP_OK.clone()
+        match backend.hmset(self.key, pairs) {
+            Ok(_) => RESP_OK.clone(),
+            Err(e) => hash_error(e),
+        }
     }
 }
 
 impl CommandExecutor for HMGet {
     fn execute(self, backend: &Backend) -> RespFrame {
+        if let Some(err) = check_hash_type(backend, &self.key) {
+            return err;
+        }
         let fields = self.fields.0.unwrap();
         let mut result = Vec::new();
         for field in fields {
             if let RespFrame::BulkString(field) = field {
-                let value = backend.hget(
-                    &self.key,
-                    &String::from_utf8_lossy(field.as_ref()).to_string(),
-                );
+                let value = backend.hget(&self.key, &String::from_utf8_lossy(field.as_ref()));
                 result.push(value.unwrap_or_else(|| RespFrame::Null(Null)));
             }
         }
@@ -81,6 +90,18 @@ impl CommandExecutor for HMGet {
     }
 }
 
+// the read-only commands don't change the hash, checking the type beforehand is enough for them
+fn check_hash_type(backend: &Backend, key: &str) -> Option<RespFrame> {
+    match backend.key_type(key) {
+        Some("hash") | None => None,
+        Some(_) => Some(RESP_WRONGTYPE.clone()),
+    }
+}
+
+fn hash_error(e: HashError) -> RespFrame {
+    SimpleError::new(e.to_string()).into()
+}
+
 impl TryFrom<Array> for HGet {
     type Error = CommandError;
     fn try_from(value: Array) -> Result<Self, Self::Error> {
@@ -138,7 +159,7 @@ impl TryFrom<Array> for HMSet {
 
     fn try_from(value: Array) -> std::result::Result<Self, Self::Error> {
         validate_command(&value, &["hmset"], value.0.as_ref().unwrap().len() - 1)?;
-        if value.0.as_ref().unwrap().len() % 2 != 0 {
+        if !value.0.as_ref().unwrap().len().is_multiple_of(2) {
             return Err(CommandError::InvalidArgument(
                 "Invalid argument".to_string(),
             ));
@@ -224,7 +245,7 @@ mod tests {
             value: RespFrame::BulkString(b"world".into()),
         };
         let result = cmd.execute(&backend);
-        assert_eq!(result, RESP_OK.clone());
+        assert_eq!(result, RespFrame::Integer(1));
 
         let cmd = HSet {
             key: "map".to_string(),
@@ -232,6 +253,12 @@ mod tests {
             value: RespFrame::BulkString(b"world1".into()),
         };
         cmd.execute(&backend);
+        let cmd = HSet {
+            key: "map".to_string(),
+            field: "hello1".to_string(),
+            value: RespFrame::BulkString(b"world1".into()),
+        };
+        assert_eq!(cmd.execute(&backend), RespFrame::Integer(0));
 
         let cmd = HGet {
             key: "map".to_string(),
@@ -332,4 +359,45 @@ mod tests {
         assert_eq!(result, expected.into());
         Ok(())
     }
+
+    #[test]
+    fn test_hash_commands_wrong_type() {
+        let backend = crate::Backend::new();
+        backend
+            .rpush("list".to_string(), vec![RespFrame::BulkString(b"a".into())])
+            .unwrap();
+        let cmd = HSet {
+            key: "list".to_string(),
+            field: "f".to_string(),
+            value: RespFrame::BulkString(b"v".into()),
+        };
+        assert_eq!(cmd.execute(&backend), RESP_WRONGTYPE.clone());
+        let cmd = HMSet {
+            key: "list".to_string(),
+            fields: Array(Some(vec![
+                RespFrame::BulkString(b"f".into()),
+                RespFrame::BulkString(b"v".into()),
+            ])),
+        };
+        assert_eq!(cmd.execute(&backend), RESP_WRONGTYPE.clone());
+        assert_eq!(backend.key_type("list"), Some("list"));
+        assert!(backend.hgetall("list").is_none());
+
+        backend.set("string".to_string(), "v".into());
+        let cmd = HGet {
+            key: "string".to_string(),
+            field: "f".to_string(),
+        };
+        assert_eq!(cmd.execute(&backend), RESP_WRONGTYPE.clone());
+        let cmd = HGetAll {
+            key: "string".to_string(),
+            sort: false,
+        };
+        assert_eq!(cmd.execute(&backend), RESP_WRONGTYPE.clone());
+        let cmd = HMGet {
+            key: "string".to_string(),
+            fields: Array(Some(vec![RespFrame::BulkString(b"f".into())])),
+        };
+        assert_eq!(cmd.execute(&backend), RESP_WRONGTYPE.clone());
+    }
 }
@@ -0,0 +1,161 @@
+use super::string::{check_string_type, parse_bytes};
+use crate::cmd::{
+    extract_args, parse_string, validate_command_at_least, CommandError, CommandExecutor, PfAdd,
+    PfCount, PfMerge, RESP_OK,
+};
+use crate::{Array, Backend, HllError, RespFrame, SimpleError};
+
+impl CommandExecutor for PfAdd {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.pfadd(&self.key, &self.elements) {
+            Ok(changed) => RespFrame::Integer(changed as i64),
+            Err(e) => hll_error(e),
+        }
+    }
+}
+
+impl CommandExecutor for PfCount {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        if let Some(err) = self
+            .keys
+            .iter()
+            .find_map(|key| check_string_type(backend, key))
+        {
+            return err;
+        }
+        match backend.pfcount(&self.keys) {
+            Ok(count) => RespFrame::Integer(count as i64),
+            Err(e) => hll_error(e),
+        }
+    }
+}
+
+impl CommandExecutor for PfMerge {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        if let Some(err) = self
+            .keys
+            .iter()
+            .find_map(|key| check_string_type(backend, key))
+        {
+            return err;
+        }
+        match backend.pfmerge(&self.dest, &self.keys) {
+            Ok(()) => RESP_OK.clone(),
+            Err(e) => hll_error(e),
+        }
+    }
+}
+
+fn hll_error(e: HllError) -> RespFrame {
+    SimpleError::new(e.to_string()).into()
+}
+
+fn parse_keys(args: impl Iterator<Item = RespFrame>) -> Result<Vec<String>, CommandError> {
+    args.map(|key| parse_string(Some(key))).collect()
+}
+
+impl TryFrom<Array> for PfAdd {
+    type Error = CommandError;
+    fn try_from(value: Array) -> Result<Self, Self::Error> {
+        validate_command_at_least(&value, &["pfadd"], 1)?;
+        let mut args = extract_args(value, 1)?.into_iter();
+        let key = parse_string(args.next())?;
+        let elements = args
+            .map(|element| parse_bytes(Some(element)))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(PfAdd { key, elements })
+    }
+}
+
+impl TryFrom<Array> for PfCount {
+    type Error = CommandError;
+    fn try_from(value: Array) -> Result<Self, Self::Error> {
+        validate_command_at_least(&value, &["pfcount"], 1)?;
+        let args = extract_args(value, 1)?.into_iter();
+        Ok(PfCount {
+            keys: parse_keys(args)?,
+        })
+    }
+}
+
+impl TryFrom<Array> for PfMerge {
+    type Error = CommandError;
+    fn try_from(value: Array) -> Result<Self, Self::Error> {
+        validate_command_at_least(&value, &["pfmerge"], 1)?;
+        let mut args = extract_args(value, 1)?.into_iter();
+        let dest = parse_string(args.next())?;
+        Ok(PfMerge {
+            dest,
+            keys: parse_keys(args)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use bytes::{Bytes, BytesMut};
+
+    use crate::cmd::RESP_WRONGTYPE;
+    use crate::RespDecode;
+
+    use super::*;
+
+    #[test]
+    fn test_pfadd_try_from_array() -> Result<()> {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(b"*4\r\n$5\r\npfadd\r\n$4\r\nhll1\r\n$1\r\na\r\n$1\r\nb\r\n");
+        let frame = Array::decode(&mut buf)?;
+        let cmd = PfAdd::try_from(frame)?;
+        assert_eq!(cmd.key, "hll1");
+        assert_eq!(cmd.elements, vec![Bytes::from("a"), Bytes::from("b")]);
+
+        buf.extend_from_slice(b"*1\r\n$7\r\npfmerge\r\n");
+        let frame = Array::decode(&mut buf)?;
+        assert!(PfMerge::try_from(frame).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_hll_commands() {
+        let backend = Backend::new();
+        let pfadd = |key: &str, elements: &[&str]| {
+            PfAdd {
+                key: key.to_string(),
+                elements: elements
+                    .iter()
+                    .map(|e| Bytes::from(e.to_string()))
+                    .collect(),
+            }
+            .execute(&backend)
+        };
+        let pfcount = |keys: &[&str]| {
+            PfCount {
+                keys: keys.iter().map(|k| k.to_string()).collect(),
+            }
+            .execute(&backend)
+        };
+        assert_eq!(pfadd("a", &["x", "y", "z"]), RespFrame::Integer(1));
+        assert_eq!(pfadd("a", &["x"]), RespFrame::Integer(0));
+        assert_eq!(pfadd("b", &["z", "w"]), RespFrame::Integer(1));
+        assert_eq!(pfcount(&["a"]), RespFrame::Integer(3));
+        assert_eq!(pfcount(&["a", "b", "missing"]), RespFrame::Integer(4));
+
+        let cmd = PfMerge {
+            dest: "c".to_string(),
+            keys: vec!["a".to_string(), "b".to_string()],
+        };
+        assert_eq!(cmd.execute(&backend), RESP_OK.clone());
+        assert_eq!(pfcount(&["c"]), RespFrame::Integer(4));
+
+        backend.set("s".to_string(), "value".into());
+        assert_eq!(
+            pfcount(&["s"]),
+            SimpleError::new("WRONGTYPE Key is not a valid HyperLogLog string value.").into()
+        );
+        backend
+            .lpush("l".to_string(), vec![RespFrame::BulkString("x".into())])
+            .unwrap();
+        assert_eq!(pfadd("l", &["x"]), RESP_WRONGTYPE.clone());
+    }
+}
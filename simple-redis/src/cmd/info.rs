@@ -0,0 +1,297 @@
+use std::fmt::Write as _;
+
+use crate::cmd::{
+    extract_args, not_in_context, parse_i64, parse_string, validate_command,
+    validate_command_at_least, CommandError, CommandExecutor, Info, Monitor, SlowLogCommand,
+    SlowLogSubcommand, RESP_OK,
+};
+use crate::{Array, Backend, BulkString, RespFrame};
+
+// the sections of INFO in order, with whether they are reported without arguments
+const SECTIONS: &[(&str, bool)] = &[
+    ("server", true),
+    ("clients", true),
+    ("memory", true),
+    ("persistence", true),
+    ("stats", true),
+    ("replication", true),
+    ("commandstats", false),
+    ("cluster", true),
+    ("keyspace", true),
+];
+
+impl CommandExecutor for Info {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let requested = |name: &str, default: bool| {
+            if self.sections.is_empty() {
+                return default;
+            }
+            self.sections.iter().any(|s| match s.as_str() {
+                "all" | "everything" => true,
+                "default" => default,
+                s => s == name,
+            })
+        };
+        let sections = SECTIONS
+            .iter()
+            .filter(|(name, default)| requested(name, *default))
+            .map(|(name, _)| section(backend, name))
+            .collect::<Vec<_>>();
+        BulkString::new(sections.join("\r\n")).into()
+    }
+}
+
+fn section(backend: &Backend, name: &str) -> String {
+    match name {
+        "server" => {
+            let config = backend.config();
+            let uptime = backend.uptime();
+            format!(
+                "# Server\r\nredis_version:{}\r\nredis_mode:{}\r\nos:{} {}\r\nprocess_id:{}\r\n\
+                 tcp_port:{}\r\nuptime_in_seconds:{}\r\nuptime_in_days:{}\r\nconfig_file:{}\r\n",
+                env!("CARGO_PKG_VERSION"),
+                if backend.cluster().is_some() {
+                    "cluster"
+                } else {
+                    "standalone"
+                },
+                std::env::consts::OS,
+                std::env::consts::ARCH,
+                std::process::id(),
+                config.server.port,
+                uptime,
+                uptime / 86400,
+                config
+                    .path
+                    .as_ref()
+                    .map(|path| path.display().to_string())
+                    .unwrap_or_default(),
+            )
+        }
+        "clients" => format!(
+            "# Clients\r\nconnected_clients:{}\r\nmaxclients:{}\r\n",
+            backend.connected_clients(),
+            backend.config().server.maxclients
+        ),
+        "memory" => {
+            let config = &backend.config().memory;
+            format!(
+                "# Memory\r\nused_memory:{}\r\nmaxmemory:{}\r\nmaxmemory_policy:{}\r\nevicted_keys:{}\r\n",
+                backend.used_memory(),
+                config.maxmemory,
+                config.maxmemory_policy.as_str(),
+                backend.evicted_keys()
+            )
+        }
+        "persistence" => format!(
+            "# Persistence\r\nloading:0\r\nrdb_changes_since_last_save:{}\r\n\
+             rdb_bgsave_in_progress:{}\r\nrdb_last_save_time:{}\r\naof_enabled:{}\r\n",
+            backend.changes_since_last_save(),
+            backend.bgsave_in_progress() as u8,
+            backend.last_save(),
+            backend.config().aof.enabled as u8
+        ),
+        "stats" => format!(
+            "# Stats\r\ntotal_connections_received:{}\r\ntotal_commands_processed:{}\r\n\
+             rejected_connections:{}\r\nexpired_keys:{}\r\nevicted_keys:{}\r\n\
+             pubsub_channels:{}\r\npubsub_patterns:{}\r\n",
+            backend.total_connections_received(),
+            backend.total_commands_processed(),
+            backend.rejected_connections(),
+            backend.expired_keys(),
+            backend.evicted_keys(),
+            backend.pubsub_channels(None).len(),
+            backend.pubsub_numpat()
+        ),
+        "replication" => backend.replication_info(),
+        "commandstats" => {
+            let mut info = String::from("# Commandstats\r\n");
+            for (name, stats) in backend.command_stats() {
+                let _ = write!(
+                    info,
+                    "cmdstat_{}:calls={},usec={},usec_per_call={:.2},rejected_calls=0,failed_calls={}\r\n",
+                    name,
+                    stats.calls,
+                    stats.usec,
+                    stats.usec as f64 / stats.calls as f64,
+                    stats.failed_calls
+                );
+            }
+            info
+        }
+        "cluster" => format!(
+            "# Cluster\r\ncluster_enabled:{}\r\n",
+            backend.cluster().is_some() as u8
+        ),
+        "keyspace" => {
+            let mut info = String::from("# Keyspace\r\n");
+            for db in backend.all_dbs().filter(|db| db.dbsize() > 0) {
+                let _ = write!(
+                    info,
+                    "db{}:keys={},expires={},avg_ttl=0\r\n",
+                    db.db(),
+                    db.dbsize(),
+                    db.volatile_keys()
+                );
+            }
+            info
+        }
+        _ => String::new(),
+    }
+}
+
+impl CommandExecutor for SlowLogCommand {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match self.sub {
+            SlowLogSubcommand::Get(count) => {
+                let count = usize::try_from(count).unwrap_or(usize::MAX);
+                let entries = backend
+                    .slowlog_get(count)
+                    .into_iter()
+                    .map(|entry| {
+                        let args = entry
+                            .args
+                            .into_iter()
+                            .map(|arg| BulkString::new(arg).into())
+                            .collect::<Vec<RespFrame>>();
+                        Array::new(vec![
+                            RespFrame::Integer(entry.id as i64),
+                            RespFrame::Integer(entry.timestamp as i64),
+                            RespFrame::Integer(entry.duration as i64),
+                            Array::new(args).into(),
+                            BulkString::new(entry.addr).into(),
+                            BulkString::new(entry.name).into(),
+                        ])
+                        .into()
+                    })
+                    .collect::<Vec<RespFrame>>();
+                Array::new(entries).into()
+            }
+            SlowLogSubcommand::Len => RespFrame::Integer(backend.slowlog_len() as i64),
+            SlowLogSubcommand::Reset => {
+                backend.slowlog_reset();
+                RESP_OK.clone()
+            }
+        }
+    }
+}
+
+// the connection turns into a monitor, which only the connection handler can do
+impl CommandExecutor for Monitor {
+    fn execute(self, _backend: &Backend) -> RespFrame {
+        not_in_context("monitor")
+    }
+}
+
+impl TryFrom<Array> for Info {
+    type Error = CommandError;
+    fn try_from(value: Array) -> Result<Self, Self::Error> {
+        validate_command_at_least(&value, &["info"], 0)?;
+        let sections = extract_args(value, 1)?
+            .into_iter()
+            .map(|v| parse_string(Some(v)).map(|s| s.to_ascii_lowercase()))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Info { sections })
+    }
+}
+
+impl TryFrom<Array> for SlowLogCommand {
+    type Error = CommandError;
+    fn try_from(value: Array) -> Result<Self, Self::Error> {
+        validate_command_at_least(&value, &["slowlog"], 1)?;
+        let mut args = extract_args(value, 1)?.into_iter();
+        let sub = parse_string(args.next())?.to_ascii_lowercase();
+        let sub = match (sub.as_str(), args.next(), args.next()) {
+            ("get", None, _) => SlowLogSubcommand::Get(10),
+            ("get", Some(count), None) => match parse_i64(Some(count))? {
+                count if count < -1 => {
+                    return Err(CommandError::InvalidArgument(
+                        "count should be greater than or equal to -1".to_string(),
+                    ))
+                }
+                count => SlowLogSubcommand::Get(count),
+            },
+            ("len", None, _) => SlowLogSubcommand::Len,
+            ("reset", None, _) => SlowLogSubcommand::Reset,
+            (sub, _, _) => {
+                return Err(CommandError::InvalidArgument(format!(
+                    "unknown subcommand or wrong number of arguments for '{}'",
+                    sub
+                )))
+            }
+        };
+        Ok(SlowLogCommand { sub })
+    }
+}
+
+impl TryFrom<Array> for Monitor {
+    type Error = CommandError;
+    fn try_from(value: Array) -> Result<Self, Self::Error> {
+        validate_command(&value, &["monitor"], 0)?;
+        Ok(Monitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bytes::Bytes;
+
+    use super::*;
+
+    #[test]
+    fn test_info_sections() {
+        let backend = Backend::new();
+        let info = |sections: &[&str]| {
+            let cmd = Info {
+                sections: sections.iter().map(|s| s.to_string()).collect(),
+            };
+            match cmd.execute(&backend) {
+                RespFrame::BulkString(s) => String::from_utf8_lossy(s.as_ref()).to_string(),
+                frame => panic!("INFO should reply with a bulk string, got {:?}", frame),
+            }
+        };
+        assert!(info(&[]).starts_with("# Server\r\n"));
+        assert!(info(&[]).contains("# Memory\r\nused_memory:0\r\n"));
+        assert!(info(&[]).contains("# Replication\r\nrole:master\r\n"));
+        assert!(!info(&[]).contains("# Commandstats"));
+        assert!(info(&[]).ends_with("# Cluster\r\ncluster_enabled:0\r\n\r\n# Keyspace\r\n"));
+        assert!(info(&["replication"]).ends_with("repl_backlog_histlen:0\r\n"));
+
+        backend.set("k".to_string(), "v".into());
+        backend.select(3).unwrap().set("k".to_string(), "v".into());
+        assert_eq!(
+            info(&["keyspace"]),
+            "# Keyspace\r\ndb0:keys=1,expires=0,avg_ttl=0\r\ndb3:keys=1,expires=0,avg_ttl=0\r\n"
+        );
+        backend.record_call("get", Duration::from_micros(3), false);
+        assert!(info(&["everything"]).contains(
+            "# Commandstats\r\ncmdstat_get:calls=1,usec=3,usec_per_call=3.00,rejected_calls=0,failed_calls=0\r\n"
+        ));
+    }
+
+    #[test]
+    fn test_slowlog_command() {
+        let backend = Backend::new();
+        let slowlog = |sub| SlowLogCommand { sub }.execute(&backend);
+        let args = [Bytes::from("keys"), Bytes::from("*")];
+        backend.slowlog_record(
+            &args,
+            Duration::from_millis(20),
+            "127.0.0.1:6000".to_string(),
+            "worker".to_string(),
+        );
+        assert_eq!(slowlog(SlowLogSubcommand::Len), RespFrame::Integer(1));
+        let RespFrame::Array(Array(Some(entries))) = slowlog(SlowLogSubcommand::Get(-1)) else {
+            panic!("SLOWLOG GET should reply with an array");
+        };
+        let RespFrame::Array(Array(Some(entry))) = &entries[0] else {
+            panic!("a slowlog entry should be an array");
+        };
+        assert_eq!(entry[2], RespFrame::Integer(20000));
+        assert_eq!(entry[5], BulkString::new("worker").into());
+        slowlog(SlowLogSubcommand::Reset);
+        assert_eq!(slowlog(SlowLogSubcommand::Len), RespFrame::Integer(0));
+    }
+}
@@ -0,0 +1,426 @@
+use crate::cmd::{
+    command_name, extract_args, not_in_context, parse_i64, parse_string, validate_command,
+    validate_command_at_least, CommandError, CommandExecutor, DbSize, Del, Exists, Flush, HScan,
+    Keys, Rename, Scan, ScanOptions, Select, Type, RESP_OK, RESP_WRONGTYPE,
+};
+use crate::{Array, Backend, BulkString, RespFrame, ScanStep, SimpleError, SimpleString};
+
+// the number of keys a SCAN step looks at, unless COUNT is given
+const DEFAULT_SCAN_COUNT: usize = 10;
+
+impl CommandExecutor for Del {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        RespFrame::Integer(backend.del(&self.keys) as i64)
+    }
+}
+
+impl CommandExecutor for Exists {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        // a key given several times is counted as many times
+        let n = self.keys.iter().filter(|key| backend.exists(key)).count();
+        RespFrame::Integer(n as i64)
+    }
+}
+
+impl CommandExecutor for Type {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let type_ = backend.key_type(&self.key).unwrap_or("none");
+        SimpleString::new(type_).into()
+    }
+}
+
+impl CommandExecutor for Keys {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let keys = backend
+            .keys(&self.pattern)
+            .into_iter()
+            .map(|key| BulkString::from(key).into())
+            .collect::<Vec<RespFrame>>();
+        Array::new(keys).into()
+    }
+}
+
+impl CommandExecutor for Scan {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let options = self.options;
+        let step = backend.scan(
+            self.cursor,
+            options.pattern.as_deref(),
+            options.count,
+            options.type_.as_deref(),
+        );
+        scan_reply(step, |key| vec![BulkString::from(key).into()])
+    }
+}
+
+impl CommandExecutor for HScan {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        if matches!(backend.key_type(&self.key), Some(type_) if type_ != "hash") {
+            return RESP_WRONGTYPE.clone();
+        }
+        let options = self.options;
+        let step = backend.hscan(
+            &self.key,
+            self.cursor,
+            options.pattern.as_deref(),
+            options.count,
+        );
+        scan_reply(step, |(field, value)| {
+            vec![BulkString::from(field).into(), value]
+        })
+    }
+}
+
+impl CommandExecutor for Rename {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match (backend.rename(&self.key, &self.new_key, self.nx), self.nx) {
+            (None, _) => SimpleError::new("ERR no such key").into(),
+            (Some(renamed), true) => RespFrame::Integer(renamed as i64),
+            (Some(_), false) => RESP_OK.clone(),
+        }
+    }
+}
+
+impl CommandExecutor for Flush {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match self.all {
+            true => backend.flushall(),
+            false => backend.flushdb(),
+        }
+        RESP_OK.clone()
+    }
+}
+
+impl CommandExecutor for DbSize {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        RespFrame::Integer(backend.dbsize() as i64)
+    }
+}
+
+// the selected database is part of the state of a connection
+impl CommandExecutor for Select {
+    fn execute(self, _backend: &Backend) -> RespFrame {
+        not_in_context("select")
+    }
+}
+
+impl Select {
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Switch the handle of the connection to the requested database.
+    pub fn execute_with(self, selected: &mut Backend) -> RespFrame {
+        if selected.cluster().is_some() && self.index != 0 {
+            return SimpleError::new("ERR SELECT is not allowed in cluster mode").into();
+        }
+        match selected.select(self.index) {
+            Some(backend) => {
+                *selected = backend;
+                RESP_OK.clone()
+            }
+            None => SimpleError::new("ERR DB index is out of range").into(),
+        }
+    }
+}
+
+impl TryFrom<Array> for Del {
+    type Error = CommandError;
+    fn try_from(value: Array) -> Result<Self, Self::Error> {
+        validate_command_at_least(&value, &["del"], 1)?;
+        Ok(Del {
+            keys: parse_keys(value)?,
+        })
+    }
+}
+
+impl TryFrom<Array> for Exists {
+    type Error = CommandError;
+    fn try_from(value: Array) -> Result<Self, Self::Error> {
+        validate_command_at_least(&value, &["exists"], 1)?;
+        Ok(Exists {
+            keys: parse_keys(value)?,
+        })
+    }
+}
+
+impl TryFrom<Array> for Type {
+    type Error = CommandError;
+    fn try_from(value: Array) -> Result<Self, Self::Error> {
+        validate_command(&value, &["type"], 1)?;
+        let mut args = extract_args(value, 1)?.into_iter();
+        Ok(Type {
+            key: parse_string(args.next())?,
+        })
+    }
+}
+
+impl TryFrom<Array> for Keys {
+    type Error = CommandError;
+    fn try_from(value: Array) -> Result<Self, Self::Error> {
+        validate_command(&value, &["keys"], 1)?;
+        let mut args = extract_args(value, 1)?.into_iter();
+        Ok(Keys {
+            pattern: parse_string(args.next())?,
+        })
+    }
+}
+
+impl TryFrom<Array> for Scan {
+    type Error = CommandError;
+    fn try_from(value: Array) -> Result<Self, Self::Error> {
+        validate_command_at_least(&value, &["scan"], 1)?;
+        let mut args = extract_args(value, 1)?.into_iter();
+        let cursor = parse_cursor(args.next())?;
+        Ok(Scan {
+            cursor,
+            options: parse_scan_options(args, true)?,
+        })
+    }
+}
+
+impl TryFrom<Array> for HScan {
+    type Error = CommandError;
+    fn try_from(value: Array) -> Result<Self, Self::Error> {
+        validate_command_at_least(&value, &["hscan"], 2)?;
+        let mut args = extract_args(value, 1)?.into_iter();
+        let key = parse_string(args.next())?;
+        let cursor = parse_cursor(args.next())?;
+        Ok(HScan {
+            key,
+            cursor,
+            options: parse_scan_options(args, false)?,
+        })
+    }
+}
+
+impl TryFrom<Array> for Rename {
+    type Error = CommandError;
+    fn try_from(value: Array) -> Result<Self, Self::Error> {
+        let name = command_name(&value, &["rename", "renamenx"])?;
+        validate_command(&value, &[name], 2)?;
+        let mut args = extract_args(value, 1)?.into_iter();
+        Ok(Rename {
+            key: parse_string(args.next())?,
+            new_key: parse_string(args.next())?,
+            nx: name == "renamenx",
+        })
+    }
+}
+
+impl TryFrom<Array> for Flush {
+    type Error = CommandError;
+    fn try_from(value: Array) -> Result<Self, Self::Error> {
+        let name = command_name(&value, &["flushdb", "flushall"])?;
+        validate_command_at_least(&value, &[name], 0)?;
+        let args = extract_args(value, 1)?;
+        // the keys are always freed right away, ASYNC is accepted for compatibility
+        match args.len() {
+            0 => {}
+            1 => match parse_string(args.into_iter().next())?
+                .to_ascii_lowercase()
+                .as_str()
+            {
+                "async" | "sync" => {}
+                _ => return Err(syntax_error()),
+            },
+            _ => return Err(syntax_error()),
+        }
+        Ok(Flush {
+            all: name == "flushall",
+        })
+    }
+}
+
+impl TryFrom<Array> for DbSize {
+    type Error = CommandError;
+    fn try_from(value: Array) -> Result<Self, Self::Error> {
+        validate_command(&value, &["dbsize"], 0)?;
+        Ok(DbSize)
+    }
+}
+
+impl TryFrom<Array> for Select {
+    type Error = CommandError;
+    fn try_from(value: Array) -> Result<Self, Self::Error> {
+        validate_command(&value, &["select"], 1)?;
+        let mut args = extract_args(value, 1)?.into_iter();
+        let index = usize::try_from(parse_i64(args.next())?)
+            .map_err(|_| CommandError::InvalidArgument("DB index is out of range".to_string()))?;
+        Ok(Select { index })
+    }
+}
+
+fn parse_keys(value: Array) -> Result<Vec<String>, CommandError> {
+    extract_args(value, 1)?
+        .into_iter()
+        .map(|v| parse_string(Some(v)))
+        .collect()
+}
+
+fn parse_cursor(frame: Option<RespFrame>) -> Result<u64, CommandError> {
+    parse_string(frame)?
+        .parse()
+        .map_err(|_| CommandError::InvalidArgument("invalid cursor".to_string()))
+}
+
+// MATCH pattern, COUNT count and, when `with_type`, TYPE type in any order
+fn parse_scan_options(
+    args: impl Iterator<Item = RespFrame>,
+    with_type: bool,
+) -> Result<ScanOptions, CommandError> {
+    let mut options = ScanOptions {
+        pattern: None,
+        count: DEFAULT_SCAN_COUNT,
+        type_: None,
+    };
+    let mut args = args;
+    while let Some(option) = args.next() {
+        let option = parse_string(Some(option))?.to_ascii_lowercase();
+        let arg = args.next().ok_or_else(syntax_error)?;
+        match option.as_str() {
+            "match" => options.pattern = Some(parse_string(Some(arg))?),
+            "count" => {
+                options.count = match parse_i64(Some(arg))? {
+                    count if count < 1 => return Err(syntax_error()),
+                    count => count as usize,
+                }
+            }
+            "type" if with_type => {
+                options.type_ = Some(parse_string(Some(arg))?.to_ascii_lowercase())
+            }
+            _ => return Err(syntax_error()),
+        }
+    }
+    Ok(options)
+}
+
+// the cursor to continue from and the items of the step
+fn scan_reply<T>(step: ScanStep<T>, item: impl Fn(T) -> Vec<RespFrame>) -> RespFrame {
+    let (cursor, items) = step;
+    let items = items.into_iter().flat_map(item).collect::<Vec<_>>();
+    Array::new(vec![
+        BulkString::from(cursor.to_string()).into(),
+        Array::new(items).into(),
+    ])
+    .into()
+}
+
+fn syntax_error() -> CommandError {
+    CommandError::InvalidArgument("syntax error".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use bytes::BytesMut;
+
+    use crate::cmd::Command;
+    use crate::{AppConfig, RespDecode};
+
+    use super::*;
+
+    #[test]
+    fn test_scan_try_from_array() -> Result<()> {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(
+            b"*8\r\n$4\r\nscan\r\n$2\r\n17\r\n$5\r\nMATCH\r\n$2\r\nk*\r\n$5\r\ncount\r\n$3\r\n100\r\n$4\r\ntype\r\n$4\r\nHASH\r\n",
+        );
+        let frame = Array::decode(&mut buf)?;
+        let cmd = Scan::try_from(frame)?;
+        assert_eq!(cmd.cursor, 17);
+        assert_eq!(
+            cmd.options,
+            ScanOptions {
+                pattern: Some("k*".to_string()),
+                count: 100,
+                type_: Some("hash".to_string()),
+            }
+        );
+
+        buf.extend_from_slice(b"*4\r\n$5\r\nhscan\r\n$3\r\nkey\r\n$1\r\n0\r\n$4\r\ntype\r\n");
+        let frame = Array::decode(&mut buf)?;
+        assert!(HScan::try_from(frame).is_err());
+        buf.extend_from_slice(b"*4\r\n$4\r\nscan\r\n$1\r\n0\r\n$5\r\ncount\r\n$1\r\n0\r\n");
+        let frame = Array::decode(&mut buf)?;
+        assert!(Scan::try_from(frame).is_err());
+        buf.extend_from_slice(b"*2\r\n$4\r\nscan\r\n$2\r\n-1\r\n");
+        let frame = Array::decode(&mut buf)?;
+        assert!(Scan::try_from(frame).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_keyspace_commands() -> Result<()> {
+        let backend = Backend::new();
+        let run = |backend: &Backend, args: &[&str]| -> Result<RespFrame> {
+            let frame = Array::new(
+                args.iter()
+                    .map(|arg| BulkString::new(*arg).into())
+                    .collect::<Vec<RespFrame>>(),
+            );
+            Ok(Command::try_from(frame)?.execute(backend))
+        };
+        run(&backend, &["set", "a", "1"])?;
+        run(&backend, &["hset", "h", "f", "v"])?;
+        assert_eq!(
+            run(&backend, &["exists", "a", "a", "b"])?,
+            RespFrame::Integer(2)
+        );
+        assert_eq!(
+            run(&backend, &["type", "h"])?,
+            SimpleString::new("hash").into()
+        );
+        assert_eq!(
+            run(&backend, &["type", "b"])?,
+            SimpleString::new("none").into()
+        );
+        assert_eq!(run(&backend, &["dbsize"])?, RespFrame::Integer(2));
+        assert_eq!(run(&backend, &["scan", "0", "type", "string"])?, {
+            let keys = Array::new(vec![BulkString::new("a").into()]);
+            Array::new(vec![BulkString::new("0").into(), keys.into()]).into()
+        });
+        assert_eq!(run(&backend, &["hscan", "a", "0"])?, RESP_WRONGTYPE.clone());
+
+        assert_eq!(
+            run(&backend, &["renamenx", "a", "h"])?,
+            RespFrame::Integer(0)
+        );
+        assert_eq!(run(&backend, &["rename", "a", "b"])?, RESP_OK.clone());
+        assert!(matches!(
+            run(&backend, &["rename", "a", "b"])?,
+            RespFrame::Error(e) if e.contains("no such key")
+        ));
+        assert_eq!(
+            run(&backend, &["del", "b", "h", "x"])?,
+            RespFrame::Integer(2)
+        );
+
+        run(&backend, &["set", "a", "1"])?;
+        assert_eq!(run(&backend, &["flushdb", "async"])?, RESP_OK.clone());
+        assert_eq!(run(&backend, &["dbsize"])?, RespFrame::Integer(0));
+        assert!(run(&backend, &["flushall", "now"]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_select() {
+        let mut selected = Backend::new();
+        let select = |index| Select { index };
+        assert_eq!(select(3).execute_with(&mut selected), RESP_OK.clone());
+        assert_eq!(selected.db(), 3);
+        assert!(matches!(
+            select(16).execute_with(&mut selected),
+            RespFrame::Error(e) if e.contains("out of range")
+        ));
+        assert_eq!(selected.db(), 3);
+
+        let mut config = AppConfig::default();
+        config.server.databases = 2;
+        let mut selected = Backend::with_config(config);
+        assert!(matches!(
+            select(2).execute_with(&mut selected),
+            RespFrame::Error(_)
+        ));
+    }
+}
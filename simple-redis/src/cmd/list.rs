@@ -0,0 +1,644 @@
+use std::time::Duration;
+
+use futures::future::select_all;
+use tokio::time::{timeout_at, Instant};
+
+use crate::cmd::{
+    extract_args, parse_i64, parse_string, validate_command, validate_command_at_least, BLPop,
+    BRPop, CommandError, CommandExecutor, LIndex, LLen, LPop, LPush, LPushX, LRange, LRem, LSet,
+    LTrim, RPop, RPush, RPushX, RESP_OK, RESP_WRONGTYPE,
+};
+use crate::{Array, Backend, BulkString, CommandLog, ListError, Null, RespFrame, SimpleError};
+
+impl CommandExecutor for LPush {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.lpush(self.key, self.values) {
+            Ok(len) => RespFrame::Integer(len as i64),
+            Err(e) => list_error(e),
+        }
+    }
+}
+
+impl CommandExecutor for RPush {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.rpush(self.key, self.values) {
+            Ok(len) => RespFrame::Integer(len as i64),
+            Err(e) => list_error(e),
+        }
+    }
+}
+
+impl CommandExecutor for LPushX {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.lpushx(self.key, self.values) {
+            Ok(len) => RespFrame::Integer(len as i64),
+            Err(e) => list_error(e),
+        }
+    }
+}
+
+impl CommandExecutor for RPushX {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.rpushx(self.key, self.values) {
+            Ok(len) => RespFrame::Integer(len as i64),
+            Err(e) => list_error(e),
+        }
+    }
+}
+
+impl CommandExecutor for LPop {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.lpop(&self.key, self.count.unwrap_or(1)) {
+            Ok(values) => pop_reply(values, self.count.is_some()),
+            Err(e) => list_error(e),
+        }
+    }
+}
+
+impl CommandExecutor for RPop {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.rpop(&self.key, self.count.unwrap_or(1)) {
+            Ok(values) => pop_reply(values, self.count.is_some()),
+            Err(e) => list_error(e),
+        }
+    }
+}
+
+impl CommandExecutor for LLen {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        if let Some(err) = check_list_type(backend, &self.key) {
+            return err;
+        }
+        RespFrame::Integer(backend.llen(&self.key) as i64)
+    }
+}
+
+impl CommandExecutor for LRange {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        if let Some(err) = check_list_type(backend, &self.key) {
+            return err;
+        }
+        Array::new(backend.lrange(&self.key, self.start, self.stop)).into()
+    }
+}
+
+impl CommandExecutor for LIndex {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        if let Some(err) = check_list_type(backend, &self.key) {
+            return err;
+        }
+        backend
+            .lindex(&self.key, self.index)
+            .unwrap_or(RespFrame::Null(Null))
+    }
+}
+
+impl CommandExecutor for LSet {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.lset(&self.key, self.index, self.value) {
+            Ok(Some(true)) => RESP_OK.clone(),
+            Ok(Some(false)) => SimpleError::new("ERR index out of range").into(),
+            Ok(None) => SimpleError::new("ERR no such key").into(),
+            Err(e) => list_error(e),
+        }
+    }
+}
+
+impl CommandExecutor for LTrim {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.ltrim(&self.key, self.start, self.stop) {
+            Ok(()) => RESP_OK.clone(),
+            Err(e) => list_error(e),
+        }
+    }
+}
+
+impl CommandExecutor for LRem {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.lrem(&self.key, self.count, &self.value) {
+            Ok(removed) => RespFrame::Integer(removed as i64),
+            Err(e) => list_error(e),
+        }
+    }
+}
+
+// without blocking (e.g. inside a transaction) BLPOP/BRPOP behave like a single pop attempt
+impl CommandExecutor for BLPop {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        try_pop_any(backend, &self.keys, true).unwrap_or(RespFrame::Null(Null))
+    }
+}
+
+impl CommandExecutor for BRPop {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        try_pop_any(backend, &self.keys, false).unwrap_or(RespFrame::Null(Null))
+    }
+}
+
+impl BLPop {
+    /// Pop from the first non-empty list, waiting for a push until the timeout expires.
+    pub async fn execute_blocking(self, backend: &Backend) -> RespFrame {
+        blocking_pop(backend, self.keys, self.timeout, true).await
+    }
+}
+
+impl BRPop {
+    /// Pop from the first non-empty list, waiting for a push until the timeout expires.
+    pub async fn execute_blocking(self, backend: &Backend) -> RespFrame {
+        blocking_pop(backend, self.keys, self.timeout, false).await
+    }
+}
+
+async fn blocking_pop(backend: &Backend, keys: Vec<String>, timeout: f64, left: bool) -> RespFrame {
+    // a timeout of zero blocks indefinitely
+    let deadline = (timeout > 0.0).then(|| Instant::now() + Duration::from_secs_f64(timeout));
+    let name = if left { "lpop" } else { "rpop" };
+    block_on_keys(backend, &keys, deadline, || {
+        // the command log is taken before the pop, as for any write, so that the pop is
+        // propagated in the order it happened in relative to the pushes to the same keys
+        let mut log = backend.command_log();
+        let ret = try_pop_any(backend, &keys, left)?;
+        if let Some(log) = log.as_mut() {
+            log.set_db(backend.db());
+            log_blocking_pop(log, name, &ret);
+        }
+        Some(ret)
+    })
+    .await
+    .unwrap_or(RespFrame::Null(Null))
+}
+
+/// Retry `attempt` whenever an element is pushed to one of the keys, until it succeeds or the
+/// deadline passes. Returns None on timeout.
+pub(super) async fn block_on_keys(
+    backend: &Backend,
+    keys: &[String],
+    deadline: Option<Instant>,
+    mut attempt: impl FnMut() -> Option<RespFrame>,
+) -> Option<RespFrame> {
+    let notifiers = keys
+        .iter()
+        .map(|key| backend.key_notifier(key))
+        .collect::<Vec<_>>();
+
+    let ret = loop {
+        // register interest before the attempt, so that a push in between is not missed
+        let mut notified = notifiers
+            .iter()
+            .map(|notify| Box::pin(notify.notified()))
+            .collect::<Vec<_>>();
+        for n in notified.iter_mut() {
+            n.as_mut().enable();
+        }
+
+        let ret = {
+            let _guard = backend.lock_shared();
+            attempt()
+        };
+        if ret.is_some() {
+            break ret;
+        }
+
+        match deadline {
+            Some(deadline) => {
+                if timeout_at(deadline, select_all(notified)).await.is_err() {
+                    break None;
+                }
+            }
+            None => {
+                select_all(notified).await;
+            }
+        }
+    };
+
+    drop(notifiers);
+    for key in keys.iter() {
+        backend.release_key_notifier(key);
+    }
+    ret
+}
+
+// pop one element from the first non-empty list, replying with [key, element]
+fn try_pop_any(backend: &Backend, keys: &[String], left: bool) -> Option<RespFrame> {
+    for key in keys {
+        let values = if left {
+            backend.lpop(key, 1)
+        } else {
+            backend.rpop(key, 1)
+        };
+        let values = match values {
+            Ok(values) => values,
+            Err(e) => return Some(list_error(e)),
+        };
+        if let Some(value) = values.and_then(|mut v| v.pop()) {
+            return Some(Array::new([BulkString::from(key.as_str()).into(), value]).into());
+        }
+    }
+    None
+}
+
+/// Log a blocking pop which got an element as the equivalent non-blocking pop.
+pub(crate) fn log_blocking_pop(log: &mut CommandLog, name: &str, ret: &RespFrame) {
+    let key = match ret {
+        RespFrame::Array(Array(Some(items))) => match items.first() {
+            Some(RespFrame::BulkString(key)) => key.clone(),
+            _ => return,
+        },
+        _ => return,
+    };
+    log.append(Array::new([BulkString::new(name).into(), key.into()]).into());
+}
+
+fn pop_reply(values: Option<Vec<RespFrame>>, with_count: bool) -> RespFrame {
+    match values {
+        Some(values) if with_count => Array::new(values).into(),
+        Some(mut values) => values.pop().unwrap_or(RespFrame::Null(Null)),
+        None => RespFrame::Null(Null),
+    }
+}
+
+fn list_error(e: ListError) -> RespFrame {
+    SimpleError::new(e.to_string()).into()
+}
+
+// the read-only commands don't change the list, checking the type beforehand is enough for them
+fn check_list_type(backend: &Backend, key: &str) -> Option<RespFrame> {
+    match backend.key_type(key) {
+        Some("list") | None => None,
+        Some(_) => Some(RESP_WRONGTYPE.clone()),
+    }
+}
+
+fn parse_key_values(
+    value: Array,
+    name: &'static str,
+) -> Result<(String, Vec<RespFrame>), CommandError> {
+    validate_command_at_least(&value, &[name], 2)?;
+    let mut args = extract_args(value, 1)?.into_iter();
+    let key = parse_string(args.next())?;
+    Ok((key, args.collect()))
+}
+
+fn parse_pop(value: Array, name: &'static str) -> Result<(String, Option<usize>), CommandError> {
+    validate_command_at_least(&value, &[name], 1)?;
+    let mut args = extract_args(value, 1)?.into_iter();
+    let key = parse_string(args.next())?;
+    let count = match args.next() {
+        Some(count) => {
+            let count = parse_i64(Some(count))?;
+            if count < 0 {
+                return Err(CommandError::InvalidArgument(
+                    "value is out of range, must be positive".to_string(),
+                ));
+            }
+            Some(count as usize)
+        }
+        None => None,
+    };
+    if args.next().is_some() {
+        return Err(CommandError::InvalidArgument(format!(
+            "{} command requires 1 or 2 arguments",
+            name
+        )));
+    }
+    Ok((key, count))
+}
+
+fn parse_range(value: Array, name: &'static str) -> Result<(String, i64, i64), CommandError> {
+    validate_command(&value, &[name], 3)?;
+    let mut args = extract_args(value, 1)?.into_iter();
+    let key = parse_string(args.next())?;
+    let start = parse_i64(args.next())?;
+    let stop = parse_i64(args.next())?;
+    Ok((key, start, stop))
+}
+
+fn parse_blocking_pop(
+    value: Array,
+    name: &'static str,
+) -> Result<(Vec<String>, f64), CommandError> {
+    validate_command_at_least(&value, &[name], 2)?;
+    let mut args = extract_args(value, 1)?;
+    let timeout = parse_string(args.pop())?.parse::<f64>().map_err(|_| {
+        CommandError::InvalidArgument("timeout is not a float or out of range".to_string())
+    })?;
+    if timeout < 0.0 || !timeout.is_finite() {
+        return Err(CommandError::InvalidArgument(
+            "timeout is negative".to_string(),
+        ));
+    }
+    let keys = args
+        .into_iter()
+        .map(|key| parse_string(Some(key)))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok((keys, timeout))
+}
+
+impl TryFrom<Array> for LPush {
+    type Error = CommandError;
+    fn try_from(value: Array) -> Result<Self, Self::Error> {
+        let (key, values) = parse_key_values(value, "lpush")?;
+        Ok(LPush { key, values })
+    }
+}
+
+impl TryFrom<Array> for RPush {
+    type Error = CommandError;
+    fn try_from(value: Array) -> Result<Self, Self::Error> {
+        let (key, values) = parse_key_values(value, "rpush")?;
+        Ok(RPush { key, values })
+    }
+}
+
+impl TryFrom<Array> for LPushX {
+    type Error = CommandError;
+    fn try_from(value: Array) -> Result<Self, Self::Error> {
+        let (key, values) = parse_key_values(value, "lpushx")?;
+        Ok(LPushX { key, values })
+    }
+}
+
+impl TryFrom<Array> for RPushX {
+    type Error = CommandError;
+    fn try_from(value: Array) -> Result<Self, Self::Error> {
+        let (key, values) = parse_key_values(value, "rpushx")?;
+        Ok(RPushX { key, values })
+    }
+}
+
+impl TryFrom<Array> for LPop {
+    type Error = CommandError;
+    fn try_from(value: Array) -> Result<Self, Self::Error> {
+        let (key, count) = parse_pop(value, "lpop")?;
+        Ok(LPop { key, count })
+    }
+}
+
+impl TryFrom<Array> for RPop {
+    type Error = CommandError;
+    fn try_from(value: Array) -> Result<Self, Self::Error> {
+        let (key, count) = parse_pop(value, "rpop")?;
+        Ok(RPop { key, count })
+    }
+}
+
+impl TryFrom<Array> for LLen {
+    type Error = CommandError;
+    fn try_from(value: Array) -> Result<Self, Self::Error> {
+        validate_command(&value, &["llen"], 1)?;
+        let mut args = extract_args(value, 1)?.into_iter();
+        Ok(LLen {
+            key: parse_string(args.next())?,
+        })
+    }
+}
+
+impl TryFrom<Array> for LRange {
+    type Error = CommandError;
+    fn try_from(value: Array) -> Result<Self, Self::Error> {
+        let (key, start, stop) = parse_range(value, "lrange")?;
+        Ok(LRange { key, start, stop })
+    }
+}
+
+impl TryFrom<Array> for LTrim {
+    type Error = CommandError;
+    fn try_from(value: Array) -> Result<Self, Self::Error> {
+        let (key, start, stop) = parse_range(value, "ltrim")?;
+        Ok(LTrim { key, start, stop })
+    }
+}
+
+impl TryFrom<Array> for LIndex {
+    type Error = CommandError;
+    fn try_from(value: Array) -> Result<Self, Self::Error> {
+        validate_command(&value, &["lindex"], 2)?;
+        let mut args = extract_args(value, 1)?.into_iter();
+        Ok(LIndex {
+            key: parse_string(args.next())?,
+            index: parse_i64(args.next())?,
+        })
+    }
+}
+
+impl TryFrom<Array> for LSet {
+    type Error = CommandError;
+    fn try_from(value: Array) -> Result<Self, Self::Error> {
+        validate_command(&value, &["lset"], 3)?;
+        let mut args = extract_args(value, 1)?.into_iter();
+        let key = parse_string(args.next())?;
+        let index = parse_i64(args.next())?;
+        match args.next() {
+            Some(value) => Ok(LSet { key, index, value }),
+            None => Err(CommandError::InvalidArgument(
+                "Invalid argument".to_string(),
+            )),
+        }
+    }
+}
+
+impl TryFrom<Array> for LRem {
+    type Error = CommandError;
+    fn try_from(value: Array) -> Result<Self, Self::Error> {
+        validate_command(&value, &["lrem"], 3)?;
+        let mut args = extract_args(value, 1)?.into_iter();
+        let key = parse_string(args.next())?;
+        let count = parse_i64(args.next())?;
+        match args.next() {
+            Some(value) => Ok(LRem { key, count, value }),
+            None => Err(CommandError::InvalidArgument(
+                "Invalid argument".to_string(),
+            )),
+        }
+    }
+}
+
+impl TryFrom<Array> for BLPop {
+    type Error = CommandError;
+    fn try_from(value: Array) -> Result<Self, Self::Error> {
+        let (keys, timeout) = parse_blocking_pop(value, "blpop")?;
+        Ok(BLPop { keys, timeout })
+    }
+}
+
+impl TryFrom<Array> for BRPop {
+    type Error = CommandError;
+    fn try_from(value: Array) -> Result<Self, Self::Error> {
+        let (keys, timeout) = parse_blocking_pop(value, "brpop")?;
+        Ok(BRPop { keys, timeout })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use bytes::BytesMut;
+
+    use crate::{RespDecode, RespEncode};
+
+    use super::*;
+
+    fn bulk(s: &str) -> RespFrame {
+        RespFrame::BulkString(s.into())
+    }
+
+    #[test]
+    fn test_lpush_try_from_array() -> Result<()> {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(b"*4\r\n$5\r\nlpush\r\n$4\r\nlist\r\n$1\r\na\r\n$1\r\nb\r\n");
+        let frame = Array::decode(&mut buf)?;
+        let result = LPush::try_from(frame)?;
+        assert_eq!(result.key, "list");
+        assert_eq!(result.values, vec![bulk("a"), bulk("b")]);
+
+        buf.extend_from_slice(b"*2\r\n$5\r\nrpush\r\n$4\r\nlist\r\n");
+        let frame = Array::decode(&mut buf)?;
+        assert!(RPush::try_from(frame).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_blpop_try_from_array() -> Result<()> {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(b"*4\r\n$5\r\nblpop\r\n$1\r\na\r\n$1\r\nb\r\n$3\r\n0.5\r\n");
+        let frame = Array::decode(&mut buf)?;
+        let result = BLPop::try_from(frame)?;
+        assert_eq!(result.keys, vec!["a".to_string(), "b".to_string()]);
+        assert_eq!(result.timeout, 0.5);
+
+        buf.extend_from_slice(b"*3\r\n$5\r\nbrpop\r\n$1\r\na\r\n$2\r\n-1\r\n");
+        let frame = Array::decode(&mut buf)?;
+        assert!(BRPop::try_from(frame).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_list_commands() -> Result<()> {
+        let backend = Backend::new();
+        let cmd = RPush {
+            key: "list".to_string(),
+            values: vec![bulk("a"), bulk("b"), bulk("c")],
+        };
+        assert_eq!(cmd.execute(&backend), RespFrame::Integer(3));
+
+        let cmd = LPushX {
+            key: "missing".to_string(),
+            values: vec![bulk("a")],
+        };
+        assert_eq!(cmd.execute(&backend), RespFrame::Integer(0));
+
+        let cmd = LRange {
+            key: "list".to_string(),
+            start: 0,
+            stop: -1,
+        };
+        assert_eq!(
+            cmd.execute(&backend),
+            Array::new([bulk("a"), bulk("b"), bulk("c")]).into()
+        );
+
+        let cmd = LPop {
+            key: "list".to_string(),
+            count: None,
+        };
+        assert_eq!(cmd.execute(&backend), bulk("a"));
+
+        let cmd = RPop {
+            key: "list".to_string(),
+            count: Some(5),
+        };
+        assert_eq!(
+            cmd.execute(&backend),
+            Array::new([bulk("c"), bulk("b")]).into()
+        );
+
+        let cmd = LLen {
+            key: "list".to_string(),
+        };
+        assert_eq!(cmd.execute(&backend), RespFrame::Integer(0));
+
+        let cmd = LSet {
+            key: "list".to_string(),
+            index: 0,
+            value: bulk("x"),
+        };
+        assert_eq!(
+            cmd.execute(&backend),
+            SimpleError::new("ERR no such key").into()
+        );
+
+        backend.set("string".to_string(), "value".into());
+        let cmd = LPush {
+            key: "string".to_string(),
+            values: vec![bulk("a")],
+        };
+        assert_eq!(cmd.execute(&backend), RESP_WRONGTYPE.clone());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_blpop_wakes_up_on_push() -> Result<()> {
+        let backend = Backend::new();
+        let cloned = backend.clone();
+        let handle = tokio::spawn(async move {
+            let cmd = BLPop {
+                keys: vec!["a".to_string(), "b".to_string()],
+                timeout: 0.0,
+            };
+            cmd.execute_blocking(&cloned).await
+        });
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        backend.rpush("b".to_string(), vec![bulk("value")]).unwrap();
+
+        let ret = handle.await?;
+        assert_eq!(ret, Array::new([bulk("b"), bulk("value")]).into());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_blpop_logged_after_push() -> Result<()> {
+        let backend = Backend::new();
+        let mut replica = backend.psync(1, "127.0.0.1".to_string(), 6380, "?", -1);
+        replica.stream.recv().await.unwrap();
+        let cloned = backend.clone();
+        let handle = tokio::spawn(async move {
+            let cmd = BLPop {
+                keys: vec!["a".to_string()],
+                timeout: 0.0,
+            };
+            cmd.execute_blocking(&cloned).await
+        });
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        let push = Array::new([bulk("rpush"), bulk("a"), bulk("value")]);
+        {
+            // as a push request is executed, with the command log held
+            let mut log = backend.command_log().unwrap();
+            backend.rpush("a".to_string(), vec![bulk("value")]).unwrap();
+            log.append(push.clone().into());
+        }
+
+        assert_eq!(handle.await?, Array::new([bulk("a"), bulk("value")]).into());
+        let mut stream = Vec::new();
+        for _ in 0..3 {
+            stream.extend_from_slice(&replica.stream.recv().await.unwrap());
+        }
+        let mut expected = Array::new([bulk("select"), bulk("0")]).encode();
+        expected.extend(push.encode());
+        expected.extend(Array::new([bulk("lpop"), bulk("a")]).encode());
+        assert_eq!(stream, expected);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_brpop_timeout() -> Result<()> {
+        let backend = Backend::new();
+        let cmd = BRPop {
+            keys: vec!["a".to_string()],
+            timeout: 0.05,
+        };
+        assert_eq!(cmd.execute_blocking(&backend).await, RespFrame::Null(Null));
+        Ok(())
+    }
+}
@@ -1,18 +1,61 @@
-use crate::{Backend, cmd::Get, Array, RespFrame, Null};
-use crate::cmd::{CommandError, CommandExecutor, extract_args, RESP_OK, Set, validate_command};
+use crate::cmd::{
+    extract_args, parse_i64, parse_string, validate_command, validate_command_at_least,
+    CommandError, CommandExecutor, Set, SetCondition, SetExpiration, RESP_OK, RESP_WRONGTYPE,
+};
+use crate::{cmd::Get, now_ms, Array, Backend, BulkString, Null, RespFrame, SetTtl};
 
 impl CommandExecutor for Get {
     fn execute(self, backend: &Backend) -> RespFrame {
+        if backend.is_non_string(&self.key) {
+            return RESP_WRONGTYPE.clone();
+        }
         backend
             .get(&self.key)
-            .unwrap_or_else(|| RespFrame::Null(Null))
+            .map(|v| BulkString::from(v).into())
+            .unwrap_or(RespFrame::Null(Null))
     }
 }
 
 impl CommandExecutor for Set {
     fn execute(self, backend: &Backend) -> RespFrame {
-        backend.set(self.key.clone(), self.value.clone());
-        RESP_OK.clone()
+        let ttl = match self.expiration {
+            None => SetTtl::Persist,
+            Some(SetExpiration::KeepTtl) => SetTtl::Keep,
+            Some(expiration) => SetTtl::At(expiration.deadline()),
+        };
+        let condition = self.condition;
+        let ret = backend.set_with(
+            self.key,
+            self.value,
+            ttl,
+            self.get,
+            |exists| match condition {
+                None => true,
+                Some(SetCondition::Nx) => !exists,
+                Some(SetCondition::Xx) => exists,
+            },
+        );
+        match (ret, self.get) {
+            (Err(_), _) => RESP_WRONGTYPE.clone(),
+            (Ok((_, old)), true) => old
+                .map(|v| BulkString::from(v).into())
+                .unwrap_or(RespFrame::Null(Null)),
+            (Ok((true, _)), false) => RESP_OK.clone(),
+            (Ok((false, _)), false) => RespFrame::Null(Null),
+        }
+    }
+}
+
+impl SetExpiration {
+    // absolute unix timestamp in milliseconds
+    fn deadline(&self) -> u64 {
+        match *self {
+            SetExpiration::Ex(seconds) => now_ms().saturating_add(seconds.saturating_mul(1000)),
+            SetExpiration::Px(milliseconds) => now_ms().saturating_add(milliseconds),
+            SetExpiration::ExAt(seconds) => seconds.saturating_mul(1000),
+            SetExpiration::PxAt(milliseconds) => milliseconds,
+            SetExpiration::KeepTtl => u64::MAX,
+        }
     }
 }
 
@@ -32,25 +75,74 @@ impl TryFrom<Array> for Get {
     }
 }
 
+// SET key value [NX | XX] [GET] [EX seconds | PX milliseconds |
+//   EXAT unix-time-seconds | PXAT unix-time-milliseconds | KEEPTTL]
 impl TryFrom<Array> for Set {
     type Error = CommandError;
     fn try_from(value: Array) -> Result<Self, Self::Error> {
-        validate_command(&value, &["set"], 2)?;
+        validate_command_at_least(&value, &["set"], 2)?;
         let mut args = extract_args(value, 1)?.into_iter();
-        match (args.next(), args.next()) {
-            (Some(RespFrame::BulkString(k)), Some(v)) => Ok(Set {
-                key: String::from_utf8_lossy(k.as_ref()).to_string(),
-                value: v,
-            }),
-            _ => Err(CommandError::InvalidArgument(
-                "Invalid argument".to_string(),
-            )),
+        let (key, value) = match (args.next(), args.next()) {
+            (Some(RespFrame::BulkString(k)), Some(RespFrame::BulkString(v))) => (
+                String::from_utf8_lossy(k.as_ref()).to_string(),
+                v.0.unwrap_or_default(),
+            ),
+            _ => {
+                return Err(CommandError::InvalidArgument(
+                    "Invalid argument".to_string(),
+                ))
+            }
+        };
+
+        let mut set = Set {
+            key,
+            value,
+            expiration: None,
+            condition: None,
+            get: false,
+        };
+        while let Some(option) = args.next() {
+            let option = parse_string(Some(option))?.to_ascii_lowercase();
+            match option.as_str() {
+                "nx" | "xx" if set.condition.is_none() => {
+                    set.condition = Some(if option == "nx" {
+                        SetCondition::Nx
+                    } else {
+                        SetCondition::Xx
+                    });
+                }
+                "get" => set.get = true,
+                "keepttl" if set.expiration.is_none() => {
+                    set.expiration = Some(SetExpiration::KeepTtl)
+                }
+                "ex" | "px" | "exat" | "pxat" if set.expiration.is_none() => {
+                    let n = parse_i64(args.next())?;
+                    if n <= 0 {
+                        return Err(CommandError::InvalidArgument(
+                            "invalid expire time in 'set' command".to_string(),
+                        ));
+                    }
+                    let n = n as u64;
+                    set.expiration = Some(match option.as_str() {
+                        "ex" => SetExpiration::Ex(n),
+                        "px" => SetExpiration::Px(n),
+                        "exat" => SetExpiration::ExAt(n),
+                        _ => SetExpiration::PxAt(n),
+                    });
+                }
+                _ => {
+                    return Err(CommandError::InvalidArgument("syntax error".to_string()));
+                }
+            }
         }
+        Ok(set)
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::sync::Barrier;
+
     use anyhow::Result;
     use bytes::BytesMut;
 
@@ -64,7 +156,10 @@ mod tests {
         let backend = Backend::new();
         let cmd = Set {
             key: "hello".to_string(),
-            value: RespFrame::BulkString(b"world".into()),
+            value: "world".into(),
+            expiration: None,
+            condition: None,
+            get: false,
         };
         let result = cmd.execute(&backend);
         assert_eq!(result, RESP_OK.clone());
@@ -94,7 +189,116 @@ mod tests {
         let frame = Array::decode(&mut buf)?;
         let result = Set::try_from(frame)?;
         assert_eq!(result.key, "key");
-        assert_eq!(result.value, RespFrame::BulkString(b"value".into()));
+        assert_eq!(result.value, "value");
+        Ok(())
+    }
+
+    #[test]
+    fn test_set_options_try_from_array() -> Result<()> {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(
+            b"*6\r\n$3\r\nset\r\n$3\r\nkey\r\n$5\r\nvalue\r\n$2\r\nEX\r\n$2\r\n10\r\n$2\r\nnx\r\n",
+        );
+        let frame = Array::decode(&mut buf)?;
+        let result = Set::try_from(frame)?;
+        assert_eq!(result.expiration, Some(SetExpiration::Ex(10)));
+        assert_eq!(result.condition, Some(SetCondition::Nx));
+        assert!(!result.get);
+
+        buf.extend_from_slice(
+            b"*6\r\n$3\r\nset\r\n$3\r\nkey\r\n$5\r\nvalue\r\n$2\r\nEX\r\n$2\r\n10\r\n$7\r\nkeepttl\r\n",
+        );
+        let frame = Array::decode(&mut buf)?;
+        assert!(Set::try_from(frame).is_err());
         Ok(())
     }
+
+    #[test]
+    fn test_set_nx_xx_get_ex_command() -> Result<()> {
+        let backend = Backend::new();
+        let cmd = Set {
+            key: "key".to_string(),
+            value: "v1".into(),
+            expiration: None,
+            condition: Some(SetCondition::Xx),
+            get: false,
+        };
+        assert_eq!(cmd.execute(&backend), RespFrame::Null(Null));
+
+        let cmd = Set {
+            key: "key".to_string(),
+            value: "v1".into(),
+            expiration: Some(SetExpiration::Px(10_000)),
+            condition: Some(SetCondition::Nx),
+            get: false,
+        };
+        assert_eq!(cmd.execute(&backend), RESP_OK.clone());
+        assert!(backend.pttl("key") > 0);
+
+        let cmd = Set {
+            key: "key".to_string(),
+            value: "v2".into(),
+            expiration: Some(SetExpiration::KeepTtl),
+            condition: None,
+            get: true,
+        };
+        assert_eq!(cmd.execute(&backend), RespFrame::BulkString(b"v1".into()));
+        assert!(backend.pttl("key") > 0);
+
+        let cmd = Set {
+            key: "key".to_string(),
+            value: "v3".into(),
+            expiration: None,
+            condition: None,
+            get: false,
+        };
+        cmd.execute(&backend);
+        assert_eq!(backend.pttl("key"), -1);
+
+        backend
+            .hset("hash".to_string(), "f".to_string(), 1.into())
+            .unwrap();
+        let cmd = Set {
+            key: "hash".to_string(),
+            value: "v".into(),
+            expiration: None,
+            condition: None,
+            get: true,
+        };
+        assert_eq!(cmd.execute(&backend), RESP_WRONGTYPE.clone());
+        Ok(())
+    }
+
+    #[test]
+    fn test_concurrent_set_nx() {
+        let backend = Backend::new();
+        let barrier = Barrier::new(8);
+        // only one of the clients racing for the lock gets it
+        let replies = std::thread::scope(|s| {
+            let handles = (0..8)
+                .map(|i| {
+                    let (backend, barrier) = (backend.clone(), &barrier);
+                    s.spawn(move || {
+                        let cmd = Set {
+                            key: "lock".to_string(),
+                            value: format!("client:{}", i).into(),
+                            expiration: None,
+                            condition: Some(SetCondition::Nx),
+                            get: false,
+                        };
+                        barrier.wait();
+                        cmd.execute(&backend)
+                    })
+                })
+                .collect::<Vec<_>>();
+            handles
+                .into_iter()
+                .map(|h| h.join().unwrap())
+                .collect::<Vec<_>>()
+        });
+        let ok = replies.iter().filter(|v| **v == RESP_OK.clone()).count();
+        assert_eq!(ok, 1);
+        assert_eq!(replies.len() - ok, 7);
+        assert!(backend.get("lock").is_some());
+    }
 }
@@ -1,15 +1,48 @@
+use bytes::Bytes;
 use enum_dispatch::enum_dispatch;
 use lazy_static::lazy_static;
 use thiserror::Error;
 
-use crate::{Backend, Array, RespError, RespFrame};
+pub use self::table::{lookup, CommandSpec, ACL_CATEGORIES, COMMAND_TABLE};
 
+pub(crate) use self::list::log_blocking_pop;
+
+use crate::{
+    Array, Backend, BitOperator, ClaimOptions, ExpireCondition, GeoPoint, GeoShape, RespError,
+    RespFrame, ScoreBound, SimpleError, StreamId, StreamTrim, XAddId, ZAddFlags,
+};
+
+mod acl;
+mod client;
+mod cluster;
+mod command;
+mod config;
+mod connection;
 mod echo;
+mod expire;
+mod geo;
 mod hmap;
+mod hyperloglog;
+mod info;
+mod keyspace;
+mod list;
 mod map;
+mod object;
+mod persistence;
+mod pubsub;
+mod replication;
+mod script;
+mod stream;
+mod string;
+mod table;
+mod transaction;
+mod zset;
 
 lazy_static! {
     static ref RESP_OK: RespFrame = RespFrame::SimpleString("OK".into());
+    static ref RESP_WRONGTYPE: RespFrame = RespFrame::Error(SimpleError::new(
+        "WRONGTYPE Operation against a key holding the wrong kind of value"
+    ));
 }
 
 #[enum_dispatch]
@@ -19,11 +52,16 @@ pub trait CommandExecutor {
 
 #[derive(Debug, Error)]
 pub enum CommandError {
-    #[error("Invalid command: {0}")]
+    #[error("{0}")]
     InvalidCommand(String),
-    #[error("Invalid argument: {0}")]
-    InvalidArgument(String),
     #[error("{0}")]
+    InvalidArgument(String),
+    // the name of the command and its first arguments, quoted
+    #[error("unknown command '{0}', with args beginning with: {1}")]
+    UnknownCommand(String, String),
+    #[error("wrong number of arguments for '{0}' command")]
+    WrongArity(String),
+    #[error("Protocol error: {0}")]
     RespError(#[from] RespError),
     #[error("Utf8 error: {0}")]
     Utf8Error(#[from] std::string::FromUtf8Error),
@@ -40,9 +78,109 @@ pub enum Command {
     HMSet(HMSet),
     HGetAll(HGetAll),
     Echo(Echo),
-
-    // unrecognized command
-    Unrecognized(Unrecognized),
+    Ping(Ping),
+    Hello(Hello),
+    Auth(Auth),
+    AclCommand(AclCommand),
+    ConfigCommand(ConfigCommand),
+    Expire(Expire),
+    PExpire(PExpire),
+    ExpireAt(ExpireAt),
+    PExpireAt(PExpireAt),
+    Ttl(Ttl),
+    PTtl(PTtl),
+    Persist(Persist),
+    LPush(LPush),
+    RPush(RPush),
+    LPushX(LPushX),
+    RPushX(RPushX),
+    LPop(LPop),
+    RPop(RPop),
+    LLen(LLen),
+    LRange(LRange),
+    LIndex(LIndex),
+    LSet(LSet),
+    LTrim(LTrim),
+    LRem(LRem),
+    BLPop(BLPop),
+    BRPop(BRPop),
+    ZAdd(ZAdd),
+    ZIncrBy(ZIncrBy),
+    ZRem(ZRem),
+    ZScore(ZScore),
+    ZCard(ZCard),
+    ZCount(ZCount),
+    ZRank(ZRank),
+    ZRange(ZRange),
+    Save(Save),
+    BgSave(BgSave),
+    LastSave(LastSave),
+    BgRewriteAof(BgRewriteAof),
+    Publish(Publish),
+    Subscribe(Subscribe),
+    Unsubscribe(Unsubscribe),
+    PSubscribe(PSubscribe),
+    PUnsubscribe(PUnsubscribe),
+    PubSub(PubSub),
+    Multi(Multi),
+    Exec(Exec),
+    Discard(Discard),
+    Watch(Watch),
+    Unwatch(Unwatch),
+    Commands(Commands),
+    ReplicaOf(ReplicaOf),
+    PSync(PSync),
+    ReplConf(ReplConf),
+    Info(Info),
+    SlowLogCommand(SlowLogCommand),
+    Monitor(Monitor),
+    ClientCommand(ClientCommand),
+    ClusterCommand(ClusterCommand),
+    Asking(Asking),
+    Eval(Eval),
+    Script(Script),
+    Object(Object),
+    XAdd(XAdd),
+    XLen(XLen),
+    XRange(XRange),
+    XTrim(XTrim),
+    XRead(XRead),
+    XGroup(XGroup),
+    XReadGroup(XReadGroup),
+    XAck(XAck),
+    XPending(XPending),
+    XClaim(XClaim),
+    Del(Del),
+    Exists(Exists),
+    Type(Type),
+    Keys(Keys),
+    Scan(Scan),
+    HScan(HScan),
+    Rename(Rename),
+    Flush(Flush),
+    DbSize(DbSize),
+    Select(Select),
+    IncrBy(IncrBy),
+    IncrByFloat(IncrByFloat),
+    Append(Append),
+    GetRange(GetRange),
+    SetRange(SetRange),
+    StrLen(StrLen),
+    GetDel(GetDel),
+    MGet(MGet),
+    MSet(MSet),
+    SetBit(SetBit),
+    GetBit(GetBit),
+    BitCount(BitCount),
+    BitOp(BitOp),
+    GeoAdd(GeoAdd),
+    GeoDist(GeoDist),
+    GeoPos(GeoPos),
+    GeoHash(GeoHash),
+    GeoSearch(GeoSearch),
+    PfAdd(PfAdd),
+    PfCount(PfCount),
+    PfMerge(PfMerge),
 }
 
 #[derive(Debug)]
@@ -53,7 +191,27 @@ pub struct Get {
 #[derive(Debug)]
 pub struct Set {
     key: String,
-    value: RespFrame,
+    value: Bytes,
+    expiration: Option<SetExpiration>,
+    condition: Option<SetCondition>,
+    get: bool,
+}
+
+// EX / PX / EXAT / PXAT / KEEPTTL options of SET
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SetExpiration {
+    Ex(u64),
+    Px(u64),
+    ExAt(u64),
+    PxAt(u64),
+    KeepTtl,
+}
+
+// NX / XX options of SET
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SetCondition {
+    Nx,
+    Xx,
 }
 
 #[derive(Debug)]
@@ -93,7 +251,840 @@ pub struct Echo {
 }
 
 #[derive(Debug)]
-pub struct Unrecognized;
+pub struct Ping {
+    message: Option<RespFrame>,
+}
+
+// AUTH [username] password
+#[derive(Debug)]
+pub struct Auth {
+    username: Option<String>,
+    password: String,
+}
+
+// HELLO [protover [AUTH username password] [SETNAME clientname]]
+#[derive(Debug)]
+pub struct Hello {
+    protover: Option<i64>,
+    auth: Option<(String, String)>,
+    setname: Option<String>,
+}
+
+#[derive(Debug)]
+pub struct Expire {
+    key: String,
+    seconds: i64,
+    condition: Option<ExpireCondition>,
+}
+
+#[derive(Debug)]
+pub struct PExpire {
+    key: String,
+    milliseconds: i64,
+    condition: Option<ExpireCondition>,
+}
+
+#[derive(Debug)]
+pub struct ExpireAt {
+    key: String,
+    // unix time in seconds
+    timestamp: i64,
+    condition: Option<ExpireCondition>,
+}
+
+#[derive(Debug)]
+pub struct PExpireAt {
+    key: String,
+    // unix time in milliseconds
+    timestamp: i64,
+    condition: Option<ExpireCondition>,
+}
+
+#[derive(Debug)]
+pub struct Ttl {
+    key: String,
+}
+
+#[derive(Debug)]
+pub struct PTtl {
+    key: String,
+}
+
+#[derive(Debug)]
+pub struct Persist {
+    key: String,
+}
+
+#[derive(Debug)]
+pub struct LPush {
+    key: String,
+    values: Vec<RespFrame>,
+}
+
+#[derive(Debug)]
+pub struct RPush {
+    key: String,
+    values: Vec<RespFrame>,
+}
+
+#[derive(Debug)]
+pub struct LPushX {
+    key: String,
+    values: Vec<RespFrame>,
+}
+
+#[derive(Debug)]
+pub struct RPushX {
+    key: String,
+    values: Vec<RespFrame>,
+}
+
+#[derive(Debug)]
+pub struct LPop {
+    key: String,
+    count: Option<usize>,
+}
+
+#[derive(Debug)]
+pub struct RPop {
+    key: String,
+    count: Option<usize>,
+}
+
+#[derive(Debug)]
+pub struct LLen {
+    key: String,
+}
+
+#[derive(Debug)]
+pub struct LRange {
+    key: String,
+    start: i64,
+    stop: i64,
+}
+
+#[derive(Debug)]
+pub struct LIndex {
+    key: String,
+    index: i64,
+}
+
+#[derive(Debug)]
+pub struct LSet {
+    key: String,
+    index: i64,
+    value: RespFrame,
+}
+
+#[derive(Debug)]
+pub struct LTrim {
+    key: String,
+    start: i64,
+    stop: i64,
+}
+
+#[derive(Debug)]
+pub struct LRem {
+    key: String,
+    count: i64,
+    value: RespFrame,
+}
+
+#[derive(Debug)]
+pub struct BLPop {
+    keys: Vec<String>,
+    // in seconds, 0 blocks indefinitely
+    timeout: f64,
+}
+
+#[derive(Debug)]
+pub struct BRPop {
+    keys: Vec<String>,
+    timeout: f64,
+}
+
+#[derive(Debug)]
+pub struct ZAdd {
+    key: String,
+    entries: Vec<(f64, String)>,
+    flags: ZAddFlags,
+    // reply with the number of changed members instead of added ones
+    ch: bool,
+    incr: bool,
+}
+
+#[derive(Debug)]
+pub struct ZIncrBy {
+    key: String,
+    delta: f64,
+    member: String,
+}
+
+#[derive(Debug)]
+pub struct ZRem {
+    key: String,
+    members: Vec<String>,
+}
+
+#[derive(Debug)]
+pub struct ZScore {
+    key: String,
+    member: String,
+}
+
+#[derive(Debug)]
+pub struct ZCard {
+    key: String,
+}
+
+#[derive(Debug)]
+pub struct ZCount {
+    key: String,
+    min: ScoreBound,
+    max: ScoreBound,
+}
+
+// ZRANK and ZREVRANK
+#[derive(Debug)]
+pub struct ZRank {
+    key: String,
+    member: String,
+    rev: bool,
+}
+
+// ZRANGE, ZREVRANGE, ZRANGEBYSCORE and ZREVRANGEBYSCORE
+#[derive(Debug)]
+pub struct ZRange {
+    key: String,
+    by: ZRangeBy,
+    rev: bool,
+    // (offset, count)
+    limit: Option<(usize, i64)>,
+    with_scores: bool,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ZRangeBy {
+    Rank(i64, i64),
+    Score(ScoreBound, ScoreBound),
+}
+
+#[derive(Debug)]
+pub struct Save;
+
+#[derive(Debug)]
+pub struct BgSave;
+
+#[derive(Debug)]
+pub struct LastSave;
+
+#[derive(Debug)]
+pub struct BgRewriteAof;
+
+#[derive(Debug)]
+pub struct Publish {
+    channel: String,
+    message: RespFrame,
+}
+
+#[derive(Debug)]
+pub struct Subscribe {
+    channels: Vec<String>,
+}
+
+#[derive(Debug)]
+pub struct Unsubscribe {
+    // empty to unsubscribe from all channels
+    channels: Vec<String>,
+}
+
+#[derive(Debug)]
+pub struct PSubscribe {
+    patterns: Vec<String>,
+}
+
+#[derive(Debug)]
+pub struct PUnsubscribe {
+    // empty to unsubscribe from all patterns
+    patterns: Vec<String>,
+}
+
+#[derive(Debug)]
+pub struct PubSub {
+    sub: PubSubSubcommand,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum PubSubSubcommand {
+    Channels(Option<String>),
+    NumSub(Vec<String>),
+    NumPat,
+}
+
+#[derive(Debug)]
+pub struct Multi;
+
+#[derive(Debug)]
+pub struct Exec;
+
+#[derive(Debug)]
+pub struct Discard;
+
+#[derive(Debug)]
+pub struct Watch {
+    keys: Vec<String>,
+}
+
+#[derive(Debug)]
+pub struct Unwatch;
+
+// COMMAND [COUNT | LIST | INFO [command ...] | DOCS [command ...]]
+#[derive(Debug)]
+pub struct Commands {
+    sub: CommandsSubcommand,
+}
+
+#[derive(Debug, PartialEq)]
+enum CommandsSubcommand {
+    All,
+    Count,
+    List,
+    Info(Vec<String>),
+    Docs(Vec<String>),
+}
+
+// ACL SETUSER username [rule ...] | GETUSER username | DELUSER username [username ...] |
+//   WHOAMI | LIST | USERS | CAT [category]
+#[derive(Debug)]
+pub struct AclCommand {
+    sub: AclSubcommand,
+}
+
+#[derive(Debug, PartialEq)]
+enum AclSubcommand {
+    SetUser(String, Vec<String>),
+    GetUser(String),
+    DelUser(Vec<String>),
+    WhoAmI,
+    List,
+    Users,
+    Cat(Option<String>),
+}
+
+// CONFIG GET parameter [parameter ...] | SET parameter value [parameter value ...] | REWRITE
+#[derive(Debug)]
+pub struct ConfigCommand {
+    sub: ConfigSubcommand,
+}
+
+#[derive(Debug, PartialEq)]
+enum ConfigSubcommand {
+    Get(Vec<String>),
+    Set(Vec<(String, String)>),
+    Rewrite,
+}
+
+// REPLICAOF host port | NO ONE, also known as SLAVEOF
+#[derive(Debug)]
+pub struct ReplicaOf {
+    // None to stop replicating
+    master: Option<(String, u16)>,
+}
+
+// PSYNC replicationid offset, sent by a replica to its master
+#[derive(Debug)]
+pub struct PSync {
+    // "?" and -1 when the replica has no history to resume
+    replid: String,
+    offset: i64,
+}
+
+// REPLCONF option value [option value ...], sent by a replica to its master
+#[derive(Debug)]
+pub struct ReplConf {
+    options: Vec<ReplConfOption>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ReplConfOption {
+    ListeningPort(u16),
+    Capa(String),
+    // the offset processed by the replica
+    Ack(u64),
+    GetAck,
+}
+
+// CLUSTER SLOTS | SHARDS | NODES | INFO | MYID | KEYSLOT key
+#[derive(Debug)]
+pub struct ClusterCommand {
+    sub: ClusterSubcommand,
+}
+
+#[derive(Debug, PartialEq)]
+enum ClusterSubcommand {
+    Slots,
+    Shards,
+    Nodes,
+    Info,
+    MyId,
+    KeySlot(String),
+}
+
+#[derive(Debug)]
+pub struct Asking;
+
+// INFO [section ...]
+#[derive(Debug)]
+pub struct Info {
+    sections: Vec<String>,
+}
+
+// SLOWLOG GET [count] | LEN | RESET
+#[derive(Debug)]
+pub struct SlowLogCommand {
+    sub: SlowLogSubcommand,
+}
+
+#[derive(Debug, PartialEq)]
+enum SlowLogSubcommand {
+    // all the entries for a negative count
+    Get(i64),
+    Len,
+    Reset,
+}
+
+// MONITOR
+#[derive(Debug)]
+pub struct Monitor;
+
+// CLIENT LIST | INFO | ID | GETNAME | SETNAME name | KILL addr |
+//   KILL [ID id] [ADDR addr] [USER username] [SKIPME yes|no]
+#[derive(Debug)]
+pub struct ClientCommand {
+    sub: ClientSubcommand,
+}
+
+#[derive(Debug, PartialEq)]
+enum ClientSubcommand {
+    List,
+    Info,
+    Id,
+    GetName,
+    SetName(String),
+    Kill(ClientKill),
+}
+
+// the clients matching every filter are killed
+#[derive(Debug, PartialEq)]
+struct ClientKill {
+    id: Option<u64>,
+    addr: Option<String>,
+    user: Option<String>,
+    skipme: bool,
+    // KILL addr replies OK rather than the number of killed clients
+    legacy: bool,
+}
+
+// EVAL script numkeys [key ...] [arg ...], also EVALSHA sha1 numkeys [key ...] [arg ...]
+#[derive(Debug)]
+pub struct Eval {
+    source: ScriptSource,
+    keys: Vec<RespFrame>,
+    args: Vec<RespFrame>,
+}
+
+#[derive(Debug, PartialEq)]
+enum ScriptSource {
+    Body(String),
+    Sha(String),
+}
+
+// SCRIPT LOAD script | EXISTS sha1 [sha1 ...] | FLUSH [ASYNC|SYNC]
+#[derive(Debug)]
+pub struct Script {
+    sub: ScriptSubcommand,
+}
+
+#[derive(Debug, PartialEq)]
+enum ScriptSubcommand {
+    Load(String),
+    Exists(Vec<String>),
+    Flush,
+}
+
+// OBJECT FREQ | IDLETIME key
+#[derive(Debug)]
+pub struct Object {
+    sub: ObjectSubcommand,
+    key: String,
+}
+
+#[derive(Debug, PartialEq)]
+enum ObjectSubcommand {
+    Freq,
+    IdleTime,
+}
+
+// XADD key [NOMKSTREAM] [MAXLEN | MINID [= | ~] threshold [LIMIT count]] * | id field value
+//   [field value ...]
+#[derive(Debug)]
+pub struct XAdd {
+    key: String,
+    id: XAddId,
+    // position of the id in the request, a generated id is propagated in its place
+    id_index: usize,
+    fields: Vec<RespFrame>,
+    trim: Option<StreamTrim>,
+    no_mkstream: bool,
+}
+
+// XLEN key
+#[derive(Debug)]
+pub struct XLen {
+    key: String,
+}
+
+// XRANGE key start end [COUNT count], also XREVRANGE key end start [COUNT count]
+#[derive(Debug)]
+pub struct XRange {
+    key: String,
+    start: StreamId,
+    end: StreamId,
+    count: Option<usize>,
+    rev: bool,
+}
+
+// XTRIM key MAXLEN | MINID [= | ~] threshold [LIMIT count]
+#[derive(Debug)]
+pub struct XTrim {
+    key: String,
+    trim: StreamTrim,
+}
+
+// XREAD [COUNT count] [BLOCK milliseconds] STREAMS key [key ...] id [id ...]
+#[derive(Debug)]
+pub struct XRead {
+    keys: Vec<String>,
+    // None for `$`, the entries added from now on
+    ids: Vec<Option<StreamId>>,
+    count: Option<usize>,
+    // milliseconds to wait for an entry, 0 waits forever
+    block: Option<u64>,
+}
+
+// XGROUP CREATE key group id | $ [MKSTREAM] | SETID key group id | $ | DESTROY key group
+//   | CREATECONSUMER key group consumer | DELCONSUMER key group consumer
+#[derive(Debug)]
+pub struct XGroup {
+    key: String,
+    group: String,
+    sub: XGroupSubcommand,
+}
+
+#[derive(Debug, PartialEq)]
+enum XGroupSubcommand {
+    // None for `$`, the last entry of the stream
+    Create(Option<StreamId>, bool),
+    SetId(Option<StreamId>),
+    Destroy,
+    CreateConsumer(String),
+    DelConsumer(String),
+}
+
+// XREADGROUP GROUP group consumer [COUNT count] [BLOCK milliseconds] [NOACK] STREAMS key
+//   [key ...] id [id ...]
+#[derive(Debug)]
+pub struct XReadGroup {
+    group: String,
+    consumer: String,
+    keys: Vec<String>,
+    // None for `>`, the entries never delivered to the group
+    ids: Vec<Option<StreamId>>,
+    count: Option<usize>,
+    block: Option<u64>,
+    no_ack: bool,
+}
+
+// XACK key group id [id ...]
+#[derive(Debug)]
+pub struct XAck {
+    key: String,
+    group: String,
+    ids: Vec<StreamId>,
+}
+
+// XPENDING key group [[IDLE min-idle-time] start end count [consumer]]
+#[derive(Debug)]
+pub struct XPending {
+    key: String,
+    group: String,
+    // None for the summary of the pending entries
+    range: Option<XPendingRange>,
+}
+
+#[derive(Debug, PartialEq)]
+struct XPendingRange {
+    min_idle: u64,
+    start: StreamId,
+    end: StreamId,
+    count: usize,
+    consumer: Option<String>,
+}
+
+// XCLAIM key group consumer min-idle-time id [id ...] [IDLE ms] [TIME unix-time-milliseconds]
+//   [RETRYCOUNT count] [FORCE] [JUSTID] [LASTID id]
+#[derive(Debug)]
+pub struct XClaim {
+    key: String,
+    group: String,
+    consumer: String,
+    min_idle: u64,
+    ids: Vec<StreamId>,
+    options: ClaimOptions,
+}
+
+// DEL key [key ...]
+#[derive(Debug)]
+pub struct Del {
+    keys: Vec<String>,
+}
+
+// EXISTS key [key ...]
+#[derive(Debug)]
+pub struct Exists {
+    keys: Vec<String>,
+}
+
+// TYPE key
+#[derive(Debug)]
+pub struct Type {
+    key: String,
+}
+
+// KEYS pattern
+#[derive(Debug)]
+pub struct Keys {
+    pattern: String,
+}
+
+// SCAN cursor [MATCH pattern] [COUNT count] [TYPE type]
+#[derive(Debug)]
+pub struct Scan {
+    cursor: u64,
+    options: ScanOptions,
+}
+
+// HSCAN key cursor [MATCH pattern] [COUNT count]
+#[derive(Debug)]
+pub struct HScan {
+    key: String,
+    cursor: u64,
+    options: ScanOptions,
+}
+
+#[derive(Debug, PartialEq)]
+struct ScanOptions {
+    pattern: Option<String>,
+    count: usize,
+    type_: Option<String>,
+}
+
+// RENAME key newkey, also RENAMENX key newkey
+#[derive(Debug)]
+pub struct Rename {
+    key: String,
+    new_key: String,
+    nx: bool,
+}
+
+// FLUSHDB [ASYNC | SYNC], also FLUSHALL [ASYNC | SYNC]
+#[derive(Debug)]
+pub struct Flush {
+    all: bool,
+}
+
+#[derive(Debug)]
+pub struct DbSize;
+
+// SELECT index
+#[derive(Debug)]
+pub struct Select {
+    index: usize,
+}
+
+// INCRBY key increment, also INCR key, DECR key and DECRBY key decrement
+#[derive(Debug)]
+pub struct IncrBy {
+    key: String,
+    delta: i64,
+}
+
+// INCRBYFLOAT key increment
+#[derive(Debug)]
+pub struct IncrByFloat {
+    key: String,
+    delta: f64,
+}
+
+// APPEND key value
+#[derive(Debug)]
+pub struct Append {
+    key: String,
+    value: Bytes,
+}
+
+// GETRANGE key start end
+#[derive(Debug)]
+pub struct GetRange {
+    key: String,
+    start: i64,
+    end: i64,
+}
+
+// SETRANGE key offset value
+#[derive(Debug)]
+pub struct SetRange {
+    key: String,
+    offset: usize,
+    value: Bytes,
+}
+
+#[derive(Debug)]
+pub struct StrLen {
+    key: String,
+}
+
+#[derive(Debug)]
+pub struct GetDel {
+    key: String,
+}
+
+// MGET key [key ...]
+#[derive(Debug)]
+pub struct MGet {
+    keys: Vec<String>,
+}
+
+// MSET key value [key value ...], also MSETNX key value [key value ...]
+#[derive(Debug)]
+pub struct MSet {
+    pairs: Vec<(String, Bytes)>,
+    nx: bool,
+}
+
+// SETBIT key offset value
+#[derive(Debug)]
+pub struct SetBit {
+    key: String,
+    offset: usize,
+    bit: bool,
+}
+
+// GETBIT key offset
+#[derive(Debug)]
+pub struct GetBit {
+    key: String,
+    offset: usize,
+}
+
+// BITCOUNT key [start end [BYTE | BIT]]
+#[derive(Debug)]
+pub struct BitCount {
+    key: String,
+    // inclusive range, of bits rather than bytes if the flag is set
+    range: Option<(i64, i64, bool)>,
+}
+
+// BITOP AND | OR | XOR | NOT destkey key [key ...]
+#[derive(Debug)]
+pub struct BitOp {
+    op: BitOperator,
+    dest: String,
+    keys: Vec<String>,
+}
+
+// GEOADD key [NX | XX] [CH] longitude latitude member [longitude latitude member ...]
+#[derive(Debug)]
+pub struct GeoAdd {
+    key: String,
+    entries: Vec<(GeoPoint, String)>,
+    flags: ZAddFlags,
+    ch: bool,
+}
+
+// GEODIST key member1 member2 [M | KM | FT | MI]
+#[derive(Debug)]
+pub struct GeoDist {
+    key: String,
+    from: String,
+    to: String,
+    // meters per unit of the reply
+    unit: f64,
+}
+
+// GEOPOS key [member ...]
+#[derive(Debug)]
+pub struct GeoPos {
+    key: String,
+    members: Vec<String>,
+}
+
+// GEOHASH key [member ...]
+#[derive(Debug)]
+pub struct GeoHash {
+    key: String,
+    members: Vec<String>,
+}
+
+// GEOSEARCH key FROMMEMBER member | FROMLONLAT longitude latitude
+//   BYRADIUS radius M | KM | FT | MI | BYBOX width height M | KM | FT | MI
+//   [ASC | DESC] [COUNT count [ANY]] [WITHCOORD] [WITHDIST] [WITHHASH]
+#[derive(Debug)]
+pub struct GeoSearch {
+    key: String,
+    from: GeoFrom,
+    // in meters
+    shape: GeoShape,
+    unit: f64,
+    sort: Option<GeoSort>,
+    // the count and whether any members within the shape will do
+    count: Option<(usize, bool)>,
+    with_coord: bool,
+    with_dist: bool,
+    with_hash: bool,
+}
+
+#[derive(Debug, PartialEq)]
+enum GeoFrom {
+    Member(String),
+    Point(GeoPoint),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum GeoSort {
+    Asc,
+    Desc,
+}
+
+// PFADD key [element ...]
+#[derive(Debug)]
+pub struct PfAdd {
+    key: String,
+    elements: Vec<Bytes>,
+}
+
+// PFCOUNT key [key ...]
+#[derive(Debug)]
+pub struct PfCount {
+    keys: Vec<String>,
+}
+
+// PFMERGE destkey [sourcekey ...]
+#[derive(Debug)]
+pub struct PfMerge {
+    dest: String,
+    keys: Vec<String>,
+}
 
 impl TryFrom<RespFrame> for Command {
     type Error = CommandError;
@@ -118,21 +1109,134 @@ impl TryFrom<Array> for Command {
                     ));
                 }
 
-                match data.first() {
-                    Some(RespFrame::BulkString(ref cmd)) => match cmd.as_ref() {
-                        b"get" => Ok(Get::try_from(v)?.into()),
-                        b"set" => Ok(Set::try_from(v)?.into()),
-                        b"hget" => Ok(HGet::try_from(v)?.into()),
-                        b"hset" => Ok(HSet::try_from(v)?.into()),
-                        b"hgetall" => Ok(HGetAll::try_from(v)?.into()),
-                        b"echo" => Ok(Echo::try_from(v)?.into()),
-                        b"hmset" => Ok(HMSet::try_from(v)?.into()),
-                        b"hmget" => Ok(HMGet::try_from(v)?.into()),
-                        _ => Ok(Unrecognized.into()),
-                    },
-                    _ => Err(CommandError::InvalidCommand(
-                        "Command requires a BulkString as the first argument".to_string(),
-                    )),
+                let name = match data.first() {
+                    Some(RespFrame::BulkString(ref cmd)) => {
+                        String::from_utf8_lossy(cmd.as_ref()).to_ascii_lowercase()
+                    }
+                    _ => {
+                        return Err(CommandError::InvalidCommand(
+                            "Command requires a BulkString as the first argument".to_string(),
+                        ))
+                    }
+                };
+                let spec = lookup(&name).ok_or_else(|| unknown_command(data))?;
+                spec.check_arity(data.len())?;
+
+                match name.as_str() {
+                    "get" => Ok(Get::try_from(v)?.into()),
+                    "set" => Ok(Set::try_from(v)?.into()),
+                    "hget" => Ok(HGet::try_from(v)?.into()),
+                    "hset" => Ok(HSet::try_from(v)?.into()),
+                    "hgetall" => Ok(HGetAll::try_from(v)?.into()),
+                    "echo" => Ok(Echo::try_from(v)?.into()),
+                    "ping" => Ok(Ping::try_from(v)?.into()),
+                    "hello" => Ok(Hello::try_from(v)?.into()),
+                    "auth" => Ok(Auth::try_from(v)?.into()),
+                    "acl" => Ok(AclCommand::try_from(v)?.into()),
+                    "config" => Ok(ConfigCommand::try_from(v)?.into()),
+                    "hmset" => Ok(HMSet::try_from(v)?.into()),
+                    "hmget" => Ok(HMGet::try_from(v)?.into()),
+                    "expire" => Ok(Expire::try_from(v)?.into()),
+                    "pexpire" => Ok(PExpire::try_from(v)?.into()),
+                    "expireat" => Ok(ExpireAt::try_from(v)?.into()),
+                    "pexpireat" => Ok(PExpireAt::try_from(v)?.into()),
+                    "ttl" => Ok(Ttl::try_from(v)?.into()),
+                    "pttl" => Ok(PTtl::try_from(v)?.into()),
+                    "persist" => Ok(Persist::try_from(v)?.into()),
+                    "lpush" => Ok(LPush::try_from(v)?.into()),
+                    "rpush" => Ok(RPush::try_from(v)?.into()),
+                    "lpushx" => Ok(LPushX::try_from(v)?.into()),
+                    "rpushx" => Ok(RPushX::try_from(v)?.into()),
+                    "lpop" => Ok(LPop::try_from(v)?.into()),
+                    "rpop" => Ok(RPop::try_from(v)?.into()),
+                    "llen" => Ok(LLen::try_from(v)?.into()),
+                    "lrange" => Ok(LRange::try_from(v)?.into()),
+                    "lindex" => Ok(LIndex::try_from(v)?.into()),
+                    "lset" => Ok(LSet::try_from(v)?.into()),
+                    "ltrim" => Ok(LTrim::try_from(v)?.into()),
+                    "lrem" => Ok(LRem::try_from(v)?.into()),
+                    "blpop" => Ok(BLPop::try_from(v)?.into()),
+                    "brpop" => Ok(BRPop::try_from(v)?.into()),
+                    "zadd" => Ok(ZAdd::try_from(v)?.into()),
+                    "zincrby" => Ok(ZIncrBy::try_from(v)?.into()),
+                    "zrem" => Ok(ZRem::try_from(v)?.into()),
+                    "zscore" => Ok(ZScore::try_from(v)?.into()),
+                    "zcard" => Ok(ZCard::try_from(v)?.into()),
+                    "zcount" => Ok(ZCount::try_from(v)?.into()),
+                    "zrank" | "zrevrank" => Ok(ZRank::try_from(v)?.into()),
+                    "zrange" | "zrevrange" | "zrangebyscore" | "zrevrangebyscore" => {
+                        Ok(ZRange::try_from(v)?.into())
+                    }
+                    "save" => Ok(Save::try_from(v)?.into()),
+                    "bgsave" => Ok(BgSave::try_from(v)?.into()),
+                    "lastsave" => Ok(LastSave::try_from(v)?.into()),
+                    "bgrewriteaof" => Ok(BgRewriteAof::try_from(v)?.into()),
+                    "publish" => Ok(Publish::try_from(v)?.into()),
+                    "subscribe" => Ok(Subscribe::try_from(v)?.into()),
+                    "unsubscribe" => Ok(Unsubscribe::try_from(v)?.into()),
+                    "psubscribe" => Ok(PSubscribe::try_from(v)?.into()),
+                    "punsubscribe" => Ok(PUnsubscribe::try_from(v)?.into()),
+                    "pubsub" => Ok(PubSub::try_from(v)?.into()),
+                    "multi" => Ok(Multi::try_from(v)?.into()),
+                    "exec" => Ok(Exec::try_from(v)?.into()),
+                    "discard" => Ok(Discard::try_from(v)?.into()),
+                    "watch" => Ok(Watch::try_from(v)?.into()),
+                    "unwatch" => Ok(Unwatch::try_from(v)?.into()),
+                    "command" => Ok(Commands::try_from(v)?.into()),
+                    "replicaof" | "slaveof" => Ok(ReplicaOf::try_from(v)?.into()),
+                    "psync" => Ok(PSync::try_from(v)?.into()),
+                    "replconf" => Ok(ReplConf::try_from(v)?.into()),
+                    "info" => Ok(Info::try_from(v)?.into()),
+                    "slowlog" => Ok(SlowLogCommand::try_from(v)?.into()),
+                    "monitor" => Ok(Monitor::try_from(v)?.into()),
+                    "client" => Ok(ClientCommand::try_from(v)?.into()),
+                    "cluster" => Ok(ClusterCommand::try_from(v)?.into()),
+                    "asking" => Ok(Asking::try_from(v)?.into()),
+                    "eval" | "evalsha" => Ok(Eval::try_from(v)?.into()),
+                    "script" => Ok(Script::try_from(v)?.into()),
+                    "object" => Ok(Object::try_from(v)?.into()),
+                    "xadd" => Ok(XAdd::try_from(v)?.into()),
+                    "xlen" => Ok(XLen::try_from(v)?.into()),
+                    "xrange" | "xrevrange" => Ok(XRange::try_from(v)?.into()),
+                    "xtrim" => Ok(XTrim::try_from(v)?.into()),
+                    "xread" => Ok(XRead::try_from(v)?.into()),
+                    "xgroup" => Ok(XGroup::try_from(v)?.into()),
+                    "xreadgroup" => Ok(XReadGroup::try_from(v)?.into()),
+                    "xack" => Ok(XAck::try_from(v)?.into()),
+                    "xpending" => Ok(XPending::try_from(v)?.into()),
+                    "xclaim" => Ok(XClaim::try_from(v)?.into()),
+                    "del" => Ok(Del::try_from(v)?.into()),
+                    "exists" => Ok(Exists::try_from(v)?.into()),
+                    "type" => Ok(Type::try_from(v)?.into()),
+                    "keys" => Ok(Keys::try_from(v)?.into()),
+                    "scan" => Ok(Scan::try_from(v)?.into()),
+                    "hscan" => Ok(HScan::try_from(v)?.into()),
+                    "rename" | "renamenx" => Ok(Rename::try_from(v)?.into()),
+                    "flushdb" | "flushall" => Ok(Flush::try_from(v)?.into()),
+                    "dbsize" => Ok(DbSize::try_from(v)?.into()),
+                    "select" => Ok(Select::try_from(v)?.into()),
+                    "incr" | "decr" | "incrby" | "decrby" => Ok(IncrBy::try_from(v)?.into()),
+                    "incrbyfloat" => Ok(IncrByFloat::try_from(v)?.into()),
+                    "append" => Ok(Append::try_from(v)?.into()),
+                    "getrange" => Ok(GetRange::try_from(v)?.into()),
+                    "setrange" => Ok(SetRange::try_from(v)?.into()),
+                    "strlen" => Ok(StrLen::try_from(v)?.into()),
+                    "getdel" => Ok(GetDel::try_from(v)?.into()),
+                    "mget" => Ok(MGet::try_from(v)?.into()),
+                    "mset" | "msetnx" => Ok(MSet::try_from(v)?.into()),
+                    "setbit" => Ok(SetBit::try_from(v)?.into()),
+                    "getbit" => Ok(GetBit::try_from(v)?.into()),
+                    "bitcount" => Ok(BitCount::try_from(v)?.into()),
+                    "bitop" => Ok(BitOp::try_from(v)?.into()),
+                    "geoadd" => Ok(GeoAdd::try_from(v)?.into()),
+                    "geodist" => Ok(GeoDist::try_from(v)?.into()),
+                    "geopos" => Ok(GeoPos::try_from(v)?.into()),
+                    "geohash" => Ok(GeoHash::try_from(v)?.into()),
+                    "geosearch" => Ok(GeoSearch::try_from(v)?.into()),
+                    "pfadd" => Ok(PfAdd::try_from(v)?.into()),
+                    "pfcount" => Ok(PfCount::try_from(v)?.into()),
+                    "pfmerge" => Ok(PfMerge::try_from(v)?.into()),
+                    _ => Err(unknown_command(data)),
                 }
             }
             None => Err(CommandError::InvalidCommand(
@@ -142,12 +1246,107 @@ impl TryFrom<Array> for Command {
     }
 }
 
-impl CommandExecutor for Unrecognized {
-    fn execute(self, _: &Backend) -> RespFrame {
-        RESP_OK.clone()
+impl Command {
+    /// The position in the request of an id generated by the command, the id is propagated in
+    /// its place so that replaying the command generates the same one.
+    pub fn generated_id_index(&self) -> Option<usize> {
+        match self {
+            Command::XAdd(cmd) => cmd.generated_id_index(),
+            _ => None,
+        }
+    }
+
+    /// Whether the command may be executed by a RESP2 connection in subscribed mode.
+    pub fn allowed_in_subscribed_mode(&self) -> bool {
+        matches!(
+            self,
+            Command::Subscribe(_)
+                | Command::Unsubscribe(_)
+                | Command::PSubscribe(_)
+                | Command::PUnsubscribe(_)
+                | Command::Ping(_)
+        )
+    }
+
+    /// Whether the command controls a transaction, such commands are never queued by MULTI.
+    pub fn is_transaction_control(&self) -> bool {
+        matches!(
+            self,
+            Command::Multi(_) | Command::Exec(_) | Command::Discard(_) | Command::Watch(_)
+        )
+    }
+
+    /// The key whose ttl is set relative to now by the command. Such a ttl is logged as
+    /// an absolute PEXPIREAT, so that replaying the log later does not extend it.
+    pub fn relative_expire_key(&self) -> Option<&str> {
+        match self {
+            Command::Expire(cmd) => Some(&cmd.key),
+            Command::PExpire(cmd) => Some(&cmd.key),
+            Command::Set(cmd) => match cmd.expiration {
+                Some(SetExpiration::Ex(_)) | Some(SetExpiration::Px(_)) => Some(&cmd.key),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// The key whose new value is logged as a SET ... KEEPTTL in place of the command, so that
+    /// replaying the log stores the same value rather than computing it again.
+    pub fn logged_as_set_key(&self) -> Option<&str> {
+        match self {
+            Command::IncrByFloat(cmd) => Some(&cmd.key),
+            _ => None,
+        }
+    }
+}
+
+impl From<CommandError> for RespFrame {
+    fn from(e: CommandError) -> Self {
+        SimpleError::new(format!("ERR {}", e)).into()
     }
 }
 
+// the error for a command missing from the command table, quoting its first arguments
+fn unknown_command(data: &[RespFrame]) -> CommandError {
+    let name = match data.first() {
+        Some(RespFrame::BulkString(name)) => String::from_utf8_lossy(name.as_ref()).to_string(),
+        _ => String::new(),
+    };
+    let args = data
+        .iter()
+        .skip(1)
+        .take(10)
+        .map(|arg| match arg {
+            RespFrame::BulkString(arg) => format!("'{}' ", String::from_utf8_lossy(arg.as_ref())),
+            _ => String::new(),
+        })
+        .collect::<String>();
+    CommandError::UnknownCommand(name, args)
+}
+
+// reply of a command which changes the state of the connection, when executed without one
+fn not_in_context(name: &str) -> RespFrame {
+    SimpleError::new(format!("ERR Can't execute '{}' in this context", name)).into()
+}
+
+// the lowercase name of a command which is parsed by a shared struct, e.g. ZRANK and ZREVRANK
+fn command_name(value: &Array, names: &[&'static str]) -> Result<&'static str, CommandError> {
+    let name = value
+        .0
+        .as_ref()
+        .and_then(|v| v.first())
+        .map(|v| match v {
+            RespFrame::BulkString(s) => String::from_utf8_lossy(s.as_ref()).to_ascii_lowercase(),
+            _ => String::new(),
+        })
+        .unwrap_or_default();
+    names
+        .iter()
+        .find(|n| **n == name)
+        .copied()
+        .ok_or_else(|| CommandError::InvalidCommand(format!("Invalid command: {}", name)))
+}
+
 fn validate_command(
     value: &Array,
     names: &[&'static str],
@@ -156,11 +1355,7 @@ fn validate_command(
     match value.0.as_ref() {
         Some(data) => {
             if data.len() != n_args + names.len() {
-                return Err(CommandError::InvalidArgument(format!(
-                    "{} command must have exactly {} arguments",
-                    names.join(" "),
-                    n_args,
-                )));
+                return Err(CommandError::WrongArity(names.join("|")));
             }
 
             for (i, name) in names.iter().enumerate() {
@@ -181,16 +1376,23 @@ fn validate_command(
                     }
                 }
             }
-            return Ok(());
-        }
-        None => {
-            return Err(CommandError::InvalidArgument(format!(
-                "{} command requires {} arguments",
-                names.join(" "),
-                n_args
-            )));
+            Ok(())
         }
+        None => Err(CommandError::WrongArity(names.join("|"))),
+    }
+}
+
+// validate a variadic command which takes at least `min_args` arguments
+fn validate_command_at_least(
+    value: &Array,
+    names: &[&'static str],
+    min_args: usize,
+) -> Result<(), CommandError> {
+    let n = value.0.as_ref().map(|v| v.len()).unwrap_or_default();
+    if n < min_args + names.len() {
+        return Err(CommandError::WrongArity(names.join("|")));
     }
+    validate_command(value, names, n - names.len())
 }
 
 fn extract_args(value: Array, start: usize) -> Result<Vec<RespFrame>, CommandError> {
@@ -202,12 +1404,34 @@ fn extract_args(value: Array, start: usize) -> Result<Vec<RespFrame>, CommandErr
         .collect::<Vec<RespFrame>>())
 }
 
+// parse a string argument, e.g. a key or an option name
+fn parse_string(frame: Option<RespFrame>) -> Result<String, CommandError> {
+    match frame {
+        Some(RespFrame::BulkString(s)) => Ok(String::from_utf8_lossy(s.as_ref()).to_string()),
+        _ => Err(CommandError::InvalidArgument(
+            "Invalid argument".to_string(),
+        )),
+    }
+}
+
+fn parse_i64(frame: Option<RespFrame>) -> Result<i64, CommandError> {
+    let err =
+        || CommandError::InvalidArgument("value is not an integer or out of range".to_string());
+    match frame {
+        Some(RespFrame::BulkString(s)) => String::from_utf8_lossy(s.as_ref())
+            .parse::<i64>()
+            .map_err(|_| err()),
+        Some(RespFrame::Integer(i)) => Ok(i),
+        _ => Err(err()),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use anyhow::Result;
     use bytes::BytesMut;
 
-    use crate::{RespDecode, Null};
+    use crate::{Null, RespDecode};
 
     use super::*;
 
@@ -227,4 +1451,29 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_command_errors() -> Result<()> {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(b"*2\r\n$3\r\nGET\r\n$5\r\nhello\r\n");
+        let frame = Array::decode(&mut buf)?;
+        assert!(matches!(Command::try_from(frame)?, Command::Get(_)));
+
+        buf.extend_from_slice(b"*2\r\n$3\r\nfoo\r\n$3\r\nbar\r\n");
+        let frame = Array::decode(&mut buf)?;
+        let err = Command::try_from(frame).unwrap_err();
+        assert_eq!(
+            RespFrame::from(err),
+            SimpleError::new("ERR unknown command 'foo', with args beginning with: 'bar' ").into()
+        );
+
+        buf.extend_from_slice(b"*1\r\n$3\r\nget\r\n");
+        let frame = Array::decode(&mut buf)?;
+        let err = Command::try_from(frame).unwrap_err();
+        assert_eq!(
+            RespFrame::from(err),
+            SimpleError::new("ERR wrong number of arguments for 'get' command").into()
+        );
+        Ok(())
+    }
 }
@@ -0,0 +1,120 @@
+use crate::cmd::{
+    extract_args, parse_string, validate_command_at_least, CommandError, CommandExecutor, Object,
+    ObjectSubcommand,
+};
+use crate::{Array, Backend, MaxMemoryPolicy, Null, RespFrame, SimpleError};
+
+impl CommandExecutor for Object {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let lfu = backend.config().memory.maxmemory_policy == MaxMemoryPolicy::AllKeysLfu;
+        let ret = match self.sub {
+            ObjectSubcommand::Freq => backend.object_freq(&self.key).map(|freq| freq as i64),
+            ObjectSubcommand::IdleTime => {
+                backend.object_idletime(&self.key).map(|idle| idle as i64)
+            }
+        };
+        match (ret, self.sub) {
+            (None, _) => RespFrame::Null(Null),
+            (Some(_), ObjectSubcommand::Freq) if !lfu => SimpleError::new(
+                "ERR An LFU maxmemory policy is not selected, access frequency not tracked. \
+                 Please note that when switching between policies at runtime LRU and LFU data \
+                 will take some time to adjust.",
+            )
+            .into(),
+            (Some(_), ObjectSubcommand::IdleTime) if lfu => SimpleError::new(
+                "ERR An LFU maxmemory policy is selected, idle time not tracked. Please note \
+                 that when switching between policies at runtime LRU and LFU data will take \
+                 some time to adjust.",
+            )
+            .into(),
+            (Some(ret), _) => RespFrame::Integer(ret),
+        }
+    }
+}
+
+impl TryFrom<Array> for Object {
+    type Error = CommandError;
+    fn try_from(value: Array) -> Result<Self, Self::Error> {
+        validate_command_at_least(&value, &["object"], 1)?;
+        let mut args = extract_args(value, 1)?.into_iter();
+        let sub = parse_string(args.next())?.to_ascii_lowercase();
+        let (sub, key) = match (sub.as_str(), args.next(), args.next()) {
+            ("freq", Some(key), None) => (ObjectSubcommand::Freq, key),
+            ("idletime", Some(key), None) => (ObjectSubcommand::IdleTime, key),
+            (sub, _, _) => {
+                return Err(CommandError::InvalidArgument(format!(
+                    "unknown subcommand or wrong number of arguments for '{}'",
+                    sub
+                )))
+            }
+        };
+        Ok(Object {
+            sub,
+            key: parse_string(Some(key))?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use bytes::BytesMut;
+
+    use crate::{AppConfig, RespDecode};
+
+    use super::*;
+
+    #[test]
+    fn test_object_try_from_array() -> Result<()> {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(b"*3\r\n$6\r\nobject\r\n$8\r\nIDLETIME\r\n$3\r\nkey\r\n");
+        let frame = Array::decode(&mut buf)?;
+        let cmd = Object::try_from(frame)?;
+        assert_eq!(cmd.sub, ObjectSubcommand::IdleTime);
+        assert_eq!(cmd.key, "key");
+
+        buf.extend_from_slice(b"*2\r\n$6\r\nobject\r\n$4\r\nfreq\r\n");
+        let frame = Array::decode(&mut buf)?;
+        assert!(Object::try_from(frame).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_object_depends_on_policy() {
+        let backend = Backend::new();
+        backend.set("key".to_string(), "value".into());
+        let object = |backend: &Backend, sub| {
+            let cmd = Object {
+                sub,
+                key: "key".to_string(),
+            };
+            cmd.execute(backend)
+        };
+        assert_eq!(
+            object(&backend, ObjectSubcommand::IdleTime),
+            RespFrame::Integer(0)
+        );
+        assert!(matches!(
+            object(&backend, ObjectSubcommand::Freq),
+            RespFrame::Error(e) if e.contains("LFU maxmemory policy is not selected")
+        ));
+
+        let mut config = AppConfig::default();
+        config.memory.maxmemory_policy = MaxMemoryPolicy::AllKeysLfu;
+        let backend = Backend::with_config(config);
+        backend.set("key".to_string(), "value".into());
+        assert_eq!(
+            object(&backend, ObjectSubcommand::Freq),
+            RespFrame::Integer(5)
+        );
+        assert!(matches!(
+            object(&backend, ObjectSubcommand::IdleTime),
+            RespFrame::Error(_)
+        ));
+        backend.flushall();
+        assert_eq!(
+            object(&backend, ObjectSubcommand::Freq),
+            RespFrame::Null(Null)
+        );
+    }
+}
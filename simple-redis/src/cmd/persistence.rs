@@ -0,0 +1,131 @@
+use tracing::warn;
+
+use std::fmt::Display;
+
+use crate::cmd::{
+    validate_command, BgRewriteAof, BgSave, CommandError, CommandExecutor, LastSave, Save, RESP_OK,
+};
+use crate::{Array, Backend, RdbError, RespFrame, SimpleError, SimpleString};
+
+impl CommandExecutor for Save {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        if backend.bgsave_in_progress() {
+            return save_error(RdbError::AlreadyInProgress);
+        }
+        // the connection runs SAVE with the exclusive lock held
+        match backend.save_locked() {
+            Ok(_) => RESP_OK.clone(),
+            Err(e) => save_error(e),
+        }
+    }
+}
+
+impl CommandExecutor for BgSave {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.bgsave() {
+            Ok(_) => SimpleString::new("Background saving started").into(),
+            Err(e) => save_error(e),
+        }
+    }
+}
+
+impl CommandExecutor for LastSave {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        RespFrame::Integer(backend.last_save() as i64)
+    }
+}
+
+impl CommandExecutor for BgRewriteAof {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.bgrewriteaof() {
+            Ok(_) => SimpleString::new("Background append only file rewriting started").into(),
+            Err(e) => save_error(e),
+        }
+    }
+}
+
+fn save_error(e: impl Display) -> RespFrame {
+    warn!("save error: {}", e);
+    SimpleError::new(format!("ERR {}", e)).into()
+}
+
+impl TryFrom<Array> for Save {
+    type Error = CommandError;
+    fn try_from(value: Array) -> Result<Self, Self::Error> {
+        validate_command(&value, &["save"], 0)?;
+        Ok(Save)
+    }
+}
+
+impl TryFrom<Array> for BgSave {
+    type Error = CommandError;
+    fn try_from(value: Array) -> Result<Self, Self::Error> {
+        validate_command(&value, &["bgsave"], 0)?;
+        Ok(BgSave)
+    }
+}
+
+impl TryFrom<Array> for LastSave {
+    type Error = CommandError;
+    fn try_from(value: Array) -> Result<Self, Self::Error> {
+        validate_command(&value, &["lastsave"], 0)?;
+        Ok(LastSave)
+    }
+}
+
+impl TryFrom<Array> for BgRewriteAof {
+    type Error = CommandError;
+    fn try_from(value: Array) -> Result<Self, Self::Error> {
+        validate_command(&value, &["bgrewriteaof"], 0)?;
+        Ok(BgRewriteAof)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use bytes::BytesMut;
+
+    use crate::{AppConfig, RespDecode};
+
+    use super::*;
+
+    #[test]
+    fn test_save_decode() -> Result<()> {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(b"*1\r\n$6\r\nBGSAVE\r\n");
+        let frame = Array::decode(&mut buf)?;
+        let _: BgSave = frame.try_into()?;
+
+        buf.extend_from_slice(b"*2\r\n$4\r\nsave\r\n$3\r\nnow\r\n");
+        let frame = Array::decode(&mut buf)?;
+        let ret: Result<Save, CommandError> = frame.try_into();
+        assert!(ret.is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_save_and_lastsave() -> Result<()> {
+        let mut config = AppConfig::default();
+        config.rdb.dir = std::env::temp_dir();
+        config.rdb.dbfilename = format!("simple-redis-cmd-test-{}.rdb", std::process::id());
+        let backend = Backend::with_config(config.clone());
+        backend.set("key".to_string(), "value".into());
+
+        assert_eq!(Save.execute(&backend), RESP_OK.clone());
+        assert!(config.rdb.path().exists());
+        match LastSave.execute(&backend) {
+            RespFrame::Integer(ts) => assert!(ts > 0),
+            frame => panic!("unexpected reply {:?}", frame),
+        }
+
+        std::fs::remove_file(config.rdb.path())?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_bgrewriteaof_requires_aof() {
+        let frame = BgRewriteAof.execute(&Backend::new());
+        assert_eq!(frame, SimpleError::new("ERR AOF is disabled").into());
+    }
+}
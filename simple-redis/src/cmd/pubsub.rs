@@ -0,0 +1,266 @@
+use crate::cmd::{
+    extract_args, not_in_context, parse_string, validate_command, validate_command_at_least,
+    CommandError, CommandExecutor, PSubscribe, PUnsubscribe, PubSub, PubSubSubcommand, Publish,
+    Subscribe, Unsubscribe,
+};
+use crate::{Array, Backend, BulkString, PubSubMessage, RespFrame, Subscriber};
+
+impl CommandExecutor for Publish {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        RespFrame::Integer(backend.publish(&self.channel, self.message) as i64)
+    }
+}
+
+impl CommandExecutor for PubSub {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match self.sub {
+            PubSubSubcommand::Channels(pattern) => {
+                let channels = backend
+                    .pubsub_channels(pattern.as_deref())
+                    .into_iter()
+                    .map(|channel| BulkString::new(channel).into())
+                    .collect::<Vec<RespFrame>>();
+                Array::new(channels).into()
+            }
+            PubSubSubcommand::NumSub(channels) => {
+                let mut ret = Vec::with_capacity(channels.len() * 2);
+                for channel in channels {
+                    let count = backend.pubsub_numsub(&channel);
+                    ret.push(BulkString::new(channel).into());
+                    ret.push(RespFrame::Integer(count as i64));
+                }
+                Array::new(ret).into()
+            }
+            PubSubSubcommand::NumPat => RespFrame::Integer(backend.pubsub_numpat() as i64),
+        }
+    }
+}
+
+// (un)subscribing changes the state of the connection, so these commands are handled by
+// the connection through `execute_with`, executing them without one is an error
+impl CommandExecutor for Subscribe {
+    fn execute(self, _backend: &Backend) -> RespFrame {
+        not_in_context("subscribe")
+    }
+}
+
+impl CommandExecutor for Unsubscribe {
+    fn execute(self, _backend: &Backend) -> RespFrame {
+        not_in_context("unsubscribe")
+    }
+}
+
+impl CommandExecutor for PSubscribe {
+    fn execute(self, _backend: &Backend) -> RespFrame {
+        not_in_context("psubscribe")
+    }
+}
+
+impl CommandExecutor for PUnsubscribe {
+    fn execute(self, _backend: &Backend) -> RespFrame {
+        not_in_context("punsubscribe")
+    }
+}
+
+impl Subscribe {
+    pub fn execute_with(self, subscriber: &mut Subscriber) -> Vec<PubSubMessage> {
+        self.channels
+            .into_iter()
+            .map(|channel| subscriber.subscribe(channel))
+            .collect()
+    }
+}
+
+impl Unsubscribe {
+    pub fn execute_with(self, subscriber: &mut Subscriber) -> Vec<PubSubMessage> {
+        subscriber.unsubscribe(self.channels)
+    }
+}
+
+impl PSubscribe {
+    pub fn execute_with(self, subscriber: &mut Subscriber) -> Vec<PubSubMessage> {
+        self.patterns
+            .into_iter()
+            .map(|pattern| subscriber.psubscribe(pattern))
+            .collect()
+    }
+}
+
+impl PUnsubscribe {
+    pub fn execute_with(self, subscriber: &mut Subscriber) -> Vec<PubSubMessage> {
+        subscriber.punsubscribe(self.patterns)
+    }
+}
+
+impl TryFrom<Array> for Publish {
+    type Error = CommandError;
+    fn try_from(value: Array) -> Result<Self, Self::Error> {
+        validate_command(&value, &["publish"], 2)?;
+        let mut args = extract_args(value, 1)?.into_iter();
+        match (parse_string(args.next())?, args.next()) {
+            (channel, Some(message)) => Ok(Publish { channel, message }),
+            _ => Err(CommandError::InvalidArgument(
+                "Invalid argument".to_string(),
+            )),
+        }
+    }
+}
+
+impl TryFrom<Array> for Subscribe {
+    type Error = CommandError;
+    fn try_from(value: Array) -> Result<Self, Self::Error> {
+        Ok(Subscribe {
+            channels: parse_names(value, "subscribe", 1)?,
+        })
+    }
+}
+
+impl TryFrom<Array> for Unsubscribe {
+    type Error = CommandError;
+    fn try_from(value: Array) -> Result<Self, Self::Error> {
+        Ok(Unsubscribe {
+            channels: parse_names(value, "unsubscribe", 0)?,
+        })
+    }
+}
+
+impl TryFrom<Array> for PSubscribe {
+    type Error = CommandError;
+    fn try_from(value: Array) -> Result<Self, Self::Error> {
+        Ok(PSubscribe {
+            patterns: parse_names(value, "psubscribe", 1)?,
+        })
+    }
+}
+
+impl TryFrom<Array> for PUnsubscribe {
+    type Error = CommandError;
+    fn try_from(value: Array) -> Result<Self, Self::Error> {
+        Ok(PUnsubscribe {
+            patterns: parse_names(value, "punsubscribe", 0)?,
+        })
+    }
+}
+
+// PUBSUB CHANNELS [pattern] | NUMSUB [channel ...] | NUMPAT
+impl TryFrom<Array> for PubSub {
+    type Error = CommandError;
+    fn try_from(value: Array) -> Result<Self, Self::Error> {
+        validate_command_at_least(&value, &["pubsub"], 1)?;
+        let mut args = extract_args(value, 1)?.into_iter();
+        let sub = match parse_string(args.next())?.to_ascii_lowercase().as_str() {
+            "channels" => {
+                let pattern = args.next().map(|v| parse_string(Some(v))).transpose()?;
+                if args.next().is_some() {
+                    return Err(CommandError::InvalidArgument(
+                        "PUBSUB CHANNELS takes at most one pattern".to_string(),
+                    ));
+                }
+                PubSubSubcommand::Channels(pattern)
+            }
+            "numsub" => PubSubSubcommand::NumSub(
+                args.map(|v| parse_string(Some(v)))
+                    .collect::<Result<_, _>>()?,
+            ),
+            "numpat" if args.next().is_none() => PubSubSubcommand::NumPat,
+            sub => {
+                return Err(CommandError::InvalidArgument(format!(
+                    "Unknown PUBSUB subcommand or wrong number of arguments for '{}'",
+                    sub
+                )))
+            }
+        };
+        Ok(PubSub { sub })
+    }
+}
+
+fn parse_names(value: Array, name: &'static str, min: usize) -> Result<Vec<String>, CommandError> {
+    validate_command_at_least(&value, &[name], min)?;
+    extract_args(value, 1)?
+        .into_iter()
+        .map(|v| parse_string(Some(v)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use bytes::BytesMut;
+
+    use crate::RespDecode;
+
+    use super::*;
+
+    #[test]
+    fn test_pubsub_try_from_array() -> Result<()> {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(b"*3\r\n$9\r\nSUBSCRIBE\r\n$1\r\na\r\n$1\r\nb\r\n");
+        let frame = Array::decode(&mut buf)?;
+        let cmd = Subscribe::try_from(frame)?;
+        assert_eq!(cmd.channels, vec!["a".to_string(), "b".to_string()]);
+
+        buf.extend_from_slice(b"*1\r\n$11\r\nunsubscribe\r\n");
+        let frame = Array::decode(&mut buf)?;
+        assert!(Unsubscribe::try_from(frame)?.channels.is_empty());
+
+        buf.extend_from_slice(b"*1\r\n$10\r\npsubscribe\r\n");
+        let frame = Array::decode(&mut buf)?;
+        assert!(PSubscribe::try_from(frame).is_err());
+
+        buf.extend_from_slice(b"*3\r\n$6\r\npubsub\r\n$8\r\nchannels\r\n$2\r\nn*\r\n");
+        let frame = Array::decode(&mut buf)?;
+        let cmd = PubSub::try_from(frame)?;
+        assert_eq!(cmd.sub, PubSubSubcommand::Channels(Some("n*".to_string())));
+        Ok(())
+    }
+
+    #[test]
+    fn test_publish_and_introspection() {
+        let backend = Backend::new();
+        let (mut subscriber, mut rx) = Subscriber::new(backend.clone(), 1);
+        let cmd = Subscribe {
+            channels: vec!["news".to_string(), "sport".to_string()],
+        };
+        assert_eq!(cmd.execute_with(&mut subscriber).len(), 2);
+
+        let cmd = Publish {
+            channel: "news".to_string(),
+            message: BulkString::new("hello").into(),
+        };
+        assert_eq!(cmd.execute(&backend), RespFrame::Integer(1));
+        assert!(rx.try_recv().is_ok());
+
+        let cmd = PubSub {
+            sub: PubSubSubcommand::NumSub(vec!["news".to_string(), "none".to_string()]),
+        };
+        assert_eq!(
+            cmd.execute(&backend),
+            Array::new(vec![
+                BulkString::new("news").into(),
+                RespFrame::Integer(1),
+                BulkString::new("none").into(),
+                RespFrame::Integer(0),
+            ])
+            .into()
+        );
+
+        let cmd = PubSub {
+            sub: PubSubSubcommand::Channels(None),
+        };
+        assert_eq!(
+            cmd.execute(&backend),
+            Array::new(vec![
+                BulkString::new("news").into(),
+                BulkString::new("sport").into(),
+            ])
+            .into()
+        );
+
+        let cmd = Unsubscribe { channels: vec![] };
+        assert_eq!(cmd.execute_with(&mut subscriber).len(), 2);
+        let cmd = PubSub {
+            sub: PubSubSubcommand::Channels(None),
+        };
+        assert_eq!(cmd.execute(&backend), Array::new(vec![]).into());
+    }
+}
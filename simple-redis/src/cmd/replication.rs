@@ -0,0 +1,182 @@
+use crate::cmd::{
+    command_name, extract_args, not_in_context, parse_i64, parse_string, validate_command,
+    validate_command_at_least, CommandError, CommandExecutor, PSync, ReplConf, ReplConfOption,
+    ReplicaOf, RESP_OK,
+};
+use crate::{Array, Backend, PSyncReply, RespFrame, SimpleString};
+
+impl CommandExecutor for ReplicaOf {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let replicating = self.master.is_some();
+        match backend.replicaof(self.master) {
+            false if replicating => {
+                SimpleString::new("OK Already connected to specified master").into()
+            }
+            _ => RESP_OK.clone(),
+        }
+    }
+}
+
+// a replica turns its connection into a replication stream, so PSYNC / REPLCONF are handled
+// by the connection
+impl CommandExecutor for PSync {
+    fn execute(self, _backend: &Backend) -> RespFrame {
+        not_in_context("psync")
+    }
+}
+
+impl CommandExecutor for ReplConf {
+    fn execute(self, _backend: &Backend) -> RespFrame {
+        not_in_context("replconf")
+    }
+}
+
+impl PSync {
+    /// Attach the connection `id` as a replica listening on `ip`:`port`.
+    pub fn execute_with(self, backend: &Backend, id: u64, ip: String, port: u16) -> PSyncReply {
+        backend.psync(id, ip, port, &self.replid, self.offset)
+    }
+}
+
+impl ReplConf {
+    /// Configure the replica connection `id`. An ACK has no reply.
+    pub fn execute_with(self, backend: &Backend, id: u64, port: &mut u16) -> Option<RespFrame> {
+        let mut reply = Some(RESP_OK.clone());
+        for option in self.options {
+            match option {
+                ReplConfOption::ListeningPort(listening_port) => *port = listening_port,
+                ReplConfOption::Capa(_) => {}
+                ReplConfOption::Ack(offset) => {
+                    backend.replica_ack(id, offset);
+                    reply = None;
+                }
+                // only a master asks for acks
+                ReplConfOption::GetAck => reply = None,
+            }
+        }
+        reply
+    }
+}
+
+impl TryFrom<Array> for ReplicaOf {
+    type Error = CommandError;
+    fn try_from(value: Array) -> Result<Self, Self::Error> {
+        let name = command_name(&value, &["replicaof", "slaveof"])?;
+        validate_command(&value, &[name], 2)?;
+        let mut args = extract_args(value, 1)?.into_iter();
+        let host = parse_string(args.next())?;
+        let port = parse_string(args.next())?;
+        if host.eq_ignore_ascii_case("no") && port.eq_ignore_ascii_case("one") {
+            return Ok(ReplicaOf { master: None });
+        }
+        let port = port
+            .parse()
+            .map_err(|_| CommandError::InvalidArgument("Invalid master port".to_string()))?;
+        Ok(ReplicaOf {
+            master: Some((host, port)),
+        })
+    }
+}
+
+impl TryFrom<Array> for PSync {
+    type Error = CommandError;
+    fn try_from(value: Array) -> Result<Self, Self::Error> {
+        validate_command(&value, &["psync"], 2)?;
+        let mut args = extract_args(value, 1)?.into_iter();
+        Ok(PSync {
+            replid: parse_string(args.next())?,
+            offset: parse_i64(args.next())?,
+        })
+    }
+}
+
+impl TryFrom<Array> for ReplConf {
+    type Error = CommandError;
+    fn try_from(value: Array) -> Result<Self, Self::Error> {
+        validate_command_at_least(&value, &["replconf"], 0)?;
+        let mut args = extract_args(value, 1)?.into_iter();
+        let mut options = Vec::new();
+        while let Some(option) = args.next() {
+            let option = parse_string(Some(option))?.to_ascii_lowercase();
+            let value = parse_string(args.next())
+                .map_err(|_| CommandError::InvalidArgument("syntax error".to_string()))?;
+            let option = match option.as_str() {
+                "listening-port" => ReplConfOption::ListeningPort(value.parse().map_err(|_| {
+                    CommandError::InvalidArgument("value is out of range".to_string())
+                })?),
+                "capa" => ReplConfOption::Capa(value),
+                "ack" => ReplConfOption::Ack(value.parse().map_err(|_| {
+                    CommandError::InvalidArgument("value is out of range".to_string())
+                })?),
+                "getack" => ReplConfOption::GetAck,
+                option => {
+                    return Err(CommandError::InvalidArgument(format!(
+                        "Unrecognized REPLCONF option: {}",
+                        option
+                    )))
+                }
+            };
+            options.push(option);
+        }
+        Ok(ReplConf { options })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use bytes::BytesMut;
+
+    use crate::RespDecode;
+
+    use super::*;
+
+    #[test]
+    fn test_replicaof_try_from_array() -> Result<()> {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(b"*3\r\n$7\r\nslaveof\r\n$9\r\nlocalhost\r\n$4\r\n6380\r\n");
+        let frame = Array::decode(&mut buf)?;
+        let cmd = ReplicaOf::try_from(frame)?;
+        assert_eq!(cmd.master, Some(("localhost".to_string(), 6380)));
+
+        buf.extend_from_slice(b"*3\r\n$9\r\nreplicaof\r\n$2\r\nNO\r\n$3\r\none\r\n");
+        let frame = Array::decode(&mut buf)?;
+        assert_eq!(ReplicaOf::try_from(frame)?.master, None);
+
+        buf.extend_from_slice(b"*3\r\n$9\r\nreplicaof\r\n$9\r\nlocalhost\r\n$1\r\nx\r\n");
+        let frame = Array::decode(&mut buf)?;
+        assert!(ReplicaOf::try_from(frame).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_replconf_options() -> Result<()> {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(
+            b"*5\r\n$8\r\nreplconf\r\n$14\r\nlistening-port\r\n$4\r\n6380\r\n$4\r\ncapa\r\n$6\r\npsync2\r\n",
+        );
+        let frame = Array::decode(&mut buf)?;
+        let cmd = ReplConf::try_from(frame)?;
+        assert_eq!(
+            cmd.options,
+            vec![
+                ReplConfOption::ListeningPort(6380),
+                ReplConfOption::Capa("psync2".to_string())
+            ]
+        );
+
+        let backend = Backend::new();
+        let mut port = 0;
+        assert_eq!(
+            cmd.execute_with(&backend, 1, &mut port),
+            Some(RESP_OK.clone())
+        );
+        assert_eq!(port, 6380);
+
+        let cmd = ReplConf {
+            options: vec![ReplConfOption::Ack(42)],
+        };
+        assert_eq!(cmd.execute_with(&backend, 1, &mut port), None);
+        Ok(())
+    }
+}
@@ -0,0 +1,185 @@
+use crate::cmd::{
+    command_name, extract_args, parse_i64, parse_string, validate_command_at_least, Command,
+    CommandError, CommandExecutor, Eval, Script, ScriptSource, ScriptSubcommand, RESP_OK,
+};
+use crate::{Array, Backend, BulkString, RespFrame, SimpleError};
+
+// the commands of a script run as is, the connection propagates them with execute_with
+impl CommandExecutor for Eval {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        self.execute_with(backend, |frame| match Command::try_from(frame) {
+            Ok(cmd) => cmd.execute(backend),
+            Err(e) => e.into(),
+        })
+    }
+}
+
+impl Eval {
+    /// Run the script, executing the commands it calls with `call`.
+    pub fn execute_with(
+        self,
+        backend: &Backend,
+        call: impl FnMut(RespFrame) -> RespFrame,
+    ) -> RespFrame {
+        let body = match self.source {
+            ScriptSource::Body(body) => body,
+            ScriptSource::Sha(sha) => match backend.script_get(&sha) {
+                Some(body) => body,
+                None => {
+                    return SimpleError::new("NOSCRIPT No matching script. Please use EVAL.").into()
+                }
+            },
+        };
+        backend.eval(&body, self.keys, self.args, call)
+    }
+}
+
+impl CommandExecutor for Script {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match self.sub {
+            ScriptSubcommand::Load(body) => BulkString::new(backend.script_load(&body)).into(),
+            ScriptSubcommand::Exists(shas) => Array::new(
+                shas.iter()
+                    .map(|sha| RespFrame::Integer(backend.script_exists(sha) as i64))
+                    .collect::<Vec<_>>(),
+            )
+            .into(),
+            ScriptSubcommand::Flush => {
+                backend.script_flush();
+                RESP_OK.clone()
+            }
+        }
+    }
+}
+
+impl TryFrom<Array> for Eval {
+    type Error = CommandError;
+    fn try_from(value: Array) -> Result<Self, Self::Error> {
+        let name = command_name(&value, &["eval", "evalsha"])?;
+        validate_command_at_least(&value, &[name], 2)?;
+        let mut args = extract_args(value, 1)?.into_iter();
+        let script = parse_string(args.next())?;
+        let source = match name {
+            "eval" => ScriptSource::Body(script),
+            _ => ScriptSource::Sha(script),
+        };
+        let numkeys = parse_i64(args.next())?;
+        let mut args = args.collect::<Vec<_>>();
+        if numkeys < 0 {
+            return Err(CommandError::InvalidArgument(
+                "Number of keys can't be negative".to_string(),
+            ));
+        }
+        if numkeys as usize > args.len() {
+            return Err(CommandError::InvalidArgument(
+                "Number of keys can't be greater than number of args".to_string(),
+            ));
+        }
+        let rest = args.split_off(numkeys as usize);
+        Ok(Eval {
+            source,
+            keys: args,
+            args: rest,
+        })
+    }
+}
+
+impl TryFrom<Array> for Script {
+    type Error = CommandError;
+    fn try_from(value: Array) -> Result<Self, Self::Error> {
+        validate_command_at_least(&value, &["script"], 1)?;
+        let mut args = extract_args(value, 1)?.into_iter();
+        let sub = parse_string(args.next())?.to_ascii_lowercase();
+        let args = args
+            .map(|v| parse_string(Some(v)))
+            .collect::<Result<Vec<_>, _>>()?;
+        let sub = match (sub.as_str(), args.len()) {
+            ("load", 1) => ScriptSubcommand::Load(args.into_iter().next().unwrap_or_default()),
+            ("exists", n) if n > 0 => ScriptSubcommand::Exists(args),
+            ("flush", 0) => ScriptSubcommand::Flush,
+            ("flush", 1)
+                if args[0].eq_ignore_ascii_case("sync")
+                    || args[0].eq_ignore_ascii_case("async") =>
+            {
+                ScriptSubcommand::Flush
+            }
+            (sub, _) => {
+                return Err(CommandError::InvalidArgument(format!(
+                    "unknown subcommand or wrong number of arguments for '{}'",
+                    sub
+                )))
+            }
+        };
+        Ok(Script { sub })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use bytes::BytesMut;
+
+    use crate::RespDecode;
+
+    use super::*;
+
+    #[test]
+    fn test_eval_try_from_array() -> Result<()> {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(
+            b"*5\r\n$4\r\neval\r\n$8\r\nreturn 1\r\n$1\r\n1\r\n$3\r\nkey\r\n$3\r\narg\r\n",
+        );
+        let frame = Array::decode(&mut buf)?;
+        let cmd = Eval::try_from(frame)?;
+        assert_eq!(cmd.source, ScriptSource::Body("return 1".to_string()));
+        assert_eq!(cmd.keys, vec![BulkString::new("key").into()]);
+        assert_eq!(cmd.args, vec![BulkString::new("arg").into()]);
+
+        buf.extend_from_slice(b"*4\r\n$7\r\nevalsha\r\n$3\r\nabc\r\n$1\r\n2\r\n$3\r\nkey\r\n");
+        let frame = Array::decode(&mut buf)?;
+        assert!(Eval::try_from(frame).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_script_cache_commands() -> Result<()> {
+        let backend = Backend::new();
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(b"*3\r\n$6\r\nscript\r\n$4\r\nLOAD\r\n$16\r\nreturn ARGV[1]+1\r\n");
+        let frame = Array::decode(&mut buf)?;
+        let RespFrame::BulkString(sha) = Script::try_from(frame)?.execute(&backend) else {
+            panic!("SCRIPT LOAD should reply with the sha1 of the script");
+        };
+        let sha = String::from_utf8_lossy(sha.as_ref()).to_string();
+
+        let cmd = Eval {
+            source: ScriptSource::Sha(sha.clone()),
+            keys: vec![],
+            args: vec![BulkString::new("41").into()],
+        };
+        assert_eq!(cmd.execute(&backend), RespFrame::Integer(42));
+
+        let cmd = Script {
+            sub: ScriptSubcommand::Exists(vec![sha.clone(), "nope".to_string()]),
+        };
+        assert_eq!(
+            cmd.execute(&backend),
+            Array::new(vec![RespFrame::Integer(1), RespFrame::Integer(0)]).into()
+        );
+
+        let cmd = Script {
+            sub: ScriptSubcommand::Flush,
+        };
+        assert_eq!(cmd.execute(&backend), RESP_OK.clone());
+        let cmd = Eval {
+            source: ScriptSource::Sha(sha),
+            keys: vec![],
+            args: vec![],
+        };
+        assert!(matches!(
+            cmd.execute(&backend),
+            RespFrame::Error(e) if e.starts_with("NOSCRIPT")
+        ));
+        Ok(())
+    }
+}
@@ -0,0 +1,940 @@
+use std::iter::Peekable;
+use std::time::Duration;
+use std::vec::IntoIter;
+
+use tokio::time::Instant;
+
+use super::list::block_on_keys;
+use crate::cmd::{
+    command_name, extract_args, parse_i64, parse_string, validate_command,
+    validate_command_at_least, CommandError, CommandExecutor, XAck, XAdd, XClaim, XGroup,
+    XGroupSubcommand, XLen, XPending, XPendingRange, XRange, XRead, XReadGroup, XTrim, RESP_OK,
+    RESP_WRONGTYPE,
+};
+use crate::{
+    Array, Backend, BulkString, ClaimOptions, GroupEntry, RespFrame, SimpleError, StreamError,
+    StreamFields, StreamId, StreamTrim, TrimStrategy, XAddId,
+};
+
+impl CommandExecutor for XAdd {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        if let Some(err) = check_stream_type(backend, &self.key) {
+            return err;
+        }
+        match backend.xadd(self.key, self.id, self.fields, self.trim, self.no_mkstream) {
+            Ok(Some(id)) => BulkString::new(id.to_string()).into(),
+            Ok(None) => BulkString::none().into(),
+            Err(e) => stream_error(e),
+        }
+    }
+}
+
+impl XAdd {
+    /// The position of the id in the request, if the command generates it.
+    pub fn generated_id_index(&self) -> Option<usize> {
+        match self.id {
+            XAddId::Explicit(_) => None,
+            _ => Some(self.id_index),
+        }
+    }
+}
+
+impl CommandExecutor for XLen {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        if let Some(err) = check_stream_type(backend, &self.key) {
+            return err;
+        }
+        RespFrame::Integer(backend.xlen(&self.key) as i64)
+    }
+}
+
+impl CommandExecutor for XRange {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        if let Some(err) = check_stream_type(backend, &self.key) {
+            return err;
+        }
+        let entries = backend.xrange(&self.key, self.start, self.end, self.count, self.rev);
+        entries_reply(entries.into_iter().map(|(id, fields)| (id, Some(fields))))
+    }
+}
+
+impl CommandExecutor for XTrim {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        if let Some(err) = check_stream_type(backend, &self.key) {
+            return err;
+        }
+        RespFrame::Integer(backend.xtrim(&self.key, &self.trim) as i64)
+    }
+}
+
+impl CommandExecutor for XRead {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        try_read(backend, &self.keys, &self.ids, self.count).unwrap_or(Array::none().into())
+    }
+}
+
+impl XRead {
+    pub fn is_blocking(&self) -> bool {
+        self.block.is_some()
+    }
+
+    /// Read the streams, waiting for an entry until the block timeout expires.
+    pub async fn execute_blocking(self, backend: &Backend) -> RespFrame {
+        // `$` is the last entry when the command starts waiting
+        let ids = {
+            let _guard = backend.lock_shared();
+            self.keys
+                .iter()
+                .zip(self.ids)
+                .map(|(key, id)| {
+                    id.or_else(|| Some(backend.stream_last_id(key).unwrap_or_default()))
+                })
+                .collect::<Vec<_>>()
+        };
+        let deadline = block_deadline(self.block);
+        block_on_keys(backend, &self.keys, deadline, || {
+            try_read(backend, &self.keys, &ids, self.count)
+        })
+        .await
+        .unwrap_or(Array::none().into())
+    }
+}
+
+impl CommandExecutor for XReadGroup {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        self.try_read(backend).unwrap_or(Array::none().into())
+    }
+}
+
+impl XReadGroup {
+    /// Only the reads of new entries block, the history of a consumer is replied right away.
+    pub fn is_blocking(&self) -> bool {
+        self.block.is_some() && self.ids.iter().all(Option::is_none)
+    }
+
+    /// Read the streams as a consumer, waiting for a new entry until the block timeout expires.
+    pub async fn execute_blocking(self, backend: &Backend) -> RespFrame {
+        let deadline = block_deadline(self.block);
+        block_on_keys(backend, &self.keys, deadline, || self.try_read(backend))
+            .await
+            .unwrap_or(Array::none().into())
+    }
+
+    // the entries of each stream as [key, entries], None if there are none to deliver
+    fn try_read(&self, backend: &Backend) -> Option<RespFrame> {
+        let mut streams = Vec::with_capacity(self.keys.len());
+        for (key, id) in self.keys.iter().zip(self.ids.iter()) {
+            if let Some(err) = check_stream_type(backend, key) {
+                return Some(err);
+            }
+            let entries = backend.xreadgroup(
+                key,
+                &self.group,
+                &self.consumer,
+                *id,
+                self.count,
+                self.no_ack,
+            );
+            match entries {
+                // new entries are only replied for the streams having some
+                Ok(entries) if entries.is_empty() && id.is_none() => {}
+                Ok(entries) => streams.push(stream_reply(key, entries)),
+                Err(e) => return Some(stream_error(e)),
+            }
+        }
+        (!streams.is_empty()).then(|| Array::new(streams).into())
+    }
+}
+
+impl CommandExecutor for XGroup {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.key_type(&self.key) {
+            Some("stream") => {}
+            None if matches!(self.sub, XGroupSubcommand::Create(_, true)) => {}
+            None => return stream_error(StreamError::NoKey),
+            Some(_) => return RESP_WRONGTYPE.clone(),
+        }
+        let (key, group) = (&self.key, &self.group);
+        let ret = match self.sub {
+            XGroupSubcommand::Create(id, mkstream) => backend
+                .xgroup_create(key, group.clone(), id, mkstream)
+                .map(|_| RESP_OK.clone()),
+            XGroupSubcommand::SetId(id) => backend
+                .xgroup_setid(key, group, id)
+                .map(|_| RESP_OK.clone()),
+            XGroupSubcommand::Destroy => backend
+                .xgroup_destroy(key, group)
+                .map(|destroyed| RespFrame::Integer(destroyed as i64)),
+            XGroupSubcommand::CreateConsumer(consumer) => backend
+                .xgroup_create_consumer(key, group, &consumer)
+                .map(|created| RespFrame::Integer(created as i64)),
+            XGroupSubcommand::DelConsumer(consumer) => backend
+                .xgroup_delete_consumer(key, group, &consumer)
+                .map(|pending| RespFrame::Integer(pending as i64)),
+        };
+        ret.unwrap_or_else(stream_error)
+    }
+}
+
+impl CommandExecutor for XAck {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        if let Some(err) = check_stream_type(backend, &self.key) {
+            return err;
+        }
+        RespFrame::Integer(backend.xack(&self.key, &self.group, &self.ids) as i64)
+    }
+}
+
+impl CommandExecutor for XPending {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        if let Some(err) = check_stream_type(backend, &self.key) {
+            return err;
+        }
+        let ret = match self.range {
+            None => backend.with_group(&self.key, &self.group, |group| {
+                let (count, bounds, consumers) = group.pending_summary();
+                let id = |id: Option<StreamId>| match id {
+                    Some(id) => BulkString::new(id.to_string()).into(),
+                    None => BulkString::none().into(),
+                };
+                let consumers = match consumers.is_empty() {
+                    true => Array::none(),
+                    false => Array::new(
+                        consumers
+                            .into_iter()
+                            .map(|(name, count)| {
+                                Array::new(vec![
+                                    BulkString::new(name).into(),
+                                    BulkString::new(count.to_string()).into(),
+                                ])
+                                .into()
+                            })
+                            .collect::<Vec<_>>(),
+                    ),
+                };
+                Array::new(vec![
+                    RespFrame::Integer(count as i64),
+                    id(bounds.map(|(first, _)| first)),
+                    id(bounds.map(|(_, last)| last)),
+                    consumers.into(),
+                ])
+                .into()
+            }),
+            Some(range) => backend.with_group(&self.key, &self.group, |group| {
+                let pending = group.pending_range(
+                    range.start,
+                    range.end,
+                    range.count,
+                    range.min_idle,
+                    range.consumer.as_deref(),
+                );
+                Array::new(
+                    pending
+                        .into_iter()
+                        .map(|(id, consumer, idle, deliveries)| {
+                            Array::new(vec![
+                                BulkString::new(id.to_string()).into(),
+                                BulkString::new(consumer).into(),
+                                RespFrame::Integer(idle as i64),
+                                RespFrame::Integer(deliveries as i64),
+                            ])
+                            .into()
+                        })
+                        .collect::<Vec<_>>(),
+                )
+                .into()
+            }),
+        };
+        ret.unwrap_or_else(stream_error)
+    }
+}
+
+impl CommandExecutor for XClaim {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        if let Some(err) = check_stream_type(backend, &self.key) {
+            return err;
+        }
+        let claimed = backend.xclaim(
+            &self.key,
+            &self.group,
+            &self.consumer,
+            self.min_idle,
+            &self.ids,
+            &self.options,
+        );
+        match claimed {
+            Ok(claimed) if self.options.just_id => Array::new(
+                claimed
+                    .into_iter()
+                    .map(|(id, _)| BulkString::new(id.to_string()).into())
+                    .collect::<Vec<_>>(),
+            )
+            .into(),
+            Ok(claimed) => entries_reply(claimed),
+            Err(e) => stream_error(e),
+        }
+    }
+}
+
+// the entries after the given ids of each stream as [key, entries], None if there are none
+fn try_read(
+    backend: &Backend,
+    keys: &[String],
+    ids: &[Option<StreamId>],
+    count: Option<usize>,
+) -> Option<RespFrame> {
+    let mut streams = Vec::with_capacity(keys.len());
+    for (key, id) in keys.iter().zip(ids.iter()) {
+        if let Some(err) = check_stream_type(backend, key) {
+            return Some(err);
+        }
+        // `$` never has entries to read without blocking
+        let Some(start) = id.and_then(|id| id.next()) else {
+            continue;
+        };
+        let entries = backend.xrange(key, start, StreamId::MAX, count, false);
+        if !entries.is_empty() {
+            let entries = entries.into_iter().map(|(id, fields)| (id, Some(fields)));
+            streams.push(stream_reply(key, entries.collect()));
+        }
+    }
+    (!streams.is_empty()).then(|| Array::new(streams).into())
+}
+
+// a block timeout of zero blocks indefinitely
+fn block_deadline(block: Option<u64>) -> Option<Instant> {
+    block
+        .filter(|ms| *ms > 0)
+        .map(|ms| Instant::now() + Duration::from_millis(ms))
+}
+
+fn stream_reply(key: &str, entries: Vec<GroupEntry>) -> RespFrame {
+    Array::new(vec![BulkString::new(key).into(), entries_reply(entries)]).into()
+}
+
+// each entry as [id, [field, value, ...]], the fields of a deleted entry are nil
+fn entries_reply(entries: impl IntoIterator<Item = (StreamId, Option<StreamFields>)>) -> RespFrame {
+    Array::new(
+        entries
+            .into_iter()
+            .map(|(id, fields)| {
+                let fields = match fields {
+                    Some(fields) => Array::new(fields),
+                    None => Array::none(),
+                };
+                Array::new(vec![BulkString::new(id.to_string()).into(), fields.into()]).into()
+            })
+            .collect::<Vec<_>>(),
+    )
+    .into()
+}
+
+fn stream_error(e: StreamError) -> RespFrame {
+    SimpleError::new(e.to_string()).into()
+}
+
+fn check_stream_type(backend: &Backend, key: &str) -> Option<RespFrame> {
+    match backend.key_type(key) {
+        Some("stream") | None => None,
+        Some(_) => Some(RESP_WRONGTYPE.clone()),
+    }
+}
+
+impl TryFrom<Array> for XAdd {
+    type Error = CommandError;
+    fn try_from(value: Array) -> Result<Self, Self::Error> {
+        validate_command_at_least(&value, &["xadd"], 4)?;
+        let mut args = extract_args(value, 1)?.into_iter().peekable();
+        let key = parse_string(args.next())?;
+        let mut id_index = 2;
+
+        let (mut trim, mut no_mkstream) = (None, false);
+        loop {
+            match peek_option(&mut args).as_deref() {
+                Some("nomkstream") => {
+                    args.next();
+                    id_index += 1;
+                    no_mkstream = true;
+                }
+                Some("maxlen") | Some("minid") => {
+                    let len = args.len();
+                    trim = Some(parse_trim(&mut args)?);
+                    id_index += len - args.len();
+                }
+                _ => break,
+            }
+        }
+
+        let id = parse_string(args.next())?;
+        let id = match id.as_str() {
+            "*" => XAddId::Auto,
+            id => match id.strip_suffix("-*") {
+                Some(ms) => XAddId::AutoSeq(ms.parse().map_err(|_| invalid_id())?),
+                None => XAddId::Explicit(parse_id(id, 0)?),
+            },
+        };
+        let fields = args.collect::<Vec<_>>();
+        if fields.is_empty() || fields.len() % 2 != 0 {
+            return Err(CommandError::WrongArity("xadd".to_string()));
+        }
+        Ok(XAdd {
+            key,
+            id,
+            id_index,
+            fields,
+            trim,
+            no_mkstream,
+        })
+    }
+}
+
+impl TryFrom<Array> for XLen {
+    type Error = CommandError;
+    fn try_from(value: Array) -> Result<Self, Self::Error> {
+        validate_command(&value, &["xlen"], 1)?;
+        let mut args = extract_args(value, 1)?.into_iter();
+        Ok(XLen {
+            key: parse_string(args.next())?,
+        })
+    }
+}
+
+impl TryFrom<Array> for XRange {
+    type Error = CommandError;
+    fn try_from(value: Array) -> Result<Self, Self::Error> {
+        let name = command_name(&value, &["xrange", "xrevrange"])?;
+        validate_command_at_least(&value, &[name], 3)?;
+        let rev = name == "xrevrange";
+        let mut args = extract_args(value, 1)?.into_iter();
+        let key = parse_string(args.next())?;
+        let (first, second) = (parse_string(args.next())?, parse_string(args.next())?);
+        let (start, end) = match rev {
+            true => (parse_bound(&second, true)?, parse_bound(&first, false)?),
+            false => (parse_bound(&first, true)?, parse_bound(&second, false)?),
+        };
+        let count = match (parse_option(args.next())?.as_deref(), args.next()) {
+            (None, _) => None,
+            (Some("count"), Some(count)) if args.next().is_none() => {
+                Some(parse_count(Some(count))?)
+            }
+            _ => return Err(syntax_error()),
+        };
+        Ok(XRange {
+            key,
+            start,
+            end,
+            count,
+            rev,
+        })
+    }
+}
+
+impl TryFrom<Array> for XTrim {
+    type Error = CommandError;
+    fn try_from(value: Array) -> Result<Self, Self::Error> {
+        validate_command_at_least(&value, &["xtrim"], 3)?;
+        let mut args = extract_args(value, 1)?.into_iter().peekable();
+        let key = parse_string(args.next())?;
+        let trim = parse_trim(&mut args)?;
+        if args.next().is_some() {
+            return Err(syntax_error());
+        }
+        Ok(XTrim { key, trim })
+    }
+}
+
+impl TryFrom<Array> for XRead {
+    type Error = CommandError;
+    fn try_from(value: Array) -> Result<Self, Self::Error> {
+        validate_command_at_least(&value, &["xread"], 3)?;
+        let mut args = extract_args(value, 1)?.into_iter().peekable();
+        let (mut count, mut block) = (None, None);
+        loop {
+            match parse_option(args.next())?.as_deref() {
+                Some("count") => count = Some(parse_count(args.next())?),
+                Some("block") => block = Some(parse_timeout(args.next())?),
+                Some("streams") => break,
+                _ => return Err(syntax_error()),
+            }
+        }
+        let (keys, ids) = parse_streams(args, "$")?;
+        Ok(XRead {
+            keys,
+            ids,
+            count,
+            block,
+        })
+    }
+}
+
+impl TryFrom<Array> for XGroup {
+    type Error = CommandError;
+    fn try_from(value: Array) -> Result<Self, Self::Error> {
+        validate_command_at_least(&value, &["xgroup"], 1)?;
+        let mut args = extract_args(value, 1)?.into_iter();
+        let sub = parse_string(args.next())?.to_ascii_lowercase();
+        let args = args
+            .map(|v| parse_string(Some(v)))
+            .collect::<Result<Vec<_>, _>>()?;
+        let group_id = |id: &str| match id {
+            "$" => Ok(None),
+            id => parse_id(id, 0).map(Some),
+        };
+        let sub = match (sub.as_str(), args.as_slice()) {
+            ("create", [_, _, id]) => XGroupSubcommand::Create(group_id(id)?, false),
+            ("create", [_, _, id, mkstream]) if mkstream.eq_ignore_ascii_case("mkstream") => {
+                XGroupSubcommand::Create(group_id(id)?, true)
+            }
+            ("setid", [_, _, id]) => XGroupSubcommand::SetId(group_id(id)?),
+            ("destroy", [_, _]) => XGroupSubcommand::Destroy,
+            ("createconsumer", [_, _, consumer]) => {
+                XGroupSubcommand::CreateConsumer(consumer.clone())
+            }
+            ("delconsumer", [_, _, consumer]) => XGroupSubcommand::DelConsumer(consumer.clone()),
+            (sub, _) => {
+                return Err(CommandError::InvalidArgument(format!(
+                    "unknown subcommand or wrong number of arguments for '{}'",
+                    sub
+                )))
+            }
+        };
+        let mut args = args.into_iter();
+        Ok(XGroup {
+            key: args.next().unwrap_or_default(),
+            group: args.next().unwrap_or_default(),
+            sub,
+        })
+    }
+}
+
+impl TryFrom<Array> for XReadGroup {
+    type Error = CommandError;
+    fn try_from(value: Array) -> Result<Self, Self::Error> {
+        validate_command_at_least(&value, &["xreadgroup"], 6)?;
+        let mut args = extract_args(value, 1)?.into_iter().peekable();
+        if parse_option(args.next())?.as_deref() != Some("group") {
+            return Err(syntax_error());
+        }
+        let group = parse_string(args.next())?;
+        let consumer = parse_string(args.next())?;
+        let (mut count, mut block, mut no_ack) = (None, None, false);
+        loop {
+            match parse_option(args.next())?.as_deref() {
+                Some("count") => count = Some(parse_count(args.next())?),
+                Some("block") => block = Some(parse_timeout(args.next())?),
+                Some("noack") => no_ack = true,
+                Some("streams") => break,
+                _ => return Err(syntax_error()),
+            }
+        }
+        let (keys, ids) = parse_streams(args, ">")?;
+        Ok(XReadGroup {
+            group,
+            consumer,
+            keys,
+            ids,
+            count,
+            block,
+            no_ack,
+        })
+    }
+}
+
+impl TryFrom<Array> for XAck {
+    type Error = CommandError;
+    fn try_from(value: Array) -> Result<Self, Self::Error> {
+        validate_command_at_least(&value, &["xack"], 3)?;
+        let mut args = extract_args(value, 1)?.into_iter();
+        let key = parse_string(args.next())?;
+        let group = parse_string(args.next())?;
+        let ids = args
+            .map(|id| parse_id(&parse_string(Some(id))?, 0))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(XAck { key, group, ids })
+    }
+}
+
+impl TryFrom<Array> for XPending {
+    type Error = CommandError;
+    fn try_from(value: Array) -> Result<Self, Self::Error> {
+        validate_command_at_least(&value, &["xpending"], 2)?;
+        let mut args = extract_args(value, 1)?.into_iter().peekable();
+        let key = parse_string(args.next())?;
+        let group = parse_string(args.next())?;
+        if args.peek().is_none() {
+            return Ok(XPending {
+                key,
+                group,
+                range: None,
+            });
+        }
+
+        let min_idle = match peek_option(&mut args).as_deref() {
+            Some("idle") => {
+                args.next();
+                parse_timeout(args.next())?
+            }
+            _ => 0,
+        };
+        let start = parse_bound(&parse_string(args.next())?, true)?;
+        let end = parse_bound(&parse_string(args.next())?, false)?;
+        let count = parse_count(args.next())?;
+        let consumer = args.next().map(|v| parse_string(Some(v))).transpose()?;
+        if args.next().is_some() {
+            return Err(syntax_error());
+        }
+        let range = XPendingRange {
+            min_idle,
+            start,
+            end,
+            count,
+            consumer,
+        };
+        Ok(XPending {
+            key,
+            group,
+            range: Some(range),
+        })
+    }
+}
+
+impl TryFrom<Array> for XClaim {
+    type Error = CommandError;
+    fn try_from(value: Array) -> Result<Self, Self::Error> {
+        validate_command_at_least(&value, &["xclaim"], 5)?;
+        let mut args = extract_args(value, 1)?.into_iter().peekable();
+        let key = parse_string(args.next())?;
+        let group = parse_string(args.next())?;
+        let consumer = parse_string(args.next())?;
+        let min_idle = parse_timeout(args.next()).map_err(|_| {
+            CommandError::InvalidArgument("Invalid min-idle-time argument for XCLAIM".to_string())
+        })?;
+
+        // the ids come first, up to the first option
+        let mut ids = vec![];
+        while let Some(arg) = args.peek() {
+            let Ok(id) = parse_id(&parse_string(Some(arg.clone()))?, 0) else {
+                break;
+            };
+            ids.push(id);
+            args.next();
+        }
+        if ids.is_empty() {
+            return Err(invalid_id());
+        }
+
+        let mut options = ClaimOptions::default();
+        while let Some(option) = parse_option(args.next())? {
+            match option.as_str() {
+                "idle" => options.idle = Some(parse_timeout(args.next())?),
+                "time" => options.time = Some(parse_timeout(args.next())?),
+                "retrycount" => options.retry_count = Some(parse_timeout(args.next())?),
+                "force" => options.force = true,
+                "justid" => options.just_id = true,
+                "lastid" => options.last_id = Some(parse_id(&parse_string(args.next())?, 0)?),
+                _ => {
+                    return Err(CommandError::InvalidArgument(format!(
+                        "Unrecognized XCLAIM option '{}'",
+                        option
+                    )))
+                }
+            }
+        }
+        Ok(XClaim {
+            key,
+            group,
+            consumer,
+            min_idle,
+            ids,
+            options,
+        })
+    }
+}
+
+fn parse_id(s: &str, seq: u64) -> Result<StreamId, CommandError> {
+    StreamId::parse(s, seq).map_err(|_| invalid_id())
+}
+
+// an interval bound: `-`, `+`, an id, or an exclusive `(id`; a missing sequence number is the
+// smallest one at the start and the greatest one at the end
+fn parse_bound(s: &str, start: bool) -> Result<StreamId, CommandError> {
+    let seq = if start { 0 } else { u64::MAX };
+    match (s, s.strip_prefix('(')) {
+        ("-", _) => Ok(StreamId::MIN),
+        ("+", _) => Ok(StreamId::MAX),
+        (_, Some(id)) => {
+            let id = parse_id(id, seq)?;
+            let id = if start { id.next() } else { id.prev() };
+            id.ok_or_else(|| {
+                let bound = if start { "start" } else { "end" };
+                CommandError::InvalidArgument(format!("invalid {} ID for the interval", bound))
+            })
+        }
+        (s, None) => parse_id(s, seq),
+    }
+}
+
+// MAXLEN | MINID [= | ~] threshold [LIMIT count], approximate trimming is exact
+fn parse_trim(args: &mut Peekable<IntoIter<RespFrame>>) -> Result<StreamTrim, CommandError> {
+    let strategy = parse_option(args.next())?;
+    if matches!(peek_option(args).as_deref(), Some("=") | Some("~")) {
+        args.next();
+    }
+    let threshold = parse_string(args.next())?;
+    let strategy = match strategy.as_deref() {
+        Some("maxlen") => match threshold.parse::<usize>() {
+            Ok(len) => TrimStrategy::MaxLen(len),
+            Err(_) => {
+                return Err(CommandError::InvalidArgument(
+                    "The MAXLEN argument must be >= 0.".to_string(),
+                ))
+            }
+        },
+        Some("minid") => TrimStrategy::MinId(parse_id(&threshold, 0)?),
+        _ => return Err(syntax_error()),
+    };
+    let limit = match peek_option(args).as_deref() {
+        Some("limit") => {
+            args.next();
+            Some(parse_count(args.next())?)
+        }
+        _ => None,
+    };
+    Ok(StreamTrim { strategy, limit })
+}
+
+// the keys then as many ids after STREAMS, `last` standing for None
+#[allow(clippy::type_complexity)]
+fn parse_streams(
+    args: impl Iterator<Item = RespFrame>,
+    last: &str,
+) -> Result<(Vec<String>, Vec<Option<StreamId>>), CommandError> {
+    let mut args = args
+        .map(|v| parse_string(Some(v)))
+        .collect::<Result<Vec<_>, _>>()?;
+    if args.is_empty() || args.len() % 2 != 0 {
+        return Err(CommandError::InvalidArgument(
+            "Unbalanced 'xread' list of streams: for each stream key an ID or '$' must be specified."
+                .to_string(),
+        ));
+    }
+    let ids = args
+        .split_off(args.len() / 2)
+        .iter()
+        .map(|id| match id.as_str() {
+            id if id == last => Ok(None),
+            id => parse_id(id, 0).map(Some),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok((args, ids))
+}
+
+// a lowercase option name, None once the arguments are exhausted
+fn parse_option(frame: Option<RespFrame>) -> Result<Option<String>, CommandError> {
+    frame
+        .map(|v| parse_string(Some(v)).map(|s| s.to_ascii_lowercase()))
+        .transpose()
+}
+
+fn peek_option(args: &mut Peekable<IntoIter<RespFrame>>) -> Option<String> {
+    match args.peek() {
+        Some(RespFrame::BulkString(option)) => {
+            Some(String::from_utf8_lossy(option.as_ref()).to_ascii_lowercase())
+        }
+        _ => None,
+    }
+}
+
+fn parse_count(frame: Option<RespFrame>) -> Result<usize, CommandError> {
+    usize::try_from(parse_i64(frame)?).map_err(|_| syntax_error())
+}
+
+fn parse_timeout(frame: Option<RespFrame>) -> Result<u64, CommandError> {
+    u64::try_from(parse_i64(frame)?)
+        .map_err(|_| CommandError::InvalidArgument("timeout is negative".to_string()))
+}
+
+fn invalid_id() -> CommandError {
+    CommandError::InvalidArgument(
+        "Invalid stream ID specified as stream command argument".to_string(),
+    )
+}
+
+fn syntax_error() -> CommandError {
+    CommandError::InvalidArgument("syntax error".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use bytes::BytesMut;
+
+    use crate::RespDecode;
+
+    use super::*;
+
+    fn request(args: &[&str]) -> Array {
+        let frame = Array::new(
+            args.iter()
+                .map(|arg| BulkString::new(*arg).into())
+                .collect::<Vec<RespFrame>>(),
+        );
+        let mut buf = BytesMut::from(&crate::RespEncode::encode(frame)[..]);
+        Array::decode(&mut buf).expect("a valid request")
+    }
+
+    fn bulk(s: &str) -> RespFrame {
+        BulkString::new(s).into()
+    }
+
+    #[test]
+    fn test_xadd_try_from_array() -> Result<()> {
+        let cmd = XAdd::try_from(request(&[
+            "xadd",
+            "s",
+            "NOMKSTREAM",
+            "MAXLEN",
+            "~",
+            "10",
+            "LIMIT",
+            "5",
+            "*",
+            "f",
+            "v",
+        ]))?;
+        assert_eq!(cmd.id, XAddId::Auto);
+        assert_eq!(cmd.generated_id_index(), Some(8));
+        assert!(cmd.no_mkstream);
+        assert_eq!(
+            cmd.trim,
+            Some(StreamTrim {
+                strategy: TrimStrategy::MaxLen(10),
+                limit: Some(5),
+            })
+        );
+        assert_eq!(cmd.fields, vec![bulk("f"), bulk("v")]);
+
+        let cmd = XAdd::try_from(request(&["xadd", "s", "5-*", "f", "v"]))?;
+        assert_eq!(cmd.id, XAddId::AutoSeq(5));
+        assert_eq!(cmd.generated_id_index(), Some(2));
+        assert!(XAdd::try_from(request(&["xadd", "s", "1-1", "f"])).is_err());
+        assert!(XAdd::try_from(request(&["xadd", "s", "x-1", "f", "v"])).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_xrange_and_xread() -> Result<()> {
+        let backend = Backend::new();
+        for id in ["1-1", "1-2", "2-0"] {
+            XAdd::try_from(request(&["xadd", "s", id, "f", id]))?.execute(&backend);
+        }
+        let entry =
+            |id: &str| Array::new(vec![bulk(id), Array::new(vec![bulk("f"), bulk(id)]).into()]);
+
+        let cmd = XRange::try_from(request(&["xrevrange", "s", "+", "(1-1", "COUNT", "5"]))?;
+        assert_eq!(
+            cmd.execute(&backend),
+            Array::new(vec![entry("2-0").into(), entry("1-2").into()]).into()
+        );
+        let cmd = XRange::try_from(request(&["xrange", "s", "1", "1"]))?;
+        assert_eq!(
+            cmd.execute(&backend),
+            Array::new(vec![entry("1-1").into(), entry("1-2").into()]).into()
+        );
+
+        let cmd = XRead::try_from(request(&["xread", "COUNT", "1", "STREAMS", "s", "1-1"]))?;
+        assert_eq!(
+            cmd.execute(&backend),
+            Array::new(vec![Array::new(vec![
+                bulk("s"),
+                Array::new(vec![entry("1-2").into()]).into()
+            ])
+            .into()])
+            .into()
+        );
+        let cmd = XRead::try_from(request(&["xread", "STREAMS", "s", "$"]))?;
+        assert_eq!(cmd.execute(&backend), Array::none().into());
+        assert!(XRead::try_from(request(&["xread", "STREAMS", "s", "t", "0"])).is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_xread_blocks_until_xadd() -> Result<()> {
+        let backend = Backend::new();
+        let cmd = XRead::try_from(request(&["xread", "BLOCK", "0", "STREAMS", "s", "$"]))?;
+        let waiting = tokio::spawn({
+            let backend = backend.clone();
+            async move { cmd.execute_blocking(&backend).await }
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        XAdd::try_from(request(&["xadd", "s", "1-0", "f", "v"]))?.execute(&backend);
+        let RespFrame::Array(Array(Some(streams))) = waiting.await? else {
+            panic!("XREAD should reply with the added entry");
+        };
+        assert_eq!(streams.len(), 1);
+
+        let cmd = XRead::try_from(request(&["xread", "BLOCK", "20", "STREAMS", "s", "$"]))?;
+        assert_eq!(cmd.execute_blocking(&backend).await, Array::none().into());
+        Ok(())
+    }
+
+    #[test]
+    fn test_consumer_group_commands() -> Result<()> {
+        let backend = Backend::new();
+        let cmd = XGroup::try_from(request(&["xgroup", "CREATE", "s", "g", "$"]))?;
+        assert!(
+            matches!(cmd.execute(&backend), RespFrame::Error(e) if e.starts_with("ERR The XGROUP"))
+        );
+        let cmd = XGroup::try_from(request(&["xgroup", "CREATE", "s", "g", "0", "MKSTREAM"]))?;
+        assert_eq!(cmd.execute(&backend), RESP_OK.clone());
+        XAdd::try_from(request(&["xadd", "s", "1-0", "f", "v"]))?.execute(&backend);
+
+        let cmd = XReadGroup::try_from(request(&[
+            "xreadgroup",
+            "GROUP",
+            "g",
+            "c",
+            "STREAMS",
+            "s",
+            ">",
+        ]))?;
+        assert!(!cmd.is_blocking());
+        let RespFrame::Array(Array(Some(streams))) = cmd.execute(&backend) else {
+            panic!("XREADGROUP should deliver the new entry");
+        };
+        assert_eq!(streams.len(), 1);
+
+        let cmd = XPending::try_from(request(&["xpending", "s", "g"]))?;
+        assert_eq!(
+            cmd.execute(&backend),
+            Array::new(vec![
+                RespFrame::Integer(1),
+                bulk("1-0"),
+                bulk("1-0"),
+                Array::new(vec![Array::new(vec![bulk("c"), bulk("1")]).into()]).into(),
+            ])
+            .into()
+        );
+        let cmd = XClaim::try_from(request(&["xclaim", "s", "g", "d", "0", "1-0", "JUSTID"]))?;
+        assert_eq!(cmd.execute(&backend), Array::new(vec![bulk("1-0")]).into());
+        let cmd = XPending::try_from(request(&["xpending", "s", "g", "-", "+", "10", "d"]))?;
+        let RespFrame::Array(Array(Some(pending))) = cmd.execute(&backend) else {
+            panic!("XPENDING should reply with the pending entries");
+        };
+        assert_eq!(pending.len(), 1);
+
+        let cmd = XAck::try_from(request(&["xack", "s", "g", "1-0", "2-0"]))?;
+        assert_eq!(cmd.execute(&backend), RespFrame::Integer(1));
+        let cmd = XReadGroup::try_from(request(&[
+            "xreadgroup",
+            "GROUP",
+            "nope",
+            "c",
+            "STREAMS",
+            "s",
+            ">",
+        ]))?;
+        assert!(matches!(cmd.execute(&backend), RespFrame::Error(e) if e.starts_with("NOGROUP")));
+        Ok(())
+    }
+}
@@ -0,0 +1,515 @@
+use bytes::Bytes;
+
+use crate::cmd::{
+    command_name, extract_args, parse_i64, parse_string, validate_command,
+    validate_command_at_least, Append, BitCount, BitOp, CommandError, CommandExecutor, GetBit,
+    GetDel, GetRange, IncrBy, IncrByFloat, MGet, MSet, SetBit, SetRange, StrLen, RESP_OK,
+    RESP_WRONGTYPE,
+};
+use crate::{Array, Backend, BitOperator, BulkString, Null, RespFrame, SimpleError, StringError};
+
+// the highest bit offset of SETBIT and GETBIT, strings are at most 512MB
+const MAX_BIT_OFFSET: i64 = 4 * 1024 * 1024 * 1024 - 1;
+
+impl CommandExecutor for IncrBy {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.incr_by(&self.key, self.delta) {
+            Ok(value) => RespFrame::Integer(value),
+            Err(e) => string_error(e),
+        }
+    }
+}
+
+impl CommandExecutor for IncrByFloat {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.incr_by_float(&self.key, self.delta) {
+            Ok(value) => BulkString::from(value).into(),
+            Err(e) => string_error(e),
+        }
+    }
+}
+
+impl CommandExecutor for Append {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.append(&self.key, &self.value) {
+            Ok(len) => RespFrame::Integer(len as i64),
+            Err(e) => string_error(e),
+        }
+    }
+}
+
+impl CommandExecutor for GetRange {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        if let Some(err) = check_string_type(backend, &self.key) {
+            return err;
+        }
+        BulkString::from(backend.getrange(&self.key, self.start, self.end)).into()
+    }
+}
+
+impl CommandExecutor for SetRange {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.setrange(&self.key, self.offset, &self.value) {
+            Ok(len) => RespFrame::Integer(len as i64),
+            Err(e) => string_error(e),
+        }
+    }
+}
+
+impl CommandExecutor for StrLen {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        if let Some(err) = check_string_type(backend, &self.key) {
+            return err;
+        }
+        RespFrame::Integer(backend.strlen(&self.key) as i64)
+    }
+}
+
+impl CommandExecutor for GetDel {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        if let Some(err) = check_string_type(backend, &self.key) {
+            return err;
+        }
+        match backend.getdel(&self.key) {
+            Some(value) => BulkString::from(value).into(),
+            None => RespFrame::Null(Null),
+        }
+    }
+}
+
+// keys holding another type are reported as missing
+impl CommandExecutor for MGet {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let values = self
+            .keys
+            .iter()
+            .map(|key| match backend.get(key) {
+                Some(value) => BulkString::from(value).into(),
+                None => RespFrame::Null(Null),
+            })
+            .collect::<Vec<_>>();
+        Array::new(values).into()
+    }
+}
+
+impl CommandExecutor for MSet {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        if self.nx {
+            RespFrame::Integer(backend.msetnx(self.pairs) as i64)
+        } else {
+            backend.mset(self.pairs);
+            RESP_OK.clone()
+        }
+    }
+}
+
+impl CommandExecutor for SetBit {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.setbit(&self.key, self.offset, self.bit) {
+            Ok(bit) => RespFrame::Integer(bit as i64),
+            Err(e) => string_error(e),
+        }
+    }
+}
+
+impl CommandExecutor for GetBit {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        if let Some(err) = check_string_type(backend, &self.key) {
+            return err;
+        }
+        RespFrame::Integer(backend.getbit(&self.key, self.offset) as i64)
+    }
+}
+
+impl CommandExecutor for BitCount {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        if let Some(err) = check_string_type(backend, &self.key) {
+            return err;
+        }
+        RespFrame::Integer(backend.bitcount(&self.key, self.range) as i64)
+    }
+}
+
+// the destination is overwritten whatever its type, the sources must be strings
+impl CommandExecutor for BitOp {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        if let Some(err) = self
+            .keys
+            .iter()
+            .find_map(|key| check_string_type(backend, key))
+        {
+            return err;
+        }
+        RespFrame::Integer(backend.bitop(self.op, self.dest, &self.keys) as i64)
+    }
+}
+
+// the commands which don't store a string can't race with the write of another type, checking
+// the type beforehand is enough for them
+pub(super) fn check_string_type(backend: &Backend, key: &str) -> Option<RespFrame> {
+    backend.is_non_string(key).then(|| RESP_WRONGTYPE.clone())
+}
+
+fn string_error(e: StringError) -> RespFrame {
+    SimpleError::new(e.to_string()).into()
+}
+
+// a string value, kept as the bytes of the request
+pub(super) fn parse_bytes(frame: Option<RespFrame>) -> Result<Bytes, CommandError> {
+    match frame {
+        Some(RespFrame::BulkString(s)) => Ok(s.0.unwrap_or_default()),
+        _ => Err(CommandError::InvalidArgument(
+            "Invalid argument".to_string(),
+        )),
+    }
+}
+
+fn parse_bit_offset(frame: Option<RespFrame>) -> Result<usize, CommandError> {
+    match parse_i64(frame) {
+        Ok(offset) if (0..=MAX_BIT_OFFSET).contains(&offset) => Ok(offset as usize),
+        _ => Err(CommandError::InvalidArgument(
+            "bit offset is not an integer or out of range".to_string(),
+        )),
+    }
+}
+
+impl TryFrom<Array> for IncrBy {
+    type Error = CommandError;
+    fn try_from(value: Array) -> Result<Self, Self::Error> {
+        let name = command_name(&value, &["incr", "decr", "incrby", "decrby"])?;
+        let with_delta = name.ends_with("by");
+        validate_command(&value, &[name], if with_delta { 2 } else { 1 })?;
+        let mut args = extract_args(value, 1)?.into_iter();
+        let key = parse_string(args.next())?;
+        let delta = if with_delta {
+            parse_i64(args.next())?
+        } else {
+            1
+        };
+        let delta = if name.starts_with("decr") {
+            delta.checked_neg().ok_or_else(|| {
+                CommandError::InvalidArgument("decrement would overflow".to_string())
+            })?
+        } else {
+            delta
+        };
+        Ok(IncrBy { key, delta })
+    }
+}
+
+impl TryFrom<Array> for IncrByFloat {
+    type Error = CommandError;
+    fn try_from(value: Array) -> Result<Self, Self::Error> {
+        validate_command(&value, &["incrbyfloat"], 2)?;
+        let mut args = extract_args(value, 1)?.into_iter();
+        let key = parse_string(args.next())?;
+        let delta = parse_string(args.next())?
+            .parse::<f64>()
+            .ok()
+            .filter(|v| v.is_finite())
+            .ok_or_else(|| {
+                CommandError::InvalidArgument("value is not a valid float".to_string())
+            })?;
+        Ok(IncrByFloat { key, delta })
+    }
+}
+
+impl TryFrom<Array> for Append {
+    type Error = CommandError;
+    fn try_from(value: Array) -> Result<Self, Self::Error> {
+        validate_command(&value, &["append"], 2)?;
+        let mut args = extract_args(value, 1)?.into_iter();
+        Ok(Append {
+            key: parse_string(args.next())?,
+            value: parse_bytes(args.next())?,
+        })
+    }
+}
+
+impl TryFrom<Array> for GetRange {
+    type Error = CommandError;
+    fn try_from(value: Array) -> Result<Self, Self::Error> {
+        validate_command(&value, &["getrange"], 3)?;
+        let mut args = extract_args(value, 1)?.into_iter();
+        Ok(GetRange {
+            key: parse_string(args.next())?,
+            start: parse_i64(args.next())?,
+            end: parse_i64(args.next())?,
+        })
+    }
+}
+
+impl TryFrom<Array> for SetRange {
+    type Error = CommandError;
+    fn try_from(value: Array) -> Result<Self, Self::Error> {
+        validate_command(&value, &["setrange"], 3)?;
+        let mut args = extract_args(value, 1)?.into_iter();
+        let key = parse_string(args.next())?;
+        let offset = parse_i64(args.next())?;
+        if offset < 0 {
+            return Err(CommandError::InvalidArgument(
+                "offset is out of range".to_string(),
+            ));
+        }
+        Ok(SetRange {
+            key,
+            offset: offset as usize,
+            value: parse_bytes(args.next())?,
+        })
+    }
+}
+
+impl TryFrom<Array> for StrLen {
+    type Error = CommandError;
+    fn try_from(value: Array) -> Result<Self, Self::Error> {
+        validate_command(&value, &["strlen"], 1)?;
+        let mut args = extract_args(value, 1)?.into_iter();
+        Ok(StrLen {
+            key: parse_string(args.next())?,
+        })
+    }
+}
+
+impl TryFrom<Array> for GetDel {
+    type Error = CommandError;
+    fn try_from(value: Array) -> Result<Self, Self::Error> {
+        validate_command(&value, &["getdel"], 1)?;
+        let mut args = extract_args(value, 1)?.into_iter();
+        Ok(GetDel {
+            key: parse_string(args.next())?,
+        })
+    }
+}
+
+impl TryFrom<Array> for MGet {
+    type Error = CommandError;
+    fn try_from(value: Array) -> Result<Self, Self::Error> {
+        validate_command_at_least(&value, &["mget"], 1)?;
+        let keys = extract_args(value, 1)?
+            .into_iter()
+            .map(|v| parse_string(Some(v)))
+            .collect::<Result<_, _>>()?;
+        Ok(MGet { keys })
+    }
+}
+
+impl TryFrom<Array> for MSet {
+    type Error = CommandError;
+    fn try_from(value: Array) -> Result<Self, Self::Error> {
+        let name = command_name(&value, &["mset", "msetnx"])?;
+        validate_command_at_least(&value, &[name], 2)?;
+        let args = extract_args(value, 1)?;
+        if args.len() % 2 != 0 {
+            return Err(CommandError::WrongArity(name.to_string()));
+        }
+        let mut args = args.into_iter();
+        let mut pairs = Vec::with_capacity(args.len() / 2);
+        while let Some(key) = args.next() {
+            pairs.push((parse_string(Some(key))?, parse_bytes(args.next())?));
+        }
+        Ok(MSet {
+            pairs,
+            nx: name == "msetnx",
+        })
+    }
+}
+
+impl TryFrom<Array> for SetBit {
+    type Error = CommandError;
+    fn try_from(value: Array) -> Result<Self, Self::Error> {
+        validate_command(&value, &["setbit"], 3)?;
+        let mut args = extract_args(value, 1)?.into_iter();
+        let key = parse_string(args.next())?;
+        let offset = parse_bit_offset(args.next())?;
+        let bit = match parse_string(args.next())?.as_str() {
+            "0" => false,
+            "1" => true,
+            _ => {
+                return Err(CommandError::InvalidArgument(
+                    "bit is not an integer or out of range".to_string(),
+                ))
+            }
+        };
+        Ok(SetBit { key, offset, bit })
+    }
+}
+
+impl TryFrom<Array> for GetBit {
+    type Error = CommandError;
+    fn try_from(value: Array) -> Result<Self, Self::Error> {
+        validate_command(&value, &["getbit"], 2)?;
+        let mut args = extract_args(value, 1)?.into_iter();
+        Ok(GetBit {
+            key: parse_string(args.next())?,
+            offset: parse_bit_offset(args.next())?,
+        })
+    }
+}
+
+impl TryFrom<Array> for BitCount {
+    type Error = CommandError;
+    fn try_from(value: Array) -> Result<Self, Self::Error> {
+        validate_command_at_least(&value, &["bitcount"], 1)?;
+        let mut args = extract_args(value, 1)?.into_iter();
+        let key = parse_string(args.next())?;
+        let range = match args.len() {
+            0 => None,
+            2 | 3 => {
+                let start = parse_i64(args.next())?;
+                let end = parse_i64(args.next())?;
+                let bit = match args.next() {
+                    None => false,
+                    Some(unit) => match parse_string(Some(unit))?.to_ascii_lowercase().as_str() {
+                        "byte" => false,
+                        "bit" => true,
+                        _ => return Err(CommandError::InvalidArgument("syntax error".to_string())),
+                    },
+                };
+                Some((start, end, bit))
+            }
+            _ => return Err(CommandError::InvalidArgument("syntax error".to_string())),
+        };
+        Ok(BitCount { key, range })
+    }
+}
+
+impl TryFrom<Array> for BitOp {
+    type Error = CommandError;
+    fn try_from(value: Array) -> Result<Self, Self::Error> {
+        validate_command_at_least(&value, &["bitop"], 3)?;
+        let mut args = extract_args(value, 1)?.into_iter();
+        let op = match parse_string(args.next())?.to_ascii_lowercase().as_str() {
+            "and" => BitOperator::And,
+            "or" => BitOperator::Or,
+            "xor" => BitOperator::Xor,
+            "not" => BitOperator::Not,
+            _ => return Err(CommandError::InvalidArgument("syntax error".to_string())),
+        };
+        let dest = parse_string(args.next())?;
+        let keys = args
+            .map(|v| parse_string(Some(v)))
+            .collect::<Result<Vec<_>, _>>()?;
+        if op == BitOperator::Not && keys.len() != 1 {
+            return Err(CommandError::InvalidArgument(
+                "BITOP NOT must be called with a single source key.".to_string(),
+            ));
+        }
+        Ok(BitOp { op, dest, keys })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use bytes::BytesMut;
+
+    use crate::cmd::Command;
+    use crate::RespDecode;
+
+    use super::*;
+
+    fn parse(request: &[u8]) -> Result<Command, CommandError> {
+        let mut buf = BytesMut::from(request);
+        let frame = Array::decode(&mut buf).unwrap();
+        Command::try_from(frame)
+    }
+
+    #[test]
+    fn test_string_try_from_array() -> Result<()> {
+        let Command::IncrBy(cmd) = parse(b"*3\r\n$6\r\ndecrby\r\n$1\r\nn\r\n$1\r\n5\r\n")? else {
+            panic!("expected DECRBY");
+        };
+        assert_eq!((cmd.key.as_str(), cmd.delta), ("n", -5));
+        let Command::IncrBy(cmd) = parse(b"*2\r\n$4\r\nINCR\r\n$1\r\nn\r\n")? else {
+            panic!("expected INCR");
+        };
+        assert_eq!(cmd.delta, 1);
+        assert!(parse(b"*3\r\n$6\r\nincrby\r\n$1\r\nn\r\n$1\r\nx\r\n").is_err());
+
+        let Command::MSet(cmd) =
+            parse(b"*5\r\n$6\r\nmsetnx\r\n$1\r\na\r\n$1\r\n1\r\n$1\r\nb\r\n$1\r\n2\r\n")?
+        else {
+            panic!("expected MSETNX");
+        };
+        assert!(cmd.nx);
+        assert_eq!(
+            cmd.pairs,
+            vec![("a".to_string(), "1".into()), ("b".to_string(), "2".into())]
+        );
+        assert!(parse(b"*4\r\n$4\r\nmset\r\n$1\r\na\r\n$1\r\n1\r\n$1\r\nb\r\n").is_err());
+
+        let Command::BitCount(cmd) =
+            parse(b"*5\r\n$8\r\nbitcount\r\n$1\r\nk\r\n$1\r\n1\r\n$2\r\n-1\r\n$3\r\nBIT\r\n")?
+        else {
+            panic!("expected BITCOUNT");
+        };
+        assert_eq!(cmd.range, Some((1, -1, true)));
+        assert!(parse(b"*3\r\n$8\r\nbitcount\r\n$1\r\nk\r\n$1\r\n1\r\n").is_err());
+        assert!(parse(b"*4\r\n$6\r\nsetbit\r\n$1\r\nk\r\n$2\r\n-1\r\n$1\r\n1\r\n").is_err());
+        assert!(
+            parse(b"*5\r\n$5\r\nbitop\r\n$3\r\nnot\r\n$1\r\nd\r\n$1\r\na\r\n$1\r\nb\r\n").is_err()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_string_commands() {
+        let backend = Backend::new();
+        let incr = |delta| IncrBy {
+            key: "n".to_string(),
+            delta,
+        };
+        assert_eq!(incr(1).execute(&backend), RespFrame::Integer(1));
+        assert_eq!(incr(-3).execute(&backend), RespFrame::Integer(-2));
+
+        backend.set("s".to_string(), "abc".into());
+        let cmd = IncrBy {
+            key: "s".to_string(),
+            delta: 1,
+        };
+        assert_eq!(
+            cmd.execute(&backend),
+            SimpleError::new("ERR value is not an integer or out of range").into()
+        );
+
+        backend
+            .rpush("list".to_string(), vec![BulkString::new("a").into()])
+            .unwrap();
+        let cmd = Append {
+            key: "list".to_string(),
+            value: "x".into(),
+        };
+        assert_eq!(cmd.execute(&backend), RESP_WRONGTYPE.clone());
+
+        let cmd = MGet {
+            keys: vec!["s".to_string(), "list".to_string(), "none".to_string()],
+        };
+        assert_eq!(
+            cmd.execute(&backend),
+            Array::new(vec![
+                BulkString::new("abc").into(),
+                RespFrame::Null(Null),
+                RespFrame::Null(Null),
+            ])
+            .into()
+        );
+
+        let cmd = MSet {
+            pairs: vec![
+                ("s".to_string(), "1".into()),
+                ("new".to_string(), "2".into()),
+            ],
+            nx: true,
+        };
+        assert_eq!(cmd.execute(&backend), RespFrame::Integer(0));
+        assert!(!backend.exists("new"));
+
+        let cmd = BitOp {
+            op: BitOperator::Or,
+            dest: "list".to_string(),
+            keys: vec!["s".to_string()],
+        };
+        assert_eq!(cmd.execute(&backend), RespFrame::Integer(3));
+        assert_eq!(backend.key_type("list"), Some("string"));
+    }
+}
@@ -0,0 +1,366 @@
+use crate::cmd::CommandError;
+use crate::{BulkString, RespFrame};
+
+/// Static description of a command, as reported by COMMAND INFO.
+#[derive(Debug, PartialEq)]
+pub struct CommandSpec {
+    pub name: &'static str,
+    // number of arguments including the command name, a negative arity is a minimum
+    pub arity: i64,
+    pub flags: &'static [&'static str],
+    // position of the first and the last key and the step between keys, 0 if there are none
+    // and a negative last key counts from the end
+    pub first_key: i64,
+    pub last_key: i64,
+    pub step: i64,
+}
+
+const WRITE: &[&str] = &["write", "denyoom"];
+const WRITE_FAST: &[&str] = &["write", "denyoom", "fast"];
+const DELETE: &[&str] = &["write", "fast"];
+const READONLY: &[&str] = &["readonly"];
+const READONLY_FAST: &[&str] = &["readonly", "fast"];
+const BLOCKING: &[&str] = &["write", "blocking"];
+const ADMIN: &[&str] = &["admin", "noscript"];
+const PUBSUB: &[&str] = &["pubsub", "noscript", "loading", "stale"];
+const CONNECTION: &[&str] = &["noscript", "loading", "stale", "fast"];
+// may be sent before the client authenticated
+const AUTH: &[&str] = &["noscript", "loading", "stale", "fast", "no_auth"];
+// the keys of a script follow its number of keys
+const SCRIPT: &[&str] = &["noscript", "stale", "skip_monitor", "movablekeys"];
+// the keys of a stream read are the first half of the arguments after STREAMS
+const STREAM_READ: &[&str] = &["readonly", "blocking", "movablekeys"];
+
+/// The ACL categories, which group commands by the type of their keys and by their flags.
+pub const ACL_CATEGORIES: &[&str] = &[
+    "keyspace",
+    "read",
+    "write",
+    "set",
+    "sortedset",
+    "list",
+    "hash",
+    "string",
+    "bitmap",
+    "hyperloglog",
+    "geo",
+    "stream",
+    "pubsub",
+    "admin",
+    "fast",
+    "slow",
+    "blocking",
+    "dangerous",
+    "connection",
+    "transaction",
+    "scripting",
+];
+
+macro_rules! spec {
+    ($name:literal, $arity:literal, $flags:expr) => {
+        spec!($name, $arity, $flags, 0, 0, 0)
+    };
+    ($name:literal, $arity:literal, $flags:expr, $first:literal, $last:literal, $step:literal) => {
+        CommandSpec {
+            name: $name,
+            arity: $arity,
+            flags: $flags,
+            first_key: $first,
+            last_key: $last,
+            step: $step,
+        }
+    };
+}
+
+/// Every command known to the server, sorted by name.
+pub static COMMAND_TABLE: &[CommandSpec] = &[
+    spec!("acl", -2, &["admin", "noscript", "loading", "stale"]),
+    spec!("append", 3, WRITE_FAST, 1, 1, 1),
+    spec!("asking", 1, &["fast"]),
+    spec!("auth", -2, AUTH),
+    spec!("bgrewriteaof", 1, ADMIN),
+    spec!("bgsave", 1, ADMIN),
+    spec!("bitcount", -2, READONLY, 1, 1, 1),
+    spec!("bitop", -4, WRITE, 2, -1, 1),
+    spec!("blpop", -3, BLOCKING, 1, -2, 1),
+    spec!("brpop", -3, BLOCKING, 1, -2, 1),
+    spec!("client", -2, &["noscript", "loading", "stale"]),
+    spec!("cluster", -2, &["stale"]),
+    spec!("command", -1, &["loading", "stale"]),
+    spec!("config", -2, &["admin", "noscript", "loading", "stale"]),
+    spec!("dbsize", 1, READONLY_FAST),
+    spec!("decr", 2, WRITE_FAST, 1, 1, 1),
+    spec!("decrby", 3, WRITE_FAST, 1, 1, 1),
+    spec!("del", -2, &["write"], 1, -1, 1),
+    spec!("discard", 1, CONNECTION),
+    spec!("echo", 2, &["fast"]),
+    spec!("eval", -3, SCRIPT),
+    spec!("evalsha", -3, SCRIPT),
+    spec!("exec", 1, &["noscript", "loading", "stale"]),
+    spec!("exists", -2, READONLY_FAST, 1, -1, 1),
+    spec!("expire", -3, DELETE, 1, 1, 1),
+    spec!("expireat", -3, DELETE, 1, 1, 1),
+    spec!("flushall", -1, &["write"]),
+    spec!("flushdb", -1, &["write"]),
+    spec!("geoadd", -5, WRITE, 1, 1, 1),
+    spec!("geodist", -4, READONLY, 1, 1, 1),
+    spec!("geohash", -2, READONLY, 1, 1, 1),
+    spec!("geopos", -2, READONLY, 1, 1, 1),
+    spec!("geosearch", -7, READONLY, 1, 1, 1),
+    spec!("get", 2, READONLY_FAST, 1, 1, 1),
+    spec!("getbit", 3, READONLY_FAST, 1, 1, 1),
+    spec!("getdel", 2, DELETE, 1, 1, 1),
+    spec!("getrange", 4, READONLY, 1, 1, 1),
+    spec!("hello", -1, AUTH),
+    spec!("hget", 3, READONLY_FAST, 1, 1, 1),
+    spec!("hgetall", 2, READONLY, 1, 1, 1),
+    spec!("hmget", -3, READONLY_FAST, 1, 1, 1),
+    spec!("hmset", -4, WRITE_FAST, 1, 1, 1),
+    spec!("hscan", -3, READONLY, 1, 1, 1),
+    spec!("hset", 4, WRITE_FAST, 1, 1, 1),
+    spec!("incr", 2, WRITE_FAST, 1, 1, 1),
+    spec!("incrby", 3, WRITE_FAST, 1, 1, 1),
+    spec!("incrbyfloat", 3, WRITE_FAST, 1, 1, 1),
+    spec!("info", -1, &["loading", "stale"]),
+    spec!("keys", 2, READONLY),
+    spec!("lastsave", 1, &["loading", "stale", "fast"]),
+    spec!("lindex", 3, READONLY, 1, 1, 1),
+    spec!("llen", 2, READONLY_FAST, 1, 1, 1),
+    spec!("lpop", -2, DELETE, 1, 1, 1),
+    spec!("lpush", -3, WRITE_FAST, 1, 1, 1),
+    spec!("lpushx", -3, WRITE_FAST, 1, 1, 1),
+    spec!("lrange", 4, READONLY, 1, 1, 1),
+    spec!("lrem", 4, &["write"], 1, 1, 1),
+    spec!("lset", 4, WRITE, 1, 1, 1),
+    spec!("ltrim", 4, &["write"], 1, 1, 1),
+    spec!("mget", -2, READONLY_FAST, 1, -1, 1),
+    spec!("monitor", 1, &["admin", "noscript", "loading", "stale"]),
+    spec!("mset", -3, WRITE, 1, -1, 2),
+    spec!("msetnx", -3, WRITE, 1, -1, 2),
+    spec!("multi", 1, CONNECTION),
+    spec!("object", -2, &["readonly"], 2, 2, 1),
+    spec!("persist", 2, DELETE, 1, 1, 1),
+    spec!("pexpire", -3, DELETE, 1, 1, 1),
+    spec!("pexpireat", -3, DELETE, 1, 1, 1),
+    spec!("pfadd", -2, WRITE_FAST, 1, 1, 1),
+    spec!("pfcount", -2, READONLY, 1, -1, 1),
+    spec!("pfmerge", -2, WRITE, 1, -1, 1),
+    spec!("ping", -1, &["fast"]),
+    spec!("psubscribe", -2, PUBSUB),
+    spec!("psync", -3, ADMIN),
+    spec!("pttl", 2, READONLY_FAST, 1, 1, 1),
+    spec!("publish", 3, &["pubsub", "loading", "stale", "fast"]),
+    spec!("pubsub", -2, &["pubsub", "loading", "stale"]),
+    spec!("punsubscribe", -1, PUBSUB),
+    spec!("rename", 3, &["write"], 1, 2, 1),
+    spec!("renamenx", 3, DELETE, 1, 2, 1),
+    spec!("replconf", -1, &["admin", "noscript", "loading", "stale"]),
+    spec!("replicaof", 3, &["admin", "noscript", "stale"]),
+    spec!("rpop", -2, DELETE, 1, 1, 1),
+    spec!("rpush", -3, WRITE_FAST, 1, 1, 1),
+    spec!("rpushx", -3, WRITE_FAST, 1, 1, 1),
+    spec!("save", 1, ADMIN),
+    spec!("scan", -2, READONLY),
+    spec!("script", -2, &["noscript"]),
+    spec!("select", 2, CONNECTION),
+    spec!("set", -3, WRITE, 1, 1, 1),
+    spec!("setbit", 4, WRITE, 1, 1, 1),
+    spec!("setrange", 4, WRITE, 1, 1, 1),
+    spec!("slaveof", 3, &["admin", "noscript", "stale"]),
+    spec!("slowlog", -2, &["admin", "loading", "stale"]),
+    spec!("strlen", 2, READONLY_FAST, 1, 1, 1),
+    spec!("subscribe", -2, PUBSUB),
+    spec!("ttl", 2, READONLY_FAST, 1, 1, 1),
+    spec!("type", 2, READONLY_FAST, 1, 1, 1),
+    spec!("unsubscribe", -1, PUBSUB),
+    spec!("unwatch", 1, CONNECTION),
+    spec!("watch", -2, CONNECTION, 1, -1, 1),
+    spec!("xack", -4, DELETE, 1, 1, 1),
+    spec!("xadd", -5, WRITE_FAST, 1, 1, 1),
+    spec!("xclaim", -6, DELETE, 1, 1, 1),
+    spec!("xgroup", -2, WRITE, 2, 2, 1),
+    spec!("xlen", 2, READONLY_FAST, 1, 1, 1),
+    spec!("xpending", -3, READONLY, 1, 1, 1),
+    spec!("xrange", -4, READONLY, 1, 1, 1),
+    spec!("xread", -4, STREAM_READ),
+    spec!("xreadgroup", -7, &["write", "blocking", "movablekeys"]),
+    spec!("xrevrange", -4, READONLY, 1, 1, 1),
+    spec!("xtrim", -4, DELETE, 1, 1, 1),
+    spec!("zadd", -4, WRITE_FAST, 1, 1, 1),
+    spec!("zcard", 2, READONLY_FAST, 1, 1, 1),
+    spec!("zcount", 4, READONLY_FAST, 1, 1, 1),
+    spec!("zincrby", 4, WRITE_FAST, 1, 1, 1),
+    spec!("zrange", -4, READONLY, 1, 1, 1),
+    spec!("zrangebyscore", -4, READONLY, 1, 1, 1),
+    spec!("zrank", 3, READONLY_FAST, 1, 1, 1),
+    spec!("zrem", -3, DELETE, 1, 1, 1),
+    spec!("zrevrange", -4, READONLY, 1, 1, 1),
+    spec!("zrevrangebyscore", -4, READONLY, 1, 1, 1),
+    spec!("zrevrank", 3, READONLY_FAST, 1, 1, 1),
+    spec!("zscore", 3, READONLY_FAST, 1, 1, 1),
+];
+
+/// Look up a command by its lowercase name.
+pub fn lookup(name: &str) -> Option<&'static CommandSpec> {
+    COMMAND_TABLE
+        .binary_search_by(|spec| spec.name.cmp(name))
+        .ok()
+        .map(|i| &COMMAND_TABLE[i])
+}
+
+impl CommandSpec {
+    /// Check the number of arguments of a request, including the command name.
+    pub fn check_arity(&self, n: usize) -> Result<(), CommandError> {
+        let n = n as i64;
+        let valid = match self.arity {
+            arity if arity < 0 => n >= -arity,
+            arity => n == arity,
+        };
+        match valid {
+            true => Ok(()),
+            false => Err(CommandError::WrongArity(self.name.to_string())),
+        }
+    }
+
+    pub fn has_flag(&self, flag: &str) -> bool {
+        self.flags.contains(&flag)
+    }
+
+    /// The ACL categories of the command, derived from its flags and from the type it works on.
+    pub fn acl_categories(&self) -> Vec<&'static str> {
+        let mut categories = Vec::new();
+        for (flag, category) in [
+            ("write", "write"),
+            ("readonly", "read"),
+            ("admin", "admin"),
+            ("pubsub", "pubsub"),
+            ("blocking", "blocking"),
+        ] {
+            if self.has_flag(flag) {
+                categories.push(category);
+            }
+        }
+        categories.push(if self.has_flag("fast") {
+            "fast"
+        } else {
+            "slow"
+        });
+        let dangerous = matches!(
+            self.name,
+            "acl" | "client" | "flushall" | "flushdb" | "info" | "keys"
+        );
+        if self.has_flag("admin") || dangerous {
+            categories.push("dangerous");
+        }
+        if let Some(category) = type_category(self.name) {
+            categories.push(category);
+        }
+        categories
+    }
+
+    /// The positions of the keys in a request with `n` arguments.
+    pub fn key_positions(&self, n: usize) -> Vec<usize> {
+        if self.first_key <= 0 {
+            return vec![];
+        }
+        let last = match self.last_key {
+            last if last < 0 => n as i64 + last,
+            last => last,
+        };
+        (self.first_key..=last.min(n as i64 - 1))
+            .step_by(self.step.max(1) as usize)
+            .map(|i| i as usize)
+            .collect()
+    }
+
+    /// The positions of the keys of a "movablekeys" command, found in its arguments.
+    pub fn movable_key_positions(&self, args: &[RespFrame]) -> Vec<usize> {
+        let arg = |i: usize| match args.get(i) {
+            Some(RespFrame::BulkString(BulkString(Some(arg)))) => std::str::from_utf8(arg).ok(),
+            _ => None,
+        };
+        match self.name {
+            "eval" | "evalsha" => {
+                let n = arg(2).and_then(|n| n.parse::<usize>().ok());
+                (3..3 + n.unwrap_or_default()).collect()
+            }
+            "xread" | "xreadgroup" => {
+                let streams = (1..args.len())
+                    .find(|i| arg(*i).is_some_and(|arg| arg.eq_ignore_ascii_case("streams")));
+                match streams {
+                    Some(i) => (i + 1..i + 1 + (args.len() - i - 1) / 2).collect(),
+                    None => vec![],
+                }
+            }
+            _ => vec![],
+        }
+    }
+}
+
+// the category of the commands of a data type, or of a group of commands
+fn type_category(name: &str) -> Option<&'static str> {
+    let category = match name {
+        "append" | "decr" | "decrby" | "get" | "getdel" | "getrange" | "incr" | "incrby"
+        | "incrbyfloat" | "mget" | "mset" | "msetnx" | "set" | "setrange" | "strlen" => "string",
+        "bitcount" | "bitop" | "getbit" | "setbit" => "bitmap",
+        "blpop" | "brpop" | "lindex" | "llen" | "lpop" | "lpush" | "lpushx" | "lrange" | "lrem"
+        | "lset" | "ltrim" | "rpop" | "rpush" | "rpushx" => "list",
+        "dbsize" | "del" | "exists" | "expire" | "expireat" | "flushall" | "flushdb" | "keys"
+        | "object" | "persist" | "pexpire" | "pexpireat" | "pttl" | "rename" | "renamenx"
+        | "scan" | "ttl" | "type" => "keyspace",
+        "asking" | "auth" | "client" | "command" | "echo" | "hello" | "ping" | "select" => {
+            "connection"
+        }
+        "discard" | "exec" | "multi" | "unwatch" | "watch" => "transaction",
+        "eval" | "evalsha" | "script" => "scripting",
+        _ if name.starts_with("geo") => "geo",
+        _ if name.starts_with("pf") => "hyperloglog",
+        _ if name.starts_with('h') => "hash",
+        _ if name.starts_with('z') => "sortedset",
+        _ if name.starts_with('x') => "stream",
+        _ => return None,
+    };
+    Some(category)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_command_table_is_sorted() {
+        assert!(COMMAND_TABLE.windows(2).all(|w| w[0].name < w[1].name));
+        assert_eq!(lookup("get").map(|spec| spec.arity), Some(2));
+        assert!(lookup("nope").is_none());
+    }
+
+    #[test]
+    fn test_check_arity_and_keys() {
+        let get = lookup("get").unwrap();
+        assert!(get.check_arity(2).is_ok());
+        assert!(get.check_arity(3).is_err());
+
+        let blpop = lookup("blpop").unwrap();
+        assert!(blpop.check_arity(2).is_err());
+        assert!(blpop.check_arity(5).is_ok());
+        assert!(blpop.has_flag("blocking"));
+        assert_eq!(blpop.key_positions(5), vec![1, 2, 3]);
+        assert_eq!(lookup("watch").unwrap().key_positions(3), vec![1, 2]);
+        assert!(lookup("ping").unwrap().key_positions(2).is_empty());
+
+        let args = ["xread", "count", "1", "streams", "a", "b", "0", "0"]
+            .map(|arg| BulkString::new(arg).into())
+            .to_vec();
+        assert_eq!(
+            lookup("xread").unwrap().movable_key_positions(&args),
+            vec![4, 5]
+        );
+        let args = ["eval", "return 1", "1", "key", "arg"]
+            .map(|arg| BulkString::new(arg).into())
+            .to_vec();
+        assert_eq!(
+            lookup("eval").unwrap().movable_key_positions(&args),
+            vec![3]
+        );
+    }
+}
@@ -0,0 +1,142 @@
+use crate::cmd::{
+    extract_args, not_in_context, parse_string, validate_command, validate_command_at_least,
+    CommandError, CommandExecutor, Discard, Exec, Multi, Unwatch, Watch, RESP_OK,
+};
+use crate::{Array, Backend, RespFrame, Watcher};
+
+// a transaction is part of the state of a connection, so MULTI / EXEC / DISCARD / WATCH are
+// handled by the connection, executing them without one is an error
+impl CommandExecutor for Multi {
+    fn execute(self, _backend: &Backend) -> RespFrame {
+        not_in_context("multi")
+    }
+}
+
+impl CommandExecutor for Exec {
+    fn execute(self, _backend: &Backend) -> RespFrame {
+        not_in_context("exec")
+    }
+}
+
+impl CommandExecutor for Discard {
+    fn execute(self, _backend: &Backend) -> RespFrame {
+        not_in_context("discard")
+    }
+}
+
+impl CommandExecutor for Watch {
+    fn execute(self, _backend: &Backend) -> RespFrame {
+        not_in_context("watch")
+    }
+}
+
+// UNWATCH inside MULTI is queued and does nothing, the keys are unwatched by EXEC anyway
+impl CommandExecutor for Unwatch {
+    fn execute(self, _backend: &Backend) -> RespFrame {
+        RESP_OK.clone()
+    }
+}
+
+impl Watch {
+    /// Watch the keys of the database selected by the connection.
+    pub fn execute_with(self, backend: &Backend, watcher: &mut Watcher) -> RespFrame {
+        for key in self.keys {
+            watcher.watch(backend.db(), key);
+        }
+        RESP_OK.clone()
+    }
+}
+
+impl Unwatch {
+    pub fn execute_with(self, watcher: &mut Watcher) -> RespFrame {
+        watcher.unwatch();
+        RESP_OK.clone()
+    }
+}
+
+impl TryFrom<Array> for Multi {
+    type Error = CommandError;
+    fn try_from(value: Array) -> Result<Self, Self::Error> {
+        validate_command(&value, &["multi"], 0)?;
+        Ok(Multi)
+    }
+}
+
+impl TryFrom<Array> for Exec {
+    type Error = CommandError;
+    fn try_from(value: Array) -> Result<Self, Self::Error> {
+        validate_command(&value, &["exec"], 0)?;
+        Ok(Exec)
+    }
+}
+
+impl TryFrom<Array> for Discard {
+    type Error = CommandError;
+    fn try_from(value: Array) -> Result<Self, Self::Error> {
+        validate_command(&value, &["discard"], 0)?;
+        Ok(Discard)
+    }
+}
+
+impl TryFrom<Array> for Watch {
+    type Error = CommandError;
+    fn try_from(value: Array) -> Result<Self, Self::Error> {
+        validate_command_at_least(&value, &["watch"], 1)?;
+        let keys = extract_args(value, 1)?
+            .into_iter()
+            .map(|v| parse_string(Some(v)))
+            .collect::<Result<_, _>>()?;
+        Ok(Watch { keys })
+    }
+}
+
+impl TryFrom<Array> for Unwatch {
+    type Error = CommandError;
+    fn try_from(value: Array) -> Result<Self, Self::Error> {
+        validate_command(&value, &["unwatch"], 0)?;
+        Ok(Unwatch)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use bytes::BytesMut;
+
+    use crate::RespDecode;
+
+    use super::*;
+
+    #[test]
+    fn test_transaction_try_from_array() -> Result<()> {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(b"*3\r\n$5\r\nWATCH\r\n$1\r\na\r\n$1\r\nb\r\n");
+        let frame = Array::decode(&mut buf)?;
+        let cmd = Watch::try_from(frame)?;
+        assert_eq!(cmd.keys, vec!["a".to_string(), "b".to_string()]);
+
+        buf.extend_from_slice(b"*1\r\n$5\r\nwatch\r\n");
+        let frame = Array::decode(&mut buf)?;
+        assert!(Watch::try_from(frame).is_err());
+
+        buf.extend_from_slice(b"*2\r\n$5\r\nmulti\r\n$1\r\nx\r\n");
+        let frame = Array::decode(&mut buf)?;
+        assert!(Multi::try_from(frame).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_watch_and_unwatch() {
+        let backend = Backend::new();
+        let mut watcher = Watcher::new(backend.clone(), 1);
+        let cmd = Watch {
+            keys: vec!["key".to_string()],
+        };
+        assert_eq!(cmd.execute_with(&backend, &mut watcher), RESP_OK.clone());
+
+        backend.set("key".to_string(), "v".into());
+        assert!(watcher.is_dirty());
+        assert_eq!(Unwatch.execute_with(&mut watcher), RESP_OK.clone());
+        assert!(!watcher.is_dirty());
+    }
+}
@@ -0,0 +1,544 @@
+use crate::cmd::{
+    command_name, extract_args, parse_i64, parse_string, validate_command,
+    validate_command_at_least, CommandError, CommandExecutor, ZAdd, ZCard, ZCount, ZIncrBy, ZRange,
+    ZRangeBy, ZRank, ZRem, ZScore, RESP_WRONGTYPE,
+};
+use crate::{
+    Array, Backend, BulkString, Null, RespFrame, ScoreBound, SimpleError, ZAddFlags, ZSetError,
+};
+
+impl CommandExecutor for ZAdd {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        if self.incr {
+            // INCR is validated to carry exactly one score / member pair
+            let (delta, member) = self.entries.into_iter().next().unwrap_or_default();
+            return incr_reply(backend.zincrby(self.key, member, delta, self.flags));
+        }
+        match backend.zadd(self.key, self.entries, self.flags) {
+            Ok((added, changed)) => {
+                RespFrame::Integer(if self.ch { changed } else { added } as i64)
+            }
+            Err(e) => zset_error(e),
+        }
+    }
+}
+
+impl CommandExecutor for ZIncrBy {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let ret = backend.zincrby(self.key, self.member, self.delta, ZAddFlags::default());
+        incr_reply(ret)
+    }
+}
+
+impl CommandExecutor for ZRem {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.zrem(&self.key, &self.members) {
+            Ok(removed) => RespFrame::Integer(removed as i64),
+            Err(e) => zset_error(e),
+        }
+    }
+}
+
+impl CommandExecutor for ZScore {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        if let Some(err) = check_zset_type(backend, &self.key) {
+            return err;
+        }
+        match backend.zscore(&self.key, &self.member) {
+            Some(score) => RespFrame::Double(score),
+            None => RespFrame::Null(Null),
+        }
+    }
+}
+
+impl CommandExecutor for ZCard {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        if let Some(err) = check_zset_type(backend, &self.key) {
+            return err;
+        }
+        RespFrame::Integer(backend.zcard(&self.key) as i64)
+    }
+}
+
+impl CommandExecutor for ZCount {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        if let Some(err) = check_zset_type(backend, &self.key) {
+            return err;
+        }
+        RespFrame::Integer(backend.zcount(&self.key, self.min, self.max) as i64)
+    }
+}
+
+impl CommandExecutor for ZRank {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        if let Some(err) = check_zset_type(backend, &self.key) {
+            return err;
+        }
+        match backend.zrank(&self.key, &self.member, self.rev) {
+            Some(rank) => RespFrame::Integer(rank as i64),
+            None => RespFrame::Null(Null),
+        }
+    }
+}
+
+impl CommandExecutor for ZRange {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        if let Some(err) = check_zset_type(backend, &self.key) {
+            return err;
+        }
+        let members = match self.by {
+            ZRangeBy::Rank(start, stop) => backend.zrange(&self.key, start, stop, self.rev),
+            ZRangeBy::Score(min, max) => {
+                backend.zrange_by_score(&self.key, min, max, self.rev, self.limit)
+            }
+        };
+
+        let mut ret = Vec::with_capacity(members.len() * if self.with_scores { 2 } else { 1 });
+        for (member, score) in members {
+            ret.push(BulkString::from(member).into());
+            if self.with_scores {
+                ret.push(RespFrame::Double(score));
+            }
+        }
+        Array::new(ret).into()
+    }
+}
+
+fn incr_reply(score: Result<Option<f64>, ZSetError>) -> RespFrame {
+    match score {
+        Ok(Some(score)) if score.is_nan() => {
+            SimpleError::new("ERR resulting score is not a number (NaN)").into()
+        }
+        Ok(Some(score)) => RespFrame::Double(score),
+        Ok(None) => RespFrame::Null(Null),
+        Err(e) => zset_error(e),
+    }
+}
+
+pub(super) fn zset_error(e: ZSetError) -> RespFrame {
+    SimpleError::new(e.to_string()).into()
+}
+
+// the read-only commands don't change the sorted set, checking the type beforehand is enough for them
+pub(super) fn check_zset_type(backend: &Backend, key: &str) -> Option<RespFrame> {
+    match backend.key_type(key) {
+        Some("zset") | None => None,
+        Some(_) => Some(RESP_WRONGTYPE.clone()),
+    }
+}
+
+// parse a score, accepting `inf`, `+inf` and `-inf`
+fn parse_score(frame: Option<RespFrame>) -> Result<f64, CommandError> {
+    let s = parse_string(frame)?;
+    let score = match s.to_ascii_lowercase().as_str() {
+        "inf" | "+inf" => f64::INFINITY,
+        "-inf" => f64::NEG_INFINITY,
+        s => s.parse::<f64>().unwrap_or(f64::NAN),
+    };
+    if score.is_nan() {
+        return Err(CommandError::InvalidArgument(
+            "value is not a valid float".to_string(),
+        ));
+    }
+    Ok(score)
+}
+
+// parse a min / max of a score range, a `(` prefix makes it exclusive
+fn parse_score_bound(frame: Option<RespFrame>) -> Result<ScoreBound, CommandError> {
+    let s = parse_string(frame)?;
+    let (s, exclusive) = match s.strip_prefix('(') {
+        Some(s) => (s, true),
+        None => (s.as_str(), false),
+    };
+    let value = parse_score(Some(RespFrame::BulkString(s.into())))
+        .map_err(|_| CommandError::InvalidArgument("min or max is not a float".to_string()))?;
+    Ok(ScoreBound { value, exclusive })
+}
+
+// ZADD key [NX | XX] [GT | LT] [CH] [INCR] score member [score member ...]
+impl TryFrom<Array> for ZAdd {
+    type Error = CommandError;
+    fn try_from(value: Array) -> Result<Self, Self::Error> {
+        validate_command_at_least(&value, &["zadd"], 3)?;
+        let mut args = extract_args(value, 1)?.into_iter().peekable();
+        let key = parse_string(args.next())?;
+
+        let (mut flags, mut ch, mut incr) = (ZAddFlags::default(), false, false);
+        while let Some(RespFrame::BulkString(option)) = args.peek() {
+            match option.as_ref().to_ascii_lowercase().as_slice() {
+                b"nx" => flags.nx = true,
+                b"xx" => flags.xx = true,
+                b"gt" => flags.gt = true,
+                b"lt" => flags.lt = true,
+                b"ch" => ch = true,
+                b"incr" => incr = true,
+                _ => break,
+            }
+            args.next();
+        }
+        if flags.nx && flags.xx {
+            return Err(CommandError::InvalidArgument(
+                "XX and NX options at the same time are not compatible".to_string(),
+            ));
+        }
+        if (flags.gt && flags.lt) || (flags.nx && (flags.gt || flags.lt)) {
+            return Err(CommandError::InvalidArgument(
+                "GT, LT, and/or NX options at the same time are not compatible".to_string(),
+            ));
+        }
+
+        let args = args.collect::<Vec<_>>();
+        if args.is_empty() || args.len() % 2 != 0 {
+            return Err(CommandError::InvalidArgument("syntax error".to_string()));
+        }
+        if incr && args.len() != 2 {
+            return Err(CommandError::InvalidArgument(
+                "INCR option supports a single increment-element pair".to_string(),
+            ));
+        }
+        let mut entries = Vec::with_capacity(args.len() / 2);
+        let mut args = args.into_iter();
+        while let (Some(score), Some(member)) = (args.next(), args.next()) {
+            entries.push((parse_score(Some(score))?, parse_string(Some(member))?));
+        }
+
+        Ok(ZAdd {
+            key,
+            entries,
+            flags,
+            ch,
+            incr,
+        })
+    }
+}
+
+impl TryFrom<Array> for ZIncrBy {
+    type Error = CommandError;
+    fn try_from(value: Array) -> Result<Self, Self::Error> {
+        validate_command(&value, &["zincrby"], 3)?;
+        let mut args = extract_args(value, 1)?.into_iter();
+        Ok(ZIncrBy {
+            key: parse_string(args.next())?,
+            delta: parse_score(args.next())?,
+            member: parse_string(args.next())?,
+        })
+    }
+}
+
+impl TryFrom<Array> for ZRem {
+    type Error = CommandError;
+    fn try_from(value: Array) -> Result<Self, Self::Error> {
+        validate_command_at_least(&value, &["zrem"], 2)?;
+        let mut args = extract_args(value, 1)?.into_iter();
+        let key = parse_string(args.next())?;
+        let members = args
+            .map(|member| parse_string(Some(member)))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(ZRem { key, members })
+    }
+}
+
+impl TryFrom<Array> for ZScore {
+    type Error = CommandError;
+    fn try_from(value: Array) -> Result<Self, Self::Error> {
+        validate_command(&value, &["zscore"], 2)?;
+        let mut args = extract_args(value, 1)?.into_iter();
+        Ok(ZScore {
+            key: parse_string(args.next())?,
+            member: parse_string(args.next())?,
+        })
+    }
+}
+
+impl TryFrom<Array> for ZCard {
+    type Error = CommandError;
+    fn try_from(value: Array) -> Result<Self, Self::Error> {
+        validate_command(&value, &["zcard"], 1)?;
+        let mut args = extract_args(value, 1)?.into_iter();
+        Ok(ZCard {
+            key: parse_string(args.next())?,
+        })
+    }
+}
+
+impl TryFrom<Array> for ZCount {
+    type Error = CommandError;
+    fn try_from(value: Array) -> Result<Self, Self::Error> {
+        validate_command(&value, &["zcount"], 3)?;
+        let mut args = extract_args(value, 1)?.into_iter();
+        Ok(ZCount {
+            key: parse_string(args.next())?,
+            min: parse_score_bound(args.next())?,
+            max: parse_score_bound(args.next())?,
+        })
+    }
+}
+
+// ZRANK key member / ZREVRANK key member
+impl TryFrom<Array> for ZRank {
+    type Error = CommandError;
+    fn try_from(value: Array) -> Result<Self, Self::Error> {
+        let name = command_name(&value, &["zrank", "zrevrank"])?;
+        validate_command(&value, &[name], 2)?;
+        let mut args = extract_args(value, 1)?.into_iter();
+        Ok(ZRank {
+            key: parse_string(args.next())?,
+            member: parse_string(args.next())?,
+            rev: name == "zrevrank",
+        })
+    }
+}
+
+// ZRANGE key start stop [BYSCORE] [REV] [LIMIT offset count] [WITHSCORES]
+// ZREVRANGE key start stop [WITHSCORES]
+// ZRANGEBYSCORE key min max [WITHSCORES] [LIMIT offset count]
+// ZREVRANGEBYSCORE key max min [WITHSCORES] [LIMIT offset count]
+impl TryFrom<Array> for ZRange {
+    type Error = CommandError;
+    fn try_from(value: Array) -> Result<Self, Self::Error> {
+        let name = command_name(
+            &value,
+            &["zrange", "zrevrange", "zrangebyscore", "zrevrangebyscore"],
+        )?;
+        validate_command_at_least(&value, &[name], 3)?;
+        let mut args = extract_args(value, 1)?.into_iter();
+        let key = parse_string(args.next())?;
+        let (start, stop) = (args.next(), args.next());
+
+        let mut by_score = name.ends_with("byscore");
+        let mut rev = name.starts_with("zrev");
+        let (mut limit, mut with_scores) = (None, false);
+        while let Some(option) = args.next() {
+            match parse_string(Some(option))?.to_ascii_lowercase().as_str() {
+                "withscores" => with_scores = true,
+                "byscore" if name == "zrange" => by_score = true,
+                "rev" if name == "zrange" => rev = true,
+                "limit" if name != "zrevrange" => {
+                    let offset = parse_i64(args.next())?;
+                    let count = parse_i64(args.next())?;
+                    // a negative offset returns an empty range
+                    limit = Some((usize::try_from(offset).unwrap_or(usize::MAX), count));
+                }
+                _ => return Err(CommandError::InvalidArgument("syntax error".to_string())),
+            }
+        }
+        if limit.is_some() && !by_score {
+            return Err(CommandError::InvalidArgument(
+                "syntax error, LIMIT is only supported in combination with either BYSCORE or BYLEX"
+                    .to_string(),
+            ));
+        }
+
+        let by = if by_score {
+            let (first, second) = (parse_score_bound(start)?, parse_score_bound(stop)?);
+            // the reversed forms take the range as max min
+            if rev {
+                ZRangeBy::Score(second, first)
+            } else {
+                ZRangeBy::Score(first, second)
+            }
+        } else {
+            ZRangeBy::Rank(parse_i64(start)?, parse_i64(stop)?)
+        };
+        Ok(ZRange {
+            key,
+            by,
+            rev,
+            limit,
+            with_scores,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use bytes::BytesMut;
+
+    use crate::RespDecode;
+
+    use super::*;
+
+    fn bulk(s: &str) -> RespFrame {
+        RespFrame::BulkString(s.into())
+    }
+
+    #[test]
+    fn test_zadd_try_from_array() -> Result<()> {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(
+            b"*7\r\n$4\r\nzadd\r\n$1\r\nz\r\n$2\r\nXX\r\n$2\r\nCH\r\n$1\r\n1\r\n$1\r\na\r\n$4\r\n-inf\r\n",
+        );
+        let frame = Array::decode(&mut buf)?;
+        assert!(ZAdd::try_from(frame).is_err());
+
+        buf.extend_from_slice(
+            b"*8\r\n$4\r\nzadd\r\n$1\r\nz\r\n$2\r\nXX\r\n$2\r\nCH\r\n$1\r\n1\r\n$1\r\na\r\n$4\r\n-inf\r\n$1\r\nb\r\n",
+        );
+        let frame = Array::decode(&mut buf)?;
+        let result = ZAdd::try_from(frame)?;
+        assert_eq!(result.key, "z");
+        assert!(result.flags.xx && result.ch && !result.incr);
+        assert_eq!(
+            result.entries,
+            vec![(1.0, "a".to_string()), (f64::NEG_INFINITY, "b".to_string())]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_zrange_try_from_array() -> Result<()> {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(
+            b"*9\r\n$6\r\nzrange\r\n$1\r\nz\r\n$2\r\n(5\r\n$4\r\n-inf\r\n$7\r\nBYSCORE\r\n$3\r\nREV\r\n$5\r\nLIMIT\r\n$1\r\n1\r\n$1\r\n2\r\n",
+        );
+        let frame = Array::decode(&mut buf)?;
+        let result = ZRange::try_from(frame)?;
+        assert!(result.rev);
+        assert_eq!(result.limit, Some((1, 2)));
+        assert_eq!(
+            result.by,
+            ZRangeBy::Score(
+                ScoreBound::inclusive(f64::NEG_INFINITY),
+                ScoreBound {
+                    value: 5.0,
+                    exclusive: true
+                }
+            )
+        );
+
+        buf.extend_from_slice(
+            b"*7\r\n$6\r\nzrange\r\n$1\r\nz\r\n$1\r\n0\r\n$2\r\n-1\r\n$5\r\nlimit\r\n$1\r\n0\r\n$1\r\n1\r\n",
+        );
+        let frame = Array::decode(&mut buf)?;
+        assert!(ZRange::try_from(frame).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_zset_commands() -> Result<()> {
+        let backend = Backend::new();
+        let cmd = ZAdd {
+            key: "board".to_string(),
+            entries: vec![
+                (100.0, "alice".to_string()),
+                (80.0, "bob".to_string()),
+                (100.0, "carol".to_string()),
+            ],
+            flags: ZAddFlags::default(),
+            ch: false,
+            incr: false,
+        };
+        assert_eq!(cmd.execute(&backend), RespFrame::Integer(3));
+
+        let cmd = ZIncrBy {
+            key: "board".to_string(),
+            delta: 30.0,
+            member: "bob".to_string(),
+        };
+        assert_eq!(cmd.execute(&backend), RespFrame::Double(110.0));
+
+        let cmd = ZRange {
+            key: "board".to_string(),
+            by: ZRangeBy::Rank(0, -1),
+            rev: true,
+            limit: None,
+            with_scores: true,
+        };
+        assert_eq!(
+            cmd.execute(&backend),
+            Array::new([
+                bulk("bob"),
+                RespFrame::Double(110.0),
+                bulk("carol"),
+                RespFrame::Double(100.0),
+                bulk("alice"),
+                RespFrame::Double(100.0),
+            ])
+            .into()
+        );
+
+        let cmd = ZRank {
+            key: "board".to_string(),
+            member: "carol".to_string(),
+            rev: false,
+        };
+        assert_eq!(cmd.execute(&backend), RespFrame::Integer(1));
+
+        let cmd = ZCount {
+            key: "board".to_string(),
+            min: ScoreBound {
+                value: 100.0,
+                exclusive: true,
+            },
+            max: ScoreBound::inclusive(f64::INFINITY),
+        };
+        assert_eq!(cmd.execute(&backend), RespFrame::Integer(1));
+
+        let cmd = ZRem {
+            key: "board".to_string(),
+            members: vec!["alice".to_string(), "dave".to_string()],
+        };
+        assert_eq!(cmd.execute(&backend), RespFrame::Integer(1));
+
+        let cmd = ZScore {
+            key: "board".to_string(),
+            member: "alice".to_string(),
+        };
+        assert_eq!(cmd.execute(&backend), RespFrame::Null(Null));
+
+        let cmd = ZCard {
+            key: "board".to_string(),
+        };
+        assert_eq!(cmd.execute(&backend), RespFrame::Integer(2));
+
+        let cmd = ZAdd {
+            key: "board".to_string(),
+            entries: vec![(f64::NEG_INFINITY, "bob".to_string())],
+            flags: ZAddFlags::default(),
+            ch: false,
+            incr: true,
+        };
+        cmd.execute(&backend);
+        let cmd = ZAdd {
+            key: "board".to_string(),
+            entries: vec![(f64::INFINITY, "bob".to_string())],
+            flags: ZAddFlags::default(),
+            ch: false,
+            incr: true,
+        };
+        assert_eq!(
+            cmd.execute(&backend),
+            SimpleError::new("ERR resulting score is not a number (NaN)").into()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_zset_commands_wrong_type() {
+        let backend = Backend::new();
+        backend.set("s".to_string(), "v".into());
+        let cmd = ZAdd {
+            key: "s".to_string(),
+            entries: vec![(1.0, "a".to_string())],
+            flags: ZAddFlags::default(),
+            ch: false,
+            incr: false,
+        };
+        assert_eq!(cmd.execute(&backend), RESP_WRONGTYPE.clone());
+
+        let cmd = ZIncrBy {
+            key: "s".to_string(),
+            delta: 1.0,
+            member: "a".to_string(),
+        };
+        assert_eq!(cmd.execute(&backend), RESP_WRONGTYPE.clone());
+
+        let cmd = ZRem {
+            key: "s".to_string(),
+            members: vec!["a".to_string()],
+        };
+        assert_eq!(cmd.execute(&backend), RESP_WRONGTYPE.clone());
+        assert_eq!(backend.get("s"), Some("v".into()));
+    }
+}
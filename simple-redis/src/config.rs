@@ -0,0 +1,629 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// The parameters of CONFIG GET and CONFIG SET, by their redis name.
+pub const CONFIG_PARAMETERS: &[&str] = &[
+    "appendfilename",
+    "appendfsync",
+    "appendonly",
+    "bind",
+    "cluster-enabled",
+    "databases",
+    "dbfilename",
+    "dir",
+    "maxclients",
+    "maxmemory",
+    "maxmemory-policy",
+    "maxmemory-samples",
+    "port",
+    "repl-backlog-size",
+    "replicaof",
+    "requirepass",
+    "save",
+    "slowlog-log-slower-than",
+    "slowlog-max-len",
+    "timeout",
+    "tls-cert-file",
+    "tls-key-file",
+    "tls-port",
+];
+
+#[derive(Error, Debug, PartialEq)]
+pub enum ConfigError {
+    #[error("ERR Unknown option or number of arguments for CONFIG SET - '{0}'")]
+    UnknownParameter(String),
+    #[error(
+        "ERR CONFIG SET failed (possibly related to argument '{0}') - can't set immutable config"
+    )]
+    Immutable(String),
+    #[error("ERR CONFIG SET failed (possibly related to argument '{0}') - {1}")]
+    InvalidValue(String, String),
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AppConfig {
+    /// file the configuration was loaded from, rewritten by CONFIG REWRITE
+    #[serde(skip)]
+    pub path: Option<PathBuf>,
+    pub server: ServerConfig,
+    pub tls: TlsConfig,
+    pub rdb: RdbConfig,
+    pub aof: AofConfig,
+    pub replication: ReplicationConfig,
+    pub cluster: ClusterConfig,
+    pub memory: MemoryConfig,
+    pub slowlog: SlowlogConfig,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ServerConfig {
+    pub bind: String,
+    pub port: u16,
+    /// number of logical databases, selected with SELECT
+    pub databases: usize,
+    /// password of the default user, clients must AUTH before running commands when set
+    pub requirepass: Option<String>,
+    /// seconds after which an idle client is disconnected, 0 to never disconnect
+    pub timeout: u64,
+    /// connections refused once that many clients are connected
+    pub maxclients: usize,
+}
+
+/// A second listener serving the same protocol over TLS.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TlsConfig {
+    /// 0 to disable the listener
+    pub port: u16,
+    /// PEM certificate chain of the server
+    pub cert_file: Option<PathBuf>,
+    /// PEM private key of the server
+    pub key_file: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RdbConfig {
+    /// directory where the snapshot is written to and loaded from
+    pub dir: PathBuf,
+    pub dbfilename: String,
+    /// snapshot if at least `changes` writes happened within `seconds`, empty to disable
+    pub save: Vec<SaveRule>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SaveRule {
+    pub seconds: u64,
+    pub changes: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AofConfig {
+    /// when enabled, the append only file takes precedence over the snapshot at startup
+    pub enabled: bool,
+    pub dir: PathBuf,
+    pub filename: String,
+    pub fsync: AofFsync,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ReplicationConfig {
+    /// the master to replicate at startup, as "host port"
+    pub replicaof: Option<String>,
+    /// bytes of the replication stream kept for replicas to resume from after a disconnection
+    pub backlog_size: usize,
+}
+
+/// A static cluster topology, the same on every node except for `myself`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ClusterConfig {
+    pub enabled: bool,
+    /// id of this node in `nodes`
+    pub myself: String,
+    pub nodes: Vec<ClusterNodeConfig>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClusterNodeConfig {
+    pub id: String,
+    pub host: String,
+    pub port: u16,
+    /// slots served by the node, as "first-last" ranges or single slots
+    #[serde(default)]
+    pub slots: Vec<String>,
+    /// slots being moved to another node, by id of the target node
+    #[serde(default)]
+    pub migrating: BTreeMap<u16, String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MemoryConfig {
+    /// bytes the dataset may use before keys get evicted, 0 for no limit
+    pub maxmemory: u64,
+    pub maxmemory_policy: MaxMemoryPolicy,
+    /// keys sampled to pick each key to evict, more is more accurate and slower
+    pub maxmemory_samples: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SlowlogConfig {
+    /// microseconds a command must run for to be logged, 0 to log every command, -1 to log none
+    pub log_slower_than: i64,
+    /// entries kept, the oldest ones are dropped first
+    pub max_len: usize,
+}
+
+/// Which keys are evicted when the dataset uses more than `maxmemory`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MaxMemoryPolicy {
+    /// refuse the commands which may use more memory
+    #[default]
+    #[serde(rename = "noeviction")]
+    NoEviction,
+    /// the least recently used key
+    #[serde(rename = "allkeys-lru")]
+    AllKeysLru,
+    /// the least frequently used key
+    #[serde(rename = "allkeys-lfu")]
+    AllKeysLfu,
+    /// the least recently used key among the keys with a ttl
+    #[serde(rename = "volatile-lru")]
+    VolatileLru,
+    /// the key with the nearest expiry
+    #[serde(rename = "volatile-ttl")]
+    VolatileTtl,
+}
+
+/// When the append only file is flushed to disk.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AofFsync {
+    /// fsync after every write command
+    Always,
+    /// fsync once per second
+    #[default]
+    Everysec,
+    /// never fsync, let the OS decide
+    No,
+}
+
+impl AppConfig {
+    /// Load the given config file, or look for one if there is none.
+    pub fn load(path: Option<&Path>) -> Result<Self> {
+        // read from ./simple-redis.yml or /etc/config/simple-redis.yml or from env SIMPLE_REDIS_CONFIG,
+        // fall back to the defaults if none exists
+        let path = match path {
+            Some(path) => Some(path.to_path_buf()),
+            None => ["simple-redis.yml", "/etc/config/simple-redis.yml"]
+                .into_iter()
+                .map(PathBuf::from)
+                .find(|path| path.exists())
+                .or_else(|| env::var("SIMPLE_REDIS_CONFIG").ok().map(PathBuf::from)),
+        };
+        let Some(path) = path else {
+            return Ok(AppConfig::default());
+        };
+        let mut ret: AppConfig = serde_yaml::from_reader(File::open(&path)?)?;
+        ret.path = Some(path);
+        Ok(ret)
+    }
+
+    /// The value of a parameter as CONFIG GET reports it.
+    pub fn get(&self, name: &str) -> Option<String> {
+        let path = |path: &Option<PathBuf>| {
+            path.as_ref()
+                .map(|path| path.display().to_string())
+                .unwrap_or_default()
+        };
+        let value = match name {
+            "appendfilename" => self.aof.filename.clone(),
+            "appendfsync" => self.aof.fsync.as_str().to_string(),
+            "appendonly" => yes_no(self.aof.enabled),
+            "bind" => self.server.bind.clone(),
+            "cluster-enabled" => yes_no(self.cluster.enabled),
+            "databases" => self.server.databases.to_string(),
+            "dbfilename" => self.rdb.dbfilename.clone(),
+            "dir" => self.rdb.dir.display().to_string(),
+            "maxclients" => self.server.maxclients.to_string(),
+            "maxmemory" => self.memory.maxmemory.to_string(),
+            "maxmemory-policy" => self.memory.maxmemory_policy.as_str().to_string(),
+            "maxmemory-samples" => self.memory.maxmemory_samples.to_string(),
+            "port" => self.server.port.to_string(),
+            "repl-backlog-size" => self.replication.backlog_size.to_string(),
+            "replicaof" => self.replication.replicaof.clone().unwrap_or_default(),
+            "requirepass" => self.server.requirepass.clone().unwrap_or_default(),
+            "save" => self
+                .rdb
+                .save
+                .iter()
+                .map(|rule| format!("{} {}", rule.seconds, rule.changes))
+                .collect::<Vec<_>>()
+                .join(" "),
+            "slowlog-log-slower-than" => self.slowlog.log_slower_than.to_string(),
+            "slowlog-max-len" => self.slowlog.max_len.to_string(),
+            "timeout" => self.server.timeout.to_string(),
+            "tls-cert-file" => path(&self.tls.cert_file),
+            "tls-key-file" => path(&self.tls.key_file),
+            "tls-port" => self.tls.port.to_string(),
+            _ => return None,
+        };
+        Some(value)
+    }
+
+    /// Change a parameter at runtime. The parameters which only apply at startup are immutable.
+    pub fn set(&mut self, name: &str, value: &str) -> Result<(), ConfigError> {
+        let invalid =
+            |reason: &str| ConfigError::InvalidValue(name.to_string(), reason.to_string());
+        match name {
+            "dbfilename" => {
+                if value.is_empty() || value.contains('/') {
+                    return Err(invalid("dbfilename can't be a path, just a filename"));
+                }
+                self.rdb.dbfilename = value.to_string();
+            }
+            "dir" => {
+                if !Path::new(value).is_dir() {
+                    return Err(invalid("No such file or directory"));
+                }
+                // as --dir, the directory holds the append only file too
+                self.rdb.dir = PathBuf::from(value);
+                self.aof.dir = PathBuf::from(value);
+            }
+            "maxclients" => {
+                self.server.maxclients = value
+                    .parse()
+                    .ok()
+                    .filter(|n| *n > 0)
+                    .ok_or_else(|| invalid("argument must be a positive integer"))?;
+            }
+            "maxmemory" => {
+                self.memory.maxmemory = parse_memory(value)
+                    .ok_or_else(|| invalid("argument must be a memory value"))?;
+            }
+            "maxmemory-policy" => {
+                self.memory.maxmemory_policy = MaxMemoryPolicy::parse(value)
+                    .ok_or_else(|| invalid("argument(s) must be one of the following: noeviction, allkeys-lru, allkeys-lfu, volatile-lru, volatile-ttl"))?;
+            }
+            "maxmemory-samples" => {
+                self.memory.maxmemory_samples = value
+                    .parse()
+                    .ok()
+                    .filter(|n| *n > 0)
+                    .ok_or_else(|| invalid("argument must be a positive integer"))?;
+            }
+            "requirepass" => {
+                self.server.requirepass = (!value.is_empty()).then(|| value.to_string());
+            }
+            "save" => {
+                let numbers = value
+                    .split_whitespace()
+                    .map(|v| v.parse::<u64>())
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(|_| invalid("Invalid save parameters"))?;
+                if numbers.len() % 2 != 0 {
+                    return Err(invalid("Invalid save parameters"));
+                }
+                self.rdb.save = numbers
+                    .chunks(2)
+                    .map(|rule| SaveRule::new(rule[0], rule[1]))
+                    .collect();
+            }
+            "slowlog-log-slower-than" => {
+                self.slowlog.log_slower_than = value
+                    .parse()
+                    .ok()
+                    .filter(|n| *n >= -1)
+                    .ok_or_else(|| invalid("argument must be -1 or a positive integer"))?;
+            }
+            "slowlog-max-len" => {
+                self.slowlog.max_len = value
+                    .parse()
+                    .map_err(|_| invalid("argument couldn't be parsed into an integer"))?;
+            }
+            "timeout" => {
+                self.server.timeout = value
+                    .parse()
+                    .map_err(|_| invalid("argument couldn't be parsed into an integer"))?;
+            }
+            name if CONFIG_PARAMETERS.contains(&name) => {
+                return Err(ConfigError::Immutable(name.to_string()))
+            }
+            name => return Err(ConfigError::UnknownParameter(name.to_string())),
+        }
+        Ok(())
+    }
+
+    /// Write the configuration back to the file it was loaded from.
+    pub fn rewrite(&self) -> Result<()> {
+        let path = self
+            .path
+            .as_ref()
+            .ok_or_else(|| anyhow!("The server is running without a config file"))?;
+        // a crash while writing never leaves a truncated config file behind
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, serde_yaml::to_string(self)?)?;
+        fs::rename(&tmp, path)?;
+        Ok(())
+    }
+}
+
+impl ServerConfig {
+    pub fn addr(&self) -> String {
+        format!("{}:{}", self.bind, self.port)
+    }
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            bind: "0.0.0.0".to_string(),
+            port: 6379,
+            databases: 16,
+            requirepass: None,
+            timeout: 0,
+            maxclients: 10000,
+        }
+    }
+}
+
+impl RdbConfig {
+    pub fn path(&self) -> PathBuf {
+        self.dir.join(&self.dbfilename)
+    }
+}
+
+impl Default for RdbConfig {
+    fn default() -> Self {
+        // same as redis: after 1 hour / 1 change, 5 minutes / 100 changes, 1 minute / 10000 changes
+        Self {
+            dir: PathBuf::from("."),
+            dbfilename: "dump.rdb".to_string(),
+            save: vec![
+                SaveRule::new(3600, 1),
+                SaveRule::new(300, 100),
+                SaveRule::new(60, 10000),
+            ],
+        }
+    }
+}
+
+impl AofConfig {
+    pub fn path(&self) -> PathBuf {
+        self.dir.join(&self.filename)
+    }
+}
+
+impl Default for AofConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            dir: PathBuf::from("."),
+            filename: "appendonly.aof".to_string(),
+            fsync: AofFsync::default(),
+        }
+    }
+}
+
+impl ReplicationConfig {
+    /// The host and port of the configured master, if any.
+    pub fn master(&self) -> Option<(String, u16)> {
+        let (host, port) = self.replicaof.as_deref()?.trim().split_once(' ')?;
+        Some((host.to_string(), port.trim().parse().ok()?))
+    }
+}
+
+impl Default for ReplicationConfig {
+    fn default() -> Self {
+        Self {
+            replicaof: None,
+            backlog_size: 1024 * 1024,
+        }
+    }
+}
+
+impl Default for MemoryConfig {
+    fn default() -> Self {
+        Self {
+            maxmemory: 0,
+            maxmemory_policy: MaxMemoryPolicy::default(),
+            maxmemory_samples: 5,
+        }
+    }
+}
+
+impl Default for SlowlogConfig {
+    fn default() -> Self {
+        Self {
+            log_slower_than: 10000,
+            max_len: 128,
+        }
+    }
+}
+
+impl MaxMemoryPolicy {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.to_ascii_lowercase().as_str() {
+            "noeviction" => Some(MaxMemoryPolicy::NoEviction),
+            "allkeys-lru" => Some(MaxMemoryPolicy::AllKeysLru),
+            "allkeys-lfu" => Some(MaxMemoryPolicy::AllKeysLfu),
+            "volatile-lru" => Some(MaxMemoryPolicy::VolatileLru),
+            "volatile-ttl" => Some(MaxMemoryPolicy::VolatileTtl),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MaxMemoryPolicy::NoEviction => "noeviction",
+            MaxMemoryPolicy::AllKeysLru => "allkeys-lru",
+            MaxMemoryPolicy::AllKeysLfu => "allkeys-lfu",
+            MaxMemoryPolicy::VolatileLru => "volatile-lru",
+            MaxMemoryPolicy::VolatileTtl => "volatile-ttl",
+        }
+    }
+
+    /// Whether the keys without a ttl are kept.
+    pub fn is_volatile(&self) -> bool {
+        matches!(
+            self,
+            MaxMemoryPolicy::VolatileLru | MaxMemoryPolicy::VolatileTtl
+        )
+    }
+}
+
+impl AofFsync {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AofFsync::Always => "always",
+            AofFsync::Everysec => "everysec",
+            AofFsync::No => "no",
+        }
+    }
+}
+
+impl SaveRule {
+    pub fn new(seconds: u64, changes: u64) -> Self {
+        Self { seconds, changes }
+    }
+}
+
+fn yes_no(value: bool) -> String {
+    match value {
+        true => "yes".to_string(),
+        false => "no".to_string(),
+    }
+}
+
+// bytes, or a number of kb, mb or gb like redis accepts them
+fn parse_memory(value: &str) -> Option<u64> {
+    let value = value.to_ascii_lowercase();
+    let units: [(&str, u64); 6] = [
+        ("kb", 1024),
+        ("mb", 1024 * 1024),
+        ("gb", 1024 * 1024 * 1024),
+        ("k", 1000),
+        ("m", 1000 * 1000),
+        ("g", 1000 * 1000 * 1000),
+    ];
+    let (number, unit) = units
+        .iter()
+        .find_map(|(suffix, unit)| Some((value.strip_suffix(suffix)?, *unit)))
+        .unwrap_or((value.as_str(), 1));
+    number.parse::<u64>().ok()?.checked_mul(unit)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_config_defaults_for_missing_fields() -> Result<()> {
+        let config: AppConfig = serde_yaml::from_str("rdb:\n  dbfilename: test.rdb\n")?;
+        assert_eq!(config.rdb.path(), PathBuf::from("./test.rdb"));
+        assert_eq!(config.rdb.save.len(), 3);
+
+        let config: AppConfig = serde_yaml::from_str("rdb:\n  save: []\n")?;
+        assert!(config.rdb.save.is_empty());
+
+        let config: AppConfig = serde_yaml::from_str("aof:\n  enabled: true\n  fsync: always\n")?;
+        assert!(config.aof.enabled);
+        assert_eq!(config.aof.fsync, AofFsync::Always);
+        assert_eq!(config.aof.path(), PathBuf::from("./appendonly.aof"));
+
+        let config: AppConfig = serde_yaml::from_str(
+            "server:\n  port: 6380\nreplication:\n  replicaof: 127.0.0.1 6379\n",
+        )?;
+        assert_eq!(config.server.addr(), "0.0.0.0:6380");
+        assert_eq!(config.server.databases, 16);
+        assert_eq!(
+            config.replication.master(),
+            Some(("127.0.0.1".to_string(), 6379))
+        );
+        assert_eq!(config.replication.backlog_size, 1024 * 1024);
+        assert!(!config.cluster.enabled);
+        assert_eq!(config.memory.maxmemory_policy, MaxMemoryPolicy::NoEviction);
+
+        let config: AppConfig = serde_yaml::from_str(
+            "memory:\n  maxmemory: 1048576\n  maxmemory_policy: allkeys-lfu\n",
+        )?;
+        assert_eq!(config.memory.maxmemory, 1048576);
+        assert_eq!(config.memory.maxmemory_policy, MaxMemoryPolicy::AllKeysLfu);
+        assert_eq!(config.memory.maxmemory_samples, 5);
+
+        let config: AppConfig = serde_yaml::from_str(
+            "cluster:\n  enabled: true\n  myself: a\n  nodes:\n    - id: a\n      host: 127.0.0.1\n      port: 7000\n      slots: [0-8191]\n      migrating: {100: b}\n",
+        )?;
+        assert_eq!(config.cluster.nodes[0].slots, vec!["0-8191".to_string()]);
+        assert_eq!(
+            config.cluster.nodes[0].migrating.get(&100),
+            Some(&"b".to_string())
+        );
+
+        let config: AppConfig =
+            serde_yaml::from_str("tls:\n  port: 6380\n  cert_file: server.crt\n")?;
+        assert_eq!(config.tls.port, 6380);
+        assert_eq!(config.tls.cert_file, Some(PathBuf::from("server.crt")));
+        assert_eq!(config.server.maxclients, 10000);
+        Ok(())
+    }
+
+    #[test]
+    fn test_config_get_set() -> Result<()> {
+        let mut config = AppConfig::default();
+        assert_eq!(
+            config.get("save").as_deref(),
+            Some("3600 1 300 100 60 10000")
+        );
+        assert_eq!(config.get("appendonly").as_deref(), Some("no"));
+        assert_eq!(config.get("nope"), None);
+
+        config.set("maxmemory", "2mb")?;
+        assert_eq!(config.memory.maxmemory, 2 * 1024 * 1024);
+        config.set("maxmemory-policy", "ALLKEYS-LRU")?;
+        assert_eq!(
+            config.get("maxmemory-policy").as_deref(),
+            Some("allkeys-lru")
+        );
+        config.set("save", "")?;
+        assert!(config.rdb.save.is_empty());
+        let dir = env::temp_dir();
+        config.set("dir", &dir.to_string_lossy())?;
+        assert_eq!(config.rdb.dir, dir);
+        assert_eq!(config.aof.dir, dir);
+        assert!(config.set("dir", "/nonexistent/simple-redis").is_err());
+
+        assert_eq!(
+            config.set("port", "6380"),
+            Err(ConfigError::Immutable("port".to_string()))
+        );
+        assert_eq!(
+            config.set("nope", "1"),
+            Err(ConfigError::UnknownParameter("nope".to_string()))
+        );
+        assert!(config.set("save", "60").is_err());
+        assert!(config.set("timeout", "soon").is_err());
+
+        assert!(config.rewrite().is_err());
+        let path = env::temp_dir().join(format!("simple-redis-{}.yml", std::process::id()));
+        config.path = Some(path.clone());
+        config.rewrite()?;
+        let rewritten = AppConfig::load(Some(&path))?;
+        fs::remove_file(&path)?;
+        assert_eq!(rewritten.memory.maxmemory, 2 * 1024 * 1024);
+        assert!(rewritten.rdb.save.is_empty());
+        Ok(())
+    }
+}
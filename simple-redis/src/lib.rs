@@ -1,9 +1,13 @@
 pub use backend::*;
+pub use config::*;
 pub use resp::*;
 pub use respv2::*;
 
 mod backend;
+pub mod client;
 pub mod cmd;
+mod config;
 pub mod network;
 mod resp;
 mod respv2;
+pub mod tls;
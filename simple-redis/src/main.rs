@@ -1,30 +1,203 @@
-use anyhow::Result;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use clap::Parser;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::TcpListener;
+use tokio_rustls::TlsAcceptor;
 use tracing::{info, warn};
 
-use simple_redis::{Backend, network};
+use simple_redis::{network, tls::tls_acceptor, AofFsync, AppConfig, Backend};
+
+const ACTIVE_EXPIRE_INTERVAL: Duration = Duration::from_millis(100);
+
+/// A redis compatible server. The flags take precedence over the config file.
+#[derive(Debug, Parser)]
+#[command(version, about)]
+struct Args {
+    /// yaml config file, ./simple-redis.yml or /etc/config/simple-redis.yml when not given
+    #[arg(short, long)]
+    config: Option<PathBuf>,
+    #[arg(long)]
+    bind: Option<String>,
+    #[arg(short, long)]
+    port: Option<u16>,
+    /// port of the TLS listener, 0 to disable it
+    #[arg(long)]
+    tls_port: Option<u16>,
+    #[arg(long)]
+    tls_cert_file: Option<PathBuf>,
+    #[arg(long)]
+    tls_key_file: Option<PathBuf>,
+    /// seconds after which an idle client is disconnected, 0 to never disconnect
+    #[arg(long)]
+    timeout: Option<u64>,
+    #[arg(long)]
+    maxclients: Option<usize>,
+    #[arg(long)]
+    requirepass: Option<String>,
+    #[arg(long)]
+    maxmemory: Option<String>,
+    /// directory of the snapshot and the append only file
+    #[arg(long)]
+    dir: Option<PathBuf>,
+    #[arg(long)]
+    dbfilename: Option<String>,
+    /// snapshot rules as "seconds changes" pairs, "" to disable snapshots
+    #[arg(long)]
+    save: Option<String>,
+    #[arg(long, value_parser = parse_yes_no)]
+    appendonly: Option<bool>,
+    #[arg(long, value_parser = parse_fsync)]
+    appendfsync: Option<AofFsync>,
+}
 
 #[tokio::main]
 async fn main() -> Result<()> {
     tracing_subscriber::fmt::init();
 
-    let addr = "0.0.0.0:6379";
+    let args = Args::parse();
+    let mut config = AppConfig::load(args.config.as_deref())?;
+    args.apply(&mut config)?;
+    let backend = Backend::with_config(config);
+    let config = backend.config().clone();
+    let addr = config.server.addr();
     info!("Simple-Redis-Server is listening on {}", addr);
 
-    let listener = tokio::net::TcpListener::bind(&addr).await?;
-    let backend = Backend::new();
+    let listener = TcpListener::bind(&addr).await?;
+    let tls_listener = match config.tls.port {
+        0 => None,
+        port => {
+            let addr = format!("{}:{}", config.server.bind, port);
+            let acceptor = tls_acceptor(&config.tls)?;
+            info!("Simple-Redis-Server is listening for TLS on {}", addr);
+            Some((TcpListener::bind(&addr).await?, acceptor))
+        }
+    };
+    // the append only file is more complete than the snapshot, prefer it when enabled
+    if config.aof.enabled {
+        backend.open_aof()?;
+        tokio::spawn(backend.clone().run_aof_fsync());
+    } else {
+        backend.load()?;
+    }
+    if config.cluster.enabled {
+        backend.enable_cluster()?;
+    }
+    tokio::spawn(backend.clone().run_active_expire(ACTIVE_EXPIRE_INTERVAL));
+    tokio::spawn(backend.clone().run_snapshot());
+    // the keyspace loaded above is replaced by the one of the master on the first sync
+    if let Some(master) = config.replication.master() {
+        backend.replicaof(Some(master));
+    }
+    if let Some((listener, acceptor)) = tls_listener {
+        tokio::spawn(serve_tls(listener, acceptor, backend.clone()));
+    }
     loop {
         let (stream, raddr) = listener.accept().await?;
         info!("Accepted connection from {}", raddr);
-        let cloned_backend = backend.clone();
+        // replies are flushed once per batch of requests, there is no point in delaying them further
+        stream.set_nodelay(true)?;
+        tokio::spawn(handle(stream, raddr, backend.clone()));
+    }
+}
+
+// the handshake runs in the task of the connection, a slow client doesn't hold up the others
+async fn serve_tls(listener: TcpListener, acceptor: TlsAcceptor, backend: Backend) -> Result<()> {
+    loop {
+        let (stream, raddr) = listener.accept().await?;
+        info!("Accepted TLS connection from {}", raddr);
+        stream.set_nodelay(true)?;
+        let (acceptor, backend) = (acceptor.clone(), backend.clone());
         tokio::spawn(async move {
-            match network::stream_handler(stream, cloned_backend).await {
-                Ok(_) => {
-                    info!("Connection from {} exited", raddr);
-                }
-                Err(e) => {
-                    warn!("handle error for {}: {:?}", raddr, e);
-                }
+            match acceptor.accept(stream).await {
+                Ok(stream) => handle(stream, raddr, backend).await,
+                Err(e) => warn!("TLS handshake failed for {}: {:?}", raddr, e),
             }
         });
     }
 }
+
+async fn handle<S>(stream: S, raddr: SocketAddr, backend: Backend)
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    match network::stream_handler(stream, raddr, backend).await {
+        Ok(_) => {
+            info!("Connection from {} exited", raddr);
+        }
+        Err(e) => {
+            warn!("handle error for {}: {:?}", raddr, e);
+        }
+    }
+}
+
+impl Args {
+    fn apply(self, config: &mut AppConfig) -> Result<()> {
+        if let Some(bind) = self.bind {
+            config.server.bind = bind;
+        }
+        if let Some(port) = self.port {
+            config.server.port = port;
+        }
+        if let Some(port) = self.tls_port {
+            config.tls.port = port;
+        }
+        if let Some(path) = self.tls_cert_file {
+            config.tls.cert_file = Some(path);
+        }
+        if let Some(path) = self.tls_key_file {
+            config.tls.key_file = Some(path);
+        }
+        if let Some(timeout) = self.timeout {
+            config.server.timeout = timeout;
+        }
+        if let Some(maxclients) = self.maxclients {
+            config.server.maxclients = maxclients;
+        }
+        if let Some(password) = self.requirepass {
+            config.server.requirepass = Some(password);
+        }
+        if let Some(dir) = self.dir {
+            config.rdb.dir = dir.clone();
+            config.aof.dir = dir;
+        }
+        if let Some(dbfilename) = self.dbfilename {
+            config.rdb.dbfilename = dbfilename;
+        }
+        if let Some(enabled) = self.appendonly {
+            config.aof.enabled = enabled;
+        }
+        if let Some(fsync) = self.appendfsync {
+            config.aof.fsync = fsync;
+        }
+        // same validation as CONFIG SET
+        for (name, value) in [("maxmemory", self.maxmemory), ("save", self.save)] {
+            if let Some(value) = value {
+                config
+                    .set(name, &value)
+                    .map_err(|_| anyhow!("invalid value for --{}: {}", name, value))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn parse_yes_no(value: &str) -> Result<bool> {
+    match value {
+        "yes" => Ok(true),
+        "no" => Ok(false),
+        _ => Err(anyhow!("expected yes or no")),
+    }
+}
+
+fn parse_fsync(value: &str) -> Result<AofFsync> {
+    match value {
+        "always" => Ok(AofFsync::Always),
+        "everysec" => Ok(AofFsync::Everysec),
+        "no" => Ok(AofFsync::No),
+        _ => Err(anyhow!("expected always, everysec or no")),
+    }
+}
@@ -1,18 +1,28 @@
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
 use anyhow::Result;
+use bytes::Bytes;
 use futures::SinkExt;
-use tokio::net::TcpStream;
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt};
+use tokio::sync::broadcast::{self, error::RecvError};
 use tokio_stream::StreamExt;
 use tokio_util::codec::{Decoder, Encoder, Framed};
-use tracing::info;
+use tracing::{debug, info};
 
 use crate::{
-    Backend,
-    cmd::{Command, CommandExecutor}, RespDecodeV2, RespEncode, RespError, RespFrame, SimpleString,
+    cmd::{log_blocking_pop, lookup, Command, CommandExecutor, CommandSpec},
+    now_ms, AclError, Array, Backend, BulkString, ClientSlot, CommandLog, MemoryError,
+    PubSubMessage, ReplicaStream, RespDecodeV2, RespEncode, RespError, RespFrame, SimpleError,
+    SimpleString, Subscriber, Watcher,
 };
-use crate::cmd::CommandError;
 
-#[derive(Debug)]
-struct RespFrameCodec;
+/// Frames RESP requests and replies, for the server and the [`crate::client::Client`].
+#[derive(Debug, Default)]
+pub struct RespFrameCodec {
+    // RESP3 only frames are downgraded for RESP2 clients
+    resp3: bool,
+}
 
 #[derive(Debug)]
 struct RedisRequest {
@@ -22,59 +32,696 @@ struct RedisRequest {
 
 #[derive(Debug)]
 struct RedisResponse {
-    frame: RespFrame,
+    // most commands reply with a single frame, (un)subscribe replies once per channel
+    frames: Vec<RespFrame>,
 }
 
-pub async fn stream_handler(stream: TcpStream, backend: Backend) -> Result<()> {
-    let mut framed = Framed::new(stream, RespFrameCodec);
+// state of a client connection
+#[derive(Debug)]
+struct Connection {
+    id: u64,
+    // handle on the database selected by the client
+    backend: Backend,
+    addr: SocketAddr,
+    name: Option<String>,
+    // acl user the client is authenticated as, none until AUTH if the default user has a password
+    user: Option<String>,
+    // whether the client speaks RESP3, which allows push frames and any command while subscribed
+    resp3: bool,
+    subscriber: Subscriber,
+    watcher: Watcher,
+    // commands queued since MULTI
+    transaction: Option<Transaction>,
+    // port announced by a replica with REPLCONF listening-port
+    replica_port: u16,
+    // set once a replica sent PSYNC, the connection then carries the replication stream
+    replica: Option<ReplicaStream>,
+    // set by ASKING, the next command may use a slot this node is importing
+    asking: bool,
+    // set by MONITOR, the commands run by every client are sent to the connection
+    monitor: Option<broadcast::Receiver<String>>,
+    // entry of the client in CLIENT LIST, removed once the connection is dropped
+    slot: ClientSlot,
+}
+
+#[derive(Debug, Default)]
+struct Transaction {
+    // each command with the request to propagate to the aof and the replicas, and its name
+    commands: Vec<(Command, Option<RespFrame>, String)>,
+    // a command failed to parse while queuing, EXEC must refuse to run the others
+    aborted: bool,
+}
+
+/// Serve a client connected from `addr`, over plain TCP or TLS.
+pub async fn stream_handler<S>(mut stream: S, addr: SocketAddr, backend: Backend) -> Result<()>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let id = backend.next_client_id();
+    let Some(slot) = backend.connect_client(id, addr) else {
+        stream
+            .write_all(b"-ERR max number of clients reached\r\n")
+            .await?;
+        return Ok(());
+    };
+    let killed = slot.killed();
+    let mut framed = Framed::new(stream, RespFrameCodec::new(false));
+    let (subscriber, mut messages) = Subscriber::new(backend.clone(), id);
+    let mut conn = Connection {
+        id,
+        backend: backend.clone(),
+        addr,
+        name: None,
+        user: backend.acl().auto_login().then(|| "default".to_string()),
+        resp3: false,
+        subscriber,
+        watcher: Watcher::new(backend.clone(), id),
+        transaction: None,
+        replica_port: 0,
+        replica: None,
+        asking: false,
+        monitor: None,
+        slot,
+    };
+    conn.update_slot();
     loop {
-        match framed.next().await {
-            Some(Ok(frame)) => {
-                info!("Received frame: {:?}", frame);
-                let request = RedisRequest {
-                    frame,
-                    backend: backend.clone(),
-                };
-                match requst_handler(request).await {
-                    Ok(response) => {
-                        info!("Sending response: {:?}", response.frame);
-                        framed.send(response.frame).await?;
-                    }
-                    Err(e) => {
-                        if let Some(CommandError::InvalidArgument(_)) =
-                            e.downcast_ref::<CommandError>()
-                        {
-                            let response = RedisResponse {
-                                frame: RespFrame::SimpleString(SimpleString::new(
-                                    "Invalid argument",
-                                )),
-                            };
-                            framed.send(response.frame).await?;
-                        } else {
-                            return Err(e);
+        let timeout = backend.config().server.timeout;
+        // subscribers, monitors and replicas are expected to stay idle, they are never disconnected
+        let idle = timeout > 0
+            && conn.replica.is_none()
+            && conn.monitor.is_none()
+            && !conn.subscriber.is_subscribed();
+        tokio::select! {
+            frame = framed.next() => match frame {
+                Some(Ok(frame)) => {
+                    // the requests of a pipelined batch which are already buffered are all
+                    // answered before flushing, so that the replies go out in a single write
+                    let mut next = Some(frame);
+                    while let Some(frame) = next {
+                        debug!("Received frame: {:?}", frame);
+                        // a blocking command may wait indefinitely, the replies before it
+                        // must not wait along
+                        if may_block(&frame) {
+                            framed.flush().await?;
                         }
+                        let request = RedisRequest {
+                            frame,
+                            backend: conn.backend.clone(),
+                        };
+                        let response = requst_handler(request, &mut conn).await?;
+                        conn.update_slot();
+                        // HELLO switches the protocol starting with its own reply
+                        framed.codec_mut().resp3 = conn.resp3;
+                        debug!("Sending response: {:?}", response.frames);
+                        for frame in response.frames {
+                            framed.feed(frame).await?;
+                        }
+                        next = match decode_frame(framed.read_buffer_mut()) {
+                            Ok(frame) => frame,
+                            Err(e) => return protocol_error(&mut framed, e).await,
+                        };
                     }
+                    framed.flush().await?;
                 }
+                Some(Err(e)) => return protocol_error(&mut framed, e).await,
+                None => return Ok(()),
+            },
+            // messages published to the subscribed channels are pushed as they arrive
+            Some(message) = messages.recv() => {
+                framed.send(message.into_frame(conn.resp3)).await?;
+            }
+            line = monitor_feed(&mut conn.monitor) => {
+                framed.send(SimpleString::new(line).into()).await?;
+            }
+            // a replica receives the write commands as they are executed
+            // replies are always flushed by now, so the stream is written to the socket as is
+            data = replication_stream(&mut conn.replica) => match data {
+                Some(data) => framed.get_mut().write_all(&data).await?,
+                // the replica was dropped, e.g. by a full resync of this server
+                None => return Ok(()),
+            },
+            _ = tokio::time::sleep(Duration::from_secs(timeout)), if idle => {
+                info!("Closing idle client {}", addr);
+                return Ok(());
+            }
+            _ = killed.notified() => {
+                info!("Closing killed client {}", addr);
+                return Ok(());
             }
-            Some(Err(e)) => return Err(e),
-            None => return Ok(()),
         }
     }
 }
 
-async fn requst_handler(request: RedisRequest) -> Result<RedisResponse> {
+async fn replication_stream(replica: &mut Option<ReplicaStream>) -> Option<Bytes> {
+    match replica {
+        Some(replica) => replica.recv().await,
+        None => std::future::pending().await,
+    }
+}
+
+// the lines of a monitor, a monitor which fell behind skips the lines it missed
+async fn monitor_feed(monitor: &mut Option<broadcast::Receiver<String>>) -> String {
+    if let Some(receiver) = monitor {
+        loop {
+            match receiver.recv().await {
+                Ok(line) => return line,
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => break,
+            }
+        }
+    }
+    std::future::pending().await
+}
+
+impl Connection {
+    // what CLIENT LIST reports of the connection, once a request was handled
+    fn update_slot(&self) {
+        let flags = match () {
+            _ if self.monitor.is_some() => "O",
+            _ if self.replica.is_some() => "S",
+            _ if self.subscriber.is_subscribed() => "P",
+            _ if self.transaction.is_some() => "x",
+            _ => "N",
+        };
+        self.slot.update(|info| {
+            info.name.clone_from(&self.name);
+            info.user.clone_from(&self.user);
+            info.db = self.backend.db();
+            info.resp = if self.resp3 { 3 } else { 2 };
+            info.flags = flags.to_string();
+            info.sub = self.subscriber.channels();
+            info.psub = self.subscriber.patterns();
+            info.multi = match &self.transaction {
+                Some(transaction) => transaction.commands.len() as i64,
+                None => -1,
+            };
+        });
+    }
+}
+
+async fn requst_handler(request: RedisRequest, conn: &mut Connection) -> Result<RedisResponse> {
     let (frame, backend) = (request.frame, request.backend);
-    let cmd = Command::try_from(frame)?;
-    info!("Executing command: {:?}", cmd);
-    let ret = cmd.execute(&backend);
-    Ok(RedisResponse { frame: ret })
+    let name = command_name(&frame);
+    let spec = lookup(&name);
+    conn.slot.update(|info| {
+        info.cmd.clone_from(&name);
+        info.last_interaction = now_ms();
+    });
+    let write = spec.is_some_and(|spec| spec.has_flag("write"));
+    // the arguments are kept for the monitors and the slowlog
+    let args = request_args(&frame);
+    // the request as received is what gets propagated to the aof and the replicas
+    let logged = write.then(|| frame.clone());
+    // keys are checked against the acl of the user and route requests within a cluster
+    let keys = match spec {
+        Some(spec) => request_keys(&frame, spec),
+        None => vec![],
+    };
+    // permissions are checked before anything else, except for the commands used to authenticate
+    if !spec.is_some_and(|spec| spec.has_flag("no_auth")) {
+        let allowed = match (conn.user.as_deref(), spec) {
+            (Some(user), Some(spec)) => check_permissions(&backend, user, spec, &frame, &keys),
+            (Some(_), None) => Ok(()),
+            (None, _) => Err(AclError::NoAuth),
+        };
+        if let Err(e) = allowed {
+            // the user was deleted, the client must authenticate again
+            if matches!(e, AclError::NoAuth) {
+                conn.user = None;
+            }
+            if let Some(transaction) = conn.transaction.as_mut() {
+                transaction.aborted = true;
+            }
+            return Ok(RedisResponse {
+                frames: vec![error(&e.to_string())],
+            });
+        }
+    }
+    let cmd = match Command::try_from(frame) {
+        Ok(cmd) => cmd,
+        // a malformed command is an error reply, the connection goes on
+        Err(e) => {
+            if let Some(transaction) = conn.transaction.as_mut() {
+                transaction.aborted = true;
+            }
+            return Ok(RedisResponse {
+                frames: vec![e.into()],
+            });
+        }
+    };
+    debug!("Executing command: {:?}", cmd);
+
+    // admin commands and the commands used to authenticate are not shown to the monitors
+    let hidden = spec.is_some_and(|spec| spec.has_flag("admin") || spec.has_flag("no_auth"));
+    if !hidden && backend.has_monitors() {
+        backend.feed_monitors(&conn.addr.to_string(), &args);
+    }
+
+    // a RESP2 connection in subscribed mode can only receive pub/sub frames
+    let subscribed = conn.subscriber.is_subscribed() && !conn.resp3;
+    if subscribed && !cmd.allowed_in_subscribed_mode() {
+        let err = SimpleError::new(format!(
+            "ERR Can't execute '{}': only (P)SUBSCRIBE / (P)UNSUBSCRIBE / PING are allowed in this context",
+            name
+        ));
+        return Ok(RedisResponse {
+            frames: vec![err.into()],
+        });
+    }
+
+    let asking = std::mem::take(&mut conn.asking);
+    if let Err(redirect) = backend.route(&keys, asking) {
+        if let Some(transaction) = conn.transaction.as_mut() {
+            transaction.aborted = true;
+        }
+        return Ok(RedisResponse {
+            frames: vec![error(&redirect.to_string())],
+        });
+    }
+
+    if write && backend.is_replica() {
+        if let Some(transaction) = conn.transaction.as_mut() {
+            transaction.aborted = true;
+        }
+        return Ok(RedisResponse {
+            frames: vec![error(
+                "READONLY You can't write against a read only replica.",
+            )],
+        });
+    }
+
+    // a command which may grow the keyspace needs room for it first
+    let denyoom = spec.is_some_and(|spec| spec.has_flag("denyoom"));
+    if denyoom && backend.over_maxmemory() {
+        if let Err(e) = free_memory(&backend) {
+            if let Some(transaction) = conn.transaction.as_mut() {
+                transaction.aborted = true;
+            }
+            return Ok(RedisResponse {
+                frames: vec![error(&e.to_string())],
+            });
+        }
+    }
+
+    if let Some(transaction) = conn.transaction.as_mut() {
+        if !cmd.is_transaction_control() {
+            transaction.commands.push((cmd, logged, name));
+            return Ok(RedisResponse {
+                frames: vec![SimpleString::new("QUEUED").into()],
+            });
+        }
+    }
+
+    let start = Instant::now();
+    let frames = match cmd {
+        Command::Multi(_) => vec![match conn.transaction {
+            Some(_) => error("ERR MULTI calls can not be nested"),
+            None => {
+                conn.transaction = Some(Transaction::default());
+                SimpleString::new("OK").into()
+            }
+        }],
+        Command::Exec(_) => vec![match conn.transaction.take() {
+            Some(transaction) => exec(transaction, conn),
+            None => error("ERR EXEC without MULTI"),
+        }],
+        Command::Discard(_) => vec![match conn.transaction.take() {
+            Some(_) => {
+                conn.watcher.unwatch();
+                SimpleString::new("OK").into()
+            }
+            None => error("ERR DISCARD without MULTI"),
+        }],
+        Command::Watch(_) if conn.transaction.is_some() => {
+            vec![error("ERR WATCH inside MULTI is not allowed")]
+        }
+        Command::Watch(cmd) => vec![cmd.execute_with(&backend, &mut conn.watcher)],
+        Command::Select(cmd) => vec![cmd.execute_with(&mut conn.backend)],
+        Command::Hello(cmd) => vec![cmd.execute_with(
+            &backend,
+            conn.id,
+            &mut conn.resp3,
+            &mut conn.name,
+            &mut conn.user,
+        )],
+        Command::Auth(cmd) => vec![cmd.execute_with(&backend, &mut conn.user)],
+        Command::ClientCommand(cmd) => vec![cmd.execute_with(&backend, conn.id, &mut conn.name)],
+        Command::Monitor(_) => {
+            conn.monitor = Some(backend.monitor());
+            vec![SimpleString::new("OK").into()]
+        }
+        Command::AclCommand(cmd) => vec![cmd.execute_with(&backend, conn.user.as_deref())],
+        Command::Asking(_) => vec![match backend.cluster() {
+            Some(_) => {
+                conn.asking = true;
+                SimpleString::new("OK").into()
+            }
+            None => error("ERR This instance has cluster support disabled"),
+        }],
+        Command::Unwatch(cmd) => vec![cmd.execute_with(&mut conn.watcher)],
+        Command::Subscribe(cmd) => pubsub_reply(cmd.execute_with(&mut conn.subscriber), conn.resp3),
+        Command::Unsubscribe(cmd) => {
+            pubsub_reply(cmd.execute_with(&mut conn.subscriber), conn.resp3)
+        }
+        Command::PSubscribe(cmd) => {
+            pubsub_reply(cmd.execute_with(&mut conn.subscriber), conn.resp3)
+        }
+        Command::PUnsubscribe(cmd) => {
+            pubsub_reply(cmd.execute_with(&mut conn.subscriber), conn.resp3)
+        }
+        Command::Ping(cmd) if subscribed => vec![cmd.execute_subscribed()],
+        Command::PSync(cmd) => {
+            let ip = conn.addr.ip().to_string();
+            let reply = cmd.execute_with(&backend, conn.id, ip, conn.replica_port);
+            conn.replica = Some(reply.stream);
+            vec![reply.frame]
+        }
+        Command::ReplConf(cmd) => cmd
+            .execute_with(&backend, conn.id, &mut conn.replica_port)
+            .into_iter()
+            .collect(),
+        // blocking commands only park this connection while waiting
+        // the pop is logged by the attempt which got an element, in the same critical section
+        Command::BLPop(cmd) => vec![cmd.execute_blocking(&backend).await],
+        Command::BRPop(cmd) => vec![cmd.execute_blocking(&backend).await],
+        Command::XRead(cmd) if cmd.is_blocking() => vec![cmd.execute_blocking(&backend).await],
+        Command::XReadGroup(cmd) if cmd.is_blocking() => {
+            let ret = cmd.execute_blocking(&backend).await;
+            // logged once it delivered entries, replaying it delivers the same ones
+            if let (Some(frame), RespFrame::Array(Array(Some(_)))) = (logged, &ret) {
+                if let Some(mut log) = backend.command_log() {
+                    log.append(frame);
+                }
+            }
+            vec![ret]
+        }
+        // no other command runs while SAVE dumps the keyspace
+        Command::Save(cmd) => {
+            let _guard = backend.lock_exclusive();
+            let user = conn.user.as_deref().unwrap_or_default();
+            vec![execute_command(cmd.into(), None, &backend, user, None)]
+        }
+        // a script runs atomically, the commands it calls are propagated one by one
+        Command::Eval(cmd) => {
+            let _guard = backend.lock_exclusive();
+            let mut log = backend.command_log();
+            let user = conn.user.as_deref().unwrap_or_default();
+            vec![execute_command(
+                cmd.into(),
+                None,
+                &backend,
+                user,
+                log.as_mut(),
+            )]
+        }
+        cmd => {
+            let user = conn.user.as_deref().unwrap_or_default();
+            vec![execute_single(cmd, logged, &backend, user)]
+        }
+    };
+    // the time a blocking command spent waiting is not counted
+    let elapsed = match spec.is_some_and(|spec| spec.has_flag("blocking")) {
+        true => Duration::ZERO,
+        false => start.elapsed(),
+    };
+    let failed = matches!(frames.first(), Some(RespFrame::Error(_)));
+    backend.record_call(&name, elapsed, failed);
+    backend.slowlog_record(
+        &args,
+        elapsed,
+        conn.addr.to_string(),
+        conn.name.clone().unwrap_or_default(),
+    );
+    Ok(RedisResponse { frames })
+}
+
+// the stream can't be parsed any further, tell the client before closing it
+async fn protocol_error<S>(framed: &mut Framed<S, RespFrameCodec>, e: anyhow::Error) -> Result<()>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let err = SimpleError::new(format!("ERR Protocol error: {}", e));
+    framed.send(err.into()).await?;
+    Err(e)
+}
+
+fn pubsub_reply(messages: Vec<PubSubMessage>, resp3: bool) -> Vec<RespFrame> {
+    messages
+        .into_iter()
+        .map(|message| message.into_frame(resp3))
+        .collect()
+}
+
+// lowercase name of the command in a request frame, for error messages
+fn command_name(frame: &RespFrame) -> String {
+    match frame {
+        RespFrame::Array(Array(Some(items))) => match items.first() {
+            Some(RespFrame::BulkString(name)) => {
+                String::from_utf8_lossy(name.as_ref()).to_ascii_lowercase()
+            }
+            _ => String::new(),
+        },
+        _ => String::new(),
+    }
+}
+
+// commands which may wait for another client, BLOCK is optional for the stream reads
+fn may_block(frame: &RespFrame) -> bool {
+    matches!(
+        command_name(frame).as_str(),
+        "blpop" | "brpop" | "xread" | "xreadgroup"
+    )
+}
+
+// the arguments of a request, the command name included
+fn request_args(frame: &RespFrame) -> Vec<Bytes> {
+    match frame {
+        RespFrame::Array(Array(Some(items))) => items
+            .iter()
+            .filter_map(|item| match item {
+                RespFrame::BulkString(BulkString(Some(arg))) => Some(arg.clone()),
+                _ => None,
+            })
+            .collect(),
+        _ => vec![],
+    }
+}
+
+// the keys of a request, at the positions given by the command table
+fn request_keys(frame: &RespFrame, spec: &CommandSpec) -> Vec<Bytes> {
+    let RespFrame::Array(Array(Some(items))) = frame else {
+        return vec![];
+    };
+    let positions = match spec.has_flag("movablekeys") {
+        true => spec.movable_key_positions(items),
+        false => spec.key_positions(items.len()),
+    };
+    positions
+        .into_iter()
+        .filter_map(|i| match items.get(i) {
+            Some(RespFrame::BulkString(BulkString(Some(key)))) => Some(key.clone()),
+            _ => None,
+        })
+        .collect()
+}
+
+// whether the user may run the command on the keys and the channels of the request
+fn check_permissions(
+    backend: &Backend,
+    user: &str,
+    spec: &CommandSpec,
+    frame: &RespFrame,
+    keys: &[Bytes],
+) -> Result<(), AclError> {
+    let channels = match (spec.name, frame) {
+        ("publish" | "subscribe" | "psubscribe", RespFrame::Array(Array(Some(items)))) => {
+            let last = match spec.name {
+                "publish" => 2,
+                _ => items.len(),
+            };
+            items[1..last.min(items.len())]
+                .iter()
+                .filter_map(|item| match item {
+                    RespFrame::BulkString(BulkString(Some(channel))) => Some(channel.clone()),
+                    _ => None,
+                })
+                .collect()
+        }
+        _ => vec![],
+    };
+    backend.acl().check(user, spec, keys, &channels)
+}
+
+// run the queued commands of a transaction atomically, unless a watched key was modified
+fn exec(transaction: Transaction, conn: &mut Connection) -> RespFrame {
+    let backend = conn.backend.clone();
+    let _guard = backend.lock_exclusive();
+    let dirty = conn.watcher.is_dirty();
+    conn.watcher.unwatch();
+    if transaction.aborted {
+        return error("EXECABORT Transaction discarded because of previous errors.");
+    }
+    if dirty {
+        return Array::none().into();
+    }
+
+    let mut log = backend.command_log();
+    let user = conn.user.clone().unwrap_or_default();
+    let frames = transaction
+        .commands
+        .into_iter()
+        .map(|(cmd, logged, name)| {
+            let start = Instant::now();
+            let ret = match cmd {
+                // the commands queued after a SELECT apply to the database it selects
+                Command::Select(cmd) => cmd.execute_with(&mut conn.backend),
+                cmd => execute_command(cmd, logged, &conn.backend, &user, log.as_mut()),
+            };
+            let failed = matches!(ret, RespFrame::Error(_));
+            backend.record_call(&name, start.elapsed(), failed);
+            ret
+        })
+        .collect::<Vec<_>>();
+    Array::new(frames).into()
+}
+
+// evict keys until the keyspace fits in maxmemory, the evicted keys are deleted on the replicas too
+fn free_memory(backend: &Backend) -> Result<(), MemoryError> {
+    let _guard = backend.lock_exclusive();
+    let mut log = backend.command_log();
+    backend.free_memory(|db, key| {
+        if let Some(log) = log.as_mut() {
+            log.set_db(db);
+            log.append(command_frame(&["del", key]));
+        }
+    })
+}
+
+fn execute_single(
+    cmd: Command,
+    logged: Option<RespFrame>,
+    backend: &Backend,
+    user: &str,
+) -> RespFrame {
+    let _guard = backend.lock_shared();
+    let mut log = match logged.is_some() {
+        true => backend.command_log(),
+        false => None,
+    };
+    execute_command(cmd, logged, backend, user, log.as_mut())
+}
+
+// execute a command, propagating writes which succeeded to the aof and the replicas, a request
+// is only given to log for the commands flagged as write in the command table
+fn execute_command(
+    cmd: Command,
+    logged: Option<RespFrame>,
+    backend: &Backend,
+    user: &str,
+    log: Option<&mut CommandLog>,
+) -> RespFrame {
+    if let Command::Eval(cmd) = cmd {
+        let mut log = log;
+        return cmd.execute_with(backend, |frame| {
+            script_call(frame, backend, user, log.as_deref_mut())
+        });
+    }
+    let pop = match &cmd {
+        Command::BLPop(_) => Some("lpop"),
+        Command::BRPop(_) => Some("rpop"),
+        _ => None,
+    };
+    let expire_key = cmd.relative_expire_key().map(|key| key.to_string());
+    let id_index = cmd.generated_id_index();
+    let set_key = cmd.logged_as_set_key().map(|key| key.to_string());
+    let ret = cmd.execute(backend);
+    let (Some(log), Some(mut frame)) = (log, logged) else {
+        return ret;
+    };
+    // the commands of a transaction may apply to several databases
+    log.set_db(backend.db());
+    if let Some(name) = pop {
+        log_blocking_pop(log, name, &ret);
+        return ret;
+    }
+    if matches!(ret, RespFrame::Error(_)) {
+        return ret;
+    }
+
+    if let (Some(i), RespFrame::BulkString(id)) = (id_index, &ret) {
+        if let RespFrame::Array(Array(Some(items))) = &mut frame {
+            items[i] = id.clone().into();
+        }
+    }
+    if let (Some(key), RespFrame::BulkString(value)) = (set_key, &ret) {
+        let value = String::from_utf8_lossy(value.as_ref());
+        frame = command_frame(&["set", &key, &value, "keepttl"]);
+    }
+    let mut frames = vec![frame];
+    if let Some(key) = expire_key {
+        if let Some(at) = backend.expire_time(&key) {
+            frames.push(command_frame(&["pexpireat", &key, &at.to_string()]));
+        }
+    }
+    for frame in frames {
+        log.append(frame);
+    }
+    ret
+}
+
+// a command called by a script, checked and propagated like a request of its own
+fn script_call(
+    frame: RespFrame,
+    backend: &Backend,
+    user: &str,
+    log: Option<&mut CommandLog>,
+) -> RespFrame {
+    let spec = lookup(&command_name(&frame));
+    if let Some(spec) = spec {
+        let keys = request_keys(&frame, spec);
+        if let Err(e) = check_permissions(backend, user, spec, &frame, &keys) {
+            return error(&e.to_string());
+        }
+    }
+    let write = spec.is_some_and(|spec| spec.has_flag("write"));
+    if write && backend.is_replica() {
+        return error("READONLY You can't write against a read only replica.");
+    }
+    let logged = write.then(|| frame.clone());
+    match Command::try_from(frame) {
+        Ok(cmd) => execute_command(cmd, logged, backend, user, log),
+        Err(e) => e.into(),
+    }
+}
+
+fn error(message: &str) -> RespFrame {
+    SimpleError::new(message).into()
+}
+
+fn command_frame(args: &[&str]) -> RespFrame {
+    Array::new(
+        args.iter()
+            .map(|arg| BulkString::new(*arg).into())
+            .collect::<Vec<RespFrame>>(),
+    )
+    .into()
+}
+
+impl RespFrameCodec {
+    /// A codec encoding RESP3 frames as they are when `resp3`, downgraded to RESP2 otherwise.
+    pub fn new(resp3: bool) -> Self {
+        Self { resp3 }
+    }
 }
 
 impl Encoder<RespFrame> for RespFrameCodec {
     type Error = anyhow::Error;
 
     fn encode(&mut self, item: RespFrame, dst: &mut bytes::BytesMut) -> Result<()> {
-        let encoded = item.encode();
+        let encoded = match self.resp3 {
+            true => item.encode(),
+            false => item.encode_resp2(),
+        };
         dst.extend_from_slice(&encoded);
         Ok(())
     }
@@ -85,10 +732,48 @@ impl Decoder for RespFrameCodec {
     type Error = anyhow::Error;
 
     fn decode(&mut self, src: &mut bytes::BytesMut) -> Result<Option<Self::Item>> {
-        match RespFrame::decode(src) {
-            Ok(frame) => Ok(Some(frame)),
-            Err(RespError::NotComplete) => Ok(None),
-            Err(e) => Err(e.into()),
-        }
+        decode_frame(src)
+    }
+}
+
+// the length of the frame is checked first, so that a partial frame is not parsed at all and
+// a complete one is split off the buffer without copying
+fn decode_frame(src: &mut bytes::BytesMut) -> Result<Option<RespFrame>> {
+    match RespFrame::decode(src) {
+        Ok(frame) => Ok(Some(frame)),
+        Err(RespError::NotComplete) => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::{duplex, AsyncReadExt};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_pipeline_before_blocking_command() -> Result<()> {
+        let backend = Backend::new();
+        let addr: SocketAddr = "127.0.0.1:6000".parse()?;
+        let (mut client, server) = duplex(64 * 1024);
+        tokio::spawn(stream_handler(server, addr, backend.clone()));
+        let (mut pusher, server) = duplex(64 * 1024);
+        tokio::spawn(stream_handler(server, addr, backend));
+
+        client
+            .write_all(b"*3\r\n$3\r\nset\r\n$1\r\na\r\n$1\r\n1\r\n*3\r\n$5\r\nblpop\r\n$1\r\nq\r\n$1\r\n0\r\n")
+            .await?;
+        // the reply to SET arrives while BLPOP is still waiting
+        let mut buf = [0; 64];
+        let n = tokio::time::timeout(Duration::from_secs(1), client.read(&mut buf)).await??;
+        assert_eq!(&buf[..n], b"+OK\r\n");
+
+        pusher
+            .write_all(b"*3\r\n$5\r\nrpush\r\n$1\r\nq\r\n$1\r\nx\r\n")
+            .await?;
+        let n = tokio::time::timeout(Duration::from_secs(1), client.read(&mut buf)).await??;
+        assert_eq!(&buf[..n], b"*2\r\n$1\r\nq\r\n$1\r\nx\r\n");
+        Ok(())
     }
 }
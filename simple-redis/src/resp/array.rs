@@ -4,7 +4,7 @@ use bytes::{Buf, BytesMut};
 
 use crate::{RespDecode, RespEncode, RespError, RespFrame};
 
-use super::{BUF_CAP, calc_total_length, CRLF_LEN, parse_length};
+use super::{calc_total_length, parse_length, BUF_CAP, CRLF_LEN};
 
 #[derive(Debug, Clone, PartialEq, PartialOrd)]
 pub struct Array(pub(crate) Option<Vec<RespFrame>>);
@@ -24,6 +24,23 @@ impl RespEncode for Array {
             None => b"*-1\r\n".to_vec(),
         }
     }
+
+    fn encode_resp2(self) -> Vec<u8> {
+        match self.0 {
+            Some(data) => encode_resp2_array(data),
+            None => b"*-1\r\n".to_vec(),
+        }
+    }
+}
+
+// the elements of an aggregate frame, as a RESP2 array
+pub(crate) fn encode_resp2_array(data: Vec<RespFrame>) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(BUF_CAP);
+    buf.extend_from_slice(&format!("*{}\r\n", data.len()).into_bytes());
+    for frame in data {
+        buf.extend_from_slice(&frame.encode_resp2());
+    }
+    buf
 }
 
 // - array: "*<number-of-elements>\r\n<element-1>...<element-n>"
@@ -113,6 +130,20 @@ mod tests {
         assert_eq!(&frame.encode(), b"*-1\r\n");
     }
 
+    #[test]
+    fn test_array_encode_resp2() {
+        let frame: RespFrame = Array::new(vec![
+            RespFrame::Null(crate::Null),
+            false.into(),
+            crate::Set::new(vec![1.5.into()]).into(),
+        ])
+        .into();
+        assert_eq!(
+            &frame.encode_resp2(),
+            b"*3\r\n$-1\r\n:+0\r\n*1\r\n$3\r\n1.5\r\n"
+        );
+    }
+
     #[test]
     fn test_array_decode() -> anyhow::Result<()> {
         let mut buf = BytesMut::new();
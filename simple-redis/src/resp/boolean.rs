@@ -9,6 +9,11 @@ impl RespEncode for bool {
     fn encode(self) -> Vec<u8> {
         format!("#{}\r\n", if self { "t" } else { "f" }).into_bytes()
     }
+
+    // RESP2 has no boolean, 1 and 0 are sent as integers instead
+    fn encode_resp2(self) -> Vec<u8> {
+        (self as i64).encode()
+    }
 }
 
 impl RespDecode for bool {
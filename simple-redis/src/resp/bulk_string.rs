@@ -1,13 +1,13 @@
 use std::ops::Deref;
 
-use bytes::{Buf, BytesMut};
+use bytes::{Buf, Bytes, BytesMut};
 
 use crate::{RespDecode, RespEncode, RespError};
 
-use super::{CRLF_LEN, parse_length};
+use super::{parse_length, CRLF_LEN};
 
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd)]
-pub struct BulkString(pub(crate) Option<Vec<u8>>);
+pub struct BulkString(pub(crate) Option<Bytes>);
 
 // - bulk string: "$<length>\r\n<data>\r\n"
 impl RespEncode for BulkString {
@@ -41,7 +41,7 @@ impl RespDecode for BulkString {
         buf.advance(end + CRLF_LEN);
 
         let data = buf.split_to(len + CRLF_LEN);
-        Ok(BulkString::new(data[..len].to_vec()))
+        Ok(BulkString(Some(data.freeze().slice(..len))))
     }
 
     fn expect_length(buf: &[u8]) -> Result<usize, RespError> {
@@ -55,7 +55,7 @@ impl RespDecode for BulkString {
 
 impl BulkString {
     pub fn new(s: impl Into<Vec<u8>>) -> Self {
-        BulkString(Some(s.into()))
+        BulkString(Some(s.into().into()))
     }
 
     pub fn none() -> Self {
@@ -65,36 +65,43 @@ impl BulkString {
 
 impl AsRef<[u8]> for BulkString {
     fn as_ref(&self) -> &[u8] {
-        self.0.as_ref().map(|v| v.as_slice()).unwrap_or_default()
+        self.0.as_deref().unwrap_or_default()
     }
 }
 
 impl<const N: usize> From<&[u8; N]> for BulkString {
     fn from(s: &[u8; N]) -> Self {
-        BulkString(Some(s.to_vec()))
+        BulkString(Some(Bytes::copy_from_slice(s)))
     }
 }
 
 impl From<&str> for BulkString {
     fn from(s: &str) -> Self {
-        BulkString(Some(s.as_bytes().to_vec()))
+        BulkString(Some(Bytes::copy_from_slice(s.as_bytes())))
     }
 }
 
 impl From<String> for BulkString {
     fn from(s: String) -> Self {
-        BulkString(Some(s.into_bytes()))
+        BulkString(Some(s.into_bytes().into()))
     }
 }
 
 impl From<&[u8]> for BulkString {
     fn from(s: &[u8]) -> Self {
-        BulkString(Some(s.to_vec()))
+        BulkString(Some(Bytes::copy_from_slice(s)))
+    }
+}
+
+// shares the buffer, e.g. a slice of the request the bulk string was decoded from
+impl From<Bytes> for BulkString {
+    fn from(s: Bytes) -> Self {
+        BulkString(Some(s))
     }
 }
 
 impl Deref for BulkString {
-    type Target = Option<Vec<u8>>;
+    type Target = Option<Bytes>;
 
     fn deref(&self) -> &Self::Target {
         &self.0
@@ -1,8 +1,8 @@
 use bytes::BytesMut;
 
-use crate::{RespDecode, RespEncode, RespError};
+use crate::{BulkString, RespDecode, RespEncode, RespError};
 
-use super::{CRLF_LEN, extract_simple_frame_data};
+use super::{extract_simple_frame_data, CRLF_LEN};
 
 // - double: ",[<+|->]<integral>[.<fractional>][<E|e>[sign]<exponent>]\r\n"
 impl RespEncode for f64 {
@@ -18,6 +18,16 @@ impl RespEncode for f64 {
         buf.extend_from_slice(&ret.into_bytes());
         buf
     }
+
+    // RESP2 has no double, it is sent as a bulk string, e.g. "1.5", "10" or "inf"
+    fn encode_resp2(self) -> Vec<u8> {
+        let s = if self.is_infinite() {
+            if self > 0.0 { "inf" } else { "-inf" }.to_string()
+        } else {
+            self.to_string()
+        };
+        BulkString::new(s).encode()
+    }
 }
 
 // - double: ",[<+|->]<integral>[.<fractional>][<E|e>[sign]<exponent>]\r\n"
@@ -57,6 +67,18 @@ mod tests {
         assert_eq!(&frame.encode(), b",-1.23456e-9\r\n");
     }
 
+    #[test]
+    fn test_double_encode_resp2() {
+        let frame: RespFrame = 123.456.into();
+        assert_eq!(frame.encode_resp2(), b"$7\r\n123.456\r\n");
+
+        let frame: RespFrame = 10.0.into();
+        assert_eq!(frame.encode_resp2(), b"$2\r\n10\r\n");
+
+        let frame: RespFrame = f64::NEG_INFINITY.into();
+        assert_eq!(frame.encode_resp2(), b"$4\r\n-inf\r\n");
+    }
+
     #[test]
     fn test_double_decode() -> anyhow::Result<()> {
         let mut buf = BytesMut::new();
@@ -1,7 +1,9 @@
 use bytes::BytesMut;
 use enum_dispatch::enum_dispatch;
 
-use crate::{Array, BulkString, Map, Null, RespDecode, RespError, Set, SimpleError, SimpleString};
+use crate::{
+    Array, BulkString, Map, Null, Push, RespDecode, RespError, Set, SimpleError, SimpleString,
+};
 
 #[enum_dispatch(RespEncode)]
 #[derive(Debug, Clone, PartialEq, PartialOrd)]
@@ -16,6 +18,7 @@ pub enum RespFrame {
     Double(f64),
     Map(Map),
     Set(Set),
+    Push(Push),
 }
 
 impl RespDecode for RespFrame {
@@ -65,6 +68,10 @@ impl RespDecode for RespFrame {
                 let frame = Set::decode(buf)?;
                 Ok(frame.into())
             }
+            Some(b'>') => {
+                let frame = Push::decode(buf)?;
+                Ok(frame.into())
+            }
             None => Err(RespError::NotComplete),
             _ => Err(RespError::InvalidFrameType(format!(
                 "expect_length: unknown frame type: {:?}",
@@ -78,6 +85,7 @@ impl RespDecode for RespFrame {
         match iter.peek() {
             Some(b'*') => Array::expect_length(buf),
             Some(b'~') => Set::expect_length(buf),
+            Some(b'>') => Push::expect_length(buf),
             Some(b'%') => Map::expect_length(buf),
             Some(b'$') => BulkString::expect_length(buf),
             Some(b':') => i64::expect_length(buf),
@@ -99,12 +107,12 @@ impl From<&str> for RespFrame {
 
 impl From<&[u8]> for RespFrame {
     fn from(s: &[u8]) -> Self {
-        BulkString(Some(s.to_vec())).into()
+        BulkString::from(s).into()
     }
 }
 
 impl<const N: usize> From<&[u8; N]> for RespFrame {
     fn from(s: &[u8; N]) -> Self {
-        BulkString(Some(s.to_vec())).into()
+        BulkString::from(s).into()
     }
 }
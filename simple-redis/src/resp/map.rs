@@ -3,9 +3,9 @@ use std::ops::{Deref, DerefMut};
 
 use bytes::{Buf, BytesMut};
 
-use crate::{RespDecode, RespEncode, RespError, RespFrame, SimpleString};
+use crate::{BulkString, RespDecode, RespEncode, RespError, RespFrame, SimpleString};
 
-use super::{BUF_CAP, calc_total_length, CRLF_LEN, parse_length};
+use super::{calc_total_length, parse_length, BUF_CAP, CRLF_LEN};
 
 #[derive(Debug, Clone, PartialEq, PartialOrd)]
 pub struct Map(pub(crate) BTreeMap<String, RespFrame>);
@@ -22,6 +22,17 @@ impl RespEncode for Map {
         }
         buf
     }
+
+    // RESP2 has no map, it is sent as a flat array of keys and values
+    fn encode_resp2(self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(BUF_CAP);
+        buf.extend_from_slice(&format!("*{}\r\n", self.len() * 2).into_bytes());
+        for (key, value) in self.0 {
+            buf.extend_from_slice(&BulkString::new(key).encode());
+            buf.extend_from_slice(&value.encode_resp2());
+        }
+        buf
+    }
 }
 
 // - map: "%<number-of-entries>\r\n<key-1><value-1>...<key-n><value-n>"
@@ -107,6 +118,19 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_map_encode_resp2() {
+        let mut map = Map::new();
+        map.insert("hello".to_string(), BulkString::new("world").into());
+        map.insert("foo".to_string(), true.into());
+
+        let frame: RespFrame = map.into();
+        assert_eq!(
+            &frame.encode_resp2(),
+            b"*4\r\n$3\r\nfoo\r\n:+1\r\n$5\r\nhello\r\n$5\r\nworld\r\n"
+        );
+    }
+
     #[test]
     fn test_map_decode() -> anyhow::Result<()> {
         let mut buf = BytesMut::new();
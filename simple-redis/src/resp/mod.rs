@@ -3,7 +3,7 @@ use enum_dispatch::enum_dispatch;
 use thiserror::Error;
 
 pub use self::{
-    array::Array, bulk_string::BulkString, frame::RespFrame, map::Map, null::Null,
+    array::Array, bulk_string::BulkString, frame::RespFrame, map::Map, null::Null, push::Push,
     set::Set, simple_error::SimpleError, simple_string::SimpleString,
 };
 
@@ -15,6 +15,7 @@ mod frame;
 mod interger;
 mod map;
 mod null;
+mod push;
 mod set;
 mod simple_error;
 mod simple_string;
@@ -26,6 +27,14 @@ const CRLF_LEN: usize = CRLF.len();
 #[enum_dispatch]
 pub trait RespEncode {
     fn encode(self) -> Vec<u8>;
+
+    /// Encode for a client speaking RESP2, frames only defined by RESP3 are downgraded.
+    fn encode_resp2(self) -> Vec<u8>
+    where
+        Self: Sized,
+    {
+        self.encode()
+    }
 }
 
 pub trait RespDecode {
@@ -117,7 +126,7 @@ fn calc_total_length(buf: &[u8], end: usize, len: usize, prefix: &str) -> Result
     let mut total = end + CRLF_LEN;
     let mut data = &buf[total..];
     match prefix {
-        "*" | "~" => {
+        "*" | "~" | ">" => {
             // find nth CRLF in the buffer, for array and set, we need to find 1 CRLF for each element
             for _ in 0..len {
                 let len = RespFrame::expect_length(data)?;
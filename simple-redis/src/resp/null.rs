@@ -12,6 +12,11 @@ impl RespEncode for Null {
     fn encode(self) -> Vec<u8> {
         b"_\r\n".to_vec()
     }
+
+    // RESP2 has no null type, the null bulk string is used instead
+    fn encode_resp2(self) -> Vec<u8> {
+        b"$-1\r\n".to_vec()
+    }
 }
 
 impl RespDecode for Null {
@@ -0,0 +1,105 @@
+use std::ops::Deref;
+
+use bytes::{Buf, BytesMut};
+
+use crate::{RespDecode, RespEncode, RespError, RespFrame};
+
+use super::array::encode_resp2_array;
+use super::{calc_total_length, parse_length, BUF_CAP, CRLF_LEN};
+
+/// Out of band data sent by the server in RESP3, e.g. pub/sub messages.
+#[derive(Debug, Clone, PartialEq, PartialOrd)]
+pub struct Push(pub(crate) Vec<RespFrame>);
+
+// - push: "><number-of-elements>\r\n<element-1>...<element-n>"
+impl RespEncode for Push {
+    fn encode(self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(BUF_CAP);
+        buf.extend_from_slice(&format!(">{}\r\n", self.len()).into_bytes());
+        for frame in self.0 {
+            buf.extend_from_slice(&frame.encode());
+        }
+        buf
+    }
+
+    fn encode_resp2(self) -> Vec<u8> {
+        encode_resp2_array(self.0)
+    }
+}
+
+// - push: "><number-of-elements>\r\n<element-1>...<element-n>"
+impl RespDecode for Push {
+    const PREFIX: &'static str = ">";
+    fn decode(buf: &mut BytesMut) -> Result<Self, RespError> {
+        let (end, len) = parse_length(buf, Self::PREFIX)?;
+
+        let total_len = calc_total_length(buf, end, len, Self::PREFIX)?;
+
+        if buf.len() < total_len {
+            return Err(RespError::NotComplete);
+        }
+
+        buf.advance(end + CRLF_LEN);
+
+        let mut frames = Vec::with_capacity(len);
+        for _ in 0..len {
+            frames.push(RespFrame::decode(buf)?);
+        }
+
+        Ok(Push::new(frames))
+    }
+
+    fn expect_length(buf: &[u8]) -> Result<usize, RespError> {
+        let (end, len) = parse_length(buf, Self::PREFIX)?;
+        calc_total_length(buf, end, len, Self::PREFIX)
+    }
+}
+
+impl Deref for Push {
+    type Target = Vec<RespFrame>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl Push {
+    pub fn new(s: impl Into<Vec<RespFrame>>) -> Self {
+        Push(s.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::BulkString;
+
+    use super::*;
+
+    #[test]
+    fn test_push_encode() {
+        let frame: RespFrame = Push::new([
+            BulkString::new("message").into(),
+            BulkString::new("news").into(),
+            BulkString::new("hello").into(),
+        ])
+        .into();
+        assert_eq!(
+            frame.encode(),
+            b">3\r\n$7\r\nmessage\r\n$4\r\nnews\r\n$5\r\nhello\r\n"
+        );
+    }
+
+    #[test]
+    fn test_push_decode() -> anyhow::Result<()> {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(b">2\r\n$9\r\nsubscribe\r\n:1\r\n");
+
+        let frame = RespFrame::decode(&mut buf)?;
+        assert_eq!(
+            frame,
+            Push::new(vec![BulkString::new("subscribe").into(), 1.into()]).into()
+        );
+
+        Ok(())
+    }
+}
@@ -4,7 +4,8 @@ use bytes::{Buf, BytesMut};
 
 use crate::{RespDecode, RespEncode, RespError, RespFrame};
 
-use super::{BUF_CAP, calc_total_length, CRLF_LEN, parse_length};
+use super::array::encode_resp2_array;
+use super::{calc_total_length, parse_length, BUF_CAP, CRLF_LEN};
 
 #[derive(Debug, Clone, PartialEq, PartialOrd)]
 pub struct Set(pub(crate) Vec<RespFrame>);
@@ -19,6 +20,11 @@ impl RespEncode for Set {
         }
         buf
     }
+
+    // RESP2 has no set, it is sent as an array
+    fn encode_resp2(self) -> Vec<u8> {
+        encode_resp2_array(self.0)
+    }
 }
 
 // - set: "~<number-of-elements>\r\n<element-1>...<element-n>"
@@ -65,7 +71,7 @@ impl Set {
 
 #[cfg(test)]
 mod tests {
-    use crate::{BulkString, Array, RespFrame};
+    use crate::{Array, BulkString, RespFrame};
 
     use super::*;
 
@@ -4,7 +4,7 @@ use bytes::BytesMut;
 
 use crate::{RespDecode, RespEncode, RespError};
 
-use super::{CRLF_LEN, extract_simple_frame_data};
+use super::{extract_simple_frame_data, CRLF_LEN};
 
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd)]
 pub struct SimpleError(pub(crate) String);
@@ -1,7 +1,7 @@
 use bytes::BytesMut;
 
+pub use crate::respv2::parser::{parse_frame, parse_frame_bytes, parse_frame_length};
 use crate::{RespError, RespFrame};
-pub use crate::respv2::parser::{parse_frame, parse_frame_length};
 
 mod parser;
 
@@ -12,10 +12,11 @@ pub trait RespDecodeV2: Sized {
 
 impl RespDecodeV2 for RespFrame {
     fn decode(buf: &mut BytesMut) -> Result<Self, RespError> {
+        // the frame is split off without copying, its bulk strings keep pointing into it
         let len = Self::expect_length(buf)?;
-        let data = buf.split_to(len);
+        let data = buf.split_to(len).freeze();
 
-        parse_frame(&mut data.as_ref()).map_err(|e| RespError::InvalidFrame(e.to_string()))
+        parse_frame_bytes(&data).map_err(|e| RespError::InvalidFrame(e.to_string()))
     }
 
     fn expect_length(buf: &[u8]) -> Result<usize, RespError> {
@@ -27,7 +28,7 @@ impl RespDecodeV2 for RespFrame {
 mod tests {
     use std::collections::BTreeMap;
 
-    use crate::{Array, BulkString, Null, Set, SimpleError, SimpleString};
+    use crate::{Array, BulkString, Null, Push, Set, SimpleError, SimpleString};
 
     use super::*;
 
@@ -241,4 +242,50 @@ mod tests {
             ]))
         );
     }
+
+    #[test]
+    fn respv2_push_should_work() {
+        let mut buf = BytesMut::from(">3\r\n$7\r\nmessage\r\n$4\r\nnews\r\n$5\r\nhello\r\n");
+        let len = RespFrame::expect_length(&buf).unwrap();
+        assert_eq!(len, buf.len());
+        let frame = RespFrame::decode(&mut buf).unwrap();
+        assert_eq!(
+            frame,
+            RespFrame::Push(Push::new(vec![
+                BulkString::new(b"message".to_vec()).into(),
+                BulkString::new(b"news".to_vec()).into(),
+                BulkString::new(b"hello".to_vec()).into(),
+            ]))
+        );
+    }
+
+    #[test]
+    fn respv2_invalid_frame_length_should_fail() {
+        let err = RespFrame::expect_length(b"?x\r\n").unwrap_err();
+        assert!(matches!(err, RespError::InvalidFrame(_)));
+
+        let err = RespFrame::expect_length(b"*1\r\n$-5\r\n").unwrap_err();
+        assert!(matches!(err, RespError::InvalidFrame(_)));
+
+        let err = RespFrame::expect_length(b"").unwrap_err();
+        assert_eq!(err, RespError::NotComplete);
+    }
+
+    #[test]
+    fn respv2_pipelined_decode_should_work() {
+        let mut buf = BytesMut::from("$0\r\n\r\n*2\r\n$3\r\nget\r\n$1\r\na\r\n*1\r\n$4\r\nping");
+        let frame = RespFrame::decode(&mut buf).unwrap();
+        assert_eq!(frame, BulkString::new("").into());
+        let frame = RespFrame::decode(&mut buf).unwrap();
+        assert_eq!(
+            frame,
+            Array::new(vec![
+                BulkString::new("get").into(),
+                BulkString::new("a").into()
+            ])
+            .into()
+        );
+        assert_eq!(RespFrame::decode(&mut buf), Err(RespError::NotComplete));
+        assert_eq!(buf.as_ref(), b"*1\r\n$4\r\nping");
+    }
 }
@@ -1,30 +1,35 @@
-use std::num::NonZeroUsize;
-use winnow::{dispatch, Parser, PResult};
+use bytes::Bytes;
 use winnow::ascii::{dec_int, float};
 use winnow::combinator::{alt, fail, preceded, terminated};
-use winnow::error::{ContextError, ErrMode, Needed};
+use winnow::error::{ContextError, ErrMode};
+use winnow::stream::{Partial, Stateful};
 use winnow::token::{any, take, take_until};
+use winnow::{dispatch, PResult, Parser};
 
-use crate::{Array, BulkString, Map, Null, RespError, RespFrame, Set, SimpleError, SimpleString};
+use crate::{
+    Array, BulkString, Map, Null, Push, RespError, RespFrame, Set, SimpleError, SimpleString,
+};
 
 const CRLF: &[u8] = b"\r\n";
 
+// a complete frame, along with the buffer it is part of when bulk strings can share it
+type Input<'i> = Stateful<&'i [u8], Option<&'i Bytes>>;
+// the length of a frame is computed while it may still be arriving
+type PartialInput<'i> = Partial<&'i [u8]>;
+
 pub fn parse_frame_length(input: &[u8]) -> Result<usize, RespError> {
-    let target = &mut (&*input);
-    let ret = parse_frame_len(target);
-    match ret {
-        Ok(_) => {
-            // calculate the distance between target and input
-            let start = input.as_ptr() as usize;
-            let end = (*target).as_ptr() as usize;
-            let len = end - start;
-            Ok(len)
-        }
-        Err(_) => Err(RespError::NotComplete),
+    let target = &mut Partial::new(input);
+    match parse_frame_len(target) {
+        Ok(_) => Ok(input.len() - target.len()),
+        Err(ErrMode::Incomplete(_)) => Err(RespError::NotComplete),
+        Err(_) => Err(RespError::InvalidFrame(format!(
+            "{:?}",
+            String::from_utf8_lossy(&input[..input.len().min(32)])
+        ))),
     }
 }
 
-fn parse_frame_len(input: &mut &[u8]) -> PResult<()> {
+fn parse_frame_len(input: &mut PartialInput) -> PResult<()> {
     let mut simple_parser = terminated(take_until(0.., CRLF), CRLF).value(());
     dispatch! {any;
         b'+' => simple_parser,
@@ -37,12 +42,31 @@ fn parse_frame_len(input: &mut &[u8]) -> PResult<()> {
         b',' => simple_parser,
         b'%' => map_len,
         b'~' => set_len,
+        b'>' => push_len,
         _v => fail::<_, _, _>
     }
     .parse_next(input)
 }
 
 pub fn parse_frame(input: &mut &[u8]) -> PResult<RespFrame> {
+    let stream = &mut Stateful {
+        input: *input,
+        state: None,
+    };
+    let frame = frame(stream)?;
+    *input = stream.input;
+    Ok(frame)
+}
+
+/// Parse a complete frame, its bulk strings are slices of `data` rather than copies.
+pub fn parse_frame_bytes(data: &Bytes) -> PResult<RespFrame> {
+    frame(&mut Stateful {
+        input: data.as_ref(),
+        state: Some(data),
+    })
+}
+
+fn frame(input: &mut Input) -> PResult<RespFrame> {
     dispatch!(any;
         b'+' => simple_string.map(RespFrame::SimpleString),
         b'-' => error.map(RespFrame::Error),
@@ -54,57 +78,58 @@ pub fn parse_frame(input: &mut &[u8]) -> PResult<RespFrame> {
         b',' => double.map(RespFrame::Double),
         b'%' => map.map(RespFrame::Map),
         b'~' => set.map(RespFrame::Set),
+        b'>' => push.map(RespFrame::Push),
         _v => fail::<_, _, _>
     )
     .parse_next(input)
 }
 
 // - simple string: "+OK\r\n"
-fn simple_string(input: &mut &[u8]) -> PResult<SimpleString> {
+fn simple_string(input: &mut Input) -> PResult<SimpleString> {
     parse_string.map(SimpleString).parse_next(input)
 }
 
 // - error: "-ERR unknown command 'foobar'\r\n"
-fn error(input: &mut &[u8]) -> PResult<SimpleError> {
+fn error(input: &mut Input) -> PResult<SimpleError> {
     parse_string.map(SimpleError).parse_next(input)
 }
 
-fn integer(input: &mut &[u8]) -> PResult<i64> {
+fn integer(input: &mut Input) -> PResult<i64> {
     terminated(dec_int, CRLF).parse_next(input)
 }
 
-fn bulk_string_len(input: &mut &[u8]) -> PResult<()> {
-    let len = integer.parse_next(input)?;
-    if len == 0 || len == -1 {
+fn length(input: &mut PartialInput) -> PResult<i64> {
+    terminated(dec_int, CRLF).parse_next(input)
+}
+
+fn bulk_string_len(input: &mut PartialInput) -> PResult<()> {
+    let len = length(input)?;
+    if len == -1 {
         return Ok(());
     } else if len < -1 {
         return Err(err_cut("bulk string length must be non-negative"));
     }
-
-    let len_with_crlf = len as usize + 2;
-    if input.len() < len_with_crlf {
-        let size = NonZeroUsize::new((len_with_crlf - input.len()) as usize).unwrap();
-        return Err(ErrMode::Incomplete(Needed::Size(size)));
-    }
-    *input = &input[(len + 2) as usize..];
-    Ok(())
+    terminated(take(len as usize), CRLF)
+        .value(())
+        .parse_next(input)
 }
 
-fn bulk_string(input: &mut &[u8]) -> PResult<BulkString> {
+fn bulk_string(input: &mut Input) -> PResult<BulkString> {
     let len: i64 = integer.parse_next(input)?;
     if len < 0 {
         return Ok(BulkString::none());
-    } else if len == 0 {
-        return Ok(BulkString::new(vec![]));
     }
 
-    let len = len as usize;
-    let data = terminated(take(len), CRLF).parse_next(input)?;
-    Ok(BulkString::new(data))
+    let data = terminated(take(len as usize), CRLF).parse_next(input)?;
+    let data = match input.state {
+        Some(buf) => buf.slice_ref(data),
+        None => Bytes::copy_from_slice(data),
+    };
+    Ok(data.into())
 }
 
-fn array_len(input: &mut &[u8]) -> PResult<()> {
-    let len: i64 = integer.parse_next(input)?;
+fn array_len(input: &mut PartialInput) -> PResult<()> {
+    let len = length(input)?;
     if len == 0 || len == -1 {
         return Ok(());
     } else if len < -1 {
@@ -117,7 +142,7 @@ fn array_len(input: &mut &[u8]) -> PResult<()> {
 }
 
 #[allow(clippy::comparison_chain)]
-fn array(input: &mut &[u8]) -> PResult<Array> {
+fn array(input: &mut Input) -> PResult<Array> {
     let len: i64 = integer.parse_next(input)?;
     if len < 0 {
         return Ok(Array::none());
@@ -128,44 +153,43 @@ fn array(input: &mut &[u8]) -> PResult<Array> {
     let len = len as usize;
     let mut frames = Vec::with_capacity(len);
     for _ in 0..len {
-        frames.push(parse_frame(input)?);
+        frames.push(frame(input)?);
     }
     Ok(Array::new(frames))
 }
 
-fn null(input: &mut &[u8]) -> PResult<Null> {
+fn null(input: &mut Input) -> PResult<Null> {
     CRLF.value(Null).parse_next(input)
 }
 
-fn boolean(input: &mut &[u8]) -> PResult<bool> {
+fn boolean(input: &mut Input) -> PResult<bool> {
     let b = alt(('t', 'f')).parse_next(input)?;
     Ok(b == 't')
 }
 
 // - float: ",3.14\r\n"
-fn double(input: &mut &[u8]) -> PResult<f64> {
+fn double(input: &mut Input) -> PResult<f64> {
     terminated(float, CRLF).parse_next(input)
 }
 
 // - map: "%2\r\n+foo\r\n+bar\r\n"
-fn map(input: &mut &[u8]) -> PResult<Map> {
+fn map(input: &mut Input) -> PResult<Map> {
     let len: i64 = integer.parse_next(input)?;
     if len <= 0 {
         return Err(err_cut("map length must be greater than 0"));
     }
 
-    let len = len;
     let mut map = Map::new();
     for _ in 0..len {
         let key = preceded('+', parse_string).parse_next(input)?;
-        let value = parse_frame(input)?;
+        let value = frame(input)?;
         map.insert(key, value);
     }
     Ok(map)
 }
 
-fn map_len(input: &mut &[u8]) -> PResult<()> {
-    let len: i64 = integer.parse_next(input)?;
+fn map_len(input: &mut PartialInput) -> PResult<()> {
+    let len = length(input)?;
     if len <= 0 {
         return Err(err_cut("map length must be non-negative"));
     }
@@ -178,7 +202,7 @@ fn map_len(input: &mut &[u8]) -> PResult<()> {
     Ok(())
 }
 
-fn set(input: &mut &[u8]) -> PResult<Set> {
+fn set(input: &mut Input) -> PResult<Set> {
     let len: i64 = integer.parse_next(input)?;
     if len <= 0 {
         return Err(err_cut("set length must be greater than 0"));
@@ -187,14 +211,14 @@ fn set(input: &mut &[u8]) -> PResult<Set> {
     let len = len as usize;
     let mut set = Vec::new();
     for _ in 0..len {
-        let v = parse_frame(input)?;
+        let v = frame(input)?;
         set.push(v);
     }
     Ok(Set::new(set))
 }
 
-fn set_len(input: &mut &[u8]) -> PResult<()> {
-    let len: i64 = integer.parse_next(input)?;
+fn set_len(input: &mut PartialInput) -> PResult<()> {
+    let len = length(input)?;
     if len <= 0 {
         return Err(err_cut("set length must be non-negative"));
     }
@@ -204,7 +228,33 @@ fn set_len(input: &mut &[u8]) -> PResult<()> {
     Ok(())
 }
 
-fn parse_string(input: &mut &[u8]) -> PResult<String> {
+// - push: ">2\r\n$7\r\nmessage\r\n$5\r\nhello\r\n"
+fn push(input: &mut Input) -> PResult<Push> {
+    let len: i64 = integer.parse_next(input)?;
+    if len <= 0 {
+        return Err(err_cut("push length must be greater than 0"));
+    }
+
+    let len = len as usize;
+    let mut frames = Vec::with_capacity(len);
+    for _ in 0..len {
+        frames.push(frame(input)?);
+    }
+    Ok(Push::new(frames))
+}
+
+fn push_len(input: &mut PartialInput) -> PResult<()> {
+    let len = length(input)?;
+    if len <= 0 {
+        return Err(err_cut("push length must be greater than 0"));
+    }
+    for _ in 0..len {
+        parse_frame_len(input)?;
+    }
+    Ok(())
+}
+
+fn parse_string(input: &mut Input) -> PResult<String> {
     terminated(take_until(0.., CRLF), CRLF)
         .map(|s: &[u8]| String::from_utf8_lossy(s).into_owned())
         .parse_next(input)
@@ -0,0 +1,55 @@
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+use std::sync::Arc;
+
+use anyhow::{anyhow, Context, Result};
+use tokio_rustls::rustls::ServerConfig;
+use tokio_rustls::TlsAcceptor;
+
+use crate::TlsConfig;
+
+/// The acceptor of the TLS listener, serving the certificate chain and private key of the config.
+pub fn tls_acceptor(config: &TlsConfig) -> Result<TlsAcceptor> {
+    let (Some(cert_file), Some(key_file)) = (&config.cert_file, &config.key_file) else {
+        return Err(anyhow!("tls needs both a cert_file and a key_file"));
+    };
+    let certs = rustls_pemfile::certs(&mut open(cert_file)?)
+        .collect::<Result<Vec<_>, _>>()
+        .with_context(|| format!("invalid certificate in {}", cert_file.display()))?;
+    let key = rustls_pemfile::private_key(&mut open(key_file)?)
+        .with_context(|| format!("invalid private key in {}", key_file.display()))?
+        .ok_or_else(|| anyhow!("no private key in {}", key_file.display()))?;
+    let config = ServerConfig::builder()
+        .with_no_client_auth()
+        .with_single_cert(certs, key)?;
+    Ok(TlsAcceptor::from(Arc::new(config)))
+}
+
+fn open(path: &Path) -> Result<BufReader<File>> {
+    let file = File::open(path).with_context(|| format!("can't open {}", path.display()))?;
+    Ok(BufReader::new(file))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    #[test]
+    fn test_tls_acceptor_needs_cert_and_key() {
+        let config = TlsConfig {
+            port: 6380,
+            cert_file: Some(PathBuf::from("server.crt")),
+            key_file: None,
+        };
+        assert!(tls_acceptor(&config).is_err());
+
+        let config = TlsConfig {
+            key_file: Some(PathBuf::from("/nonexistent/server.key")),
+            ..config
+        };
+        assert!(tls_acceptor(&config).is_err());
+    }
+}